@@ -0,0 +1,100 @@
+// Benchmarks of the tree walking evaluator, run with `cargo bench`.
+/*
+    Each program is parsed and resolved once. Every iteration creates a new evaluator,
+    which runs the prelude, and declares the globals before the timer starts. Two ways
+    of running the statements are timed:
+    - arena: the statements are lowered into an arena once, before the iterations, and
+      the evaluator borrows them from it
+    - cloned: the resolved tree is cloned and lowered again for every run, which is what
+      keeping the boxed tree and handing copies of it to the evaluator costs
+    The printed time is the mean of the fastest half of the iterations.
+*/
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use wistful::lexer_and_parser::arena::Ast;
+use wistful::lexer_and_parser::ast::Node;
+use wistful::lexer_and_parser::evaluator::Evaluator;
+use wistful::lexer_and_parser::parser::Parser;
+use wistful::passes::resolver::Resolver;
+
+const ITERATIONS: usize = 20;
+
+const LOOP: &str = "i = 0
+total = 0
+while i < 20000 {
+    total = total + i * 2
+    i = i + 1
+}";
+
+const CALLS: &str = "fn fib(n) {
+    if n < 2 {
+        return n
+    }
+    return fib(n - 1) + fib(n - 2)
+}
+fib(18)";
+
+const BRANCHES: &str = "i = 0
+small = 0
+large = 0
+while i < 10000 {
+    if i < 2500 {
+        small = small + 1
+    } elif i < 5000 {
+        small = small + 2
+    } elif i < 7500 {
+        large = large + 1
+    } else {
+        large = large + 2
+    }
+    i = i + 1
+}";
+
+// Resolved statements and the global slots they use
+fn prepare(source: &str) -> (Vec<Node>, Vec<String>) {
+    let nodes = Parser::new(source).unwrap().parse().unwrap();
+    let mut resolver = Resolver::new();
+    let nodes = resolver.resolve(nodes);
+    (nodes, resolver.globals)
+}
+
+fn evaluate(evaluator: &mut Evaluator, ast: &Rc<Ast>) {
+    for &statement in ast.list(ast.root()) {
+        evaluator.evaluate_statement(ast, statement).unwrap();
+    }
+}
+
+// Mean time of the fastest half of the iterations, in milliseconds
+fn time(globals: &[String], run: impl Fn(&mut Evaluator)) -> f64 {
+    let mut times: Vec<Duration> = (0..ITERATIONS)
+        .map(|_| {
+            let mut evaluator = Evaluator::new(None);
+            evaluator.declare_globals(globals).unwrap();
+            let start = Instant::now();
+            run(&mut evaluator);
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    let fastest = &times[..ITERATIONS / 2];
+    let mean = fastest.iter().sum::<Duration>() / fastest.len() as u32;
+    mean.as_secs_f64() * 1000.0
+}
+
+fn bench(name: &str, source: &str) {
+    let (nodes, globals) = prepare(source);
+    let ast = Rc::new(Ast::new(nodes.clone()));
+    let arena = time(&globals, |evaluator| evaluate(evaluator, &ast));
+    let cloned = time(&globals, |evaluator| {
+        evaluate(evaluator, &Rc::new(Ast::new(nodes.clone())))
+    });
+    println!("{0:<10} {1:>10.3} ms {2:>10.3} ms", name, arena, cloned);
+}
+
+fn main() {
+    println!("{0:<10} {1:>13} {2:>13}", "", "arena", "cloned");
+    bench("loop", LOOP);
+    bench("calls", CALLS);
+    bench("branches", BRANCHES);
+}
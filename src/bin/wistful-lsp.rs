@@ -0,0 +1,19 @@
+use std::io;
+use std::process;
+
+use wistful::lsp::server::Server;
+
+// Language server over stdin/stdout, started by the editor.
+fn main() {
+    let mut server = Server::new();
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(e) = server.run(stdin.lock(), stdout.lock()) {
+        eprintln!("wistful-lsp: {0}", e);
+        process::exit(1);
+    }
+    // The protocol asks for a failure exit code without a shutdown request
+    if !server.was_shut_down() {
+        process::exit(1);
+    }
+}
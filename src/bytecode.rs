@@ -0,0 +1,8 @@
+// Add public modules
+pub mod binary;
+pub mod compiler;
+pub mod instruction;
+pub mod vm;
+// Test modules
+pub mod binary_test;
+pub mod vm_test;
@@ -0,0 +1,443 @@
+// Standard libraries
+use std::convert::TryFrom;
+use std::fmt;
+
+// Internal modules
+use super::instruction::{Constant, FunctionInfo, Instruction, Program};
+use crate::lexer_and_parser::numeric::{BigInt, Number, Rational};
+
+// Binary format of compiled programs
+/*
+    Every file starts with the magic bytes and the format version, followed by the
+    sections of the program in a fixed order:
+
+        magic        "WFBC"
+        version      u16
+        constants    count, then a tag byte and the value of each constant
+        globals      count, then the names of the global variable slots
+        functions    count of names and the names, count of functions and their
+                     name index, arity, local slot count and entry address
+        code         count, then an opcode byte and the operands of each instruction
+        lines        flag byte, if set: count and (address, line) pairs
+
+    Integers are little-endian, counts and indices are u32 and strings are a length
+    followed by UTF-8 bytes. Loading validates everything the VM relies on, so a corrupt
+    file results in a LoadError instead of a panic at run time.
+*/
+pub const MAGIC: &[u8; 4] = b"WFBC";
+// Bump whenever the layout or the instruction set changes
+pub const FORMAT_VERSION: u16 = 1;
+
+// Constant tags
+const TAG_INTEGER: u8 = 0;
+const TAG_BIG_INTEGER: u8 = 1;
+const TAG_RATIONAL: u8 = 2;
+const TAG_FLOAT: u8 = 3;
+const TAG_LITERAL: u8 = 4;
+const TAG_BOOLEAN: u8 = 5;
+
+// Serialise a program into the binary format.
+pub fn write_program(program: &Program) -> Vec<u8> {
+    let mut writer = Writer { bytes: Vec::new() };
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+    writer.write_count(program.constants.len());
+    for constant in &program.constants {
+        writer.write_constant(constant);
+    }
+    writer.write_count(program.globals.len());
+    for name in &program.globals {
+        writer.write_string(name);
+    }
+    writer.write_count(program.function_names.len());
+    for name in &program.function_names {
+        writer.write_string(name);
+    }
+    writer.write_count(program.functions.len());
+    for function in &program.functions {
+        writer.write_count(function.name);
+        writer.write_count(function.arity);
+        writer.write_count(function.locals);
+        writer.write_count(function.entry);
+    }
+    writer.write_count(program.code.len());
+    for instruction in &program.code {
+        writer.write_instruction(instruction);
+    }
+    if program.lines.is_empty() {
+        writer.bytes.push(0);
+    } else {
+        writer.bytes.push(1);
+        writer.write_count(program.lines.len());
+        for (address, line) in &program.lines {
+            writer.write_count(*address);
+            writer.write_count(*line);
+        }
+    }
+    writer.bytes
+}
+
+// Load a program from the binary format and validate it.
+pub fn read_program(bytes: &[u8]) -> Result<Program, LoadError> {
+    let mut reader = Reader { bytes, position: 0 };
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(LoadError::BadMagic);
+    }
+    reader.position = MAGIC.len();
+    let version = u16::from_le_bytes([reader.read_u8()?, reader.read_u8()?]);
+    if version != FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    let mut program = Program::default();
+    for _ in 0..reader.read_count()? {
+        program.constants.push(reader.read_constant()?);
+    }
+    for _ in 0..reader.read_count()? {
+        program.globals.push(reader.read_string()?);
+    }
+    for _ in 0..reader.read_count()? {
+        program.function_names.push(reader.read_string()?);
+    }
+    for _ in 0..reader.read_count()? {
+        program.functions.push(FunctionInfo {
+            name: reader.read_index()?,
+            arity: reader.read_index()?,
+            locals: reader.read_index()?,
+            entry: reader.read_index()?,
+        });
+    }
+    for _ in 0..reader.read_count()? {
+        program.code.push(reader.read_instruction()?);
+    }
+    match reader.read_u8()? {
+        0 => {}
+        1 => {
+            for _ in 0..reader.read_count()? {
+                let address = reader.read_index()?;
+                let line = reader.read_index()?;
+                program.lines.push((address, line));
+            }
+        }
+        flag => {
+            return Err(LoadError::Invalid(format!(
+                "Unknown line table flag {0}",
+                flag
+            )))
+        }
+    }
+    if reader.position != bytes.len() {
+        return Err(LoadError::Invalid(format!(
+            "{0} unexpected bytes after the program",
+            bytes.len() - reader.position
+        )));
+    }
+    validate(&program)?;
+    Ok(program)
+}
+
+// Make sure every index in the program points to something that exists.
+fn validate(program: &Program) -> Result<(), LoadError> {
+    let invalid = |message: String| Err(LoadError::Invalid(message));
+    if program.code.last() != Some(&Instruction::Halt) {
+        return invalid("Program does not end with Halt".to_string());
+    }
+    for (i, function) in program.functions.iter().enumerate() {
+        if function.name >= program.function_names.len() {
+            return invalid(format!("Function {0} has an invalid name", i));
+        }
+        if function.entry >= program.code.len() {
+            return invalid(format!("Function {0} has an invalid entry address", i));
+        }
+        if function.arity > function.locals {
+            return invalid(format!("Function {0} has more parameters than locals", i));
+        }
+    }
+    for (address, instruction) in program.code.iter().enumerate() {
+        let valid = match *instruction {
+            Instruction::Constant(i) => i < program.constants.len(),
+            // Units are referred to by name
+            Instruction::WithUnit(i) | Instruction::Convert(i) => {
+                matches!(program.constants.get(i), Some(Constant::Literal(_)))
+            }
+            Instruction::LoadGlobal(slot) | Instruction::StoreGlobal(slot) => {
+                slot < program.globals.len()
+            }
+            Instruction::Jump(target) | Instruction::JumpIfFalse(target) => {
+                target < program.code.len()
+            }
+            Instruction::DefineFunction(i) => i < program.functions.len(),
+            Instruction::Call { name, .. } => name < program.function_names.len(),
+            _ => true,
+        };
+        if !valid {
+            return invalid(format!(
+                "Invalid operand in {0:?} at address {1}",
+                instruction, address
+            ));
+        }
+    }
+    let mut previous = None;
+    for (address, _) in &program.lines {
+        if *address >= program.code.len() || previous.is_some_and(|p| p >= *address) {
+            return invalid("Line table is not sorted by address".to_string());
+        }
+        previous = Some(*address);
+    }
+    Ok(())
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+// Private methods
+impl Writer {
+    fn write_count(&mut self, value: usize) {
+        let value = u32::try_from(value).expect("Program is too large to serialise");
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_string(&mut self, value: &str) {
+        self.write_count(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn write_big_integer(&mut self, value: &BigInt) {
+        self.bytes.push(value.is_negative() as u8);
+        self.write_count(value.digits().len());
+        for digit in value.digits() {
+            self.bytes.extend_from_slice(&digit.to_le_bytes());
+        }
+    }
+
+    fn write_constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Number(Number::Integer(i)) => {
+                self.bytes.push(TAG_INTEGER);
+                self.bytes.extend_from_slice(&i.to_le_bytes());
+            }
+            Constant::Number(Number::BigInteger(b)) => {
+                self.bytes.push(TAG_BIG_INTEGER);
+                self.write_big_integer(b);
+            }
+            Constant::Number(Number::Rational(r)) => {
+                self.bytes.push(TAG_RATIONAL);
+                self.write_big_integer(r.numerator());
+                self.write_big_integer(r.denominator());
+            }
+            Constant::Number(Number::Float(f)) => {
+                self.bytes.push(TAG_FLOAT);
+                self.bytes.extend_from_slice(&f.to_bits().to_le_bytes());
+            }
+            Constant::Literal(s) => {
+                self.bytes.push(TAG_LITERAL);
+                self.write_string(s);
+            }
+            Constant::Boolean(b) => {
+                self.bytes.push(TAG_BOOLEAN);
+                self.bytes.push(*b as u8);
+            }
+        }
+    }
+
+    fn write_instruction(&mut self, instruction: &Instruction) {
+        let (opcode, operands) = match *instruction {
+            Instruction::Constant(i) => (0, vec![i]),
+            Instruction::Empty => (1, vec![]),
+            Instruction::LoadGlobal(slot) => (2, vec![slot]),
+            Instruction::StoreGlobal(slot) => (3, vec![slot]),
+            Instruction::LoadLocal(slot) => (4, vec![slot]),
+            Instruction::StoreLocal(slot) => (5, vec![slot]),
+            Instruction::Add => (6, vec![]),
+            Instruction::Subtract => (7, vec![]),
+            Instruction::Multiply => (8, vec![]),
+            Instruction::Divide => (9, vec![]),
+            Instruction::Pow => (10, vec![]),
+            Instruction::Negate => (11, vec![]),
+            Instruction::Equals => (12, vec![]),
+            Instruction::NotEquals => (13, vec![]),
+            Instruction::GreaterThan => (14, vec![]),
+            Instruction::LessThan => (15, vec![]),
+            Instruction::GreaterThanOrEquals => (16, vec![]),
+            Instruction::LessThanOrEquals => (17, vec![]),
+            Instruction::WithUnit(i) => (18, vec![i]),
+            Instruction::Convert(i) => (19, vec![i]),
+            Instruction::Jump(address) => (20, vec![address]),
+            Instruction::JumpIfFalse(address) => (21, vec![address]),
+            Instruction::DefineFunction(i) => (22, vec![i]),
+            Instruction::Call { name, arguments } => (23, vec![name, arguments]),
+            Instruction::Return => (24, vec![]),
+            Instruction::Pop => (25, vec![]),
+            Instruction::Halt => (26, vec![]),
+        };
+        self.bytes.push(opcode);
+        for operand in operands {
+            self.write_count(operand);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+// Private methods
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], LoadError> {
+        if self.bytes.len() - self.position < count {
+            return Err(LoadError::Truncated);
+        }
+        let bytes = &self.bytes[self.position..self.position + count];
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, LoadError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, LoadError> {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    fn read_index(&mut self) -> Result<usize, LoadError> {
+        Ok(self.read_u32()? as usize)
+    }
+
+    // Counts are followed by at least one byte per element, so a count larger than
+    // the rest of the file means it is corrupt. Checked up front to avoid huge allocations.
+    fn read_count(&mut self) -> Result<usize, LoadError> {
+        let count = self.read_index()?;
+        if count > self.bytes.len() - self.position {
+            return Err(LoadError::Truncated);
+        }
+        Ok(count)
+    }
+
+    fn read_string(&mut self) -> Result<String, LoadError> {
+        let length = self.read_count()?;
+        let bytes = self.read_bytes(length)?;
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(s.to_string()),
+            Err(_) => Err(LoadError::Invalid("String is not valid UTF-8".to_string())),
+        }
+    }
+
+    fn read_big_integer(&mut self) -> Result<BigInt, LoadError> {
+        let negative = match self.read_u8()? {
+            0 => false,
+            1 => true,
+            sign => return Err(LoadError::Invalid(format!("Invalid sign {0}", sign))),
+        };
+        let mut digits = Vec::new();
+        for _ in 0..self.read_count()? {
+            digits.push(self.read_u32()?);
+        }
+        Ok(BigInt::from_digits(negative, digits))
+    }
+
+    fn read_constant(&mut self) -> Result<Constant, LoadError> {
+        let constant = match self.read_u8()? {
+            TAG_INTEGER => Constant::Number(Number::Integer(self.read_u64()? as i64)),
+            TAG_BIG_INTEGER => Constant::Number(Number::from_big(self.read_big_integer()?)),
+            TAG_RATIONAL => {
+                let numerator = self.read_big_integer()?;
+                let denominator = self.read_big_integer()?;
+                match Rational::new(numerator, denominator) {
+                    Some(r) => Constant::Number(Number::from_rational(r)),
+                    None => {
+                        return Err(LoadError::Invalid(
+                            "Rational constant has a zero denominator".to_string(),
+                        ))
+                    }
+                }
+            }
+            TAG_FLOAT => Constant::Number(Number::Float(f64::from_bits(self.read_u64()?))),
+            TAG_LITERAL => Constant::Literal(self.read_string()?),
+            TAG_BOOLEAN => match self.read_u8()? {
+                0 => Constant::Boolean(false),
+                1 => Constant::Boolean(true),
+                value => {
+                    return Err(LoadError::Invalid(format!(
+                        "Invalid boolean constant {0}",
+                        value
+                    )))
+                }
+            },
+            tag => return Err(LoadError::Invalid(format!("Unknown constant tag {0}", tag))),
+        };
+        Ok(constant)
+    }
+
+    fn read_instruction(&mut self) -> Result<Instruction, LoadError> {
+        let instruction = match self.read_u8()? {
+            0 => Instruction::Constant(self.read_index()?),
+            1 => Instruction::Empty,
+            2 => Instruction::LoadGlobal(self.read_index()?),
+            3 => Instruction::StoreGlobal(self.read_index()?),
+            4 => Instruction::LoadLocal(self.read_index()?),
+            5 => Instruction::StoreLocal(self.read_index()?),
+            6 => Instruction::Add,
+            7 => Instruction::Subtract,
+            8 => Instruction::Multiply,
+            9 => Instruction::Divide,
+            10 => Instruction::Pow,
+            11 => Instruction::Negate,
+            12 => Instruction::Equals,
+            13 => Instruction::NotEquals,
+            14 => Instruction::GreaterThan,
+            15 => Instruction::LessThan,
+            16 => Instruction::GreaterThanOrEquals,
+            17 => Instruction::LessThanOrEquals,
+            18 => Instruction::WithUnit(self.read_index()?),
+            19 => Instruction::Convert(self.read_index()?),
+            20 => Instruction::Jump(self.read_index()?),
+            21 => Instruction::JumpIfFalse(self.read_index()?),
+            22 => Instruction::DefineFunction(self.read_index()?),
+            23 => Instruction::Call {
+                name: self.read_index()?,
+                arguments: self.read_index()?,
+            },
+            24 => Instruction::Return,
+            25 => Instruction::Pop,
+            26 => Instruction::Halt,
+            opcode => return Err(LoadError::Invalid(format!("Unknown opcode {0}", opcode))),
+        };
+        Ok(instruction)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    Invalid(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            self::LoadError::BadMagic => write!(f, "Not a compiled wistful program"),
+            self::LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "Bytecode version {} is not supported, expected version {}",
+                version, FORMAT_VERSION
+            ),
+            self::LoadError::Truncated => write!(f, "Compiled program is truncated"),
+            self::LoadError::Invalid(e) => write!(f, "Invalid compiled program: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
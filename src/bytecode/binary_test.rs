@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use crate::bytecode::binary::{read_program, write_program, LoadError, FORMAT_VERSION};
+    use crate::bytecode::compiler::Compiler;
+    use crate::bytecode::instruction::{Instruction, Program};
+    use crate::bytecode::vm::Vm;
+    use crate::lexer_and_parser::parser::Parser;
+
+    const SOURCE: &str = "
+        fn area(w, h) {
+            a = w * h
+            return a
+        }
+        big = 2^100
+        third = 1/3
+        half = 0.5
+        name = \"x\"
+        flag = true
+        speed = 90 km/h in mi/h
+        if flag {
+            result = area(3 m, 4 m) + 1 m^2
+        }
+        result";
+
+    fn compile(source: &str) -> Program {
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let lines = parser.statement_lines.clone();
+        Compiler::new().with_lines(lines).compile(&nodes).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = compile(SOURCE);
+        assert!(!program.lines.is_empty());
+        let loaded = read_program(&write_program(&program)).unwrap();
+        assert_eq!(loaded, program);
+        let expected = Vm::new(program).run().unwrap();
+        assert_eq!(Vm::new(loaded).run().unwrap(), expected);
+        // Line table is optional
+        let nodes = Parser::new(SOURCE).unwrap().parse().unwrap();
+        let program = Compiler::new().compile(&nodes).unwrap();
+        assert_eq!(read_program(&write_program(&program)).unwrap(), program);
+    }
+
+    #[test]
+    fn test_error_lines() {
+        let source = "x = 1\n\nfn f(a) {\n    return a / 0\n}\ny = f(x)";
+        let program = read_program(&write_program(&compile(source))).unwrap();
+        let error = Vm::new(program).run().unwrap_err();
+        assert_eq!(error.to_string(), "Line 4: Division by zero");
+    }
+
+    #[test]
+    fn test_invalid_headers() {
+        let bytes = write_program(&compile(SOURCE));
+        assert_eq!(read_program(b""), Err(LoadError::BadMagic));
+        assert_eq!(read_program(b"x = 1"), Err(LoadError::BadMagic));
+        let mut wrong_version = bytes.clone();
+        wrong_version[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            read_program(&wrong_version),
+            Err(LoadError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(matches!(read_program(&trailing), Err(LoadError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_corrupt_files() {
+        let bytes = write_program(&compile(SOURCE));
+        // Every truncated file is rejected
+        for length in 0..bytes.len() {
+            assert!(read_program(&bytes[..length]).is_err(), "Length {}", length);
+        }
+        // Corrupting any single byte must not panic
+        for i in 0..bytes.len() {
+            for value in [0x00, 0x7f, 0xff].iter() {
+                let mut corrupt = bytes.clone();
+                corrupt[i] = *value;
+                let _ = read_program(&corrupt);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_operands() {
+        let mut program = compile("x = 1\nx");
+        program.code.insert(0, Instruction::Jump(100));
+        assert!(matches!(
+            read_program(&write_program(&program)),
+            Err(LoadError::Invalid(_))
+        ));
+        let mut program = compile("x = 1\nx");
+        program.code.insert(0, Instruction::Constant(100));
+        assert!(matches!(
+            read_program(&write_program(&program)),
+            Err(LoadError::Invalid(_))
+        ));
+        // Units have to be named by a literal constant
+        let mut program = compile("1 m");
+        program.code.insert(0, Instruction::WithUnit(0));
+        assert!(matches!(
+            read_program(&write_program(&program)),
+            Err(LoadError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,570 @@
+// Standard libraries
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// Internal modules
+use super::instruction::{Constant, FunctionInfo, Instruction, Program};
+use crate::lexer_and_parser::ast::Node;
+use crate::lexer_and_parser::builtins::Builtin;
+use crate::lexer_and_parser::evaluator::Evaluator;
+use crate::lexer_and_parser::prelude;
+use crate::lexer_and_parser::token::{Keyword, Token};
+use crate::passes::resolver::Resolver;
+
+// Compiler from the syntax tree to bytecode.
+/*
+    Semantics follow the tree walking Evaluator, which stays the reference implementation:
+    top level variables are globals, inside a function every parameter and assigned
+    variable is a local, and any other identifier refers to a global. Functions are not
+    values here: closures, lists, structs, enums, matches, try, throw, ?, imports, calls
+    through anything but a function name and functions used as values are reported as
+    unsupported, and so are calls to builtins like map and to functions of the prelude
+    like assert_eq. `wistful run` evaluates such programs with the Evaluator instead.
+*/
+pub struct Compiler {
+    program: Program,
+    global_slots: HashMap<String, usize>,
+    function_slots: HashMap<String, usize>,
+    // Functions declared anywhere in the program and variables assigned anywhere in it
+    declared_functions: HashSet<String>,
+    assigned: HashSet<String>,
+    // Functions exported by the bundled prelude, only the Evaluator runs it
+    prelude: HashSet<String>,
+    // Local variable slots of the function being compiled, None at top level
+    locals: Option<HashMap<String, usize>>,
+    // Addresses of break jumps to patch, one list per enclosing loop
+    loops: Vec<Vec<usize>>,
+    // Source lines of the statements still to be compiled, see `with_lines`
+    lines: Option<std::vec::IntoIter<usize>>,
+}
+
+// Public methods
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler {
+            program: Program::default(),
+            global_slots: HashMap::new(),
+            function_slots: HashMap::new(),
+            declared_functions: HashSet::new(),
+            assigned: HashSet::new(),
+            prelude: HashSet::new(),
+            locals: None,
+            loops: Vec::new(),
+            lines: None,
+        }
+    }
+
+    // Record a debug line table using the statement lines collected by the parser.
+    pub fn with_lines(mut self, statement_lines: Vec<usize>) -> Self {
+        self.lines = Some(statement_lines.into_iter());
+        self
+    }
+
+    // Compile parsed statements into a program.
+    /*
+        The value of the last statement is the result of the program, as long as the
+        statement is an expression or an if.
+    */
+    pub fn compile(mut self, nodes: &[Node]) -> Result<Program, CompileError> {
+        Resolver::collect_functions(nodes, &mut self.declared_functions);
+        Evaluator::collect_assignments(nodes, &mut self.assigned);
+        self.prelude.extend(prelude::names());
+        self.compile_block_value(nodes)?;
+        self.emit(Instruction::Halt);
+        Ok(self.program)
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+// Private methods
+impl Compiler {
+    // Statements leave nothing on the stack, expressions leave exactly one value.
+    fn is_statement(node: &Node) -> bool {
+        matches!(
+            node,
+            Node::AssignmentExpression { .. }
+                | Node::IfExpression { .. }
+                | Node::WhileExpression { .. }
+                | Node::BreakExpression
+                | Node::FunctionDeclaration { .. }
+                | Node::ReturnExpression(_)
+        )
+    }
+
+    // Kind of a node in words, for error messages, e.g. `Can not compile imports`.
+    fn describe(node: &Node) -> &'static str {
+        match node {
+            Node::NumberExpression(_) | Node::NegativeNumberExpression(_) => "numbers",
+            Node::LiteralExpression(..) => "literals",
+            Node::IdentifierExpression(_) | Node::VariableExpression { .. } => "variables",
+            Node::BinaryExpr { .. } => "arithmetic",
+            Node::AssignmentExpression { .. } => "assignments",
+            Node::ConditionExpression { .. } => "comparisons",
+            Node::IfExpression { .. } | Node::ElseExpression { .. } => "if expressions",
+            Node::QuantityExpression { .. } => "quantities",
+            Node::ConversionExpression { .. } => "unit conversions",
+            Node::WhileExpression { .. } => "while loops",
+            Node::BreakExpression => "break",
+            Node::FunctionDeclaration { .. } => "function declarations",
+            Node::FunctionExpression { .. } => "closures",
+            Node::CallExpression { .. } => "calls",
+            Node::ListExpression(_) => "lists",
+            Node::IndexExpression { .. } => "indexing",
+            Node::StructDeclaration { .. } => "struct declarations",
+            Node::ImplDeclaration { .. } => "impl blocks",
+            Node::StructExpression { .. } => "struct values",
+            Node::FieldExpression { .. } => "fields",
+            Node::FieldAssignmentExpression { .. } => "field assignments",
+            Node::EnumDeclaration { .. } => "enum declarations",
+            Node::VariantExpression { .. } => "enum variants",
+            Node::MatchExpression { .. } => "match expressions",
+            Node::TryExpression { .. } => "try blocks",
+            Node::ThrowExpression(_) => "throw",
+            Node::PropagateExpression(_) => "the ? operator",
+            Node::ImportExpression { .. } => "imports",
+            Node::ExportStatement(_) => "exports",
+            Node::ReturnExpression(_) => "return",
+            Node::EOF(_) => "the end of the source",
+        }
+    }
+
+    // Statements are visited in the same order the parser recorded their lines.
+    fn mark_line(&mut self) {
+        let line = match self.lines.as_mut().and_then(|lines| lines.next()) {
+            Some(line) => line,
+            None => return,
+        };
+        let address = self.program.code.len();
+        match self.program.lines.last_mut() {
+            // Nothing was emitted for the previous statement
+            Some(last) if last.0 == address => last.1 = line,
+            Some(last) if last.1 == line => {}
+            _ => self.program.lines.push((address, line)),
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.program.code.push(instruction);
+        self.program.code.len() - 1
+    }
+
+    // Point a previously emitted jump to the next instruction.
+    fn patch_jump(&mut self, address: usize) {
+        let target = self.program.code.len();
+        match &mut self.program.code[address] {
+            Instruction::Jump(to) | Instruction::JumpIfFalse(to) => *to = target,
+            _ => unreachable!("Tried to patch a non jump instruction"),
+        }
+    }
+
+    fn add_constant(&mut self, constant: Constant) -> usize {
+        // Reuse identical constants. 1 and 1.0 compare equal but must stay distinct.
+        let existing = self.program.constants.iter().position(|c| match (c, &constant) {
+            (Constant::Number(a), Constant::Number(b)) => a == b && a.is_exact() == b.is_exact(),
+            _ => *c == constant,
+        });
+        if let Some(i) = existing {
+            return i;
+        }
+        self.program.constants.push(constant);
+        self.program.constants.len() - 1
+    }
+
+    fn global_slot(&mut self, identifier: &str) -> usize {
+        if let Some(slot) = self.global_slots.get(identifier) {
+            return *slot;
+        }
+        self.program.globals.push(identifier.to_string());
+        let slot = self.program.globals.len() - 1;
+        self.global_slots.insert(identifier.to_string(), slot);
+        slot
+    }
+
+    fn function_slot(&mut self, identifier: &str) -> usize {
+        if let Some(slot) = self.function_slots.get(identifier) {
+            return *slot;
+        }
+        self.program.function_names.push(identifier.to_string());
+        let slot = self.program.function_names.len() - 1;
+        self.function_slots.insert(identifier.to_string(), slot);
+        slot
+    }
+
+    fn compile_block(&mut self, block: &[Node]) -> Result<(), CompileError> {
+        for node in block {
+            self.mark_line();
+            if Compiler::is_statement(node) {
+                self.compile_statement(node)?;
+            } else {
+                self.compile_expression(node)?;
+                self.emit(Instruction::Pop);
+            }
+        }
+        Ok(())
+    }
+
+    // Like `compile_block`, leaving the value of the last statement on the stack.
+    fn compile_block_value(&mut self, block: &[Node]) -> Result<(), CompileError> {
+        let (last, statements) = match block.split_last() {
+            Some(split) => split,
+            None => {
+                self.emit(Instruction::Empty);
+                return Ok(());
+            }
+        };
+        self.compile_block(statements)?;
+        self.mark_line();
+        match last {
+            Node::IfExpression { .. } => self.compile_expression(last)?,
+            _ if Compiler::is_statement(last) => {
+                self.compile_statement(last)?;
+                self.emit(Instruction::Empty);
+            }
+            _ => self.compile_expression(last)?,
+        }
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, node: &Node) -> Result<(), CompileError> {
+        match node {
+            Node::AssignmentExpression {
+                identifier, expr, ..
+            } => {
+                let name = match identifier.as_ref() {
+                    Node::IdentifierExpression(name)
+                    | Node::VariableExpression {
+                        identifier: name, ..
+                    } => name,
+                    _ => {
+                        return Err(CompileError::InvalidStatement(format!(
+                            "Can not assign to {0}",
+                            Compiler::describe(identifier)
+                        )))
+                    }
+                };
+                self.compile_expression(expr)?;
+                match self.locals.as_ref().and_then(|locals| locals.get(name)) {
+                    Some(slot) => {
+                        let slot = *slot;
+                        self.emit(Instruction::StoreLocal(slot))
+                    }
+                    None => {
+                        let slot = self.global_slot(name);
+                        self.emit(Instruction::StoreGlobal(slot))
+                    }
+                };
+            }
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            }
+            | Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => match condition.as_ref() {
+                Some(condition) => {
+                    self.compile_expression(condition)?;
+                    let to_else = self.emit(Instruction::JumpIfFalse(0));
+                    self.compile_block(then_branch)?;
+                    match else_branch.as_ref() {
+                        Some(else_node) => {
+                            let to_end = self.emit(Instruction::Jump(0));
+                            self.patch_jump(to_else);
+                            self.compile_statement(else_node)?;
+                            self.patch_jump(to_end);
+                        }
+                        None => self.patch_jump(to_else),
+                    }
+                }
+                // Else branch
+                None => self.compile_block(then_branch)?,
+            },
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => {
+                let start = self.program.code.len();
+                let mut to_end = None;
+                if let Some(condition) = condition.as_ref() {
+                    self.compile_expression(condition)?;
+                    to_end = Some(self.emit(Instruction::JumpIfFalse(0)));
+                }
+                self.loops.push(Vec::new());
+                self.compile_block(then_branch)?;
+                self.emit(Instruction::Jump(start));
+                if let Some(address) = to_end {
+                    self.patch_jump(address);
+                }
+                for address in self.loops.pop().unwrap() {
+                    self.patch_jump(address);
+                }
+            }
+            Node::BreakExpression => {
+                let address = self.emit(Instruction::Jump(0));
+                match self.loops.last_mut() {
+                    Some(breaks) => breaks.push(address),
+                    None => {
+                        return Err(CompileError::InvalidStatement(
+                            "Found break outside of a loop".to_string(),
+                        ))
+                    }
+                }
+            }
+            Node::ReturnExpression(value) => {
+                if self.locals.is_none() {
+                    return Err(CompileError::InvalidStatement(
+                        "Found return outside of a function".to_string(),
+                    ));
+                }
+                match value.as_ref() {
+                    Some(value) => self.compile_expression(value)?,
+                    None => {
+                        self.emit(Instruction::Empty);
+                    }
+                }
+                self.emit(Instruction::Return);
+            }
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                body,
+                ..
+            } => self.compile_function(identifier, parameters, body)?,
+            _ => {
+                return Err(CompileError::InvalidStatement(format!(
+                    "Expected a statement, got {0}",
+                    Compiler::describe(node)
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    // Function code is emitted inline, behind a jump that skips over it.
+    fn compile_function(
+        &mut self,
+        identifier: &str,
+        parameters: &[String],
+        body: &[Node],
+    ) -> Result<(), CompileError> {
+        let skip = self.emit(Instruction::Jump(0));
+        // Parameters take the first local slots, followed by assigned variables
+        let locals: HashMap<String, usize> = Evaluator::local_names(parameters, body)
+            .into_iter()
+            .enumerate()
+            .map(|(slot, name)| (name, slot))
+            .collect();
+        let function = FunctionInfo {
+            name: self.function_slot(identifier),
+            arity: parameters.len(),
+            locals: locals.len(),
+            entry: self.program.code.len(),
+        };
+        // Function bodies get their own scope and can't break out of enclosing loops
+        let outer_locals = self.locals.replace(locals);
+        let outer_loops = std::mem::take(&mut self.loops);
+        let result = self.compile_block_value(body);
+        self.locals = outer_locals;
+        self.loops = outer_loops;
+        result?;
+        // Falling off the end returns the value of the last statement
+        self.emit(Instruction::Return);
+        self.patch_jump(skip);
+        self.program.functions.push(function);
+        self.emit(Instruction::DefineFunction(self.program.functions.len() - 1));
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, node: &Node) -> Result<(), CompileError> {
+        match node {
+            Node::NumberExpression(f) => {
+                let constant = self.add_constant(Constant::Number(f.clone()));
+                self.emit(Instruction::Constant(constant));
+            }
+            Node::NegativeNumberExpression(expr) => {
+                self.compile_expression(expr)?;
+                self.emit(Instruction::Negate);
+            }
+            Node::LiteralExpression(literal, keyword) => {
+                let constant = match keyword {
+                    Keyword::True => Constant::Boolean(true),
+                    Keyword::False => Constant::Boolean(false),
+                    _ => Constant::Literal(literal.clone()),
+                };
+                let constant = self.add_constant(constant);
+                self.emit(Instruction::Constant(constant));
+            }
+            // Slots of resolved variables belong to the tree walker, the compiler assigns its own
+            Node::IdentifierExpression(identifier)
+            | Node::VariableExpression { identifier, .. } => {
+                match self.locals.as_ref().and_then(|locals| locals.get(identifier)) {
+                    Some(slot) => {
+                        let slot = *slot;
+                        self.emit(Instruction::LoadLocal(slot))
+                    }
+                    // Functions are not stored in variables here
+                    None if (self.declared_functions.contains(identifier)
+                        || self.prelude.contains(identifier))
+                        && !self.assigned.contains(identifier) =>
+                    {
+                        return Err(CompileError::Unsupported(
+                            "functions used as values".to_string(),
+                        ))
+                    }
+                    None => {
+                        let slot = self.global_slot(identifier);
+                        self.emit(Instruction::LoadGlobal(slot))
+                    }
+                };
+            }
+            Node::BinaryExpr {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                self.compile_expression(l_expr)?;
+                self.compile_expression(r_expr)?;
+                let instruction = match operator {
+                    Token::Add => Instruction::Add,
+                    Token::Subtract => Instruction::Subtract,
+                    Token::Multiply => Instruction::Multiply,
+                    Token::Divide => Instruction::Divide,
+                    Token::Pow => Instruction::Pow,
+                    _ => {
+                        return Err(CompileError::Unsupported(format!(
+                            "the operator {0}",
+                            operator.symbol()
+                        )))
+                    }
+                };
+                self.emit(instruction);
+            }
+            Node::ConditionExpression {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                self.compile_expression(l_expr)?;
+                self.compile_expression(r_expr)?;
+                let instruction = match operator {
+                    Token::Equals => Instruction::Equals,
+                    Token::NotEquals => Instruction::NotEquals,
+                    Token::GreaterThan => Instruction::GreaterThan,
+                    Token::LessThan => Instruction::LessThan,
+                    Token::GreaterThanOrEquals => Instruction::GreaterThanOrEquals,
+                    Token::LessThanOrEquals => Instruction::LessThanOrEquals,
+                    _ => {
+                        return Err(CompileError::Unsupported(format!(
+                            "the comparison {0}",
+                            operator.symbol()
+                        )))
+                    }
+                };
+                self.emit(instruction);
+            }
+            // Value of the branch taken, nothing without one
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            }
+            | Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => match condition.as_ref() {
+                Some(condition) => {
+                    self.compile_expression(condition)?;
+                    let to_else = self.emit(Instruction::JumpIfFalse(0));
+                    self.compile_block_value(then_branch)?;
+                    let to_end = self.emit(Instruction::Jump(0));
+                    self.patch_jump(to_else);
+                    match else_branch.as_ref() {
+                        Some(else_node) => self.compile_expression(else_node)?,
+                        None => {
+                            self.emit(Instruction::Empty);
+                        }
+                    }
+                    self.patch_jump(to_end);
+                }
+                // Else branch
+                None => self.compile_block_value(then_branch)?,
+            },
+            Node::QuantityExpression { value, unit } => {
+                self.compile_expression(value)?;
+                let constant = self.add_constant(Constant::Literal(unit.clone()));
+                self.emit(Instruction::WithUnit(constant));
+            }
+            Node::ConversionExpression { expr, unit } => {
+                self.compile_expression(expr)?;
+                let constant = self.add_constant(Constant::Literal(unit.clone()));
+                self.emit(Instruction::Convert(constant));
+            }
+            Node::CallExpression { callee, arguments } => {
+                let name = match callee.as_ref() {
+                    Node::IdentifierExpression(name)
+                        if !self.declared_functions.contains(name)
+                            && Builtin::from_name(name).is_some() =>
+                    {
+                        return Err(CompileError::Unsupported(format!(
+                            "calls to the builtin {0}",
+                            name
+                        )))
+                    }
+                    Node::IdentifierExpression(name)
+                        if !self.declared_functions.contains(name) && self.prelude.contains(name) =>
+                    {
+                        return Err(CompileError::Unsupported(format!(
+                            "calls to the prelude function {0}",
+                            name
+                        )))
+                    }
+                    Node::IdentifierExpression(name) => self.function_slot(name),
+                    _ => {
+                        return Err(CompileError::Unsupported(
+                            "calls through anything but a function name".to_string(),
+                        ))
+                    }
+                };
+                for argument in arguments {
+                    self.compile_expression(argument)?;
+                }
+                self.emit(Instruction::Call {
+                    name,
+                    arguments: arguments.len(),
+                });
+            }
+            // Statements used as values produce an empty result, like in the Evaluator
+            _ if Compiler::is_statement(node) => {
+                self.compile_statement(node)?;
+                self.emit(Instruction::Empty);
+            }
+            _ => return Err(CompileError::Unsupported(Compiler::describe(node).to_string())),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    Unsupported(String),
+    InvalidStatement(String),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            self::CompileError::Unsupported(e) => write!(f, "Can not compile {}", e),
+            self::CompileError::InvalidStatement(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
@@ -0,0 +1,133 @@
+// Standard libraries
+use std::fmt;
+
+// Internal modules
+use crate::lexer_and_parser::numeric::Number;
+
+// Values stored in the constant pool of a program.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Number(Number),
+    Literal(String),
+    Boolean(bool),
+}
+
+// Instruction set of the stack based VM.
+/*
+    Operands are indices: into the constant pool, global or local variable slots,
+    function tables or instruction addresses. Everything else works on the value stack.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    // Push a value from the constant pool
+    Constant(usize),
+    // Push an empty result, e.g. the value of a function without return value
+    Empty,
+    LoadGlobal(usize),
+    StoreGlobal(usize),
+    LoadLocal(usize),
+    StoreLocal(usize),
+    // Arithmetic, pops two operands (one for Negate) and pushes the result
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Pow,
+    Negate,
+    // Comparisons, pops two operands and pushes a boolean
+    Equals,
+    NotEquals,
+    GreaterThan,
+    LessThan,
+    GreaterThanOrEquals,
+    LessThanOrEquals,
+    // Attach the unit named by a constant to the number on top of the stack
+    WithUnit(usize),
+    // Convert the quantity on top of the stack to the unit named by a constant
+    Convert(usize),
+    Jump(usize),
+    // Pops a boolean and jumps if it is false
+    JumpIfFalse(usize),
+    // Bind a function to its name, from this point on it can be called
+    DefineFunction(usize),
+    // Call the function bound to a name with the given amount of arguments from the stack
+    Call { name: usize, arguments: usize },
+    // Return the value on top of the stack to the caller
+    Return,
+    Pop,
+    // End of program, value on top of the stack is the result
+    Halt,
+}
+
+// Compiled function, its code is a part of the program's instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionInfo {
+    // Index into the function name table
+    pub name: usize,
+    pub arity: usize,
+    // Amount of local variable slots, parameters take the first slots
+    pub locals: usize,
+    // Address of the first instruction
+    pub entry: usize,
+}
+
+// Compiled program. Execution starts from the first instruction.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub constants: Vec<Constant>,
+    // Names of the global variable slots
+    pub globals: Vec<String>,
+    // Names functions can be bound to
+    pub function_names: Vec<String>,
+    pub functions: Vec<FunctionInfo>,
+    pub code: Vec<Instruction>,
+    // Debug line table: (address, source line) pairs sorted by address. Every instruction
+    // belongs to the closest entry at or before its address. Empty when not available.
+    pub lines: Vec<(usize, usize)>,
+}
+
+// Public methods
+impl Program {
+    // Source line of the instruction at the given address, if known.
+    pub fn line_at(&self, address: usize) -> Option<usize> {
+        let entry = match self.lines.binary_search_by_key(&address, |(start, _)| *start) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        Some(self.lines[entry].1)
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Number(n) => write!(f, "{}", n),
+            Constant::Literal(s) => write!(f, "{:?}", s),
+            Constant::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+// Human readable listing of the program, used for debugging.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (address, instruction) in self.code.iter().enumerate() {
+            for function in self.functions.iter().filter(|function| function.entry == address) {
+                writeln!(f, "{0}:", self.function_names[function.name])?;
+            }
+            let operand = match instruction {
+                Instruction::Constant(i) | Instruction::WithUnit(i) | Instruction::Convert(i) => {
+                    format!("  ; {}", self.constants[*i])
+                }
+                Instruction::LoadGlobal(i) | Instruction::StoreGlobal(i) => {
+                    format!("  ; {}", self.globals[*i])
+                }
+                Instruction::Call { name, .. } => format!("  ; {}", self.function_names[*name]),
+                _ => String::new(),
+            };
+            writeln!(f, "{0:>5} {1:?}{2}", address, instruction, operand)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,273 @@
+// Libraries
+use std::collections::HashMap;
+use std::error;
+
+// Internal modules
+use super::instruction::{Constant, Instruction, Program};
+use crate::lexer_and_parser::builtins::amount;
+use crate::lexer_and_parser::evaluator::{apply_arithmetic, EvalResult, MAX_CALL_DEPTH};
+use crate::lexer_and_parser::token::Token;
+use crate::lexer_and_parser::units::{Quantity, UnitRegistry};
+
+// Stack based virtual machine executing compiled programs.
+pub struct Vm {
+    program: Program,
+    stack: Vec<EvalResult>,
+    // Values of global variable slots, None until assigned
+    globals: Vec<Option<EvalResult>>,
+    // Function bound to each function name, None until declared
+    functions: Vec<Option<usize>>,
+    frames: Vec<Frame>,
+    // Units known to the scripts. Hosts can register their own units here.
+    pub units: UnitRegistry,
+}
+
+// Call frame of a running function.
+struct Frame {
+    return_address: usize,
+    locals: Vec<Option<EvalResult>>,
+    // Stack height when the function was called, restored on return
+    stack_base: usize,
+}
+
+// Public methods
+impl Vm {
+    pub fn new(program: Program) -> Self {
+        Vm {
+            globals: vec![None; program.globals.len()],
+            functions: vec![None; program.function_names.len()],
+            program,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            units: UnitRegistry::default(),
+        }
+    }
+
+    // Run the program from the start and return its result.
+    pub fn run(&mut self) -> Result<EvalResult, Box<dyn error::Error>> {
+        self.stack.clear();
+        self.frames.clear();
+        let mut ip = 0;
+        match self.execute(&mut ip) {
+            Ok(result) => Ok(result),
+            // Instruction pointer has already moved past the failing instruction
+            Err(e) => match self.program.line_at(ip.saturating_sub(1)) {
+                Some(line) => Err(format!("Line {0}: {1}", line, e).into()),
+                None => Err(e),
+            },
+        }
+    }
+
+    // Get the value of a global variable.
+    pub fn get_variable(&self, identifier: &str) -> Option<EvalResult> {
+        let slot = self.program.globals.iter().position(|name| name == identifier)?;
+        self.globals[slot].clone()
+    }
+
+    // Every global variable that has been assigned.
+    pub fn globals(&self) -> HashMap<String, EvalResult> {
+        self.program
+            .globals
+            .iter()
+            .zip(self.globals.iter())
+            .filter_map(|(name, value)| value.clone().map(|value| (name.clone(), value)))
+            .collect()
+    }
+}
+
+// Private methods
+impl Vm {
+    fn execute(&mut self, ip: &mut usize) -> Result<EvalResult, Box<dyn error::Error>> {
+        loop {
+            let instruction = match self.program.code.get(*ip) {
+                Some(instruction) => *instruction,
+                None => return Err(format!("Instruction address {0} out of bounds", ip).into()),
+            };
+            *ip += 1;
+            match instruction {
+                Instruction::Constant(i) => {
+                    let value = match &self.program.constants[i] {
+                        Constant::Number(n) => EvalResult::Number(n.clone()),
+                        Constant::Literal(s) => EvalResult::Literal(s.clone()),
+                        Constant::Boolean(b) => EvalResult::Boolean(*b),
+                    };
+                    self.stack.push(value);
+                }
+                Instruction::Empty => self.stack.push(EvalResult::EmptyResult),
+                Instruction::LoadGlobal(slot) => match &self.globals[slot] {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => return Err("Could not find a variable with given identifier".into()),
+                },
+                Instruction::StoreGlobal(slot) => {
+                    let value = self.pop()?;
+                    if Vm::is_storable(&value) {
+                        self.globals[slot] = Some(value);
+                    }
+                }
+                Instruction::LoadLocal(slot) => {
+                    let frame = self.frames.last().ok_or("No function is being called")?;
+                    match frame.locals.get(slot) {
+                        Some(Some(value)) => self.stack.push(value.clone()),
+                        Some(None) => {
+                            return Err("Could not find a variable with given identifier".into())
+                        }
+                        None => return Err(format!("Invalid local variable slot {0}", slot).into()),
+                    }
+                }
+                Instruction::StoreLocal(slot) => {
+                    let value = self.pop()?;
+                    let frame = self.frames.last_mut().ok_or("No function is being called")?;
+                    let local = match frame.locals.get_mut(slot) {
+                        Some(local) => local,
+                        None => return Err(format!("Invalid local variable slot {0}", slot).into()),
+                    };
+                    if Vm::is_storable(&value) {
+                        *local = Some(value);
+                    }
+                }
+                Instruction::Add => self.arithmetic(Token::Add)?,
+                Instruction::Subtract => self.arithmetic(Token::Subtract)?,
+                Instruction::Multiply => self.arithmetic(Token::Multiply)?,
+                Instruction::Divide => self.arithmetic(Token::Divide)?,
+                Instruction::Pow => self.arithmetic(Token::Pow)?,
+                Instruction::Negate => {
+                    let value = self.pop_quantity()?;
+                    self.stack.push(EvalResult::from_quantity(value.negate()));
+                }
+                Instruction::Equals => self.comparison(Token::Equals)?,
+                Instruction::NotEquals => self.comparison(Token::NotEquals)?,
+                Instruction::GreaterThan => self.comparison(Token::GreaterThan)?,
+                Instruction::LessThan => self.comparison(Token::LessThan)?,
+                Instruction::GreaterThanOrEquals => self.comparison(Token::GreaterThanOrEquals)?,
+                Instruction::LessThanOrEquals => self.comparison(Token::LessThanOrEquals)?,
+                Instruction::WithUnit(i) => {
+                    let name = self.constant_literal(i)?;
+                    let unit = self.units.parse(&name)?;
+                    let value = self.pop_quantity()?;
+                    let quantity = Quantity::with_unit(value.value, &name, &unit);
+                    self.stack.push(EvalResult::from_quantity(quantity));
+                }
+                Instruction::Convert(i) => {
+                    let name = self.constant_literal(i)?;
+                    let unit = self.units.parse(&name)?;
+                    let value = self.pop_quantity()?;
+                    self.stack.push(EvalResult::from_quantity(value.convert(&name, &unit)?));
+                }
+                Instruction::Jump(address) => *ip = address,
+                Instruction::JumpIfFalse(address) => match self.pop()? {
+                    EvalResult::Boolean(true) => {}
+                    EvalResult::Boolean(false) => *ip = address,
+                    other => {
+                        return Err(format!("Condition must be a boolean, got {0}", other).into())
+                    }
+                },
+                Instruction::DefineFunction(i) => {
+                    let name = self.program.functions[i].name;
+                    self.functions[name] = Some(i);
+                }
+                Instruction::Call { name, arguments } => {
+                    *ip = self.call(name, arguments, *ip)?;
+                }
+                Instruction::Return => {
+                    let value = self.pop()?;
+                    let frame = self.frames.pop().ok_or("Found return outside of a function")?;
+                    self.stack.truncate(frame.stack_base);
+                    self.stack.push(value);
+                    *ip = frame.return_address;
+                }
+                Instruction::Pop => {
+                    self.pop()?;
+                }
+                Instruction::Halt => return self.pop(),
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Result<EvalResult, Box<dyn error::Error>> {
+        self.stack.pop().ok_or_else(|| "Stack underflow".into())
+    }
+
+    fn pop_quantity(&mut self) -> Result<Quantity, Box<dyn error::Error>> {
+        match self.pop()?.to_quantity() {
+            Some(quantity) => Ok(quantity),
+            None => Err("Not implemented.".into()),
+        }
+    }
+
+    fn constant_literal(&self, i: usize) -> Result<String, Box<dyn error::Error>> {
+        match &self.program.constants[i] {
+            Constant::Literal(s) => Ok(s.clone()),
+            other => Err(format!("Expected a unit, got {0}", other).into()),
+        }
+    }
+
+    // Same rule as in the Evaluator: only actual values can be stored in variables.
+    fn is_storable(value: &EvalResult) -> bool {
+        matches!(
+            value,
+            EvalResult::Number(_)
+                | EvalResult::Quantity(_)
+                | EvalResult::Literal(_)
+                | EvalResult::Boolean(_)
+        )
+    }
+
+    fn arithmetic(&mut self, operator: Token) -> Result<(), Box<dyn error::Error>> {
+        let r_value = self.pop_quantity()?;
+        let l_value = self.pop_quantity()?;
+        let result = apply_arithmetic(&l_value, &operator, &r_value)?;
+        self.stack.push(EvalResult::from_quantity(result));
+        Ok(())
+    }
+
+    fn comparison(&mut self, operator: Token) -> Result<(), Box<dyn error::Error>> {
+        let r_value = self.pop()?;
+        let l_value = self.pop()?;
+        self.stack.push(EvalResult::Boolean(l_value.compare(&operator, &r_value)?));
+        Ok(())
+    }
+
+    // Set up a call frame and return the address to continue from.
+    fn call(
+        &mut self,
+        name: usize,
+        arguments: usize,
+        return_address: usize,
+    ) -> Result<usize, Box<dyn error::Error>> {
+        let identifier = &self.program.function_names[name];
+        let function = match self.functions[name] {
+            Some(i) => &self.program.functions[i],
+            None => return Err(format!("Could not find a function named {0}", identifier).into()),
+        };
+        if arguments != function.arity {
+            return Err(format!(
+                "Function {0} expects {1}, got {2}",
+                identifier,
+                amount(function.arity, "argument"),
+                arguments
+            )
+            .into());
+        }
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err("Maximum call depth exceeded".into());
+        }
+        let mut locals = vec![None; function.locals];
+        let stack_base = match self.stack.len().checked_sub(arguments) {
+            Some(base) => base,
+            None => return Err("Stack underflow".into()),
+        };
+        for (slot, value) in self.stack.drain(stack_base..).enumerate() {
+            if !Vm::is_storable(&value) {
+                return Err(format!("Could not pass {0} to {1}", value, identifier).into());
+            }
+            locals[slot] = Some(value);
+        }
+        let entry = function.entry;
+        self.frames.push(Frame {
+            return_address,
+            locals,
+            stack_base,
+        });
+        Ok(entry)
+    }
+}
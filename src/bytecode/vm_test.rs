@@ -0,0 +1,196 @@
+// Differential tests, the VM has to agree with the tree walking Evaluator.
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::bytecode::compiler::{CompileError, Compiler};
+    use crate::bytecode::vm::Vm;
+    use crate::lexer_and_parser::evaluator::{EvalResult, Evaluator};
+    use crate::lexer_and_parser::parser::Parser;
+
+    // Result of the last statement (if it is an expression) and final global variables.
+    type Outcome = (Result<EvalResult, String>, HashMap<String, EvalResult>);
+
+    fn run_evaluator(source: &str) -> Outcome {
+        let mut evaluator = Evaluator::new(None);
+        let mut result = Ok(EvalResult::EmptyResult);
+        for expression in Parser::new(source).unwrap().parse().unwrap() {
+            evaluator.ast = Some(expression);
+            match evaluator.start_evaluating() {
+                // Statements don't produce a value for the program
+                Ok(EvalResult::Assignment { .. }) => result = Ok(EvalResult::EmptyResult),
+                Ok(value) => result = Ok(value),
+                Err(e) => {
+                    result = Err(e.to_string());
+                    break;
+                }
+            }
+        }
+        let globals = evaluator.globals();
+        (result, globals)
+    }
+
+    fn run_vm(source: &str) -> Outcome {
+        let nodes = Parser::new(source).unwrap().parse().unwrap();
+        let program = Compiler::new().compile(&nodes).unwrap();
+        let mut vm = Vm::new(program);
+        let result = vm.run().map_err(|e| e.to_string());
+        (result, vm.globals())
+    }
+
+    // Run the source with both implementations and make sure the results are identical.
+    fn assert_same(source: &str) -> EvalResult {
+        let (expected_result, expected_globals) = run_evaluator(source);
+        let (result, globals) = run_vm(source);
+        assert_eq!(result, expected_result, "Results differ for:\n{}", source);
+        assert_eq!(globals, expected_globals, "Globals differ for:\n{}", source);
+        result.unwrap_or(EvalResult::EmptyResult)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_same("1 + 2 * 3 - 4 / 2");
+        assert_same("x = 10\nx * ((1+2)*2)");
+        assert_same("1/3 + 1/6");
+        assert_same("2^200 - 1");
+        assert_same("x = 0 - 4611686018427387904 * 2\nx / (0 - 1)");
+        assert_same("-(2.5 * 4) ^ 2");
+        assert_same("v = 90 km/h\nt = 2 h\nd = v * t\nd in mi");
+        assert_same("x = (2 m)^100\nx * x");
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let source = "
+            x = 10
+            if x == 15 {
+                x = 25
+            }
+            elif x == 10 {
+                y = 10
+                x = y + 2
+            }
+            else {
+                x = 17
+            }
+            x";
+        assert_eq!(assert_same(source).to_string(), "12");
+        assert_same("a = 1\nif a > 2 { b = 1 } else { b = 2 }\nb");
+        assert_same("a = 5 m\nif a >= 500 cm { b = true }\nb");
+    }
+
+    #[test]
+    fn test_if_values() {
+        let source = "x = 2\ny = if x == 1 { 10 } elif x == 2 { 20 } else { 30 }\ny + 1";
+        assert_eq!(assert_same(source).to_string(), "21");
+        assert_same("x = 1\n1 + if x > 0 {\n    a = 2\n    a * 3\n} else { 0 }");
+        assert_same("fn f(n) {\n    return if n > 0 { n } else { 0 - n }\n}\nf(0 - 3)");
+        assert_same("x = 1\nif x > 1 { 5 }");
+        assert_same("x = 1\nif x == 1 { y = 2 } else { 3 }");
+        // Functions return the value of their last statement
+        assert_eq!(assert_same("fn f(a) { a * 2 }\nf(21)").to_string(), "42");
+        let source = "fn fact(n) {\n    if n < 2 { 1 } else { n * fact(n - 1) }\n}\nfact(5)";
+        assert_eq!(assert_same(source).to_string(), "120");
+        assert_same("fn f(n) {\n    if n > 0 { 1 }\n}\nf(0)");
+        assert_same("fn f() {\n    x = 1\n}\nf()");
+    }
+
+    #[test]
+    fn test_while_loops() {
+        let source = "
+            i = 0
+            total = 0
+            while i < 100 {
+                i = i + 1
+                if i == 50 {
+                    break
+                }
+                total = total + i
+            }
+            total";
+        assert_eq!(assert_same(source).to_string(), "1225");
+        assert_same("n = 0\nwhile {\n n = n + 1\n if n >= 10 { break }\n}\nn");
+    }
+
+    #[test]
+    fn test_functions() {
+        let source = "
+            fn factorial(n) {
+                if n <= 1 {
+                    return 1
+                }
+                return n * factorial(n - 1)
+            }
+            factorial(30)";
+        assert_same(source);
+        let source = "
+            counter = 0
+            fn fib(n) {
+                total = counter
+                if n < 2 {
+                    return n
+                }
+                return fib(n - 1) + fib(n - 2)
+            }
+            fn nothing() {
+                x = 1
+            }
+            result = fib(15)
+            nothing()";
+        assert_same(source);
+    }
+
+    #[test]
+    fn test_errors() {
+        // Both implementations fail, the messages are not compared
+        for source in [
+            "undefined + 1",
+            "1 / 0",
+            "5 m + 2 s",
+            "if 1 { x = 1 }",
+            "fn f(a) { return a }\nf(1, 2)",
+            "missing(1)",
+            "fn f(n) { return f(n + 1) }\nf(0)",
+            "fn f() { return y }\ny = 1\nfn g() { y = y + 1 }\ng()",
+        ]
+        .iter()
+        {
+            let (expected_result, _) = run_evaluator(source);
+            let (result, _) = run_vm(source);
+            assert!(expected_result.is_err(), "Evaluator should fail for {}", source);
+            assert!(result.is_err(), "VM should fail for {}", source);
+        }
+    }
+
+    #[test]
+    fn test_invalid_statements() {
+        for source in ["break", "return 1", "fn f() { break }"].iter() {
+            let nodes = Parser::new(source).unwrap().parse().unwrap();
+            assert!(Compiler::new().compile(&nodes).is_err(), "{} should not compile", source);
+        }
+    }
+
+    #[test]
+    fn test_unsupported() {
+        // The command line runs these with the Evaluator
+        for (source, message) in [
+            ("len([1])", "Can not compile calls to the builtin len"),
+            ("assert_eq(1, 1)", "Can not compile calls to the prelude function assert_eq"),
+            ("f = assert\nf(true)", "Can not compile functions used as values"),
+            ("fn f() { 1 }\ng = f", "Can not compile functions used as values"),
+            ("fn(x) { x }", "Can not compile closures"),
+            ("import \"a.wf\" as a", "Can not compile imports"),
+        ]
+        .iter()
+        {
+            let nodes = Parser::new(source).unwrap().parse().unwrap();
+            match Compiler::new().compile(&nodes) {
+                Err(error @ CompileError::Unsupported(_)) => assert_eq!(error.to_string(), *message),
+                _ => panic!("{} should not compile", source),
+            }
+        }
+        // Declared functions shadow builtins
+        assert_same("fn len(x) { return 1 }
+len(5)");
+    }
+}
@@ -1,8 +1,23 @@
 // Add public modules
+pub mod arena;
 pub mod ast;
+pub mod ast_printer;
+pub mod builtins;
 pub mod evaluator;
+pub mod modules;
+pub mod numeric;
 pub mod parser;
-mod token;
+pub mod prelude;
+pub mod token;
 pub mod tokenizer;
+pub mod units;
+pub mod visitor;
 // Test modules
+pub mod arena_test;
+pub mod ast_printer_test;
+pub mod builtins_test;
+pub mod evaluator_test;
+pub mod modules_test;
 pub mod parser_test;
+pub mod prelude_test;
+pub mod visitor_test;
@@ -0,0 +1,735 @@
+// Standard libraries
+use std::collections::HashMap;
+use std::ops::Index;
+use std::vec;
+
+// Internal modules
+use super::ast::{MatchArm, Node, Pattern, Slot, Type};
+use super::evaluator::Evaluator;
+use super::numeric::Number;
+use super::token::{Keyword, Token};
+use super::tokenizer::Span;
+
+// Arena allocated syntax tree
+/*
+    Passes rewrite the boxed `Node` tree, the evaluator runs a lowered copy of it where
+    every node lives in one vector and refers to its children by index:
+
+        x = 1 + 2       nodes: [0] Identifier x
+                               [1] Number 1
+                               [2] Number 2
+                               [3] Binary 1 + 2
+                               [4] Assignment 0 = 3
+                        lists: [4]
+                        root:  lists[0..1]
+
+    Children are lowered before their parent. Statements of a block and arguments of a
+    call are stored next to each other in `lists`, so a block is just a range.
+
+    Anything known about a node beyond its syntax lives in side tables indexed by the
+    same NodeId, instead of in the node itself:
+    - the line of the statement a node belongs to, from the parser's statement lines
+    - the span of that statement on its first line, from the parser's statement spans
+    - the storage slot of resolved variables, from the resolver's VariableExpressions
+    - the known type of variables, from the type checker
+
+    Nodes are never removed, so NodeIds stay valid for the lifetime of the Ast. Shared
+    behind an Rc, function bodies can keep pointing into the Ast they were declared in.
+*/
+#[derive(Debug)]
+pub struct Ast {
+    nodes: Vec<AstNode>,
+    lists: Vec<NodeId>,
+    root: NodeList,
+    // Side tables, one entry per node
+    lines: Vec<Option<usize>>,
+    spans: Vec<Option<Span>>,
+    slots: Vec<Option<Slot>>,
+    types: Vec<Option<Type>>,
+}
+
+// Index of a node in its Ast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+// Consecutive entries of `Ast::lists`, e.g. the statements of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeList {
+    start: u32,
+    len: u32,
+}
+
+// Nodes mirror the variants of `Node`. Resolved variables are identifiers with a slot.
+#[derive(Debug, PartialEq, Clone)]
+pub enum AstNode {
+    Number(Number),
+    Negative(NodeId),
+    Literal(String, Keyword),
+    Identifier(String),
+    Binary {
+        l_expr: NodeId,
+        operator: Token,
+        r_expr: NodeId,
+    },
+    Assignment {
+        identifier: NodeId,
+        assignment_operator: Token,
+        expr: NodeId,
+        annotation: Option<Type>,
+    },
+    Condition {
+        l_expr: NodeId,
+        operator: Token,
+        r_expr: NodeId,
+    },
+    If {
+        condition: Option<NodeId>,
+        then_branch: NodeList,
+        else_branch: Option<NodeId>,
+    },
+    Else {
+        condition: Option<NodeId>,
+        then_branch: NodeList,
+        else_branch: Option<NodeId>,
+    },
+    Quantity {
+        value: NodeId,
+        unit: String,
+    },
+    Conversion {
+        expr: NodeId,
+        unit: String,
+    },
+    While {
+        condition: Option<NodeId>,
+        then_branch: NodeList,
+    },
+    Break,
+    Function {
+        identifier: String,
+        parameters: Vec<String>,
+        // Parameters and assigned variables in slot order, see `Evaluator::local_names`
+        locals: Vec<String>,
+        body: NodeList,
+    },
+    Closure {
+        parameters: Vec<String>,
+        locals: Vec<String>,
+        body: NodeList,
+    },
+    Call {
+        callee: NodeId,
+        arguments: NodeList,
+    },
+    List(NodeList),
+    Index {
+        expr: NodeId,
+        index: NodeId,
+    },
+    Struct {
+        identifier: String,
+        fields: Vec<String>,
+    },
+    // Every method is a Function
+    Impl {
+        identifier: String,
+        methods: NodeList,
+    },
+    // Field names and their values, in the order they are written
+    StructValue {
+        identifier: String,
+        fields: Vec<String>,
+        values: NodeList,
+    },
+    Field {
+        expr: NodeId,
+        field: String,
+    },
+    FieldAssignment {
+        expr: NodeId,
+        field: String,
+        value: NodeId,
+    },
+    // Variant names and the amount of values they hold
+    Enum {
+        identifier: String,
+        variants: Vec<(String, usize)>,
+    },
+    Variant {
+        enum_name: String,
+        variant: String,
+        arguments: NodeList,
+    },
+    Match {
+        expr: NodeId,
+        arms: Vec<AstArm>,
+    },
+    // The catch is its binding and block
+    Try {
+        body: NodeList,
+        catch: Option<(NodeId, NodeList)>,
+        finally: Option<NodeList>,
+    },
+    Throw(NodeId),
+    Propagate(NodeId),
+    Import {
+        path: String,
+        alias: Option<NodeId>,
+        names: NodeList,
+    },
+    Export(Vec<String>),
+    Return(Option<NodeId>),
+    EOF,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AstArm {
+    pub pattern: AstPattern,
+    pub guard: Option<NodeId>,
+    pub body: NodeList,
+}
+
+// Patterns mirror `Pattern`, bindings are identifiers with a slot
+#[derive(Debug, PartialEq, Clone)]
+pub enum AstPattern {
+    Wildcard,
+    Binding(NodeId),
+    Literal(NodeId),
+    Range {
+        start: NodeId,
+        end: NodeId,
+        inclusive: bool,
+    },
+    List {
+        elements: Vec<AstPattern>,
+        rest: Option<Box<AstPattern>>,
+    },
+    Struct {
+        identifier: String,
+        fields: Vec<(String, AstPattern)>,
+    },
+    Variant {
+        enum_name: String,
+        variant: String,
+        elements: Vec<AstPattern>,
+    },
+}
+
+// Public methods
+impl Ast {
+    // Lower parsed (or resolved) statements into a new arena.
+    pub fn new(nodes: Vec<Node>) -> Self {
+        Ast::with_lines(nodes, Vec::new())
+    }
+
+    // Same as `new`, with the statement lines collected by the parser. They are in the
+    // same pre-order as the statements are lowered in.
+    pub fn with_lines(nodes: Vec<Node>, statement_lines: Vec<usize>) -> Self {
+        Ast::lower(nodes, statement_lines, Vec::new())
+    }
+
+    // Same as `with_lines`, with the statement spans collected by the parser. The lines
+    // are the ones of the spans.
+    pub fn with_spans(nodes: Vec<Node>, statement_spans: Vec<Span>) -> Self {
+        let lines = statement_spans.iter().map(|span| span.line).collect();
+        Ast::lower(nodes, lines, statement_spans)
+    }
+
+    // Top level statements
+    pub fn root(&self) -> NodeList {
+        self.root
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Nodes of a list, in source order.
+    pub fn list(&self, list: NodeList) -> &[NodeId] {
+        let start = list.start as usize;
+        &self.lists[start..start + list.len as usize]
+    }
+
+    // Children of a node in source order, blocks and arguments included.
+    pub fn children(&self, id: NodeId) -> Vec<NodeId> {
+        let mut children = Vec::new();
+        match &self[id] {
+            AstNode::Number(_)
+            | AstNode::Literal(..)
+            | AstNode::Identifier(_)
+            | AstNode::Break
+            | AstNode::Struct { .. }
+            | AstNode::Enum { .. }
+            | AstNode::Export(_)
+            | AstNode::EOF => {}
+            AstNode::Negative(expr)
+            | AstNode::Quantity { value: expr, .. }
+            | AstNode::Conversion { expr, .. } => children.push(*expr),
+            AstNode::Binary { l_expr, r_expr, .. } | AstNode::Condition { l_expr, r_expr, .. } => {
+                children.extend([*l_expr, *r_expr])
+            }
+            AstNode::Assignment {
+                identifier, expr, ..
+            } => children.extend([*identifier, *expr]),
+            AstNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            }
+            | AstNode::Else {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                children.extend(condition);
+                children.extend(self.list(*then_branch));
+                children.extend(else_branch);
+            }
+            AstNode::While {
+                condition,
+                then_branch,
+            } => {
+                children.extend(condition);
+                children.extend(self.list(*then_branch));
+            }
+            AstNode::Function { body, .. } | AstNode::Closure { body, .. } => {
+                children.extend(self.list(*body))
+            }
+            AstNode::Call { callee, arguments } => {
+                children.push(*callee);
+                children.extend(self.list(*arguments));
+            }
+            AstNode::List(elements) => children.extend(self.list(*elements)),
+            AstNode::Index { expr, index } => children.extend([*expr, *index]),
+            AstNode::Impl { methods, .. } => children.extend(self.list(*methods)),
+            AstNode::StructValue { values, .. } => children.extend(self.list(*values)),
+            AstNode::Field { expr, .. } => children.push(*expr),
+            AstNode::FieldAssignment { expr, value, .. } => children.extend([*expr, *value]),
+            AstNode::Variant { arguments, .. } => children.extend(self.list(*arguments)),
+            AstNode::Match { expr, arms } => {
+                children.push(*expr);
+                for arm in arms {
+                    children.extend(arm.pattern.nodes());
+                    children.extend(arm.guard);
+                    children.extend(self.list(arm.body));
+                }
+            }
+            AstNode::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                children.extend(self.list(*body));
+                if let Some((binding, body)) = catch {
+                    children.push(*binding);
+                    children.extend(self.list(*body));
+                }
+                if let Some(finally) = finally {
+                    children.extend(self.list(*finally));
+                }
+            }
+            AstNode::Throw(value) | AstNode::Propagate(value) => children.push(*value),
+            AstNode::Import { alias, names, .. } => {
+                children.extend(*alias);
+                children.extend(self.list(*names));
+            }
+            AstNode::Return(value) => children.extend(value),
+        }
+        children
+    }
+
+    // Line of the statement containing the node, if the lines are known.
+    pub fn line(&self, id: NodeId) -> Option<usize> {
+        self.lines[id.index()]
+    }
+
+    // Span of the statement containing the node, if the spans are known.
+    pub fn span(&self, id: NodeId) -> Option<Span> {
+        self.spans[id.index()]
+    }
+
+    // Storage slot of a variable bound by the resolver.
+    pub fn slot(&self, id: NodeId) -> Option<Slot> {
+        self.slots[id.index()]
+    }
+
+    pub fn node_type(&self, id: NodeId) -> Option<Type> {
+        self.types[id.index()].clone()
+    }
+
+    // Fill the type table from the variable types known to the type checker, see
+    // `TypeChecker::variable_types`. Only nodes with a known line can be matched.
+    pub fn annotate_types(&mut self, variable_types: &HashMap<(usize, String), Type>) {
+        for (index, node) in self.nodes.iter().enumerate() {
+            if let (AstNode::Identifier(name), Some(line)) = (node, self.lines[index]) {
+                self.types[index] = variable_types.get(&(line, name.clone())).cloned();
+            }
+        }
+    }
+}
+
+impl Index<NodeId> for Ast {
+    type Output = AstNode;
+
+    fn index(&self, id: NodeId) -> &AstNode {
+        &self.nodes[id.index()]
+    }
+}
+
+impl AstPattern {
+    // Binding and literal nodes of the pattern, in source order
+    pub fn nodes(&self) -> Vec<NodeId> {
+        match self {
+            AstPattern::Wildcard => Vec::new(),
+            AstPattern::Binding(id) | AstPattern::Literal(id) => vec![*id],
+            AstPattern::Range { start, end, .. } => vec![*start, *end],
+            AstPattern::List { elements, rest } => elements
+                .iter()
+                .chain(rest.as_deref())
+                .flat_map(AstPattern::nodes)
+                .collect(),
+            AstPattern::Struct { fields, .. } => fields
+                .iter()
+                .flat_map(|(_, pattern)| pattern.nodes())
+                .collect(),
+            AstPattern::Variant { elements, .. } => {
+                elements.iter().flat_map(AstPattern::nodes).collect()
+            }
+        }
+    }
+}
+
+// Public methods
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// Private methods
+impl Ast {
+    fn lower(nodes: Vec<Node>, statement_lines: Vec<usize>, statement_spans: Vec<Span>) -> Self {
+        let mut ast = Ast {
+            nodes: Vec::new(),
+            lists: Vec::new(),
+            root: NodeList { start: 0, len: 0 },
+            lines: Vec::new(),
+            spans: Vec::new(),
+            slots: Vec::new(),
+            types: Vec::new(),
+        };
+        let mut lowering = Lowering {
+            ast: &mut ast,
+            lines: statement_lines.into_iter(),
+            spans: statement_spans.into_iter(),
+            line: None,
+            span: None,
+        };
+        let root = lowering.lower_block(nodes);
+        ast.root = root;
+        ast
+    }
+
+    fn push(&mut self, node: AstNode, line: Option<usize>, span: Option<Span>) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(node);
+        self.lines.push(line);
+        self.spans.push(span);
+        self.slots.push(None);
+        self.types.push(None);
+        id
+    }
+
+    fn push_list(&mut self, ids: Vec<NodeId>) -> NodeList {
+        let list = NodeList {
+            start: self.lists.len() as u32,
+            len: ids.len() as u32,
+        };
+        self.lists.extend(ids);
+        list
+    }
+}
+
+// Moves the nodes of a tree into an Ast, following the statement lines.
+struct Lowering<'a> {
+    ast: &'a mut Ast,
+    lines: vec::IntoIter<usize>,
+    spans: vec::IntoIter<Span>,
+    // Line and span of the statement being lowered
+    line: Option<usize>,
+    span: Option<Span>,
+}
+
+// Private methods
+impl<'a> Lowering<'a> {
+    fn lower_block(&mut self, nodes: Vec<Node>) -> NodeList {
+        let (outer_line, outer_span) = (self.line, self.span);
+        let mut ids = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            // Missing lines, e.g. for trees built by hand, leave the line unknown
+            self.line = self.lines.next();
+            self.span = self.spans.next();
+            ids.push(self.lower(node));
+        }
+        self.line = outer_line;
+        self.span = outer_span;
+        self.ast.push_list(ids)
+    }
+
+    // Expressions that are not statements, e.g. arguments
+    fn lower_list(&mut self, nodes: Vec<Node>) -> NodeList {
+        let ids = nodes.into_iter().map(|node| self.lower(node)).collect();
+        self.ast.push_list(ids)
+    }
+
+    fn lower_option(&mut self, node: Option<Node>) -> Option<NodeId> {
+        node.map(|node| self.lower(node))
+    }
+
+    fn lower(&mut self, node: Node) -> NodeId {
+        let node = match node {
+            Node::NumberExpression(number) => AstNode::Number(number),
+            Node::NegativeNumberExpression(expr) => AstNode::Negative(self.lower(*expr)),
+            Node::LiteralExpression(literal, keyword) => AstNode::Literal(literal, keyword),
+            Node::IdentifierExpression(identifier) => AstNode::Identifier(identifier),
+            Node::VariableExpression { identifier, slot } => {
+                let id = self.ast.push(AstNode::Identifier(identifier), self.line, self.span);
+                self.ast.slots[id.index()] = Some(slot);
+                return id;
+            }
+            Node::BinaryExpr {
+                l_expr,
+                operator,
+                r_expr,
+            } => AstNode::Binary {
+                l_expr: self.lower(*l_expr),
+                operator,
+                r_expr: self.lower(*r_expr),
+            },
+            Node::AssignmentExpression {
+                identifier,
+                assignment_operator,
+                expr,
+                annotation,
+            } => AstNode::Assignment {
+                identifier: self.lower(*identifier),
+                assignment_operator,
+                expr: self.lower(*expr),
+                annotation,
+            },
+            Node::ConditionExpression {
+                l_expr,
+                operator,
+                r_expr,
+            } => AstNode::Condition {
+                l_expr: self.lower(*l_expr),
+                operator,
+                r_expr: self.lower(*r_expr),
+            },
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => AstNode::If {
+                condition: self.lower_option(*condition),
+                then_branch: self.lower_block(*then_branch),
+                else_branch: self.lower_option(*else_branch),
+            },
+            Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => AstNode::Else {
+                condition: self.lower_option(*condition),
+                then_branch: self.lower_block(*then_branch),
+                else_branch: self.lower_option(*else_branch),
+            },
+            Node::QuantityExpression { value, unit } => AstNode::Quantity {
+                value: self.lower(*value),
+                unit,
+            },
+            Node::ConversionExpression { expr, unit } => AstNode::Conversion {
+                expr: self.lower(*expr),
+                unit,
+            },
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => AstNode::While {
+                condition: self.lower_option(*condition),
+                then_branch: self.lower_block(*then_branch),
+            },
+            Node::BreakExpression => AstNode::Break,
+            // Types are checked before running, only the names are needed afterwards
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                body,
+                ..
+            } => AstNode::Function {
+                locals: Evaluator::local_names(&parameters, &body),
+                identifier,
+                parameters,
+                body: self.lower_block(*body),
+            },
+            Node::FunctionExpression {
+                parameters, body, ..
+            } => AstNode::Closure {
+                locals: Evaluator::local_names(&parameters, &body),
+                parameters,
+                body: self.lower_block(*body),
+            },
+            Node::CallExpression { callee, arguments } => {
+                let callee = self.lower(*callee);
+                AstNode::Call {
+                    callee,
+                    arguments: self.lower_list(arguments),
+                }
+            }
+            Node::ListExpression(elements) => AstNode::List(self.lower_list(elements)),
+            Node::IndexExpression { expr, index } => AstNode::Index {
+                expr: self.lower(*expr),
+                index: self.lower(*index),
+            },
+            Node::StructDeclaration { identifier, fields } => AstNode::Struct { identifier, fields },
+            Node::ImplDeclaration {
+                identifier,
+                methods,
+            } => AstNode::Impl {
+                identifier,
+                methods: self.lower_block(*methods),
+            },
+            Node::StructExpression { identifier, fields } => {
+                let (fields, values): (Vec<String>, Vec<Node>) = fields.into_iter().unzip();
+                AstNode::StructValue {
+                    identifier,
+                    fields,
+                    values: self.lower_list(values),
+                }
+            }
+            Node::FieldExpression { expr, field } => AstNode::Field {
+                expr: self.lower(*expr),
+                field,
+            },
+            Node::FieldAssignmentExpression { expr, field, value } => {
+                let expr = self.lower(*expr);
+                AstNode::FieldAssignment {
+                    expr,
+                    field,
+                    value: self.lower(*value),
+                }
+            }
+            Node::EnumDeclaration {
+                identifier,
+                variants,
+            } => AstNode::Enum {
+                identifier,
+                variants: variants
+                    .into_iter()
+                    .map(|(variant, fields)| (variant, fields.len()))
+                    .collect(),
+            },
+            Node::VariantExpression {
+                enum_name,
+                variant,
+                arguments,
+            } => AstNode::Variant {
+                enum_name,
+                variant,
+                arguments: self.lower_list(arguments),
+            },
+            Node::MatchExpression { expr, arms } => {
+                let expr = self.lower(*expr);
+                AstNode::Match {
+                    expr,
+                    arms: arms.into_iter().map(|arm| self.lower_arm(arm)).collect(),
+                }
+            }
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => {
+                let body = self.lower_block(*body);
+                let catch = catch.map(|catch| {
+                    let binding = self.lower(catch.binding);
+                    (binding, self.lower_block(catch.body))
+                });
+                AstNode::Try {
+                    body,
+                    catch,
+                    finally: finally.map(|finally| self.lower_block(finally)),
+                }
+            }
+            Node::ThrowExpression(value) => AstNode::Throw(self.lower(*value)),
+            Node::PropagateExpression(value) => AstNode::Propagate(self.lower(*value)),
+            Node::ImportExpression { path, alias, names } => AstNode::Import {
+                path,
+                alias: self.lower_option(*alias),
+                names: self.lower_list(names),
+            },
+            Node::ExportStatement(names) => AstNode::Export(names),
+            Node::ReturnExpression(value) => AstNode::Return(self.lower_option(*value)),
+            Node::EOF(_) => AstNode::EOF,
+        };
+        self.ast.push(node, self.line, self.span)
+    }
+
+    // Patterns and guards belong to the line of the match, the body is a block.
+    fn lower_arm(&mut self, arm: MatchArm) -> AstArm {
+        let pattern = self.lower_pattern(arm.pattern);
+        let guard = self.lower_option(arm.guard);
+        AstArm {
+            pattern,
+            guard,
+            body: self.lower_block(arm.body),
+        }
+    }
+
+    fn lower_pattern(&mut self, pattern: Pattern) -> AstPattern {
+        match pattern {
+            Pattern::Wildcard => AstPattern::Wildcard,
+            Pattern::Binding(node) => AstPattern::Binding(self.lower(*node)),
+            Pattern::Literal(node) => AstPattern::Literal(self.lower(*node)),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => AstPattern::Range {
+                start: self.lower(*start),
+                end: self.lower(*end),
+                inclusive,
+            },
+            Pattern::List { elements, rest } => AstPattern::List {
+                elements: elements
+                    .into_iter()
+                    .map(|element| self.lower_pattern(element))
+                    .collect(),
+                rest: rest.map(|rest| Box::new(self.lower_pattern(*rest))),
+            },
+            Pattern::Struct { identifier, fields } => AstPattern::Struct {
+                identifier,
+                fields: fields
+                    .into_iter()
+                    .map(|(field, pattern)| (field, self.lower_pattern(pattern)))
+                    .collect(),
+            },
+            Pattern::Variant {
+                enum_name,
+                variant,
+                elements,
+            } => AstPattern::Variant {
+                enum_name,
+                variant,
+                elements: elements
+                    .into_iter()
+                    .map(|element| self.lower_pattern(element))
+                    .collect(),
+            },
+        }
+    }
+}
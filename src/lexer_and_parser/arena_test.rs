@@ -0,0 +1,163 @@
+// Tests for lowering syntax trees into an arena.
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::lexer_and_parser::arena::{Ast, AstNode};
+    use crate::lexer_and_parser::ast::{Slot, Type};
+    use crate::lexer_and_parser::evaluator::{EvalResult, Evaluator};
+    use crate::lexer_and_parser::numeric::Number;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::lexer_and_parser::token::Token;
+    use crate::lexer_and_parser::tokenizer::Span;
+    use crate::passes::resolver::Resolver;
+    use crate::passes::type_checker::TypeChecker;
+
+    const SOURCE: &str = "x = 1 + 2
+if x > 2 {
+    y = x
+} else {
+    y = 0
+}
+fn double(n) {
+    return n * 2
+}
+double(y)";
+
+    fn lower(source: &str) -> Ast {
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        Ast::with_lines(nodes, std::mem::take(&mut parser.statement_lines))
+    }
+
+    #[test]
+    fn test_lowering() {
+        let ast = lower("x = 1 + 2");
+        // Children come before their parents
+        let statements = ast.list(ast.root());
+        assert_eq!(statements.len(), 1);
+        let (identifier, expr) = match &ast[statements[0]] {
+            AstNode::Assignment {
+                identifier,
+                assignment_operator: Token::Assignment,
+                expr,
+                annotation: None,
+            } => (*identifier, *expr),
+            other => panic!("Expected an assignment, got {0:?}", other),
+        };
+        assert_eq!(ast[identifier], AstNode::Identifier("x".to_string()));
+        assert!(identifier.index() < expr.index() && expr.index() < statements[0].index());
+        let (l_expr, r_expr) = match &ast[expr] {
+            AstNode::Binary {
+                l_expr,
+                operator: Token::Add,
+                r_expr,
+            } => (*l_expr, *r_expr),
+            other => panic!("Expected a binary expression, got {0:?}", other),
+        };
+        assert_eq!(ast[l_expr], AstNode::Number(Number::Integer(1)));
+        assert_eq!(ast[r_expr], AstNode::Number(Number::Integer(2)));
+        assert_eq!(ast.children(statements[0]), [identifier, expr]);
+        assert_eq!(ast.len(), 5);
+    }
+
+    #[test]
+    fn test_blocks_and_lines() {
+        let ast = lower(SOURCE);
+        let statements = ast.list(ast.root());
+        assert_eq!(statements.len(), 4);
+        let lines: Vec<Option<usize>> = statements.iter().map(|id| ast.line(*id)).collect();
+        assert_eq!(lines, [Some(1), Some(2), Some(7), Some(10)]);
+        // Statements of nested blocks keep their own line, expressions the line of
+        // their statement
+        let (condition, then_branch, else_branch) = match &ast[statements[1]] {
+            AstNode::If {
+                condition: Some(condition),
+                then_branch,
+                else_branch: Some(else_branch),
+            } => (*condition, *then_branch, *else_branch),
+            other => panic!("Expected an if expression, got {0:?}", other),
+        };
+        assert_eq!(ast.line(condition), Some(2));
+        assert_eq!(ast.line(ast.list(then_branch)[0]), Some(3));
+        assert_eq!(ast.line(ast.children(else_branch)[0]), Some(5));
+        match &ast[statements[2]] {
+            AstNode::Function {
+                identifier, body, ..
+            } => {
+                assert_eq!(identifier, "double");
+                assert_eq!(ast.line(ast.list(*body)[0]), Some(8));
+            }
+            other => panic!("Expected a function, got {0:?}", other),
+        }
+        match &ast[statements[3]] {
+            AstNode::Call { arguments, .. } => assert_eq!(ast.list(*arguments).len(), 1),
+            other => panic!("Expected a call, got {0:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_side_tables() {
+        let mut parser = Parser::new("x: num = 1\nx + 1").unwrap();
+        let nodes = parser.parse().unwrap();
+        let lines = std::mem::take(&mut parser.statement_lines);
+        let nodes = Resolver::new().with_lines(lines.clone()).resolve(nodes);
+        let mut checker = TypeChecker::new().with_lines(lines.clone());
+        checker.check(&nodes);
+        let mut ast = Ast::with_lines(nodes, lines);
+        ast.annotate_types(&checker.variable_types);
+        let statements = ast.list(ast.root()).to_vec();
+        let variable = ast.children(statements[1])[0];
+        assert_eq!(ast[variable], AstNode::Identifier("x".to_string()));
+        assert_eq!(ast.slot(variable), Some(Slot::Global(0)));
+        assert_eq!(ast.node_type(variable), Some(Type::Number));
+        // Only variables are bound and typed
+        let number = ast.children(statements[1])[1];
+        assert_eq!(ast.slot(number), None);
+        assert_eq!(ast.node_type(number), None);
+    }
+
+    #[test]
+    fn test_spans() {
+        let span = |line, column, length| Some(Span { line, column, length });
+        let mut parser = Parser::new(SOURCE).unwrap();
+        let nodes = parser.parse().unwrap();
+        let ast = Ast::with_spans(nodes, std::mem::take(&mut parser.statement_spans));
+        let statements = ast.list(ast.root());
+        let spans: Vec<Option<Span>> = statements.iter().map(|id| ast.span(*id)).collect();
+        // Statements spanning several lines are cut at the end of their first line
+        assert_eq!(spans, [span(1, 0, 9), span(2, 0, 10), span(7, 0, 14), span(10, 0, 9)]);
+        assert_eq!(ast.line(statements[2]), Some(7));
+        let body = match &ast[statements[2]] {
+            AstNode::Function { body, .. } => ast.list(*body)[0],
+            other => panic!("Expected a function, got {0:?}", other),
+        };
+        assert_eq!(ast.span(body), span(8, 4, 12));
+        assert_eq!(ast.span(ast.children(body)[0]), span(8, 4, 12));
+        // Blocks on a single line and trailing comments
+        let mut parser = Parser::new("while x < 3 { x = x + 1 } # loop").unwrap();
+        let nodes = parser.parse().unwrap();
+        let ast = Ast::with_spans(nodes, std::mem::take(&mut parser.statement_spans));
+        let statement = ast.list(ast.root())[0];
+        assert_eq!(ast.span(statement), span(1, 0, 25));
+        let body = match &ast[statement] {
+            AstNode::While { then_branch, .. } => ast.list(*then_branch)[0],
+            other => panic!("Expected a while loop, got {0:?}", other),
+        };
+        assert_eq!(ast.span(body), span(1, 14, 9));
+        // Lowered with lines only, the spans are unknown
+        let ast = lower("x = 1");
+        assert_eq!(ast.span(ast.list(ast.root())[0]), None);
+    }
+
+    #[test]
+    fn test_functions_outlive_their_arena() {
+        let mut evaluator = Evaluator::new(None);
+        evaluator
+            .run(&Rc::new(lower("fn double(n) {\n    return n * 2\n}")))
+            .unwrap();
+        // The first arena is only kept alive by the declared function
+        let result = evaluator.run(&Rc::new(lower("double(21)"))).unwrap();
+        assert_eq!(result, EvalResult::Number(Number::Integer(42)));
+    }
+}
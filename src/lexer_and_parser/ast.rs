@@ -1,13 +1,22 @@
+// Standard libraries
+use std::fmt;
+
 // Internal modules
+use super::numeric::Number;
 use super::token::{Keyword, Token};
 
 // Nodes for the syntax tree
 #[derive(Debug, PartialEq, Clone)]
 pub enum Node {
-    NumberExpression(f64),
+    NumberExpression(Number),
     NegativeNumberExpression(Box<Node>),
     LiteralExpression(String, Keyword),
     IdentifierExpression(String),
+    // Identifier bound to its storage slot by the resolver, see passes/resolver.rs
+    VariableExpression {
+        identifier: String,
+        slot: Slot,
+    },
     BinaryExpr {
         l_expr: Box<Node>,
         operator: Token,
@@ -17,6 +26,8 @@ pub enum Node {
         identifier: Box<Node>,
         assignment_operator: Token,
         expr: Box<Node>,
+        // Declared type, e.g. `x: num = 1`
+        annotation: Option<Type>,
     },
     ConditionExpression {
         l_expr: Box<Node>,
@@ -35,91 +46,409 @@ pub enum Node {
         then_branch: Box<Vec<Node>>,
         else_branch: Box<Option<Node>>,
     },
+    // Number with a unit annotation, e.g. `9.81 m/s^2`
+    QuantityExpression {
+        value: Box<Node>,
+        unit: String,
+    },
+    // Unit conversion, e.g. `x in km`
+    ConversionExpression {
+        expr: Box<Node>,
+        unit: String,
+    },
     WhileExpression{
         condition: Box<Option<Node>>,
         then_branch: Box<Vec<Node>>
     },
+    BreakExpression,
+    FunctionDeclaration {
+        identifier: String,
+        parameters: Vec<String>,
+        // Declared type of each parameter, same order as parameters
+        parameter_types: Vec<Option<Type>>,
+        return_type: Option<Type>,
+        body: Box<Vec<Node>>,
+    },
+    // Anonymous function, e.g. `fn(x) { x * 2 }`. Evaluates to a closure.
+    FunctionExpression {
+        parameters: Vec<String>,
+        parameter_types: Vec<Option<Type>>,
+        return_type: Option<Type>,
+        body: Box<Vec<Node>>,
+    },
+    // Callee is any expression producing a function, e.g. `f(1)` or `adders[0](1)`
+    CallExpression {
+        callee: Box<Node>,
+        arguments: Vec<Node>,
+    },
+    // List literal, e.g. `[1, 2, 3]`
+    ListExpression(Vec<Node>),
+    // Element of a list, e.g. `xs[0]`
+    IndexExpression {
+        expr: Box<Node>,
+        index: Box<Node>,
+    },
+    // e.g. `struct Point { x, y }`
+    StructDeclaration {
+        identifier: String,
+        fields: Vec<String>,
+    },
+    // Methods of a struct, e.g. `impl Point { fn norm(self) { ... } }`. Every method is a
+    // FunctionDeclaration taking the struct value as its first parameter `self`.
+    ImplDeclaration {
+        identifier: String,
+        methods: Box<Vec<Node>>,
+    },
+    // Construction of a struct value, e.g. `Point { x: 1, y: 2 }`
+    StructExpression {
+        identifier: String,
+        fields: Vec<(String, Node)>,
+    },
+    // e.g. `p.x`. Calling a field, e.g. `p.norm()`, calls a method of the struct.
+    FieldExpression {
+        expr: Box<Node>,
+        field: String,
+    },
+    // e.g. `p.x = 1`
+    FieldAssignmentExpression {
+        expr: Box<Node>,
+        field: String,
+        value: Box<Node>,
+    },
+    // Tagged enum with the field names of every variant, e.g.
+    // `enum Shape { Circle(radius), Empty }`
+    EnumDeclaration {
+        identifier: String,
+        variants: Vec<(String, Vec<String>)>,
+    },
+    // Construction of a variant, e.g. `Shape::Circle(1)` or `Shape::Empty`
+    VariantExpression {
+        enum_name: String,
+        variant: String,
+        arguments: Vec<Node>,
+    },
+    // e.g. `match x { 0 => "zero", n if n > 0 => "positive", _ => "negative" }`. Arms
+    // are tried in order, the value is the last value of the first matching arm.
+    MatchExpression {
+        expr: Box<Node>,
+        arms: Vec<MatchArm>,
+    },
+    // e.g. `try { ... } catch e { ... } finally { ... }`, with a catch, a finally or both.
+    // The value is the value of the body, or of the catch when the body failed.
+    TryExpression {
+        body: Box<Vec<Node>>,
+        catch: Box<Option<CatchClause>>,
+        finally: Box<Option<Vec<Node>>>,
+    },
+    // Raises an error, e.g. `throw "empty list"`
+    ThrowExpression(Box<Node>),
+    // Value inside ok or some, returns err and none from the function, e.g. `parse(s)?`
+    PropagateExpression(Box<Node>),
+    // Runs a file once and binds what it exports, either the whole module or some of its
+    // names, e.g. `import "lib/geometry.wf" as geo` or `from "geometry.wf" import area, pi`.
+    // The bound names are assigned like variables.
+    ImportExpression {
+        path: String,
+        alias: Box<Option<Node>>,
+        names: Vec<Node>,
+    },
+    // Names of the file visible to importers, e.g. `export area, pi`
+    ExportStatement(Vec<String>),
+    // Return value is optional, `return` alone returns nothing
+    ReturnExpression(Box<Option<Node>>),
     EOF(String),
 }
 
 impl Node {
-    pub fn print_stuff(&self, expr: Node, indent: i16) -> String {
-        let mut i = 0;
-        let mut indent_str = String::new();
-        while i < indent {
-            indent_str += " ";
-            i += 1;
-        }
-        indent_str += "-";
-        match expr {
-            Node::NumberExpression(f) => {
-                println!("\x1b[0;34m{0}Number: {1} \x1b[0m", indent_str, f);
-                return format!("{0}Number: {1}", indent_str, f);
-            }
-            Node::NegativeNumberExpression(f) => {
-                println!("\x1b[0;34m{0}NegativeNumber: {1:?} \x1b[0m", indent_str, f);
-            }
-            Node::LiteralExpression(id, _) => {
-                println!("\x1b[0;34m{0}Literal: {1} \x1b[0m", indent_str, id);
-                return format!("{0}Literal: {1}", indent_str, id);
-            }
-            Node::IdentifierExpression(id) => {
-                println!("\x1b[0;34m{0}Identifier: {1} \x1b[0m", indent_str, id);
-                return format!("{0}Identifier: {1}", indent_str, id);
-            }
-            Node::BinaryExpr { l_expr, operator, r_expr } => {
-                println!("\x1b[0;32m{0}BinaryExpression: \x1b[0m", indent_str);
-                self.print_stuff(*l_expr, indent + 1);
-                println!(" \x1b[0;34m{0}{1:?} \x1b[0m", indent_str, operator);
-                self.print_stuff(*r_expr, indent + 1);
+    // Whether the node is an if whose elif/else chain ends in a plain else, so one of its
+    // branches is always taken
+    pub fn has_else(&self) -> bool {
+        match self {
+            Node::ElseExpression { condition, .. } if condition.is_none() => true,
+            Node::IfExpression { else_branch, .. } | Node::ElseExpression { else_branch, .. } => {
+                else_branch.as_ref().as_ref().is_some_and(Node::has_else)
             }
-            Node::AssignmentExpression { identifier, assignment_operator, expr } => {
-                println!("\x1b[0;32m{0}AssignmentExpression: \x1b[0m", indent_str);
-                self.print_stuff(*identifier, indent + 1);
-                println!(" \x1b[0;34m{0}{1:?} \x1b[0m", indent_str, assignment_operator);
-                self.print_stuff(*expr, indent + 1);
+            _ => false,
+        }
+    }
+
+    // Whether the node gives a value as the last statement of a block, which functions
+    // return without a return, e.g. `x * 2` but not `x = 2` or an if without else
+    pub fn has_value(&self) -> bool {
+        match self {
+            Node::IfExpression { .. } => self.has_else(),
+            Node::AssignmentExpression { .. }
+            | Node::WhileExpression { .. }
+            | Node::BreakExpression
+            | Node::FunctionDeclaration { .. }
+            | Node::StructDeclaration { .. }
+            | Node::ImplDeclaration { .. }
+            | Node::FieldAssignmentExpression { .. }
+            | Node::EnumDeclaration { .. }
+            | Node::ThrowExpression(_)
+            | Node::ImportExpression { .. }
+            | Node::ExportStatement(_)
+            | Node::ReturnExpression(_)
+            | Node::EOF(_) => false,
+            _ => true,
+        }
+    }
+}
+
+// Arm of a match expression, e.g. `n if n > 0 => n`. The body of an arm without braces is
+// one statement.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Node>,
+    pub body: Vec<Node>,
+}
+
+// Handler of a try, e.g. `catch e { ... }`. The binding is an identifier, or a variable once
+// resolved.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CatchClause {
+    pub binding: Node,
+    pub body: Vec<Node>,
+}
+
+// Patterns of match arms
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    // `_`, matches anything
+    Wildcard,
+    // Name the value is assigned to, e.g. `n`. An identifier, or a variable once resolved.
+    Binding(Box<Node>),
+    // Number, negative number, string or boolean, e.g. `-1` or `"a"`
+    Literal(Box<Node>),
+    // Numbers in a range, e.g. `1..5` or `1..=5`
+    Range {
+        start: Box<Node>,
+        end: Box<Node>,
+        inclusive: bool,
+    },
+    // e.g. `[first, ..rest]`. The rest is a list of the remaining elements, `..` alone is
+    // a wildcard.
+    List {
+        elements: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+    },
+    // e.g. `Point { x, y: 0 }`, where `x` is short for `x: x`. Left out fields match
+    // anything.
+    Struct {
+        identifier: String,
+        fields: Vec<(String, Pattern)>,
+    },
+    // e.g. `Shape::Circle(r)` or `Shape::Empty`
+    Variant {
+        enum_name: String,
+        variant: String,
+        elements: Vec<Pattern>,
+    },
+}
+
+impl Pattern {
+    // Whether the pattern matches any value
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Pattern::Wildcard | Pattern::Binding(_))
+    }
+
+    // Names of the variables the pattern assigns, in source order
+    pub fn bindings(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.visit(&mut |pattern| {
+            if let Pattern::Binding(node) = pattern {
+                match node.as_ref() {
+                    Node::IdentifierExpression(name)
+                    | Node::VariableExpression {
+                        identifier: name, ..
+                    } => names.push(name.clone()),
+                    _ => {}
+                }
             }
-            Node::ConditionExpression { l_expr, operator, r_expr } => {
-                self.print_stuff(*l_expr, indent + 1);
-                println!(" \x1b[0;32m{0}{1:?} \x1b[0m", indent_str, operator);
-                self.print_stuff(*r_expr, indent + 1);
+        });
+        names
+    }
+
+    // Binding and literal nodes of the pattern, in source order
+    pub fn nodes(&self) -> Vec<&Node> {
+        match self {
+            Pattern::Wildcard => Vec::new(),
+            Pattern::Binding(node) | Pattern::Literal(node) => vec![node],
+            Pattern::Range { start, end, .. } => vec![start, end],
+            Pattern::List { elements, rest } => elements
+                .iter()
+                .chain(rest.as_deref())
+                .flat_map(Pattern::nodes)
+                .collect(),
+            Pattern::Struct { fields, .. } => fields
+                .iter()
+                .flat_map(|(_, pattern)| pattern.nodes())
+                .collect(),
+            Pattern::Variant { elements, .. } => elements.iter().flat_map(Pattern::nodes).collect(),
+        }
+    }
+
+    pub fn nodes_mut(&mut self) -> Vec<&mut Node> {
+        match self {
+            Pattern::Wildcard => Vec::new(),
+            Pattern::Binding(node) | Pattern::Literal(node) => vec![node],
+            Pattern::Range { start, end, .. } => vec![start, end],
+            Pattern::List { elements, rest } => elements
+                .iter_mut()
+                .chain(rest.as_deref_mut())
+                .flat_map(Pattern::nodes_mut)
+                .collect(),
+            Pattern::Struct { fields, .. } => fields
+                .iter_mut()
+                .flat_map(|(_, pattern)| pattern.nodes_mut())
+                .collect(),
+            Pattern::Variant { elements, .. } => {
+                elements.iter_mut().flat_map(Pattern::nodes_mut).collect()
             }
-            Node::IfExpression { condition, then_branch, else_branch } => {
-                let node = condition.unwrap();
-                println!("\x1b[0;32m{0}IfExpression: \x1b[0m", indent_str);
-                self.print_stuff(node, indent);
-                println!("  \x1b[0;32m{0}THEN \x1b[0m", indent_str);
-                for node in then_branch.into_iter() {
-                    self.print_stuff(node, indent + 3);
-                }
-                if else_branch.is_some() {
-                    let else_node = else_branch.unwrap();
-                    self.print_stuff(else_node, indent + 1);
+        }
+    }
+
+    // Pattern with every binding and literal node replaced, in source order
+    pub fn map(self, f: &mut impl FnMut(Node) -> Node) -> Pattern {
+        match self {
+            Pattern::Wildcard => Pattern::Wildcard,
+            Pattern::Binding(node) => Pattern::Binding(Box::new(f(*node))),
+            Pattern::Literal(node) => Pattern::Literal(Box::new(f(*node))),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = Box::new(f(*start));
+                Pattern::Range {
+                    start,
+                    end: Box::new(f(*end)),
+                    inclusive,
                 }
             }
-            Node::ElseExpression { condition, then_branch, else_branch } => {
-                if condition.is_some()
-                {
-                    let node = condition.unwrap();
-                    println!("\x1b[0;32m{0}ElseIfExpression: \x1b[0m", indent_str);
-                    self.print_stuff(node, indent + 1);
-                    println!("   \x1b[0;32m{0}THEN: \x1b[0m", indent_str);
-                } else if condition.is_none() {
-                    println!("\x1b[0;32m{0}ElseExpression: \x1b[0m", indent_str);
+            Pattern::List { elements, rest } => {
+                let elements = elements.into_iter().map(|element| element.map(f)).collect();
+                Pattern::List {
+                    elements,
+                    rest: rest.map(|rest| Box::new(rest.map(f))),
                 }
-                for node in then_branch.into_iter() {
-                    self.print_stuff(node, indent + 4);
+            }
+            Pattern::Struct { identifier, fields } => Pattern::Struct {
+                identifier,
+                fields: fields
+                    .into_iter()
+                    .map(|(field, pattern)| (field, pattern.map(f)))
+                    .collect(),
+            },
+            Pattern::Variant {
+                enum_name,
+                variant,
+                elements,
+            } => Pattern::Variant {
+                enum_name,
+                variant,
+                elements: elements.into_iter().map(|element| element.map(f)).collect(),
+            },
+        }
+    }
+
+    // Call f for the pattern and every pattern inside it
+    fn visit(&self, f: &mut impl FnMut(&Pattern)) {
+        f(self);
+        match self {
+            Pattern::List { elements, rest } => {
+                for element in elements.iter().chain(rest.as_deref()) {
+                    element.visit(f);
                 }
-                if else_branch.is_some() {
-                    let else_node = else_branch.unwrap();
-                    self.print_stuff(else_node, indent + 1);
+            }
+            Pattern::Struct { fields, .. } => {
+                for (_, pattern) in fields {
+                    pattern.visit(f);
                 }
             }
-            Node::EOF(_) => {
-                return format!("Hm");
+            Pattern::Variant { elements, .. } => {
+                for element in elements {
+                    element.visit(f);
+                }
             }
-            _ => { return format!("Not handled"); }
-        };
-        return "".to_string();
+            _ => {}
+        }
+    }
+}
+
+// Types of optional annotations. Numbers with units are numbers as well.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    Number,
+    String,
+    Boolean,
+    // Values of a declared enum, or of the builtin Result and Option
+    Enum(String),
+}
+
+impl Type {
+    // Type from its name in annotations
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "num" => Some(Type::Number),
+            "str" => Some(Type::String),
+            "bool" => Some(Type::Boolean),
+            _ => None,
+        }
+    }
+
+    // Type of an annotation, any other identifier names an enum. The type checker reports
+    // enums that are never declared.
+    pub fn from_annotation(name: &str) -> Option<Type> {
+        let mut chars = name.chars();
+        let identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_');
+        match Type::from_name(name) {
+            Some(annotation) => Some(annotation),
+            None if identifier => Some(Type::Enum(name.to_string())),
+            None => None,
+        }
     }
 }
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "num"),
+            Type::String => write!(f, "str"),
+            Type::Boolean => write!(f, "bool"),
+            Type::Enum(name) => write!(f, "{0}", name),
+        }
+    }
+}
+
+// Comment in the source. Comments are not part of the syntax tree, the parser
+// collects them separately for tools such as the linter.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    pub line: usize,
+    // Text after the `#`
+    pub text: String,
+    // Whether code precedes the comment on the same line, e.g. `x = 1 # one`
+    pub trailing: bool,
+}
+
+// Lines of the braces around a block of statements. The parser collects them for tools
+// such as the formatter, `first_statement` is an index into its statement lines.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BlockLines {
+    pub first_statement: usize,
+    pub open: usize,
+    pub close: usize,
+}
+
+// Storage of a resolved variable. Local slots are relative to the current function call.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Slot {
+    Global(usize),
+    Local(usize),
+    // Local of an enclosing function, `depth` functions up. e.g. 1 for the function
+    // a closure was created in.
+    Captured { depth: usize, slot: usize },
+}
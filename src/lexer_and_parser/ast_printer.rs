@@ -0,0 +1,497 @@
+// Standard libraries
+use std::fmt::{self, Write};
+
+// Internal modules
+use super::ast::{Node, Pattern, Slot, Type};
+use super::token::Keyword;
+
+// Output styles of the printer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    // Indented tree, one node per line
+    Plain,
+    // Indented tree with ANSI colours for terminals
+    Coloured,
+    // Compact S-expressions, one statement per line
+    SExpression,
+}
+
+impl Style {
+    // Style from its name on the command line
+    pub fn from_name(name: &str) -> Option<Style> {
+        match name {
+            "plain" => Some(Style::Plain),
+            "color" | "colour" => Some(Style::Coloured),
+            "sexpr" => Some(Style::SExpression),
+            _ => None,
+        }
+    }
+}
+
+// AST printer
+/*
+    Dumps syntax trees for debugging and snapshot tests. Every node is first turned
+    into an entry with a name, an optional detail such as the operator, and its child
+    entries. The styles only differ in how the entries are rendered, e.g. `1 + x` is
+
+        Binary +
+          Number 1
+          Identifier x
+
+    in the plain style and `(binary + 1 x)` as an S-expression.
+*/
+pub struct AstPrinter {
+    style: Style,
+}
+
+struct Entry {
+    name: &'static str,
+    detail: Option<String>,
+    children: Vec<Entry>,
+    // Numbers, literals and identifiers are printed bare in S-expressions
+    atom: bool,
+}
+
+// Public methods
+impl AstPrinter {
+    pub fn new(style: Style) -> Self {
+        AstPrinter { style }
+    }
+
+    pub fn print(&self, nodes: &[Node]) -> String {
+        let mut output = String::new();
+        // Writing to a String can not fail
+        self.write(&mut output, nodes).unwrap();
+        output
+    }
+
+    // Write the statements to any text sink, each ending with a newline.
+    pub fn write<W: Write>(&self, output: &mut W, nodes: &[Node]) -> fmt::Result {
+        for node in nodes {
+            let entry = Entry::from(node);
+            match self.style {
+                Style::SExpression => {
+                    self.write_sexpr(output, &entry)?;
+                    writeln!(output)?;
+                }
+                _ => self.write_tree(output, &entry, 0)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Node {
+    // Single node in the given style, see ast_printer.rs
+    pub fn dump(&self, style: Style) -> String {
+        AstPrinter::new(style).print(std::slice::from_ref(self))
+    }
+}
+
+// Private methods
+impl AstPrinter {
+    fn write_tree<W: Write>(&self, output: &mut W, entry: &Entry, indent: usize) -> fmt::Result {
+        let mut line = entry.name.to_string();
+        if let Some(detail) = &entry.detail {
+            line += &format!(" {0}", detail);
+        }
+        if self.style == Style::Coloured {
+            // Green for nodes with children, blue for leaves
+            let colour = if entry.children.is_empty() {
+                "34"
+            } else {
+                "32"
+            };
+            line = format!("\x1b[0;{0}m{1}\x1b[0m", colour, line);
+        }
+        writeln!(output, "{0:1$}{2}", "", indent * 2, line)?;
+        for child in &entry.children {
+            self.write_tree(output, child, indent + 1)?;
+        }
+        Ok(())
+    }
+
+    fn write_sexpr<W: Write>(&self, output: &mut W, entry: &Entry) -> fmt::Result {
+        if let (true, Some(detail)) = (entry.atom, &entry.detail) {
+            return write!(output, "{0}", detail);
+        }
+        write!(output, "({0}", entry.name.to_lowercase())?;
+        if let Some(detail) = &entry.detail {
+            write!(output, " {0}", detail)?;
+        }
+        for child in &entry.children {
+            write!(output, " ")?;
+            self.write_sexpr(output, child)?;
+        }
+        write!(output, ")")
+    }
+}
+
+impl Entry {
+    fn new(name: &'static str, detail: Option<String>, children: Vec<Entry>) -> Self {
+        Entry {
+            name,
+            detail,
+            children,
+            atom: false,
+        }
+    }
+
+    fn atom(name: &'static str, detail: String) -> Self {
+        Entry {
+            name,
+            detail: Some(detail),
+            children: Vec::new(),
+            atom: true,
+        }
+    }
+
+    fn block(name: &'static str, nodes: &[Node]) -> Self {
+        Entry::new(name, None, nodes.iter().map(Entry::from).collect())
+    }
+
+    // Name, parameters and return type of a function, e.g. `add(a: num, b) -> num`
+    fn signature(
+        name: &str,
+        parameters: &[String],
+        parameter_types: &[Option<Type>],
+        return_type: &Option<Type>,
+    ) -> String {
+        let parameters: Vec<String> = parameters
+            .iter()
+            .zip(parameter_types)
+            .map(|(parameter, annotation)| match annotation {
+                Some(annotation) => format!("{0}: {1}", parameter, annotation),
+                None => parameter.clone(),
+            })
+            .collect();
+        let mut signature = format!("{0}({1})", name, parameters.join(", "));
+        if let Some(return_type) = return_type {
+            signature += &format!(" -> {0}", return_type);
+        }
+        signature
+    }
+
+    // Condition, branch and the optional chain of else cases
+    fn branches(
+        condition: &Option<Node>,
+        then_branch: &[Node],
+        else_branch: &Option<Node>,
+    ) -> Vec<Entry> {
+        let mut children = Vec::new();
+        if let Some(condition) = condition {
+            children.push(Entry::from(condition));
+        }
+        children.push(Entry::block("Then", then_branch));
+        if let Some(else_branch) = else_branch {
+            children.push(Entry::from(else_branch));
+        }
+        children
+    }
+}
+
+impl From<&Node> for Entry {
+    fn from(node: &Node) -> Self {
+        match node {
+            Node::NumberExpression(number) => Entry::atom("Number", number.literal()),
+            Node::NegativeNumberExpression(expr) => {
+                Entry::new("Negative", None, vec![Entry::from(&**expr)])
+            }
+            Node::LiteralExpression(literal, Keyword::None) => {
+                Entry::atom("Literal", format!("{0:?}", literal))
+            }
+            Node::LiteralExpression(literal, _) => Entry::atom("Literal", literal.clone()),
+            Node::IdentifierExpression(identifier) => Entry::atom("Identifier", identifier.clone()),
+            Node::VariableExpression { identifier, slot } => {
+                let slot = match slot {
+                    Slot::Global(index) => format!("global {0}", index),
+                    Slot::Local(index) => format!("local {0}", index),
+                    Slot::Captured { depth, slot } => {
+                        format!("captured {0}, depth {1}", slot, depth)
+                    }
+                };
+                Entry::new(
+                    "Variable",
+                    Some(format!("{0} ({1})", identifier, slot)),
+                    Vec::new(),
+                )
+            }
+            Node::BinaryExpr {
+                l_expr,
+                operator,
+                r_expr,
+            } => Entry::new(
+                "Binary",
+                Some(operator.symbol().to_string()),
+                vec![Entry::from(&**l_expr), Entry::from(&**r_expr)],
+            ),
+            Node::AssignmentExpression {
+                identifier,
+                assignment_operator,
+                expr,
+                annotation,
+            } => {
+                let mut children = vec![Entry::from(&**identifier)];
+                if let Some(annotation) = annotation {
+                    children.push(Entry::new("Type", Some(annotation.to_string()), Vec::new()));
+                }
+                children.push(Entry::from(&**expr));
+                Entry::new(
+                    "Assignment",
+                    Some(assignment_operator.symbol().to_string()),
+                    children,
+                )
+            }
+            Node::ConditionExpression {
+                l_expr,
+                operator,
+                r_expr,
+            } => Entry::new(
+                "Condition",
+                Some(operator.symbol().to_string()),
+                vec![Entry::from(&**l_expr), Entry::from(&**r_expr)],
+            ),
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => Entry::new(
+                "If",
+                None,
+                Entry::branches(condition, then_branch, else_branch),
+            ),
+            // `elif` has a condition, the final `else` only statements
+            Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => match &**condition {
+                Some(_) => Entry::new(
+                    "Elif",
+                    None,
+                    Entry::branches(condition, then_branch, else_branch),
+                ),
+                None => Entry::block("Else", then_branch),
+            },
+            Node::QuantityExpression { value, unit } => {
+                Entry::new("Quantity", Some(unit.clone()), vec![Entry::from(&**value)])
+            }
+            Node::ConversionExpression { expr, unit } => {
+                Entry::new("Conversion", Some(unit.clone()), vec![Entry::from(&**expr)])
+            }
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => {
+                let mut children: Vec<Entry> = condition.iter().map(Entry::from).collect();
+                children.push(Entry::block("Body", then_branch));
+                Entry::new("While", None, children)
+            }
+            Node::BreakExpression => Entry::new("Break", None, Vec::new()),
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                parameter_types,
+                return_type,
+                body,
+            } => Entry::new(
+                "Function",
+                Some(Entry::signature(
+                    identifier,
+                    parameters,
+                    parameter_types,
+                    return_type,
+                )),
+                vec![Entry::block("Body", body)],
+            ),
+            Node::FunctionExpression {
+                parameters,
+                parameter_types,
+                return_type,
+                body,
+            } => Entry::new(
+                "Closure",
+                Some(Entry::signature("fn", parameters, parameter_types, return_type)),
+                vec![Entry::block("Body", body)],
+            ),
+            Node::CallExpression { callee, arguments } => {
+                let mut children = vec![Entry::from(&**callee)];
+                children.extend(arguments.iter().map(Entry::from));
+                Entry::new("Call", None, children)
+            }
+            Node::ListExpression(elements) => {
+                Entry::new("List", None, elements.iter().map(Entry::from).collect())
+            }
+            Node::IndexExpression { expr, index } => Entry::new(
+                "Index",
+                None,
+                vec![Entry::from(&**expr), Entry::from(&**index)],
+            ),
+            Node::StructDeclaration { identifier, fields } => Entry::new(
+                "Struct",
+                Some(identifier.clone()),
+                fields
+                    .iter()
+                    .map(|field| Entry::atom("Field", field.clone()))
+                    .collect(),
+            ),
+            Node::ImplDeclaration {
+                identifier,
+                methods,
+            } => Entry::new(
+                "Impl",
+                Some(identifier.clone()),
+                methods.iter().map(Entry::from).collect(),
+            ),
+            // Every field with its value, e.g. `(structvalue Point (init x 1) (init y 2))`
+            Node::StructExpression { identifier, fields } => Entry::new(
+                "StructValue",
+                Some(identifier.clone()),
+                fields
+                    .iter()
+                    .map(|(field, value)| {
+                        Entry::new("Init", Some(field.clone()), vec![Entry::from(value)])
+                    })
+                    .collect(),
+            ),
+            Node::FieldExpression { expr, field } => {
+                Entry::new("Field", Some(field.clone()), vec![Entry::from(&**expr)])
+            }
+            Node::FieldAssignmentExpression { expr, field, value } => Entry::new(
+                "FieldAssignment",
+                Some(field.clone()),
+                vec![Entry::from(&**expr), Entry::from(&**value)],
+            ),
+            // Variants with the names of their fields, e.g. `(enum Shape (variant Circle r))`
+            Node::EnumDeclaration {
+                identifier,
+                variants,
+            } => Entry::new(
+                "Enum",
+                Some(identifier.clone()),
+                variants
+                    .iter()
+                    .map(|(variant, fields)| {
+                        let fields = fields
+                            .iter()
+                            .map(|field| Entry::atom("Field", field.clone()))
+                            .collect();
+                        Entry::new("Variant", Some(variant.clone()), fields)
+                    })
+                    .collect(),
+            ),
+            Node::VariantExpression {
+                enum_name,
+                variant,
+                arguments,
+            } => Entry::new(
+                "VariantValue",
+                Some(format!("{0}::{1}", enum_name, variant)),
+                arguments.iter().map(Entry::from).collect(),
+            ),
+            // The value followed by every arm, e.g. `(match x (arm 1 (body ...)))`
+            Node::MatchExpression { expr, arms } => {
+                let mut children = vec![Entry::from(&**expr)];
+                for arm in arms {
+                    let mut arm_children = vec![Entry::from(&arm.pattern)];
+                    if let Some(guard) = &arm.guard {
+                        arm_children.push(Entry::new("Guard", None, vec![Entry::from(guard)]));
+                    }
+                    arm_children.push(Entry::block("Body", &arm.body));
+                    children.push(Entry::new("Arm", None, arm_children));
+                }
+                Entry::new("Match", None, children)
+            }
+            // e.g. `(try (body ...) (catch e (body ...)) (finally ...))`
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => {
+                let mut children = vec![Entry::block("Body", body)];
+                if let Some(catch) = catch.as_ref() {
+                    children.push(Entry::new(
+                        "Catch",
+                        None,
+                        vec![Entry::from(&catch.binding), Entry::block("Body", &catch.body)],
+                    ));
+                }
+                if let Some(finally) = finally.as_ref() {
+                    children.push(Entry::block("Finally", finally));
+                }
+                Entry::new("Try", None, children)
+            }
+            Node::ThrowExpression(value) => Entry::new("Throw", None, vec![Entry::from(&**value)]),
+            Node::PropagateExpression(value) => {
+                Entry::new("Propagate", None, vec![Entry::from(&**value)])
+            }
+            // e.g. `(import "geometry.wf" (as geo))` or `(import "geometry.wf" area pi)`
+            Node::ImportExpression { path, alias, names } => {
+                let mut children = Vec::new();
+                if let Some(alias) = alias.as_ref() {
+                    children.push(Entry::new("As", None, vec![Entry::from(alias)]));
+                }
+                children.extend(names.iter().map(Entry::from));
+                Entry::new("Import", Some(format!("{0:?}", path)), children)
+            }
+            Node::ExportStatement(names) => Entry::new(
+                "Export",
+                None,
+                names
+                    .iter()
+                    .map(|name| Entry::atom("Name", name.clone()))
+                    .collect(),
+            ),
+            Node::ReturnExpression(value) => {
+                Entry::new("Return", None, value.iter().map(Entry::from).collect())
+            }
+            Node::EOF(_) => Entry::new("EOF", None, Vec::new()),
+        }
+    }
+}
+
+// Literal patterns are printed as their node
+impl From<&Pattern> for Entry {
+    fn from(pattern: &Pattern) -> Self {
+        match pattern {
+            Pattern::Wildcard => Entry::new("Wildcard", None, Vec::new()),
+            Pattern::Binding(node) => Entry::new("Binding", None, vec![Entry::from(&**node)]),
+            Pattern::Literal(node) => Entry::from(&**node),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => Entry::new(
+                "Range",
+                Some(if *inclusive { "..=" } else { ".." }.to_string()),
+                vec![Entry::from(&**start), Entry::from(&**end)],
+            ),
+            Pattern::List { elements, rest } => {
+                let mut children: Vec<Entry> = elements.iter().map(Entry::from).collect();
+                if let Some(rest) = rest {
+                    children.push(Entry::new("Rest", None, vec![Entry::from(&**rest)]));
+                }
+                Entry::new("ListPattern", None, children)
+            }
+            Pattern::Struct { identifier, fields } => Entry::new(
+                "StructPattern",
+                Some(identifier.clone()),
+                fields
+                    .iter()
+                    .map(|(field, pattern)| {
+                        Entry::new("Field", Some(field.clone()), vec![Entry::from(pattern)])
+                    })
+                    .collect(),
+            ),
+            Pattern::Variant {
+                enum_name,
+                variant,
+                elements,
+            } => Entry::new(
+                "VariantPattern",
+                Some(format!("{0}::{1}", enum_name, variant)),
+                elements.iter().map(Entry::from).collect(),
+            ),
+        }
+    }
+}
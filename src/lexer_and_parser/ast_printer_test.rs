@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::ast::Node;
+    use crate::lexer_and_parser::ast_printer::{AstPrinter, Style};
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::passes::resolver::Resolver;
+
+    const SOURCE: &str = "x: num = -(1 + 2) * 3
+if x >= 2 km in m {
+    x = x ^ 2
+} elif x != 1 {
+    break
+} else {
+    flag = true
+}
+while x < 10 {
+    x = x / 2
+}
+fn add(a: num, b) -> num {
+    return a + b
+}
+fn nothing() {
+    return
+}
+add(\"one\", x)";
+
+    fn parse(source: &str) -> Vec<Node> {
+        Parser::new(source).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_plain() {
+        let expected = "Assignment =
+  Identifier x
+  Type num
+  Binary *
+    Negative
+      Binary +
+        Number 1
+        Number 2
+    Number 3
+If
+  Condition >=
+    Identifier x
+    Conversion m
+      Quantity km
+        Number 2
+  Then
+    Assignment =
+      Identifier x
+      Binary ^
+        Identifier x
+        Number 2
+  Elif
+    Condition !=
+      Identifier x
+      Number 1
+    Then
+      Break
+    Else
+      Assignment =
+        Identifier flag
+        Literal true
+While
+  Condition <
+    Identifier x
+    Number 10
+  Body
+    Assignment =
+      Identifier x
+      Binary /
+        Identifier x
+        Number 2
+Function add(a: num, b) -> num
+  Body
+    Return
+      Binary +
+        Identifier a
+        Identifier b
+Function nothing()
+  Body
+    Return
+Call
+  Identifier add
+  Literal \"one\"
+  Identifier x
+";
+        assert_eq!(
+            AstPrinter::new(Style::Plain).print(&parse(SOURCE)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_sexpr() {
+        let expected = "(assignment = x (type num) (binary * (negative (binary + 1 2)) 3))
+(if (condition >= x (conversion m (quantity km 2))) (then (assignment = x (binary ^ x 2))) \
+(elif (condition != x 1) (then (break)) (else (assignment = flag true))))
+(while (condition < x 10) (body (assignment = x (binary / x 2))))
+(function add(a: num, b) -> num (body (return (binary + a b))))
+(function nothing() (body (return)))
+(call add \"one\" x)
+";
+        assert_eq!(
+            AstPrinter::new(Style::SExpression).print(&parse(SOURCE)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_coloured() {
+        let nodes = parse(SOURCE);
+        let coloured = AstPrinter::new(Style::Coloured).print(&nodes);
+        assert!(
+            coloured.starts_with("\x1b[0;32mAssignment =\x1b[0m\n  \x1b[0;34mIdentifier x\x1b[0m")
+        );
+        // Without the colour codes the tree is the plain one
+        let stripped = coloured
+            .replace("\x1b[0;32m", "")
+            .replace("\x1b[0;34m", "")
+            .replace("\x1b[0m", "");
+        assert_eq!(stripped, AstPrinter::new(Style::Plain).print(&nodes));
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut resolver = Resolver::new();
+        let nodes = resolver.resolve(parse("fn f(a) {\n    return a\n}\ny = f(1)"));
+        assert_eq!(
+            AstPrinter::new(Style::SExpression).print(&nodes),
+            "(function f(a) (body (return (variable a (local 0)))))
+(assignment = (variable y (global 0)) (call f 1))
+"
+        );
+        assert_eq!(
+            nodes[1].dump(Style::Plain).lines().nth(1),
+            Some("  Variable y (global 0)")
+        );
+        let mut output = String::new();
+        AstPrinter::new(Style::Plain)
+            .write(&mut output, &[])
+            .unwrap();
+        assert_eq!(output, "");
+    }
+}
@@ -0,0 +1,1015 @@
+// Libraries
+use std::error;
+use std::rc::Rc;
+
+// Internal modules
+use super::ast::Type;
+use super::evaluator::{apply_arithmetic, Closure, ErrorKind, ErrorValue, EvalResult, RuntimeError};
+use super::numeric::Number;
+use super::token::Token;
+use super::units::Quantity;
+
+// Functions built into the evaluator.
+/*
+    Builtins are implemented natively and only called by name: a variable or a declared
+    function with the same name takes precedence. Functions passed to them are called
+    through the evaluator, so closures behave exactly like in a script.
+
+    Lists are never changed in place, every builtin returns a new list. Arguments of the
+    wrong type are TypeMismatch errors, which scripts can catch.
+
+    ok, err and some create values of the built-in Result and Option enums, see
+    BUILTIN_ENUMS. They work with match like any declared enum.
+
+    Strings are counted in characters, not bytes: len, find, substring and the padding
+    functions all agree on `len("é") == 1`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Builtin {
+    Map,
+    Filter,
+    Reduce,
+    Fold,
+    Any,
+    All,
+    Sum,
+    Sort,
+    Reverse,
+    Zip,
+    Enumerate,
+    Range,
+    Join,
+    Contains,
+    IndexOf,
+    // Error value with a message and an optional kind, e.g. `throw error("empty", "ValueError")`
+    Error,
+    Ok,
+    Err,
+    Some,
+    // Value inside ok or some, throws the value of err and fails on none, see
+    // RuntimeError::from_failure
+    Unwrap,
+    // Value inside ok or some, the default for err and none
+    UnwrapOr,
+    // Amount of characters of a string or elements of a list
+    Len,
+    Upper,
+    Lower,
+    Trim,
+    // Parts of a string between a separator, or between whitespace without one
+    Split,
+    Replace,
+    StartsWith,
+    EndsWith,
+    // Character index of the first occurrence, -1 when there is none
+    Find,
+    // Characters from a start index up to an end index, which is excluded
+    Substring,
+    Repeat,
+    PadLeft,
+    PadRight,
+    // ok with the number written in a string, err when it is not one
+    ParseNum,
+    // The number as a float, units are kept, e.g. `float(1/3)`
+    Float,
+    ToString,
+    // Values written into a template, e.g. `format("{} has {:.2}", a, b)`
+    Format,
+}
+
+// Longest list and string a builtin creates, larger ones fail with a runtime error
+// instead of exhausting the memory.
+pub const MAX_LIST_LENGTH: usize = 10_000_000;
+pub const MAX_STRING_LENGTH: usize = 10_000_000;
+
+// Amount of something for error messages, e.g. `1 argument` or `2 arguments`.
+pub fn amount(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {0}", noun),
+        count => format!("{0} {1}s", count, noun),
+    }
+}
+
+// Enums every script knows without declaring them, with the amount of values of every
+// variant. Their values are written `ok(v)`, `err(e)`, `some(v)` and `none`, in patterns as well.
+pub const BUILTIN_ENUMS: [(&str, [(&str, usize); 2]); 2] = [
+    ("Result", [("Ok", 1), ("Err", 1)]),
+    ("Option", [("Some", 1), ("None", 0)]),
+];
+
+// Enum and variant of a built-in variant by its short name, e.g. `some` is Option::Some.
+pub fn builtin_variant(name: &str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "ok" => Some(("Result", "Ok")),
+        "err" => Some(("Result", "Err")),
+        "some" => Some(("Option", "Some")),
+        "none" => Some(("Option", "None")),
+        _ => None,
+    }
+}
+
+// Short name of a built-in variant, the reverse of builtin_variant.
+pub fn short_variant_name(enum_name: &str, variant: &str) -> Option<&'static str> {
+    ["ok", "err", "some", "none"]
+        .iter()
+        .copied()
+        .find(|name| builtin_variant(name) == Some((enum_name, variant)))
+}
+
+// Calls a function value with the given arguments, provided by the evaluator.
+pub type Call<'a> =
+    dyn FnMut(&Rc<Closure>, Vec<EvalResult>) -> Result<EvalResult, Box<dyn error::Error>> + 'a;
+
+// Comparison used by sort, false when the elements are in order.
+type Less<'a> = dyn FnMut(&EvalResult, &EvalResult) -> Result<bool, Box<dyn error::Error>> + 'a;
+
+// Public methods
+impl Builtin {
+    pub const ALL: [Builtin; 38] = [
+        Builtin::Map,
+        Builtin::Filter,
+        Builtin::Reduce,
+        Builtin::Fold,
+        Builtin::Any,
+        Builtin::All,
+        Builtin::Sum,
+        Builtin::Sort,
+        Builtin::Reverse,
+        Builtin::Zip,
+        Builtin::Enumerate,
+        Builtin::Range,
+        Builtin::Join,
+        Builtin::Contains,
+        Builtin::IndexOf,
+        Builtin::Error,
+        Builtin::Ok,
+        Builtin::Err,
+        Builtin::Some,
+        Builtin::Unwrap,
+        Builtin::UnwrapOr,
+        Builtin::Len,
+        Builtin::Upper,
+        Builtin::Lower,
+        Builtin::Trim,
+        Builtin::Split,
+        Builtin::Replace,
+        Builtin::StartsWith,
+        Builtin::EndsWith,
+        Builtin::Find,
+        Builtin::Substring,
+        Builtin::Repeat,
+        Builtin::PadLeft,
+        Builtin::PadRight,
+        Builtin::ParseNum,
+        Builtin::Float,
+        Builtin::ToString,
+        Builtin::Format,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        Builtin::ALL
+            .iter()
+            .find(|builtin| builtin.name() == name)
+            .copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Map => "map",
+            Builtin::Filter => "filter",
+            Builtin::Reduce => "reduce",
+            Builtin::Fold => "fold",
+            Builtin::Any => "any",
+            Builtin::All => "all",
+            Builtin::Sum => "sum",
+            Builtin::Sort => "sort",
+            Builtin::Reverse => "reverse",
+            Builtin::Zip => "zip",
+            Builtin::Enumerate => "enumerate",
+            Builtin::Range => "range",
+            Builtin::Join => "join",
+            Builtin::Contains => "contains",
+            Builtin::IndexOf => "index_of",
+            Builtin::Error => "error",
+            Builtin::Ok => "ok",
+            Builtin::Err => "err",
+            Builtin::Some => "some",
+            Builtin::Unwrap => "unwrap",
+            Builtin::UnwrapOr => "unwrap_or",
+            Builtin::Len => "len",
+            Builtin::Upper => "upper",
+            Builtin::Lower => "lower",
+            Builtin::Trim => "trim",
+            Builtin::Split => "split",
+            Builtin::Replace => "replace",
+            Builtin::StartsWith => "starts_with",
+            Builtin::EndsWith => "ends_with",
+            Builtin::Find => "find",
+            Builtin::Substring => "substring",
+            Builtin::Repeat => "repeat",
+            Builtin::PadLeft => "pad_left",
+            Builtin::PadRight => "pad_right",
+            Builtin::ParseNum => "parse_num",
+            Builtin::Float => "float",
+            Builtin::ToString => "to_string",
+            Builtin::Format => "format",
+        }
+    }
+
+    // Smallest and largest amount of arguments.
+    pub fn arity(&self) -> (usize, usize) {
+        match self {
+            Builtin::Sum
+            | Builtin::Reverse
+            | Builtin::Enumerate
+            | Builtin::Ok
+            | Builtin::Err
+            | Builtin::Some
+            | Builtin::Unwrap
+            | Builtin::Len
+            | Builtin::Upper
+            | Builtin::Lower
+            | Builtin::Trim
+            | Builtin::ParseNum
+            | Builtin::Float
+            | Builtin::ToString => (1, 1),
+            Builtin::Any
+            | Builtin::All
+            | Builtin::Sort
+            | Builtin::Join
+            | Builtin::Error
+            | Builtin::Split => (1, 2),
+            Builtin::Range => (1, 3),
+            Builtin::Substring | Builtin::PadLeft | Builtin::PadRight => (2, 3),
+            Builtin::Fold | Builtin::Replace => (3, 3),
+            // Any amount of values after the template
+            Builtin::Format => (1, usize::MAX),
+            _ => (2, 2),
+        }
+    }
+
+    // Amount of arguments for error messages, e.g. `1 to 2 arguments`.
+    pub fn expected_arguments(&self) -> String {
+        match self.arity() {
+            (min, usize::MAX) => format!("at least {0}", amount(min, "argument")),
+            (min, max) if min == max => amount(min, "argument"),
+            (min, max) => format!("{0} to {1} arguments", min, max),
+        }
+    }
+
+    // Type of the result, None if it is not a num, str or bool.
+    pub fn return_type(&self) -> Option<Type> {
+        match self {
+            Builtin::Sum | Builtin::IndexOf | Builtin::Len | Builtin::Find | Builtin::Float => {
+                Some(Type::Number)
+            }
+            Builtin::Any
+            | Builtin::All
+            | Builtin::Contains
+            | Builtin::StartsWith
+            | Builtin::EndsWith => Some(Type::Boolean),
+            Builtin::Join
+            | Builtin::Upper
+            | Builtin::Lower
+            | Builtin::Trim
+            | Builtin::Replace
+            | Builtin::Substring
+            | Builtin::Repeat
+            | Builtin::PadLeft
+            | Builtin::PadRight
+            | Builtin::ToString
+            | Builtin::Format => Some(Type::String),
+            _ => None,
+        }
+    }
+
+    // Run the builtin. Arguments are already evaluated.
+    pub fn call(
+        &self,
+        arguments: Vec<EvalResult>,
+        call: &mut Call,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let (min, max) = self.arity();
+        if arguments.len() < min || arguments.len() > max {
+            return Err(format!(
+                "Function {0} expects {1}, got {2}",
+                self.name(),
+                self.expected_arguments(),
+                arguments.len()
+            )
+            .into());
+        }
+        let list = match self {
+            Builtin::Map
+            | Builtin::Filter
+            | Builtin::Reduce
+            | Builtin::Fold
+            | Builtin::Any
+            | Builtin::All
+            | Builtin::Sum
+            | Builtin::Sort
+            | Builtin::Reverse
+            | Builtin::Zip
+            | Builtin::Enumerate
+            | Builtin::Join
+            | Builtin::Contains
+            | Builtin::IndexOf => Some(self.list(&arguments, 0)?),
+            _ => None,
+        };
+        let items = list.as_deref().map(Vec::as_slice).unwrap_or_default();
+        let result = match self {
+            Builtin::Map => {
+                let function = self.function(&arguments, 1)?;
+                let mut values = Vec::new();
+                for item in items {
+                    values.push(self.value(call(&function, vec![item.clone()])?)?);
+                }
+                EvalResult::List(Rc::new(values))
+            }
+            Builtin::Filter => {
+                let function = self.function(&arguments, 1)?;
+                let mut values = Vec::new();
+                for item in items {
+                    if self.test(call(&function, vec![item.clone()])?)? {
+                        values.push(item.clone());
+                    }
+                }
+                EvalResult::List(Rc::new(values))
+            }
+            Builtin::Reduce | Builtin::Fold => {
+                let (mut accumulator, rest, function) = match self {
+                    Builtin::Reduce => match items.split_first() {
+                        Some((first, rest)) => (first.clone(), rest, self.function(&arguments, 1)?),
+                        None => return Err("Function reduce can not reduce an empty list".into()),
+                    },
+                    _ => (arguments[1].clone(), items, self.function(&arguments, 2)?),
+                };
+                for item in rest {
+                    accumulator = self.value(call(&function, vec![accumulator, item.clone()])?)?;
+                }
+                accumulator
+            }
+            Builtin::Any | Builtin::All => {
+                let function = match arguments.len() {
+                    2 => Some(self.function(&arguments, 1)?),
+                    _ => None,
+                };
+                // Stops at the first element deciding the result
+                let wanted = *self == Builtin::Any;
+                let mut found = false;
+                for item in items {
+                    let value = match &function {
+                        Some(function) => call(function, vec![item.clone()])?,
+                        None => item.clone(),
+                    };
+                    if self.test(value)? == wanted {
+                        found = true;
+                        break;
+                    }
+                }
+                EvalResult::Boolean(found == wanted)
+            }
+            Builtin::Sum => {
+                let mut total = match items.first() {
+                    Some(first) => self.number(first)?,
+                    None => return Ok(EvalResult::Number(Number::Integer(0))),
+                };
+                for item in &items[1..] {
+                    total = apply_arithmetic(&total, &Token::Add, &self.number(item)?)
+                        .map_err(|message| RuntimeError::new(ErrorKind::TypeMismatch, message))?;
+                }
+                EvalResult::from_quantity(total)
+            }
+            Builtin::Sort => {
+                let function = match arguments.len() {
+                    2 => Some(self.function(&arguments, 1)?),
+                    _ => None,
+                };
+                let mut less = |a: &EvalResult, b: &EvalResult| match &function {
+                    Some(function) => self.test(call(function, vec![a.clone(), b.clone()])?),
+                    None => Ok(a.compare(&Token::LessThan, b)?),
+                };
+                EvalResult::List(Rc::new(Builtin::merge_sort(items.to_vec(), &mut less)?))
+            }
+            Builtin::Reverse => EvalResult::List(Rc::new(items.iter().rev().cloned().collect())),
+            Builtin::Zip => {
+                let other = self.list(&arguments, 1)?;
+                let pairs = items
+                    .iter()
+                    .zip(other.iter())
+                    .map(|(a, b)| EvalResult::List(Rc::new(vec![a.clone(), b.clone()])))
+                    .collect();
+                EvalResult::List(Rc::new(pairs))
+            }
+            Builtin::Enumerate => {
+                let pairs = items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let index = EvalResult::Number(Number::Integer(i as i64));
+                        EvalResult::List(Rc::new(vec![index, item.clone()]))
+                    })
+                    .collect();
+                EvalResult::List(Rc::new(pairs))
+            }
+            Builtin::Range => self.range(&arguments)?,
+            Builtin::Join => {
+                let separator = match arguments.get(1) {
+                    Some(EvalResult::Literal(separator)) => separator.as_str(),
+                    Some(other) => return Err(self.wrong_argument(2, "a string", other)),
+                    None => "",
+                };
+                let separators = items.len().saturating_sub(1);
+                let length = items.iter().map(Builtin::text_length).fold(
+                    separator.chars().count().saturating_mul(separators),
+                    usize::saturating_add,
+                );
+                self.string_length(length)?;
+                let texts: Vec<String> = items.iter().map(Builtin::text).collect();
+                EvalResult::Literal(texts.join(separator))
+            }
+            Builtin::Contains => EvalResult::Boolean(items.contains(&arguments[1])),
+            Builtin::IndexOf => {
+                let index = items.iter().position(|item| *item == arguments[1]);
+                // -1 when the value is not in the list
+                let index = index.map_or(-1, |index| index as i64);
+                EvalResult::Number(Number::Integer(index))
+            }
+            Builtin::Error => {
+                let kind = match arguments.get(1) {
+                    Some(EvalResult::Literal(kind)) => ErrorKind::from_name(kind),
+                    Some(other) => return Err(self.wrong_argument(2, "a string", other)),
+                    None => ErrorKind::Error,
+                };
+                self.string_length(Builtin::text_length(&arguments[0]))?;
+                EvalResult::Error(Rc::new(ErrorValue {
+                    kind,
+                    message: Builtin::text(&arguments[0]),
+                    line: None,
+                }))
+            }
+            Builtin::Ok => EvalResult::ok(arguments[0].clone()),
+            Builtin::Err => EvalResult::err(arguments[0].clone()),
+            Builtin::Some => EvalResult::some(arguments[0].clone()),
+            Builtin::Unwrap => match self.unwrapped(&arguments[0])? {
+                Ok(value) => value,
+                Err(failure) => return Err(Box::new(RuntimeError::from_failure(failure))),
+            },
+            Builtin::UnwrapOr => match self.unwrapped(&arguments[0])? {
+                Ok(value) => value,
+                Err(_) => arguments[1].clone(),
+            },
+            Builtin::Len => {
+                let length = match &arguments[0] {
+                    EvalResult::Literal(text) => text.chars().count(),
+                    EvalResult::List(items) => items.len(),
+                    other => return Err(self.wrong_argument(1, "a string or a list", other)),
+                };
+                EvalResult::Number(Number::Integer(length as i64))
+            }
+            Builtin::Float => match arguments[0].to_quantity() {
+                Some(mut quantity) => {
+                    quantity.value = quantity.value.to_float();
+                    EvalResult::from_quantity(quantity)
+                }
+                None => return Err(self.wrong_argument(1, "a number", &arguments[0])),
+            },
+            Builtin::ToString => {
+                self.string_length(Builtin::text_length(&arguments[0]))?;
+                EvalResult::Literal(Builtin::text(&arguments[0]))
+            }
+            Builtin::Format => EvalResult::Literal(self.format(&arguments)?),
+            _ => self.call_string(&arguments)?,
+        };
+        Ok(result)
+    }
+
+    // Text of a value as `join` prints it.
+    pub fn text(value: &EvalResult) -> String {
+        match value {
+            EvalResult::Number(n) => n.to_string(),
+            EvalResult::Quantity(q) => q.to_string(),
+            EvalResult::Literal(s) => s.clone(),
+            EvalResult::Boolean(b) => b.to_string(),
+            EvalResult::List(items) => {
+                let texts: Vec<String> = items.iter().map(Builtin::text).collect();
+                format!("[{0}]", texts.join(", "))
+            }
+            EvalResult::Struct(instance) => {
+                let fields = instance.fields.borrow();
+                let texts: Vec<String> = fields
+                    .iter()
+                    .map(|(name, value)| format!("{0}: {1}", name, Builtin::text(value)))
+                    .collect();
+                match texts.is_empty() {
+                    true => format!("{0} {{}}", instance.name),
+                    false => format!("{0} {{ {1} }}", instance.name, texts.join(", ")),
+                }
+            }
+            EvalResult::Enum(value) => {
+                let name = match short_variant_name(&value.enum_name, &value.variant) {
+                    Some(name) => name.to_string(),
+                    None => format!("{0}::{1}", value.enum_name, value.variant),
+                };
+                let texts: Vec<String> = value.values.iter().map(Builtin::text).collect();
+                match texts.is_empty() {
+                    true => name,
+                    false => format!("{0}({1})", name, texts.join(", ")),
+                }
+            }
+            EvalResult::Error(error) => format!("{0:?}", error),
+            EvalResult::Module(module) => format!("{0:?}", module),
+            other => other.to_string(),
+        }
+    }
+
+    // Number of characters in the text of a value, without building it.
+    /*
+        A list may hold the same long string millions of times, so builtins check the
+        length of a text before creating it.
+    */
+    pub fn text_length(value: &EvalResult) -> usize {
+        // Texts of values separated by ", "
+        let joined = |values: &mut dyn Iterator<Item = usize>| {
+            let (count, length) = values.fold((0usize, 0usize), |(count, length), value| {
+                (count + 1, length.saturating_add(value))
+            });
+            length.saturating_add(count.saturating_sub(1) * 2)
+        };
+        match value {
+            EvalResult::Literal(s) => s.chars().count(),
+            EvalResult::List(items) => {
+                joined(&mut items.iter().map(Builtin::text_length)).saturating_add(2)
+            }
+            EvalResult::Struct(instance) => {
+                let fields = instance.fields.borrow();
+                let name = instance.name.chars().count();
+                match fields.is_empty() {
+                    true => name + 3,
+                    false => joined(&mut fields.iter().map(|(field, value)| {
+                        Builtin::text_length(value).saturating_add(field.chars().count() + 2)
+                    }))
+                    .saturating_add(name + 5),
+                }
+            }
+            EvalResult::Enum(value) if !value.values.is_empty() => {
+                let name = match short_variant_name(&value.enum_name, &value.variant) {
+                    Some(name) => name.chars().count(),
+                    None => value.enum_name.chars().count() + value.variant.chars().count() + 2,
+                };
+                joined(&mut value.values.iter().map(Builtin::text_length)).saturating_add(name + 2)
+            }
+            other => Builtin::text(other).chars().count(),
+        }
+    }
+}
+
+// Private methods
+impl Builtin {
+    fn wrong_argument(
+        &self,
+        position: usize,
+        expected: &str,
+        got: &EvalResult,
+    ) -> Box<dyn error::Error> {
+        let message = format!(
+            "Function {0} expects {1} as argument {2}, got {3}",
+            self.name(),
+            expected,
+            position,
+            got
+        );
+        Box::new(RuntimeError::new(ErrorKind::TypeMismatch, message))
+    }
+
+    fn list(
+        &self,
+        arguments: &[EvalResult],
+        index: usize,
+    ) -> Result<Rc<Vec<EvalResult>>, Box<dyn error::Error>> {
+        match &arguments[index] {
+            EvalResult::List(items) => Ok(items.clone()),
+            other => Err(self.wrong_argument(index + 1, "a list", other)),
+        }
+    }
+
+    fn string<'a>(
+        &self,
+        arguments: &'a [EvalResult],
+        index: usize,
+    ) -> Result<&'a str, Box<dyn error::Error>> {
+        match &arguments[index] {
+            EvalResult::Literal(text) => Ok(text),
+            other => Err(self.wrong_argument(index + 1, "a string", other)),
+        }
+    }
+
+    fn unwrapped(
+        &self,
+        value: &EvalResult,
+    ) -> Result<Result<EvalResult, Option<EvalResult>>, Box<dyn error::Error>> {
+        match value.as_result() {
+            Some(result) => Ok(result),
+            None => Err(self.wrong_argument(1, "a Result or an Option", value)),
+        }
+    }
+
+    fn function(
+        &self,
+        arguments: &[EvalResult],
+        index: usize,
+    ) -> Result<Rc<Closure>, Box<dyn error::Error>> {
+        match &arguments[index] {
+            EvalResult::Function(function) => Ok(function.clone()),
+            other => Err(self.wrong_argument(index + 1, "a function", other)),
+        }
+    }
+
+    fn number(&self, value: &EvalResult) -> Result<Quantity, Box<dyn error::Error>> {
+        match value.to_quantity() {
+            Some(quantity) => Ok(quantity),
+            None => Err(Box::new(RuntimeError::new(
+                ErrorKind::TypeMismatch,
+                format!(
+                    "Function {0} expects a list of numbers, got {1}",
+                    self.name(),
+                    value
+                ),
+            ))),
+        }
+    }
+
+    fn integer(&self, value: &EvalResult, position: usize) -> Result<i64, Box<dyn error::Error>> {
+        match value {
+            EvalResult::Number(Number::Integer(i)) => Ok(*i),
+            other => Err(self.wrong_argument(position, "an integer", other)),
+        }
+    }
+
+    // Index or count of characters, negative values are not allowed.
+    fn count(
+        &self,
+        arguments: &[EvalResult],
+        index: usize,
+    ) -> Result<usize, Box<dyn error::Error>> {
+        match self.integer(&arguments[index], index + 1)? {
+            count if count < 0 => {
+                Err(self.wrong_argument(index + 1, "an integer of at least 0", &arguments[index]))
+            }
+            count => Ok(count as usize),
+        }
+    }
+
+    // Length of a string the builtin is about to create, at most MAX_STRING_LENGTH.
+    fn string_length(&self, length: usize) -> Result<usize, Box<dyn error::Error>> {
+        match length > MAX_STRING_LENGTH {
+            true => Err(format!(
+                "Function {0} can not create a string of more than {1} characters",
+                self.name(),
+                MAX_STRING_LENGTH
+            )
+            .into()),
+            false => Ok(length),
+        }
+    }
+
+    // Builtins taking a string as their first argument.
+    fn call_string(&self, arguments: &[EvalResult]) -> Result<EvalResult, Box<dyn error::Error>> {
+        let text = self.string(arguments, 0)?;
+        let result = match self {
+            // Changing the case may grow a string, e.g. ß becomes SS
+            Builtin::Upper | Builtin::Lower => {
+                let changed = match self {
+                    Builtin::Upper => text.to_uppercase(),
+                    _ => text.to_lowercase(),
+                };
+                self.string_length(changed.chars().count())?;
+                EvalResult::Literal(changed)
+            }
+            Builtin::Trim => EvalResult::Literal(text.trim().to_string()),
+            Builtin::Split => {
+                let parts: Vec<&str> = match arguments.len() {
+                    1 => text.split_whitespace().collect(),
+                    _ => match self.string(arguments, 1)? {
+                        // Every character on its own
+                        "" => text.matches(|_| true).collect(),
+                        separator => text.split(separator).collect(),
+                    },
+                };
+                let parts = parts
+                    .into_iter()
+                    .map(|part| EvalResult::Literal(part.to_string()));
+                EvalResult::List(Rc::new(parts.collect()))
+            }
+            Builtin::Replace => {
+                let from = self.string(arguments, 1)?;
+                if from.is_empty() {
+                    let message = "Function replace expects a string to replace that is not empty";
+                    return Err(message.into());
+                }
+                let to = self.string(arguments, 2)?;
+                let matches = text.matches(from).count();
+                let kept = text.chars().count() - matches * from.chars().count();
+                self.string_length(kept.saturating_add(matches.saturating_mul(to.chars().count())))?;
+                EvalResult::Literal(text.replace(from, to))
+            }
+            Builtin::StartsWith => {
+                EvalResult::Boolean(text.starts_with(self.string(arguments, 1)?))
+            }
+            Builtin::EndsWith => EvalResult::Boolean(text.ends_with(self.string(arguments, 1)?)),
+            Builtin::Find => {
+                let index = text
+                    .find(self.string(arguments, 1)?)
+                    .map_or(-1, |byte| text[..byte].chars().count() as i64);
+                EvalResult::Number(Number::Integer(index))
+            }
+            Builtin::Substring => {
+                // Indices past the end stop at the end
+                let start = self.count(arguments, 1)?;
+                let characters = match arguments.len() {
+                    3 => self.count(arguments, 2)?.saturating_sub(start),
+                    _ => usize::MAX,
+                };
+                EvalResult::Literal(text.chars().skip(start).take(characters).collect())
+            }
+            Builtin::Repeat => {
+                let count = self.count(arguments, 1)?;
+                self.string_length(text.chars().count().saturating_mul(count))?;
+                EvalResult::Literal(text.repeat(count))
+            }
+            Builtin::PadLeft | Builtin::PadRight => {
+                let width = self.string_length(self.count(arguments, 1)?)?;
+                let fill = match arguments.get(2) {
+                    Some(EvalResult::Literal(fill)) if fill.chars().count() == 1 => fill.as_str(),
+                    Some(other) => return Err(self.wrong_argument(3, "a single character", other)),
+                    None => " ",
+                };
+                let padding = fill.repeat(width.saturating_sub(text.chars().count()));
+                match self {
+                    Builtin::PadLeft => EvalResult::Literal(padding + text),
+                    _ => EvalResult::Literal(text.to_string() + &padding),
+                }
+            }
+            Builtin::ParseNum => {
+                let trimmed = text.trim();
+                let number = Number::from_decimal(trimmed).or_else(|| {
+                    trimmed
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|float| float.is_finite())
+                        .map(Number::Float)
+                });
+                match number {
+                    Some(number) => EvalResult::ok(EvalResult::Number(number)),
+                    None => {
+                        let message = format!("Could not parse {0:?} as a number", text);
+                        EvalResult::err(EvalResult::Literal(message))
+                    }
+                }
+            }
+            _ => unreachable!(),
+        };
+        Ok(result)
+    }
+
+    // `format(template, values...)`, every placeholder in the template is replaced by the
+    // text of a value.
+    /*
+        Placeholders are `{}` for the next value or `{1}` for the value at an index, with an
+        optional specification after a colon: `{:8}` pads to 8 characters, `{:<8}`, `{:>8}`
+        and `{:^8}` align left, right or in the middle, and `{:.2}` writes numbers with 2
+        decimals (and cuts other values to 2 characters). Numbers align right by default,
+        everything else left. `{{` and `}}` are written as braces.
+
+        Every value has to be used, a missing or unused value is an error like in Rust.
+    */
+    fn format(&self, arguments: &[EvalResult]) -> Result<String, Box<dyn error::Error>> {
+        let template = self.string(arguments, 0)?;
+        let values = &arguments[1..];
+        let mut used = vec![false; values.len()];
+        let mut next = 0;
+        let mut result = String::new();
+        // Characters written for placeholders so far
+        let mut written = 0usize;
+        let mut characters = template.chars();
+        while let Some(character) = characters.next() {
+            match character {
+                '{' if characters.as_str().starts_with('{') => {
+                    characters.next();
+                    result.push('{');
+                }
+                '}' if characters.as_str().starts_with('}') => {
+                    characters.next();
+                    result.push('}');
+                }
+                '{' => {
+                    let rest = characters.as_str();
+                    let end = match rest.find('}') {
+                        Some(end) => end,
+                        None => return Err("Function format found a { without a matching }".into()),
+                    };
+                    let (position, specification) = match rest[..end].find(':') {
+                        Some(colon) => (&rest[..colon], &rest[colon + 1..end]),
+                        None => (&rest[..end], ""),
+                    };
+                    let index = match position {
+                        "" => {
+                            next += 1;
+                            next - 1
+                        }
+                        position => match position.parse::<usize>() {
+                            Ok(index) => index,
+                            Err(_) => {
+                                return Err(format!(
+                                    "Function format found an invalid placeholder {{{0}}}",
+                                    &rest[..end]
+                                )
+                                .into())
+                            }
+                        },
+                    };
+                    let value = match values.get(index) {
+                        Some(value) => value,
+                        None => {
+                            return Err(format!(
+                                "Function format has no value for placeholder {0}, got {1}",
+                                index,
+                                amount(values.len(), "value")
+                            )
+                            .into())
+                        }
+                    };
+                    used[index] = true;
+                    let text = self.format_value(value, specification)?;
+                    written = self.string_length(written + text.chars().count())?;
+                    result.push_str(&text);
+                    characters = rest[end + 1..].chars();
+                }
+                '}' => return Err("Function format found a } without a matching {".into()),
+                character => result.push(character),
+            }
+        }
+        if let Some(unused) = used.iter().position(|used| !used) {
+            return Err(format!("Function format does not use value {0}", unused).into());
+        }
+        self.string_length(result.chars().count())?;
+        Ok(result)
+    }
+
+    // Text of a value for a placeholder with the given specification, e.g. `>8.2`.
+    fn format_value(
+        &self,
+        value: &EvalResult,
+        specification: &str,
+    ) -> Result<String, Box<dyn error::Error>> {
+        let invalid = || {
+            format!(
+                "Function format found an invalid specification {0:?}",
+                specification
+            )
+        };
+        let (alignment, rest) = match specification.chars().next() {
+            Some(alignment @ '<') | Some(alignment @ '>') | Some(alignment @ '^') => {
+                (Some(alignment), &specification[1..])
+            }
+            _ => (None, specification),
+        };
+        let (width, precision) = match rest.find('.') {
+            Some(dot) => (&rest[..dot], Some(&rest[dot + 1..])),
+            None => (rest, None),
+        };
+        let width = match width {
+            "" => 0,
+            width => self.string_length(width.parse::<usize>().map_err(|_| invalid())?)?,
+        };
+        let precision = match precision {
+            Some(precision) => {
+                let precision = precision.parse::<usize>().map_err(|_| invalid())?;
+                Some(self.string_length(precision)?)
+            }
+            None => None,
+        };
+        let quantity = value.to_quantity();
+        if quantity.is_none() {
+            self.string_length(Builtin::text_length(value))?;
+        }
+        let text = match (precision, &quantity) {
+            (Some(precision), Some(_)) if precision > u16::MAX as usize => {
+                return Err(format!(
+                    "Function format can not write numbers with more than {0} decimals",
+                    u16::MAX
+                )
+                .into())
+            }
+            (Some(precision), Some(quantity)) => {
+                let number = quantity.display_value()?.decimals(precision);
+                match quantity.is_dimensionless() && quantity.unit.is_none() {
+                    true => number,
+                    false => format!("{0} {1}", number, quantity.display_unit()),
+                }
+            }
+            (Some(precision), None) => Builtin::text(value).chars().take(precision).collect(),
+            (None, _) => Builtin::text(value),
+        };
+        let padding = width.saturating_sub(text.chars().count());
+        let (left, right) = match alignment {
+            Some('<') => (0, padding),
+            Some('^') => (padding / 2, padding - padding / 2),
+            Some(_) => (padding, 0),
+            None if quantity.is_some() => (padding, 0),
+            None => (0, padding),
+        };
+        Ok(format!("{0}{1}{2}", " ".repeat(left), text, " ".repeat(right)))
+    }
+
+    // Results of the given function have to be values.
+    fn value(&self, result: EvalResult) -> Result<EvalResult, Box<dyn error::Error>> {
+        match result {
+            EvalResult::Number(_)
+            | EvalResult::Quantity(_)
+            | EvalResult::Literal(_)
+            | EvalResult::Boolean(_)
+            | EvalResult::Function(_)
+            | EvalResult::List(_)
+            | EvalResult::Struct(_)
+            | EvalResult::Enum(_)
+            | EvalResult::Error(_)
+            | EvalResult::Module(_) => Ok(result),
+            other => Err(format!(
+                "Function passed to {0} returned {1} instead of a value",
+                self.name(),
+                other
+            )
+            .into()),
+        }
+    }
+
+    // Predicates and elements tested by any/all have to be booleans.
+    fn test(&self, result: EvalResult) -> Result<bool, Box<dyn error::Error>> {
+        match result {
+            EvalResult::Boolean(b) => Ok(b),
+            other => Err(Box::new(RuntimeError::new(
+                ErrorKind::TypeMismatch,
+                format!("Function {0} expects a bool, got {1}", self.name(), other),
+            ))),
+        }
+    }
+
+    // `range(end)`, `range(start, end)` or `range(start, end, step)`, end is excluded.
+    fn range(&self, arguments: &[EvalResult]) -> Result<EvalResult, Box<dyn error::Error>> {
+        let mut bounds = Vec::new();
+        for (i, argument) in arguments.iter().enumerate() {
+            bounds.push(self.integer(argument, i + 1)?);
+        }
+        let (start, end, step) = match bounds.as_slice() {
+            [end] => (0, *end, 1),
+            [start, end] => (*start, *end, 1),
+            [start, end, step] => (*start, *end, *step),
+            _ => unreachable!(),
+        };
+        if step == 0 {
+            return Err("Function range expects a step other than 0".into());
+        }
+        // Rounded up, computed in i128 so it can't overflow
+        let (start, end, step) = (start as i128, end as i128, step as i128);
+        let length = ((end - start + step - step.signum()) / step).max(0);
+        if length > MAX_LIST_LENGTH as i128 {
+            return Err(format!(
+                "Function range can not create more than {0} values",
+                MAX_LIST_LENGTH
+            )
+            .into());
+        }
+        let values = (0..length)
+            .map(|i| EvalResult::Number(Number::Integer((start + i * step) as i64)))
+            .collect();
+        Ok(EvalResult::List(Rc::new(values)))
+    }
+
+    // Stable merge sort with a comparison that can fail.
+    fn merge_sort(
+        mut items: Vec<EvalResult>,
+        less: &mut Less,
+    ) -> Result<Vec<EvalResult>, Box<dyn error::Error>> {
+        if items.len() <= 1 {
+            return Ok(items);
+        }
+        let right = items.split_off(items.len() / 2);
+        let left = Builtin::merge_sort(items, less)?;
+        let right = Builtin::merge_sort(right, less)?;
+        let mut merged = Vec::with_capacity(left.len() + right.len());
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+        while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+            // Equal elements keep their order
+            if less(b, a)? {
+                merged.push(right.next().unwrap());
+            } else {
+                merged.push(left.next().unwrap());
+            }
+        }
+        merged.extend(left);
+        merged.extend(right);
+        Ok(merged)
+    }
+}
@@ -0,0 +1,353 @@
+// Tests for the builtin collection and string functions.
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::builtins::Builtin;
+    use crate::lexer_and_parser::evaluator::{EvalResult, Evaluator};
+    use crate::lexer_and_parser::parser::Parser;
+
+    // Evaluate every statement and return the last result as text.
+    fn run(source: &str) -> Result<String, String> {
+        let mut evaluator = Evaluator::new(None);
+        let mut result = EvalResult::EmptyResult;
+        for expression in Parser::new(source).unwrap().parse().unwrap() {
+            evaluator.ast = Some(expression);
+            result = evaluator.start_evaluating().map_err(|e| e.to_string())?;
+        }
+        Ok(Builtin::text(&result))
+    }
+
+    #[test]
+    fn test_higher_order() {
+        let cases = [
+            ("map([1, 2, 3], fn(x) { x * x })", "[1, 4, 9]"),
+            ("filter(range(10), fn(x) { x > 6 })", "[7, 8, 9]"),
+            ("reduce([1, 2, 3, 4], fn(a, b) { a * b })", "24"),
+            (
+                "fold([\"a\", \"b\"], \"x\", fn(a, b) { a == \"x\" })",
+                "false",
+            ),
+            ("fold([], 5, fn(a, b) { a + b })", "5"),
+            ("any([1, 2, 3], fn(x) { x > 2 })", "true"),
+            ("all([1, 2, 3], fn(x) { x > 2 })", "false"),
+            ("any([])", "false"),
+            ("all([true, 1 == 1])", "true"),
+            // Named functions can be passed as well
+            (
+                "fn big(n) {\n    return n > 1\n}\nfilter([1, 2, 3], big)",
+                "[2, 3]",
+            ),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Ok(expected.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_list_functions() {
+        let cases = [
+            ("sum([1, 2, 3/2])", "9/2"),
+            ("sum([])", "0"),
+            ("sum([1 km, 500 m]) in m", "1500 m"),
+            ("sort([3, 1, 2])", "[1, 2, 3]"),
+            ("sort([\"b\", \"c\", \"a\"])", "[a, b, c]"),
+            ("sort([3, 1, 2], fn(a, b) { a > b })", "[3, 2, 1]"),
+            // Sorting is stable
+            (
+                "sort([[2, \"a\"], [1, \"b\"], [2, \"c\"]], fn(a, b) { a[0] < b[0] })",
+                "[[1, b], [2, a], [2, c]]",
+            ),
+            ("reverse([1, 2, 3])", "[3, 2, 1]"),
+            ("zip([1, 2, 3], [\"a\", \"b\"])", "[[1, a], [2, b]]"),
+            ("enumerate([\"a\", \"b\"])", "[[0, a], [1, b]]"),
+            ("range(3)", "[0, 1, 2]"),
+            ("range(2, 5)", "[2, 3, 4]"),
+            ("range(5, 0, -2)", "[5, 3, 1]"),
+            ("range(2, 7, 2)", "[2, 4, 6]"),
+            ("range(3, 1)", "[]"),
+            // Too long ranges fail like any other runtime error
+            ("try { range(1, 1000000000000) } catch e { \"caught\" }", "caught"),
+            ("join([1, \"a\", true], \", \")", "1, a, true"),
+            ("join([\"a\", \"b\"])", "ab"),
+            ("contains([1, 2], 2)", "true"),
+            ("index_of([1, 2], 2)", "1"),
+            ("index_of([1, 2], 3)", "-1"),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Ok(expected.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            (
+                "map(1, fn(x) { x })",
+                "Function map expects a list as argument 1, got 1",
+            ),
+            (
+                "map([1], 2)",
+                "Function map expects a function as argument 2, got 2",
+            ),
+            ("map([1])", "Function map expects 2 arguments, got 1"),
+            (
+                "float(\"a\")",
+                "Function float expects a number as argument 1, got \"a\"",
+            ),
+            ("range()", "Function range expects 1 to 3 arguments, got 0"),
+            (
+                "range(1, 5, 0)",
+                "Function range expects a step other than 0",
+            ),
+            (
+                "range(1, 1000000000000)",
+                "Function range can not create more than 10000000 values",
+            ),
+            (
+                "range(1.5)",
+                "Function range expects an integer as argument 1, got 1.5",
+            ),
+            (
+                "filter([1], fn(x) { x })",
+                "Function filter expects a bool, got 1",
+            ),
+            (
+                "sum([1, \"a\"])",
+                "Function sum expects a list of numbers, got \"a\"",
+            ),
+            (
+                "reduce([], fn(a, b) { a })",
+                "Function reduce can not reduce an empty list",
+            ),
+            (
+                "sort([1, \"a\"])",
+                "Can not compare \"a\" and 1",
+            ),
+            (
+                "map([1], fn(a, b) { a })",
+                "Function <anonymous> expects 2 arguments, got 1",
+            ),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Err(expected.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_shadowing() {
+        // Variables and declared functions come before builtins
+        assert_eq!(
+            run("fn sum(list) {\n    return 0\n}\nsum([1, 2])"),
+            Ok("0".to_string())
+        );
+        assert_eq!(run("range = fn(n) { n }\nrange(3)"), Ok("3".to_string()));
+        assert_eq!(Builtin::from_name("index_of"), Some(Builtin::IndexOf));
+        assert_eq!(Builtin::from_name("print"), None);
+    }
+
+    #[test]
+    fn test_results_and_options() {
+        let cases = [
+            ("ok(1)", "ok(1)"),
+            ("err(\"bad\")", "err(bad)"),
+            ("some([1, 2])", "some([1, 2])"),
+            ("none", "none"),
+            ("unwrap(ok(1))", "1"),
+            ("unwrap(some(2))", "2"),
+            ("unwrap_or(err(1), 5)", "5"),
+            ("unwrap_or(none, 5)", "5"),
+            ("unwrap_or(some(3), 5)", "3"),
+            ("ok(1) == Result::Ok(1)", "true"),
+            ("some(1) == ok(1)", "false"),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Ok(expected.to_string()), "{}", source);
+        }
+        let cases = [
+            ("unwrap(err(\"bad\"))", "bad"),
+            ("unwrap(none)", "Expected a value, got none"),
+            (
+                "unwrap_or(1, 2)",
+                "Function unwrap_or expects a Result or an Option as argument 1, got 1",
+            ),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Err(expected.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_string_functions() {
+        let cases = [
+            ("len(\"héllo\")", "5"),
+            ("len([1, 2])", "2"),
+            ("upper(\"straße\")", "STRASSE"),
+            ("lower(\"ÀB\")", "àb"),
+            ("trim(\"  a b \")", "a b"),
+            ("split(\"a,b,,c\", \",\")", "[a, b, , c]"),
+            ("split(\" a  b \")", "[a, b]"),
+            ("split(\"hé\", \"\")", "[h, é]"),
+            ("replace(\"a-b-c\", \"-\", \"+\")", "a+b+c"),
+            ("starts_with(\"wistful\", \"wist\")", "true"),
+            ("ends_with(\"wistful\", \"wist\")", "false"),
+            // Indices count characters, not bytes
+            ("find(\"héllo\", \"l\")", "2"),
+            ("find(\"hello\", \"x\")", "-1"),
+            ("substring(\"héllo\", 1, 3)", "él"),
+            ("substring(\"héllo\", 3)", "lo"),
+            ("substring(\"abc\", 2, 10)", "c"),
+            ("substring(\"abc\", 2, 1)", ""),
+            ("repeat(\"ab\", 3)", "ababab"),
+            ("pad_left(\"7\", 3, \"0\")", "007"),
+            ("pad_right(\"é\", 3)", "é  "),
+            ("pad_left(\"long\", 2)", "long"),
+            ("parse_num(\" 42 \")", "ok(42)"),
+            ("parse_num(\"2.5\")", "ok(2.5)"),
+            ("parse_num(\"abc\")", "err(Could not parse \"abc\" as a number)"),
+            ("float(1/4)", "0.25"),
+            ("float(3) / 2", "1.5"),
+            ("float(1500 m) in km", "1.5 km"),
+            ("to_string([1, true])", "[1, true]"),
+            ("len(to_string(1/3))", "3"),
+            ("s = repeat(\"ab\", 2000000)\nlen(join([s, s], \"-\"))", "8000001"),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Ok(expected.to_string()), "{}", source);
+        }
+        let cases = [
+            (
+                "len(1)",
+                "Function len expects a string or a list as argument 1, got 1",
+            ),
+            (
+                "upper([\"a\"])",
+                "Function upper expects a string as argument 1, got [\"a\"]",
+            ),
+            (
+                "split(\"a\", 1)",
+                "Function split expects a string as argument 2, got 1",
+            ),
+            (
+                "replace(\"a\", \"\", \"b\")",
+                "Function replace expects a string to replace that is not empty",
+            ),
+            (
+                "substring(\"abc\", -1)",
+                "Function substring expects an integer of at least 0 as argument 2, got -1",
+            ),
+            (
+                "pad_left(\"a\", 3, \"ab\")",
+                "Function pad_left expects a single character as argument 3, got \"ab\"",
+            ),
+            ("trim()", "Function trim expects 1 argument, got 0"),
+            // Strings that would not fit in memory
+            (
+                "repeat(\"ab\", 1000000000000)",
+                "Function repeat can not create a string of more than 10000000 characters",
+            ),
+            (
+                "pad_left(\"a\", 1000000000000)",
+                "Function pad_left can not create a string of more than 10000000 characters",
+            ),
+            (
+                "s = repeat(\"a\", 6000000)\njoin([s, s])",
+                "Function join can not create a string of more than 10000000 characters",
+            ),
+            (
+                "s = repeat(\"a\", 6000000)\nto_string([s, s])",
+                "Function to_string can not create a string of more than 10000000 characters",
+            ),
+            (
+                "s = repeat(\"a\", 6000000)\nreplace(s, \"a\", \"aa\")",
+                "Function replace can not create a string of more than 10000000 characters",
+            ),
+            (
+                "upper(repeat(\"ß\", 6000000))",
+                "Function upper can not create a string of more than 10000000 characters",
+            ),
+            (
+                "s = \"ab\"\nwhile true { s = join([s, s]) }",
+                "Function join can not create a string of more than 10000000 characters",
+            ),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Err(expected.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let cases = [
+            ("format(\"{} has {:.2}\", \"pi\", 22/7)", "pi has 3.14"),
+            ("format(\"{1}, {0}\", \"a\", \"b\")", "b, a"),
+            ("format(\"[{:5}]\", 42)", "[   42]"),
+            ("format(\"[{:5}]\", \"ab\")", "[ab   ]"),
+            ("format(\"[{:<5}|{:>5}|{:^5}]\", 1, \"é\", \"ab\")", "[1    |    é| ab  ]"),
+            ("format(\"[{:8.1}]\", 2.25)", "[     2.2]"),
+            ("format(\"{:.0}\", 3)", "3"),
+            ("format(\"{:.2}\", 1500 m in km)", "1.50 km"),
+            ("format(\"{:.2}\", \"wistful\")", "wi"),
+            ("format(\"{{{}}}\", [1])", "{[1]}"),
+            ("format(\"plain\")", "plain"),
+            // Exact numbers are written without going through a float
+            (
+                "format(\"{:.2}\", 12345678901234567890123)",
+                "12345678901234567890123.00",
+            ),
+            ("format(\"{:.25}\", 1/3)", "0.3333333333333333333333333"),
+            ("format(\"{:.1}\", -1/3)", "-0.3"),
+            ("format(\"{:.2} {:.2}\", 1/8, 3/8)", "0.12 0.38"),
+            ("format(\"{:.0}\", 5/2)", "2"),
+            ("len(format(\"{:.65535}\", 1.5))", "65537"),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Ok(expected.to_string()), "{}", source);
+        }
+        let cases = [
+            (
+                "format(\"{} {}\", 1)",
+                "Function format has no value for placeholder 1, got 1 value",
+            ),
+            ("format(\"{}\", 1, 2)", "Function format does not use value 1"),
+            (
+                "format(\"{\", 1)",
+                "Function format found a { without a matching }",
+            ),
+            (
+                "format(\"}\")",
+                "Function format found a } without a matching {",
+            ),
+            (
+                "format(\"{a}\", 1)",
+                "Function format found an invalid placeholder {a}",
+            ),
+            (
+                "format(\"{:x}\", 1)",
+                "Function format found an invalid specification \"x\"",
+            ),
+            ("format()", "Function format expects at least 1 argument, got 0"),
+            (
+                "format(\"{:.1000000000}\", 1)",
+                "Function format can not create a string of more than 10000000 characters",
+            ),
+            (
+                "format(\"{:1000000000000}\", 1)",
+                "Function format can not create a string of more than 10000000 characters",
+            ),
+            (
+                "format(\"{:.65536}\", 1.5)",
+                "Function format can not write numbers with more than 65535 decimals",
+            ),
+            (
+                "s = repeat(\"a\", 6000000)\nformat(\"{}{}\", s, s)",
+                "Function format can not create a string of more than 10000000 characters",
+            ),
+            (
+                "s = \"ab\"\nwhile true { s = format(\"{}{}\", s, s) }",
+                "Function format can not create a string of more than 10000000 characters",
+            ),
+        ];
+        for (source, expected) in cases.iter() {
+            assert_eq!(run(source), Err(expected.to_string()), "{}", source);
+        }
+    }
+}
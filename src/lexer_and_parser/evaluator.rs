@@ -1,45 +1,575 @@
 // Libraries
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::error;
 use std::fmt;
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
 
 // Internal modules
-use super::ast::Node;
+use super::arena::{Ast, AstArm, AstNode, AstPattern, NodeId, NodeList};
+use super::ast::{CatchClause, Node, Slot};
+use super::builtins::{amount, Builtin, BUILTIN_ENUMS};
+use super::modules::{Module, ModuleLoader, ModuleSource};
+use super::numeric::{Number, DIVISION_BY_ZERO};
+use super::prelude::PRELUDE;
 use super::token::{Keyword, Token};
+use super::units::{Quantity, UnitRegistry};
+use super::visitor::{walk_node, Visitor};
+
+// Maximum depth of nested function calls before evaluation is aborted.
+pub const MAX_CALL_DEPTH: usize = 100;
+
+// Variables bound by a match arm with the values they shadow, None if they were unassigned.
+type Shadowed = Vec<(NodeId, Option<VariableValue>)>;
+
+// Function of the host, called with the evaluated arguments. See `Evaluator::register`.
+pub type HostFunction = Rc<dyn Fn(&[EvalResult]) -> EvalResult>;
 
 pub struct Evaluator {
     pub ast: Option<Node>,
-    // Storing evaluated variables
-    pub variable_storage: HashMap<String, VariableValue>,
+    // Values of global variables by slot, None until assigned
+    globals: Vec<Option<VariableValue>>,
+    // Slot of every known global variable
+    global_slots: HashMap<String, usize>,
+    // Units known to the scripts. Hosts can register their own units here.
+    pub units: UnitRegistry,
+    // Declared functions by name
+    pub functions: HashMap<String, Rc<Closure>>,
+    // Functions registered by the host, declared functions and variables shadow them
+    pub host_functions: HashMap<String, HostFunction>,
+    // Declared structs by name
+    pub structs: HashMap<String, StructType>,
+    // Declared enums by name, with the amount of values of every variant
+    pub enums: HashMap<String, Vec<(String, usize)>>,
+    // Local variables of the functions being called, innermost call last
+    call_stack: Vec<Rc<CallFrame>>,
+    // Files that can be imported. Hosts can add search paths here.
+    pub modules: ModuleLoader,
+    // Module whose variables and functions are the ones above, 0 for the script
+    module: usize,
+    // Variables and functions of every module by id, empty for the current one
+    environments: Vec<Environment>,
+    // Functions of the prelude, every module starts with them
+    prelude: HashMap<String, Rc<Closure>>,
 }
 
 // TODO: Move to a separate file which contains helper classes/methods.
 // TODO: struct -> enum, different variants for different types. i.e string, float, int etc.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum VariableValue {
     // Fields set to public just for debugging purposes
-    Number(f64),
+    Number(Number),
+    Quantity(Quantity),
     Literal(String),
     Boolean(bool),
+    Function(Rc<Closure>),
+    List(Rc<Vec<EvalResult>>),
+    Struct(Rc<Instance>),
+    Enum(Rc<EnumValue>),
+    Error(Rc<ErrorValue>),
+    Module(Rc<Module>),
 }
 
 // Actual result of the expression evaluating.
 #[derive(Debug, Clone, PartialEq)]
 pub enum EvalResult {
-    Number(f64),
+    Number(Number),
+    Quantity(Quantity),
     Literal(String),
     Boolean(bool),
+    Function(Rc<Closure>),
+    // Lists are immutable, so they are shared instead of copied
+    List(Rc<Vec<EvalResult>>),
+    Struct(Rc<Instance>),
+    Enum(Rc<EnumValue>),
+    // Caught error, e.g. `e` in `catch e { ... }`
+    Error(Rc<ErrorValue>),
+    // Imported module, e.g. `geo` in `import "lib/geometry.wf" as geo`
+    Module(Rc<Module>),
     Assignment {
         identifier: Box<EvalResult>,
         value: Box<EvalResult>,
     },
+    // Control flow signals, passed up until the enclosing loop or function handles them
+    Break,
+    Return(Box<EvalResult>),
     EmptyResult,
 }
 
-// Display trait for EvalResult. Used to parse values for variable instantiating and debugging.
+// User defined function.
+#[derive(Debug)]
+pub struct Function {
+    // None for anonymous functions
+    pub name: Option<String>,
+    pub parameters: Vec<String>,
+    // Arena the function was declared in, and its statements there
+    pub ast: Rc<Ast>,
+    pub body: NodeList,
+    // Slots of the parameters and every variable assigned in the body. These are local
+    // to each call, any other identifier refers to a variable of an enclosing function
+    // or to a global variable.
+    pub locals: HashMap<String, usize>,
+    // Module the function was declared in, its globals are the ones of the module
+    pub module: usize,
+}
+
+// Function value, a function together with the call it was created in.
+/*
+    Closures capture the variables of the enclosing calls by reference: the frame of a call
+    stays alive as long as a closure created in it, and reading a captured variable gives
+    its latest value. Assigning to a captured variable changes it in the call that owns it,
+    so closures can keep state between calls.
+*/
+pub struct Closure {
+    pub function: Rc<Function>,
+    // None for functions created at the top level, they only see globals
+    frame: Option<Rc<CallFrame>>,
+}
+
+// Declared struct together with the methods of its impl blocks.
+pub struct StructType {
+    pub fields: Vec<String>,
+    pub methods: HashMap<String, Rc<Closure>>,
+}
+
+// Value of a struct.
+/*
+    Struct values are shared instead of copied: assigning one to another variable or
+    passing it to a function gives the same value, so methods can change the fields
+    of `self`.
+*/
+pub struct Instance {
+    pub name: String,
+    // Field values in declaration order
+    pub fields: RefCell<Vec<(String, EvalResult)>>,
+}
+
+// Value of an enum variant, e.g. `Shape::Circle(1)`. Like lists, variants are immutable.
+#[derive(PartialEq)]
+pub struct EnumValue {
+    pub enum_name: String,
+    pub variant: String,
+    pub values: Vec<EvalResult>,
+}
+
+// Kind of a runtime error. Scripts see it as a string, e.g. `e.kind == "DivisionByZero"`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    // Thrown by a script
+    Error,
+    UndefinedVariable,
+    TypeMismatch,
+    DivisionByZero,
+    // Any other error of the evaluator, e.g. an index out of range
+    Runtime,
+    // Given to `error(message, kind)` by a script
+    Custom(String),
+}
+
+// Value of an error, e.g. `e` in `catch e { ... }`.
+#[derive(Clone, PartialEq)]
+pub struct ErrorValue {
+    pub kind: ErrorKind,
+    pub message: String,
+    // Line of the statement the error was raised in, None if it is not known
+    pub line: Option<usize>,
+}
+
+// Error of a running script, which a try can catch.
+/*
+    Errors are located while they are passed up: the first statement they leave sets
+    the line of the value, and every function call they leave adds an entry to the trace.
+    Display gives the message only, the alternate form `{:#}` gives the kind, the message
+    and the stack trace, innermost call first, e.g.
+
+        DivisionByZero: Division by zero
+          in average at line 3
+          at line 9
+
+    `x?` on an err or none is passed up the same way, but the call it leaves returns the
+    err or none instead of failing. Only at the top level it is an error like any other.
+*/
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub value: ErrorValue,
+    // Function and line of every call the error left, None for the top level
+    pub trace: Vec<(Option<String>, Option<usize>)>,
+    // Line of the statement in the current call
+    line: Option<usize>,
+    // The err or none of a `?`
+    pub propagated: Option<EvalResult>,
+}
+
+// What a call expression calls.
+enum Callee {
+    Function(Rc<Closure>),
+    Builtin(Builtin),
+    Host(HostFunction),
+    // Method of a struct, the value is passed as `self`
+    Method {
+        closure: Rc<Closure>,
+        receiver: Rc<Instance>,
+    },
+}
+
+// Global variables and declared functions of a module that is not running.
+#[derive(Default)]
+struct Environment {
+    globals: Vec<Option<VariableValue>>,
+    global_slots: HashMap<String, usize>,
+    functions: HashMap<String, Rc<Closure>>,
+}
+
+// Variables of one function call, by slot.
+struct CallFrame {
+    function: Rc<Function>,
+    variables: RefCell<Vec<Option<VariableValue>>>,
+    // Frame of the call the function was created in
+    parent: Option<Rc<CallFrame>>,
+    // Module that was running before the call
+    caller: usize,
+}
+
+impl Function {
+    // Name used in messages
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous>")
+    }
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{0}", self.function.display_name())
+    }
+}
+
+// Functions are only equal to themselves.
+impl PartialEq for Closure {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Instance {
+    // Value of a field, None if the struct has no such field.
+    pub fn get(&self, field: &str) -> Option<EvalResult> {
+        let fields = self.fields.borrow();
+        fields.iter().find(|(name, _)| name == field).map(|(_, value)| value.clone())
+    }
+}
+
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct(&self.name);
+        for (name, value) in self.fields.borrow().iter() {
+            debug.field(name, value);
+        }
+        debug.finish()
+    }
+}
+
+// Structs are equal when they are of the same struct and their fields are equal.
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+            || (self.name == other.name && *self.fields.borrow() == *other.fields.borrow())
+    }
+}
+
+impl fmt::Debug for EnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_tuple(&format!("{0}::{1}", self.enum_name, self.variant));
+        for value in &self.values {
+            debug.field(value);
+        }
+        debug.finish()
+    }
+}
+
+impl ErrorKind {
+    pub fn from_name(name: &str) -> ErrorKind {
+        match name {
+            "Error" => ErrorKind::Error,
+            "UndefinedVariable" => ErrorKind::UndefinedVariable,
+            "TypeMismatch" => ErrorKind::TypeMismatch,
+            "DivisionByZero" => ErrorKind::DivisionByZero,
+            "RuntimeError" => ErrorKind::Runtime,
+            name => ErrorKind::Custom(name.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            ErrorKind::Error => "Error",
+            ErrorKind::UndefinedVariable => "UndefinedVariable",
+            ErrorKind::TypeMismatch => "TypeMismatch",
+            ErrorKind::DivisionByZero => "DivisionByZero",
+            ErrorKind::Runtime => "RuntimeError",
+            ErrorKind::Custom(name) => name,
+        }
+    }
+}
+
+impl ErrorValue {
+    // Fields scripts can read: message, kind and line. The line is empty when unknown.
+    pub fn field(&self, field: &str) -> Option<EvalResult> {
+        match field {
+            "message" => Some(EvalResult::Literal(self.message.clone())),
+            "kind" => Some(EvalResult::Literal(self.kind.name().to_string())),
+            "line" => Some(match self.line {
+                Some(line) => EvalResult::Number(Number::Integer(line as i64)),
+                None => EvalResult::EmptyResult,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for ErrorValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{0}: {1}", self.kind.name(), self.message)
+    }
+}
+
+impl RuntimeError {
+    pub fn new(kind: ErrorKind, message: String) -> Self {
+        RuntimeError::from_value(ErrorValue {
+            kind,
+            message,
+            line: None,
+        })
+    }
+
+    // Throw a value again, it keeps its kind and line.
+    pub fn from_value(value: ErrorValue) -> Self {
+        RuntimeError {
+            value,
+            trace: Vec::new(),
+            line: None,
+            propagated: None,
+        }
+    }
+
+    // Error for the value of an err, or for none, e.g. when unwrapping it. Errors keep
+    // their kind.
+    pub fn from_failure(failure: Option<EvalResult>) -> Self {
+        match failure {
+            Some(EvalResult::Error(error)) => RuntimeError::from_value((*error).clone()),
+            Some(value) => RuntimeError::new(ErrorKind::Error, Builtin::text(&value)),
+            None => RuntimeError::new(ErrorKind::Error, "Expected a value, got none".to_string()),
+        }
+    }
+
+    // Pass an err or none up to the enclosing call.
+    pub fn propagate(value: EvalResult, failure: Option<EvalResult>) -> Self {
+        let mut error = RuntimeError::from_failure(failure);
+        error.propagated = Some(value);
+        error
+    }
+
+    // Any other error of the evaluator is a RuntimeError.
+    pub fn from_error(error: Box<dyn error::Error>) -> Self {
+        match error.downcast::<RuntimeError>() {
+            Ok(error) => *error,
+            Err(error) => RuntimeError::new(ErrorKind::Runtime, error.to_string()),
+        }
+    }
+
+    // The error left a statement on the given line, only the innermost one counts.
+    pub fn at_line(mut self, line: Option<usize>) -> Self {
+        if self.line.is_none() {
+            self.line = line;
+        }
+        if self.value.line.is_none() {
+            self.value.line = line;
+        }
+        self
+    }
+
+    // The error left a call of the given function, None for the top level.
+    pub fn leave(mut self, function: Option<String>) -> Self {
+        self.trace.push((function, self.line.take()));
+        self
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !f.alternate() {
+            return write!(f, "{0}", self.value.message);
+        }
+        write!(f, "{0:?}", self.value)?;
+        for (function, line) in &self.trace {
+            let place = match line {
+                Some(line) => format!("at line {0}", line),
+                None => "at an unknown line".to_string(),
+            };
+            match function {
+                Some(function) => write!(f, "\n  in {0} {1}", function, place)?,
+                None => write!(f, "\n  {0}", place)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for RuntimeError {}
+
+// Display trait for EvalResult. Used to name values in error messages: strings are quoted
+// so they can be told apart from other values, everything else reads like `to_string`.
 impl fmt::Display for EvalResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            EvalResult::Literal(text) => write!(f, "\"{0}\"", text),
+            EvalResult::List(items) => {
+                let texts: Vec<String> = items.iter().map(EvalResult::to_string).collect();
+                write!(f, "[{0}]", texts.join(", "))
+            }
+            EvalResult::Function(closure) => match &closure.function.name {
+                Some(name) => write!(f, "function {0}", name),
+                None => write!(f, "anonymous function"),
+            },
+            EvalResult::Assignment { identifier, value } => {
+                write!(f, "{0} = {1}", Builtin::text(identifier), value)
+            }
+            EvalResult::Break => write!(f, "break"),
+            EvalResult::Return(value) => write!(f, "{0}", value),
+            EvalResult::EmptyResult => write!(f, "nothing"),
+            value => write!(f, "{0}", Builtin::text(value)),
+        }
+    }
+}
+
+// Results of host code become `ok` and `err` values, errors are passed as their message.
+impl<E: fmt::Display> From<Result<EvalResult, E>> for EvalResult {
+    fn from(result: Result<EvalResult, E>) -> Self {
+        match result {
+            Ok(value) => EvalResult::ok(value),
+            Err(error) => EvalResult::err(EvalResult::Literal(error.to_string())),
+        }
+    }
+}
+
+impl From<Option<EvalResult>> for EvalResult {
+    fn from(option: Option<EvalResult>) -> Self {
+        match option {
+            Some(value) => EvalResult::some(value),
+            None => EvalResult::none(),
+        }
+    }
+}
+
+// Value helpers shared by the tree walker and the bytecode VM, so both agree on semantics.
+impl EvalResult {
+    // Numeric values as a quantity, plain numbers are dimensionless.
+    pub fn to_quantity(&self) -> Option<Quantity> {
+        match self {
+            EvalResult::Number(n) => Some(Quantity::number(n.clone())),
+            EvalResult::Quantity(q) => Some(q.clone()),
+            _ => None,
+        }
+    }
+
+    // Values without a unit are plain numbers.
+    pub fn from_quantity(quantity: Quantity) -> EvalResult {
+        if quantity.is_dimensionless() && quantity.unit.is_none() {
+            EvalResult::Number(quantity.value)
+        } else {
+            EvalResult::Quantity(quantity)
+        }
+    }
+
+    // Values of the built-in Result and Option enums, e.g. `ok(1)` or `none`.
+    pub fn ok(value: EvalResult) -> EvalResult {
+        EvalResult::variant("Result", "Ok", vec![value])
+    }
+
+    pub fn err(value: EvalResult) -> EvalResult {
+        EvalResult::variant("Result", "Err", vec![value])
+    }
+
+    pub fn some(value: EvalResult) -> EvalResult {
+        EvalResult::variant("Option", "Some", vec![value])
+    }
+
+    pub fn none() -> EvalResult {
+        EvalResult::variant("Option", "None", Vec::new())
+    }
+
+    // Result or Option as a Rust Result: the value inside ok or some, otherwise the value
+    // of err, or None for none. Any other value is neither.
+    pub fn as_result(&self) -> Option<Result<EvalResult, Option<EvalResult>>> {
+        let value = match self {
+            EvalResult::Enum(value) => value,
+            _ => return None,
+        };
+        match (value.enum_name.as_str(), value.variant.as_str()) {
+            ("Result", "Ok") | ("Option", "Some") => Some(Ok(value.values[0].clone())),
+            ("Result", "Err") => Some(Err(Some(value.values[0].clone()))),
+            ("Option", "None") => Some(Err(None)),
+            _ => None,
+        }
+    }
+
+    fn variant(enum_name: &str, variant: &str, values: Vec<EvalResult>) -> EvalResult {
+        EvalResult::Enum(Rc::new(EnumValue {
+            enum_name: enum_name.to_string(),
+            variant: variant.to_string(),
+            values,
+        }))
+    }
+
+    // Compare two values with the given comparison operator.
+    /*
+        Any two values can be compared for (in)equality. Ordering is defined for numbers
+        and quantities of the same dimension, and for strings.
+    */
+    pub fn compare(&self, operator: &Token, other: &EvalResult) -> Result<bool, String> {
+        let ordering = match operator {
+            Token::Equals => return Ok(self == other),
+            Token::NotEquals => return Ok(self != other),
+            _ => match (self, other) {
+                (EvalResult::Literal(a), EvalResult::Literal(b)) => Some(a.cmp(b)),
+                _ => match (self.to_quantity(), other.to_quantity()) {
+                    (Some(a), Some(b)) if a.dimension == b.dimension => {
+                        a.value.partial_cmp(&b.value)
+                    }
+                    _ => return Err(format!("Can not compare {0} and {1}", self, other)),
+                },
+            },
+        };
+        match (operator, ordering) {
+            (Token::GreaterThan, Some(o)) => Ok(o == Ordering::Greater),
+            (Token::LessThan, Some(o)) => Ok(o == Ordering::Less),
+            (Token::GreaterThanOrEquals, Some(o)) => Ok(o != Ordering::Less),
+            (Token::LessThanOrEquals, Some(o)) => Ok(o != Ordering::Greater),
+            // Comparisons with NaN are always false
+            (Token::GreaterThan, None)
+            | (Token::LessThan, None)
+            | (Token::GreaterThanOrEquals, None)
+            | (Token::LessThanOrEquals, None) => Ok(false),
+            _ => Err(format!("Invalid comparison operator {0}", operator.symbol())),
+        }
+    }
+}
+
+// Arithmetic on numeric values, shared by the tree walker and the bytecode VM.
+pub fn apply_arithmetic(
+    l_value: &Quantity,
+    operator: &Token,
+    r_value: &Quantity,
+) -> Result<Quantity, String> {
+    match operator {
+        Token::Add => l_value.add(r_value),
+        Token::Subtract => l_value.subtract(r_value),
+        Token::Multiply => l_value.multiply(r_value),
+        Token::Divide => l_value.divide(r_value),
+        Token::Pow => l_value.pow(r_value),
+        // Fix this, bad implementation
+        _ => Err("Could not evaluate Binary Expression".to_string()),
     }
 }
 
@@ -54,282 +584,1636 @@ impl Evaluator {
         Variable storage will be eventually removed from evaluator.
     */
     pub fn new(expr: Option<Node>) -> Self {
+        let mut evaluator = Evaluator::without_prelude(expr);
+        evaluator
+            .load_prelude(PRELUDE)
+            .expect("The bundled prelude is valid");
+        evaluator
+    }
+
+    // Instantiate an evaluator knowing only the builtins, e.g. for sandboxed hosts.
+    pub fn without_prelude(expr: Option<Node>) -> Self {
         Evaluator {
             ast: expr,
-            variable_storage: HashMap::new(),
+            globals: Vec::new(),
+            global_slots: HashMap::new(),
+            units: UnitRegistry::default(),
+            functions: HashMap::new(),
+            host_functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: BUILTIN_ENUMS
+                .iter()
+                .map(|(name, variants)| {
+                    let variants = variants.iter().map(|(variant, arity)| (variant.to_string(), *arity));
+                    (name.to_string(), variants.collect())
+                })
+                .collect(),
+            call_stack: Vec::new(),
+            modules: ModuleLoader::new(),
+            module: 0,
+            environments: vec![Environment::default()],
+            prelude: HashMap::new(),
+        }
+    }
+
+    // Make a Rust function callable by scripts and every module. What it returns becomes
+    // an `ok` value, its errors an `err` value with their message, so scripts can handle
+    // them with `?`, match or unwrap_or.
+    pub fn register<F, E>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&[EvalResult]) -> Result<EvalResult, E> + 'static,
+        E: fmt::Display,
+    {
+        let function: HostFunction = Rc::new(move |arguments| function(arguments).into());
+        self.host_functions.insert(name.to_string(), function);
+    }
+
+    // Load a prelude, its exported functions can be called by the script and every module.
+    /*
+        The prelude is a module without a file, it runs once. Functions it exports replace
+        the ones of an earlier prelude, functions declared by scripts replace them in turn.
+    */
+    pub fn load_prelude(&mut self, source: &str) -> Result<(), Box<dyn error::Error>> {
+        let source = self.modules.compile(source, "prelude")?;
+        let module = self.load_module(source, None, "prelude")?;
+        let mut functions = Vec::new();
+        for (name, value) in module.exports.iter() {
+            match value {
+                EvalResult::Function(closure) => functions.push((name.clone(), closure.clone())),
+                _ => return Err(format!("The prelude can only export functions, {0} is not one", name).into()),
+            }
+        }
+        for (name, closure) in functions {
+            for (id, environment) in self.environments.iter_mut().enumerate() {
+                if id != self.module {
+                    environment.functions.insert(name.clone(), closure.clone());
+                }
+            }
+            self.functions.insert(name.clone(), closure.clone());
+            self.modules.prelude.push(name.clone());
+            self.prelude.insert(name, closure);
         }
+        Ok(())
     }
     // Start evaluating
-    pub fn start_evaluating(&mut self) -> Result<(), Box<dyn error::Error>> {
-        let expr: Node = self.ast.clone().unwrap();
-        match self.evaluate(expr) {
-            Ok(r) => {
-                println!("EVALUATOR: {:?}", r);
-                Ok(())
+    pub fn start_evaluating(&mut self) -> Result<EvalResult, Box<dyn error::Error>> {
+        // The tree is moved into an arena instead of being cloned
+        let expr = match self.ast.take() {
+            Some(expr) => expr,
+            None => return Err("Nothing to evaluate".into()),
+        };
+        self.run(&Rc::new(Ast::new(vec![expr])))
+    }
+
+    // Evaluate every top level statement of the arena and return the last result.
+    pub fn run(&mut self, ast: &Rc<Ast>) -> Result<EvalResult, Box<dyn error::Error>> {
+        let mut result = EvalResult::EmptyResult;
+        for &statement in ast.list(ast.root()) {
+            result = self.evaluate_statement(ast, statement)?;
+            println!("EVALUATOR: {:?}", result);
+        }
+        Ok(result)
+    }
+
+    // Evaluate a single top level statement of the arena.
+    pub fn evaluate_statement(
+        &mut self,
+        ast: &Rc<Ast>,
+        statement: NodeId,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let result = self.evaluate(ast, statement).map_err(|error| {
+            let error = RuntimeError::from_error(error).at_line(ast.line(statement));
+            Box::new(error.leave(None))
+        })?;
+        match result {
+            EvalResult::Break => Err("Found break outside of a loop".into()),
+            EvalResult::Return(_) => Err("Found return outside of a function".into()),
+            result => Ok(result),
+        }
+    }
+
+    // Get the value of a global variable.
+    pub fn get_variable(&self, identifier: &str) -> Option<EvalResult> {
+        let slot = self.global_slots.get(identifier)?;
+        self.globals[*slot].as_ref().map(Evaluator::variable_to_result)
+    }
+
+    // Every global variable that has been assigned.
+    pub fn globals(&self) -> HashMap<String, EvalResult> {
+        self.global_slots
+            .iter()
+            .filter_map(|(name, slot)| {
+                let value = self.globals[*slot].as_ref()?;
+                Some((name.clone(), Evaluator::variable_to_result(value)))
+            })
+            .collect()
+    }
+
+    // Names of the global variable slots, in slot order.
+    pub fn global_names(&self) -> Vec<String> {
+        let mut names = vec![String::new(); self.global_slots.len()];
+        for (name, slot) in &self.global_slots {
+            names[*slot] = name.clone();
+        }
+        names
+    }
+
+    // Module of an import path, it is loaded and run the first time it is imported.
+    pub fn import(&mut self, path: &str) -> Result<Rc<Module>, Box<dyn error::Error>> {
+        let file = self.modules.find(path, self.module)?;
+        if let Some(module) = self.modules.get(&file) {
+            return Ok(module);
+        }
+        self.modules.enter(&file, path)?;
+        let module = self
+            .modules
+            .read(&file, path)
+            .and_then(|source| self.load_module(source, Some(&file), path));
+        self.modules.leave(module)
+    }
+
+    // Make sure the global slots match the ones assigned by the resolver.
+    /*
+        The resolver has to be seeded with `global_names` so that the slots of existing
+        globals stay the same, new globals are appended.
+    */
+    pub fn declare_globals(&mut self, names: &[String]) -> Result<(), Box<dyn error::Error>> {
+        for (slot, name) in names.iter().enumerate() {
+            if self.global_slot(name) != slot {
+                return Err(format!("Global variable {0} was resolved to a wrong slot", name).into());
             }
-            Err(e) => return Err(e.into()),
         }
+        Ok(())
     }
 }
 
 // Private methods
+/*
+    Nodes are borrowed from the arena they were lowered into. Function bodies are
+    evaluated in the arena they were declared in, which the function keeps alive.
+*/
 impl Evaluator {
     // Evaluate given node and return an EvalResult.
-    fn evaluate(&mut self, expr: Node) -> Result<EvalResult, Box<dyn error::Error>> {
-        match expr {
-            Node::NegativeNumberExpression(_) | Node::NumberExpression(_) => {
-                Ok(EvalResult::Number(self.evaluate_numerics(expr)?))
+    fn evaluate(&mut self, ast: &Rc<Ast>, expr: NodeId) -> Result<EvalResult, Box<dyn error::Error>> {
+        match &ast[expr] {
+            AstNode::Negative(_)
+            | AstNode::Number(_)
+            | AstNode::Binary { .. }
+            | AstNode::Quantity { .. }
+            | AstNode::Conversion { .. } => {
+                Ok(EvalResult::from_quantity(self.evaluate_numerics(ast, expr)?))
             }
-            Node::BinaryExpr { .. } => Ok(EvalResult::Number(self.evaluate_numerics(expr)?)),
-            Node::AssignmentExpression {
+            AstNode::Assignment {
                 identifier,
                 assignment_operator,
                 expr,
-            } => Ok(self.evaluate_assignments(*identifier, assignment_operator, *expr)?),
+                ..
+            } => Ok(self.evaluate_assignments(ast, *identifier, assignment_operator, *expr)?),
             // Literals/keywords
-            Node::LiteralExpression(string, keyword) => {
+            AstNode::Literal(string, keyword) => {
                 // Handle keywords
                 match keyword {
-                    Keyword::True => return Ok(EvalResult::Boolean(true)),
-                    Keyword::False => return Ok(EvalResult::Boolean(false)),
-                    _ => return Ok(EvalResult::Literal(string)),
-                };
+                    Keyword::True => Ok(EvalResult::Boolean(true)),
+                    Keyword::False => Ok(EvalResult::Boolean(false)),
+                    _ => Ok(EvalResult::Literal(string.clone())),
+                }
             }
             // Handle variables
-            Node::IdentifierExpression(identifier) => {
+            AstNode::Identifier(identifier) => {
                 // Get value from storage
-                let value = self.variable_storage.get(&identifier);
-                match value {
-                    Some(VariableValue::Boolean(b)) => Ok(EvalResult::Boolean(*b)),
-                    Some(VariableValue::Number(f)) => Ok(EvalResult::Number(*f)),
-                    Some(VariableValue::Literal(s)) => Ok(EvalResult::Literal(s.to_string())),
-                    None => Err("Could not find a variable with given identifier".into()),
+                match self.lookup_variable(ast, expr)? {
+                    Some(value) => Ok(Evaluator::variable_to_result(&value)),
+                    // Declared functions are values as well, e.g. `apply(double, 2)`
+                    None => match self.functions.get(identifier) {
+                        Some(closure) => Ok(EvalResult::Function(closure.clone())),
+                        None => Err(Box::new(RuntimeError::new(
+                            ErrorKind::UndefinedVariable,
+                            format!("Undefined variable {0}", identifier),
+                        ))),
+                    },
                 }
             }
+            // Handle comparisons outside of conditional blocks
+            AstNode::Condition {
+                l_expr,
+                operator,
+                r_expr,
+            } => Ok(EvalResult::Boolean(
+                self.evaluate_condition_expression(ast, *l_expr, operator, *r_expr)?,
+            )),
             // Handle If expressions
-            Node::IfExpression {
+            AstNode::If {
                 condition,
                 then_branch,
                 else_branch,
             } => {
                 // Evaluate given if expressions.
-                Ok(self.evaluate_if_expression(*condition, *then_branch, *else_branch)?)
+                Ok(self.evaluate_if_expression(ast, *condition, *then_branch, *else_branch)?)
+            }
+            AstNode::While {
+                condition,
+                then_branch,
+            } => Ok(self.evaluate_while_expression(ast, *condition, *then_branch)?),
+            AstNode::Break => Ok(EvalResult::Break),
+            AstNode::Function {
+                identifier,
+                parameters,
+                locals,
+                body,
+            } => {
+                let closure = self.create_closure(ast, Some(identifier), parameters, locals, *body);
+                self.functions.insert(identifier.clone(), closure);
+                Ok(EvalResult::EmptyResult)
+            }
+            AstNode::Closure {
+                parameters,
+                locals,
+                body,
+            } => Ok(EvalResult::Function(
+                self.create_closure(ast, None, parameters, locals, *body),
+            )),
+            AstNode::Call { callee, arguments } => {
+                Ok(self.evaluate_call_expression(ast, *callee, *arguments)?)
+            }
+            AstNode::List(elements) => Ok(self.evaluate_list_expression(ast, *elements)?),
+            AstNode::Index { expr, index } => Ok(self.evaluate_index_expression(ast, *expr, *index)?),
+            AstNode::Struct { .. }
+            | AstNode::Impl { .. }
+            | AstNode::StructValue { .. }
+            | AstNode::Field { .. }
+            | AstNode::FieldAssignment { .. } => self.evaluate_struct_node(ast, expr),
+            AstNode::Enum { .. } | AstNode::Variant { .. } | AstNode::Match { .. } => {
+                self.evaluate_enum_node(ast, expr)
+            }
+            AstNode::Try { .. } | AstNode::Throw(_) | AstNode::Propagate(_) => {
+                self.evaluate_error_node(ast, expr)
+            }
+            AstNode::Import { .. } | AstNode::Export(_) => self.evaluate_module_node(ast, expr),
+            AstNode::Return(value) => {
+                let value = match value {
+                    Some(expr) => self.evaluate(ast, *expr)?,
+                    None => EvalResult::EmptyResult,
+                };
+                // From a branch of the returned value, e.g. `return if x { break } else { 1 }`
+                if let EvalResult::Break | EvalResult::Return(_) = value {
+                    return Ok(value);
+                }
+                Ok(EvalResult::Return(Box::new(value)))
             }
             _ => Err("Couldn't evaluate".into()),
         }
     }
 
     // Handle numerics and binary expressions
-    fn evaluate_numerics(&mut self, expr: Node) -> Result<f64, Box<dyn error::Error>> {
-        use self::Node::*;
-        match expr {
-            NumberExpression(f) => Ok(f),
-            NegativeNumberExpression(f) => Ok(-self.evaluate_numerics(*f)?),
-            BinaryExpr {
+    /*
+        Integer arithmetic is exact: results are promoted to big integers on overflow and
+        to rationals when a division is not exact. See numeric.rs for the promotion rules.
+
+        Every value carries a dimension, plain numbers are dimensionless. Adding or
+        subtracting values of different dimensions is an error, multiplying and dividing
+        combines them. See units.rs for the rules.
+    */
+    fn evaluate_numerics(&mut self, ast: &Rc<Ast>, expr: NodeId) -> Result<Quantity, Box<dyn error::Error>> {
+        match &ast[expr] {
+            AstNode::Number(number) => Ok(Quantity::number(number.clone())),
+            AstNode::Negative(f) => Ok(self.evaluate_numerics(ast, *f)?.negate()),
+            AstNode::Binary {
                 l_expr,
                 operator,
                 r_expr,
             } => {
-                match operator {
-                    Token::Add => {
-                        Ok(self.evaluate_numerics(*l_expr)? + self.evaluate_numerics(*r_expr)?)
-                    }
-                    Token::Subtract => {
-                        Ok(self.evaluate_numerics(*l_expr)? - self.evaluate_numerics(*r_expr)?)
-                    }
-                    Token::Multiply => {
-                        Ok(self.evaluate_numerics(*l_expr)? * self.evaluate_numerics(*r_expr)?)
-                    }
-                    Token::Divide => {
-                        Ok(self.evaluate_numerics(*l_expr)? / self.evaluate_numerics(*r_expr)?)
-                    }
-                    Token::Pow => Ok(self
-                        .evaluate_numerics(*l_expr)?
-                        .powf(self.evaluate_numerics(*r_expr)?)),
-                    // Fix this, bad implementation
-                    _ => Err("Could not evaluate Binary Expression".into()),
-                }
+                let l_value = self.evaluate_numerics(ast, *l_expr)?;
+                let r_value = self.evaluate_numerics(ast, *r_expr)?;
+                apply_arithmetic(&l_value, operator, &r_value).map_err(Evaluator::arithmetic_error)
+            }
+            AstNode::Quantity { value, unit } => {
+                let value = self.evaluate_numerics(ast, *value)?;
+                let unit_value = self.units.parse(unit)?;
+                Ok(Quantity::with_unit(value.value, unit, &unit_value))
             }
-            IdentifierExpression(identifier) => {
-                let value = self.variable_storage.get(&identifier);
+            AstNode::Conversion { expr, unit } => {
+                let value = self.evaluate_numerics(ast, *expr)?;
+                let unit_value = self.units.parse(unit)?;
+                Ok(value.convert(unit, &unit_value)?)
+            }
+            AstNode::Identifier(identifier) => {
+                let value = self.lookup_variable(ast, expr)?;
                 match value {
-                    Some(VariableValue::Number(f)) => Ok(*f),
-                    _ => Err("Variable not found".into()),
+                    Some(VariableValue::Number(n)) => Ok(Quantity::number(n)),
+                    Some(VariableValue::Quantity(q)) => Ok(q),
+                    Some(other) => Err(Evaluator::not_a_number(&Evaluator::variable_to_result(&other))),
+                    None => Err(Box::new(RuntimeError::new(
+                        ErrorKind::UndefinedVariable,
+                        format!("Undefined variable {0}", identifier),
+                    ))),
+                }
+            }
+            // Function calls, branches and such, as long as they produce a number
+            AstNode::Call { .. }
+            | AstNode::Index { .. }
+            | AstNode::Field { .. }
+            | AstNode::If { .. }
+            | AstNode::Match { .. }
+            | AstNode::Literal(..)
+            | AstNode::Condition { .. }
+            | AstNode::Propagate(_) => {
+                let value = self.evaluate(ast, expr)?;
+                match value.to_quantity() {
+                    Some(quantity) => Ok(quantity),
+                    None => Err(Evaluator::not_a_number(&value)),
                 }
             }
             _ => Err("Not implemented.".into()),
         }
     }
+    // Division by zero has a kind of its own, other failing arithmetic mixes types or units.
+    fn arithmetic_error(message: String) -> Box<dyn error::Error> {
+        let kind = match message.as_str() {
+            DIVISION_BY_ZERO => ErrorKind::DivisionByZero,
+            _ => ErrorKind::TypeMismatch,
+        };
+        Box::new(RuntimeError::new(kind, message))
+    }
+
+    fn not_a_number(value: &EvalResult) -> Box<dyn error::Error> {
+        Box::new(RuntimeError::new(
+            ErrorKind::TypeMismatch,
+            format!("Expected a number, got {0}", value),
+        ))
+    }
+
     // Evaluate assignment expressions.
     fn evaluate_assignments(
         &mut self,
-        identifier: Node,
-        _assignment_operator: Token,
-        expr: Node,
+        ast: &Rc<Ast>,
+        identifier: NodeId,
+        _assignment_operator: &Token,
+        expr: NodeId,
     ) -> Result<EvalResult, Box<dyn error::Error>> {
         // Just experimental, so we'll assume that every assignment goes with '='
         // Suppot for -= / += will be added later.
         // Evaluate right hand expression
-        let value = self.evaluate(expr)?;
-        // Initialize an empty string for variable name.
-        let mut variable_name = String::new();
-        // Set identifier - as an EvalResult
-        let identifier_str = match identifier {
-            Node::IdentifierExpression(val) => {
-                variable_name = val.clone();
-                EvalResult::Literal(val)
-            }
+        let value = self.evaluate(ast, expr)?;
+        // A branch of the assigned value can break or return instead
+        if let EvalResult::Break | EvalResult::Return(_) = value {
+            return Ok(value);
+        }
+        // Set identifier - as an EvalResult, and keep the variable name for storage
+        let variable_name = match &ast[identifier] {
+            AstNode::Identifier(val) => val.clone(),
             _ => return Err("couldnt evaluate".into()),
         };
         // Depending on the assigned values type, create a properly typed variable
-        match value.clone() {
-            // Floats
-            EvalResult::Number(f) => {
-                let new_var = VariableValue::Number(f);
-                self.variable_storage.insert(variable_name, new_var);
-            }
-            // Strings
-            EvalResult::Literal(string) => {
-                let new_var = VariableValue::Literal(string);
-                self.variable_storage.insert(variable_name, new_var);
-            }
-            // Booleans
-            EvalResult::Boolean(boolean) => {
-                let new_var = VariableValue::Boolean(boolean);
-                self.variable_storage.insert(variable_name, new_var);
-            }
-            _ => println!("Could not assign {0} to {1}", value, variable_name),
+        match Evaluator::result_to_variable(value.clone()) {
+            Some(new_var) => self.store_variable(ast, identifier, new_var)?,
+            None => println!("Could not assign {0} to {1}", value, variable_name),
         }
+        let identifier_str = EvalResult::Literal(variable_name);
 
         Ok(EvalResult::Assignment {
             identifier: Box::new(identifier_str),
             value: Box::new(value),
         })
     }
-    // Evaluate if/else/elif expressions
+    // Evaluate if/else/elif expressions, to the value of the branch taken. Without a
+    // branch to take there is no value.
     fn evaluate_if_expression(
         &mut self,
-        condition: Option<Node>,
-        then_branch: Vec<Node>,
-        else_branch: Option<Node>,
+        ast: &Rc<Ast>,
+        condition: Option<NodeId>,
+        then_branch: NodeList,
+        else_branch: Option<NodeId>,
     ) -> Result<EvalResult, Box<dyn error::Error>> {
-        //
-        //
         match condition {
-            Some(Node::ConditionExpression {
-                     l_expr,
-                     operator,
-                     r_expr,
-                 }) => {
-                if self.evaluate_condition_expression(*l_expr, operator, *r_expr)? {
+            Some(condition) => {
+                if self.evaluate_condition(ast, condition)? {
                     // Evaluate then expression
-                    for expression in then_branch {
-                        self.ast = Some(expression);
-                        self.start_evaluating()?;
-                    }
+                    return self.evaluate_block(ast, then_branch);
                 }
-                else {
-                    match else_branch {
-                        Some(branch) => {
-                            if let Node::ElseExpression {
-                                condition,
-                                then_branch,
-                                else_branch,
-                            } = branch
-                            {
-                                self.evaluate_if_expression(
-                                    *condition,
-                                    *then_branch,
-                                    *else_branch,
-                                )?;
-                            }
-                        }
-                        None => { /* Nothing to do if branch was not found */ }
-                    }
+                else if let Some(AstNode::Else {
+                    condition,
+                    then_branch,
+                    else_branch,
+                }) = else_branch.map(|else_branch| &ast[else_branch])
+                {
+                    return self.evaluate_if_expression(ast, *condition, *then_branch, *else_branch);
                 }
-                /*
-                // TODO: When different kind of operands are applied for if clauses.. Create a function evaluate_condition_expression which return a boolean.
-                // pattern match operator here. Field is ignored for now. _
-                // Compare each evaluated value. Both type and value is checked here.
-                if self.evaluate(*l_expr)? == self.evaluate(*r_expr)? {
-                    // Evaluate block
-                    for expression in then_branch {
-                        self.ast = Some(expression);
-                        self.start_evaluating()?;
-                    }
-                } else {
-                    match else_branch {
-                        Some(branch) => {
-                            if let Node::ElseExpression {
-                                condition,
-                                then_branch,
-                                else_branch,
-                            } = branch
-                            {
-                                self.evaluate_if_expression(
-                                    *condition,
-                                    *then_branch,
-                                    *else_branch,
-                                )?;
-                            }
-                        }
-                        None => { /* Nothing to do if branch was not found */ }
-                    }
-                }*/
+                // Nothing to do if branch was not found
             }
             // Else clauses
-            None => {
-                for expression in then_branch {
-                    self.ast = Some(expression);
-                    self.start_evaluating()?;
-                }
-            }
-            _ => {
-                // Nothing to do
-            }
+            None => return self.evaluate_block(ast, then_branch),
         }
         Ok(EvalResult::EmptyResult)
     }
 
     // Evaluate given condition
-    fn evaluate_condition_expression(&mut self, l_expr: Node, operator: Token, r_expr: Node) -> Result<bool, Box<dyn error::Error>> {
+    fn evaluate_condition_expression(&mut self, ast: &Rc<Ast>, l_expr: NodeId, operator: &Token, r_expr: NodeId) -> Result<bool, Box<dyn error::Error>> {
         // Evaluate comparison with given operator
-        match operator {
-            Token::Equals => {
-                if self.evaluate(l_expr)? == self.evaluate(r_expr)?
-                {
-                    return Ok(true);
-                }
-                return Ok(false);
-            }
-            // Return error if nothing was matched
-            _ => {
-                return Err(format!("Invalid comparison operator {0:?}", operator).into())
-            }
-        }
+        let l_value = self.evaluate(ast, l_expr)?;
+        let r_value = self.evaluate(ast, r_expr)?;
+        l_value
+            .compare(operator, &r_value)
+            .map_err(|message| RuntimeError::new(ErrorKind::TypeMismatch, message).into())
     }
 
+    // Conditions of if and while expressions have to produce a boolean.
+    fn evaluate_condition(&mut self, ast: &Rc<Ast>, condition: NodeId) -> Result<bool, Box<dyn error::Error>> {
+        match self.evaluate(ast, condition)? {
+            EvalResult::Boolean(b) => Ok(b),
+            other => Err(Box::new(RuntimeError::new(
+                ErrorKind::TypeMismatch,
+                format!("Condition must be a boolean, got {0}", other),
+            ))),
+        }
+    }
 
     // Evaluate while expression, Condition for the loop and action to be executed
     fn evaluate_while_expression(
         &mut self,
-        condition: Option<Node>,
-        then_branch: Vec<Node>
+        ast: &Rc<Ast>,
+        condition: Option<NodeId>,
+        then_branch: NodeList
     ) -> Result<EvalResult, Box<dyn error::Error>> {
-        // Check condition
-        match condition {
-            // Check expression
-            Some(Node::ConditionExpression { l_expr, operator, r_expr }) => {
-
-            }
-            // Loop without conditions
-            None => {
-                // TODO
+        loop {
+            // Loop without conditions runs until break
+            if let Some(condition) = condition {
+                if !self.evaluate_condition(ast, condition)? {
+                    break;
+                }
             }
-            _ => {
-                // Nothing to do
+            match self.evaluate_block(ast, then_branch)? {
+                EvalResult::Break => break,
+                EvalResult::Return(value) => return Ok(EvalResult::Return(value)),
+                _ => {}
             }
         }
         Ok(EvalResult::EmptyResult)
     }
+
+    // Evaluate statements of a block until one of them breaks or returns. The value of
+    // the block is the value of its last statement, assignments have none.
+    fn evaluate_block(&mut self, ast: &Rc<Ast>, block: NodeList) -> Result<EvalResult, Box<dyn error::Error>> {
+        let mut result = EvalResult::EmptyResult;
+        for &expression in ast.list(block) {
+            result = match self.evaluate(ast, expression) {
+                Ok(EvalResult::Assignment { .. }) => EvalResult::EmptyResult,
+                Ok(signal @ (EvalResult::Break | EvalResult::Return(_))) => return Ok(signal),
+                Ok(value) => value,
+                Err(error) => return Err(Evaluator::locate(error, ast.line(expression))),
+            };
+        }
+        Ok(result)
+    }
+
+    // Errors leaving a statement know their line. Kept out of evaluate_block so its frame
+    // stays small for deep recursion.
+    #[inline(never)]
+    fn locate(error: Box<dyn error::Error>, line: Option<usize>) -> Box<dyn error::Error> {
+        Box::new(RuntimeError::from_error(error).at_line(line))
+    }
+
+    // Errors leaving a call add it to their stack trace, the err or none of a `?` is
+    // returned instead.
+    #[inline(never)]
+    fn leave_call(error: Box<dyn error::Error>, function: &Function) -> Result<EvalResult, Box<dyn error::Error>> {
+        let mut error = RuntimeError::from_error(error);
+        if let Some(value) = error.propagated.take() {
+            return Ok(value);
+        }
+        let name = function.display_name().to_string();
+        Err(Box::new(error.leave(Some(name))))
+    }
+
+    // Create a function value. It captures the current call, if there is one.
+    fn create_closure(
+        &self,
+        ast: &Rc<Ast>,
+        name: Option<&str>,
+        parameters: &[String],
+        locals: &[String],
+        body: NodeList,
+    ) -> Rc<Closure> {
+        let function = Function {
+            name: name.map(str::to_string),
+            parameters: parameters.to_vec(),
+            ast: Rc::clone(ast),
+            body,
+            locals: locals
+                .iter()
+                .enumerate()
+                .map(|(slot, name)| (name.clone(), slot))
+                .collect(),
+            module: self.module,
+        };
+        Rc::new(Closure {
+            function: Rc::new(function),
+            frame: self.call_stack.last().cloned(),
+        })
+    }
+
+    // Local variables of a function in slot order: parameters first, followed by the
+    // assigned variables sorted by name. Shared with the resolver and the bytecode compiler.
+    pub(crate) fn local_names(parameters: &[String], body: &[Node]) -> Vec<String> {
+        let mut assigned = HashSet::new();
+        Evaluator::collect_assignments(body, &mut assigned);
+        Evaluator::local_slots(parameters, assigned)
+    }
+
+    fn local_slots(parameters: &[String], assigned: HashSet<String>) -> Vec<String> {
+        let mut assigned: Vec<String> = assigned
+            .into_iter()
+            .filter(|name| !parameters.contains(name))
+            .collect();
+        assigned.sort();
+        parameters.iter().cloned().chain(assigned).collect()
+    }
+
+    // Find every variable assigned in the given block, including nested blocks.
+    pub(crate) fn collect_assignments(block: &[Node], names: &mut HashSet<String>) {
+        AssignmentCollector { names }.visit_block(block);
+    }
+
+    // Evaluate the elements of a list literal, every element has to be a value.
+    fn evaluate_list_expression(&mut self, ast: &Rc<Ast>, elements: NodeList) -> Result<EvalResult, Box<dyn error::Error>> {
+        let mut values = Vec::new();
+        for &element in ast.list(elements) {
+            let value = self.evaluate(ast, element)?;
+            match Evaluator::result_to_variable(value.clone()) {
+                Some(_) => values.push(value),
+                None => return Err(format!("{0} can not be stored in a list", value).into()),
+            }
+        }
+        Ok(EvalResult::List(Rc::new(values)))
+    }
+
+    // Call a function or a builtin with the given arguments.
+    fn evaluate_call_expression(
+        &mut self,
+        ast: &Rc<Ast>,
+        callee: NodeId,
+        arguments: NodeList,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let callee = self.evaluate_callee(ast, callee)?;
+        // Arguments are evaluated in the caller's scope
+        let mut values = Vec::new();
+        for &argument in ast.list(arguments) {
+            values.push(self.evaluate(ast, argument)?);
+        }
+        match callee {
+            Callee::Function(closure) => self.call_function(&closure, values),
+            callee => self.call_other(callee, values),
+        }
+    }
+
+    // Calls of anything but a function value. Kept apart from evaluate_call_expression so
+    // its frame stays small for deep recursion.
+    #[inline(never)]
+    fn call_other(
+        &mut self,
+        callee: Callee,
+        mut values: Vec<EvalResult>,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        match callee {
+            Callee::Function(closure) => self.call_function(&closure, values),
+            Callee::Builtin(builtin) => self.call_builtin(builtin, values),
+            // Host functions can not fail, their errors are values
+            Callee::Host(function) => Ok(function(&values)),
+            Callee::Method { closure, receiver } => {
+                // `self` is not counted in messages, it is never written in the call
+                let expected = closure.function.parameters.len() - 1;
+                if values.len() != expected {
+                    return Err(format!(
+                        "Method {0} expects {1}, got {2}",
+                        closure.function.display_name(),
+                        amount(expected, "argument"),
+                        values.len()
+                    )
+                    .into());
+                }
+                values.insert(0, EvalResult::Struct(receiver));
+                self.call_function(&closure, values)
+            }
+        }
+    }
+
+    // Kept apart from evaluate_call_expression so its frame stays small for deep recursion.
+    #[inline(never)]
+    fn call_builtin(
+        &mut self,
+        builtin: Builtin,
+        arguments: Vec<EvalResult>,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        builtin.call(arguments, &mut |closure, arguments| self.call_function(closure, arguments))
+    }
+
+    // Run a function value with evaluated arguments.
+    fn call_function(
+        &mut self,
+        closure: &Rc<Closure>,
+        arguments: Vec<EvalResult>,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        self.push_frame(closure, arguments)?;
+        let result = self.evaluate_block(&closure.function.ast, closure.function.body);
+        self.pop_frame();
+        let result = match result {
+            Ok(result) => result,
+            Err(error) => return Evaluator::leave_call(error, &closure.function),
+        };
+        match result {
+            EvalResult::Return(value) => Ok(*value),
+            EvalResult::Break => Err("Found break outside of a loop".into()),
+            // Without a return the value of the body is returned, e.g. `fn(x) { x * 2 }`
+            value => Ok(value),
+        }
+    }
+
+    // Enter the frame of a call, parameters take the first slots. The call runs in the
+    // module of the function. Kept out of call_function so deep recursion does not carry
+    // its locals on every level.
+    #[inline(never)]
+    fn push_frame(
+        &mut self,
+        closure: &Rc<Closure>,
+        arguments: Vec<EvalResult>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let function = &closure.function;
+        if arguments.len() != function.parameters.len() {
+            return Err(format!(
+                "Function {0} expects {1}, got {2}",
+                function.display_name(),
+                amount(function.parameters.len(), "argument"),
+                arguments.len()
+            )
+            .into());
+        }
+        if self.call_stack.len() >= MAX_CALL_DEPTH {
+            return Err("Maximum call depth exceeded".into());
+        }
+        let mut variables = vec![None; function.locals.len()];
+        for (slot, value) in arguments.into_iter().enumerate() {
+            match Evaluator::result_to_variable(value.clone()) {
+                Some(variable) => variables[slot] = Some(variable),
+                None => {
+                    return Err(
+                        format!("Could not pass {0} to {1}", value, function.display_name()).into(),
+                    )
+                }
+            };
+        }
+        let frame = Rc::new(CallFrame {
+            function: function.clone(),
+            variables: RefCell::new(variables),
+            parent: closure.frame.clone(),
+            caller: self.enter_module(function.module),
+        });
+        self.call_stack.push(frame);
+        Ok(())
+    }
+
+    // Leave the frame of the innermost call, and its module.
+    #[inline(never)]
+    fn pop_frame(&mut self) {
+        if let Some(frame) = self.call_stack.pop() {
+            self.enter_module(frame.caller);
+        }
+    }
+
+    // Find the function to call.
+    /*
+        A name refers to a variable holding a function if there is one, otherwise to a
+        declared function and last to a builtin. `p.m` refers to a field of p holding a
+        function if there is one, otherwise to a method of p's struct. Any other
+        expression has to evaluate to a function, e.g. `make_adder(1)(2)` or
+        `handlers[0](x)`.
+    */
+    fn evaluate_callee(&mut self, ast: &Rc<Ast>, callee: NodeId) -> Result<Callee, Box<dyn error::Error>> {
+        let value = match &ast[callee] {
+            AstNode::Identifier(identifier) => match self.lookup_variable(ast, callee)? {
+                Some(value) => Evaluator::variable_to_result(&value),
+                None => {
+                    if let Some(closure) = self.functions.get(identifier) {
+                        return Ok(Callee::Function(closure.clone()));
+                    }
+                    if let Some(function) = self.host_functions.get(identifier) {
+                        return Ok(Callee::Host(function.clone()));
+                    }
+                    return match Builtin::from_name(identifier) {
+                        Some(builtin) => Ok(Callee::Builtin(builtin)),
+                        None => Err(format!("Could not find a function named {0}", identifier).into()),
+                    };
+                }
+            },
+            AstNode::Field { expr, field } => match self.evaluate(ast, *expr)? {
+                EvalResult::Struct(instance) => match instance.get(field) {
+                    Some(value) => value,
+                    None => return self.find_method(instance, field),
+                },
+                EvalResult::Module(module) => module.get(field)?,
+                other => {
+                    return Err(Box::new(RuntimeError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("Can not call method {0} of {1}", field, other),
+                    )))
+                }
+            },
+            _ => self.evaluate(ast, callee)?,
+        };
+        match value {
+            EvalResult::Function(closure) => Ok(Callee::Function(closure)),
+            other => Err(Box::new(RuntimeError::new(
+                ErrorKind::TypeMismatch,
+                format!("{0} is not a function", other),
+            ))),
+        }
+    }
+
+    // Struct declarations and struct values. Kept out of evaluate so its frame stays
+    // small for deep recursion.
+    #[inline(never)]
+    fn evaluate_struct_node(&mut self, ast: &Rc<Ast>, expr: NodeId) -> Result<EvalResult, Box<dyn error::Error>> {
+        match &ast[expr] {
+            AstNode::Struct { identifier, fields } => {
+                // Declaring a struct again drops the methods of its previous declaration
+                let struct_type = StructType {
+                    fields: fields.clone(),
+                    methods: HashMap::new(),
+                };
+                self.structs.insert(identifier.clone(), struct_type);
+                Ok(EvalResult::EmptyResult)
+            }
+            AstNode::Impl { identifier, methods } => self.evaluate_impl(ast, identifier, *methods),
+            AstNode::StructValue {
+                identifier,
+                fields,
+                values,
+            } => self.evaluate_struct_expression(ast, identifier, fields, *values),
+            AstNode::Field { expr, field } => self.evaluate_field_expression(ast, *expr, field),
+            AstNode::FieldAssignment { expr, field, value } => {
+                self.evaluate_field_assignment(ast, *expr, field, *value)
+            }
+            _ => Err("Couldn't evaluate".into()),
+        }
+    }
+
+    // Method of the struct of the given value.
+    fn find_method(&self, instance: Rc<Instance>, method: &str) -> Result<Callee, Box<dyn error::Error>> {
+        let closure = self
+            .structs
+            .get(&instance.name)
+            .and_then(|struct_type| struct_type.methods.get(method));
+        match closure {
+            Some(closure) => Ok(Callee::Method {
+                closure: closure.clone(),
+                receiver: instance,
+            }),
+            None => Err(format!("{0} has no field or method {1}", instance.name, method).into()),
+        }
+    }
+
+    // Add the methods of an impl block to its struct.
+    fn evaluate_impl(
+        &mut self,
+        ast: &Rc<Ast>,
+        identifier: &str,
+        methods: NodeList,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        if !self.structs.contains_key(identifier) {
+            return Err(format!("Could not find a struct named {0}", identifier).into());
+        }
+        for &method in ast.list(methods) {
+            if let AstNode::Function {
+                identifier: name,
+                parameters,
+                locals,
+                body,
+            } = &ast[method]
+            {
+                let display_name = format!("{0}.{1}", identifier, name);
+                let closure = self.create_closure(ast, Some(&display_name), parameters, locals, *body);
+                if let Some(struct_type) = self.structs.get_mut(identifier) {
+                    struct_type.methods.insert(name.clone(), closure);
+                }
+            }
+        }
+        Ok(EvalResult::EmptyResult)
+    }
+
+    // Create a struct value, every field has to be given exactly once.
+    fn evaluate_struct_expression(
+        &mut self,
+        ast: &Rc<Ast>,
+        identifier: &str,
+        fields: &[String],
+        values: NodeList,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let declared = match self.structs.get(identifier) {
+            Some(struct_type) => struct_type.fields.clone(),
+            None => return Err(format!("Could not find a struct named {0}", identifier).into()),
+        };
+        if let Some(field) = fields.iter().find(|field| !declared.contains(field)) {
+            return Err(format!("Struct {0} has no field {1}", identifier, field).into());
+        }
+        if let Some(field) = declared.iter().find(|field| !fields.contains(field)) {
+            return Err(format!("Missing field {0} for {1}", field, identifier).into());
+        }
+        // Values are evaluated in the order they are written, and stored in declaration order
+        let mut given = HashMap::new();
+        for (field, &value) in fields.iter().zip(ast.list(values)) {
+            given.insert(field, self.storable(ast, value)?);
+        }
+        let fields = declared
+            .iter()
+            .filter_map(|field| Some((field.clone(), given.remove(field)?)))
+            .collect();
+        Ok(EvalResult::Struct(Rc::new(Instance {
+            name: identifier.to_string(),
+            fields: RefCell::new(fields),
+        })))
+    }
+
+    // Get a field of a struct value.
+    fn evaluate_field_expression(
+        &mut self,
+        ast: &Rc<Ast>,
+        expr: NodeId,
+        field: &str,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        match self.evaluate(ast, expr)? {
+            EvalResult::Struct(instance) => match instance.get(field) {
+                Some(value) => Ok(value),
+                None => Err(format!("{0} has no field {1}", instance.name, field).into()),
+            },
+            EvalResult::Error(error) => match error.field(field) {
+                Some(value) => Ok(value),
+                None => Err(format!("Errors have no field {0}", field).into()),
+            },
+            EvalResult::Module(module) => module.get(field),
+            other => Err(Box::new(RuntimeError::new(
+                ErrorKind::TypeMismatch,
+                format!("Can not get field {0} of {1}", field, other),
+            ))),
+        }
+    }
+
+    // Change a field of a struct value, every holder of the value sees the change.
+    fn evaluate_field_assignment(
+        &mut self,
+        ast: &Rc<Ast>,
+        expr: NodeId,
+        field: &str,
+        value: NodeId,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let instance = match self.evaluate(ast, expr)? {
+            EvalResult::Struct(instance) => instance,
+            other => {
+                return Err(Box::new(RuntimeError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("Can not set field {0} of {1}", field, other),
+                )))
+            }
+        };
+        let value = self.storable(ast, value)?;
+        let mut fields = instance.fields.borrow_mut();
+        match fields.iter_mut().find(|(name, _)| name == field) {
+            Some((_, storage)) => *storage = value.clone(),
+            None => return Err(format!("{0} has no field {1}", instance.name, field).into()),
+        }
+        Ok(EvalResult::Assignment {
+            identifier: Box::new(EvalResult::Literal(field.to_string())),
+            value: Box::new(value),
+        })
+    }
+
+    // Evaluate an expression whose value is kept in a struct.
+    fn storable(&mut self, ast: &Rc<Ast>, expr: NodeId) -> Result<EvalResult, Box<dyn error::Error>> {
+        let value = self.evaluate(ast, expr)?;
+        match Evaluator::result_to_variable(value.clone()) {
+            Some(_) => Ok(value),
+            None => Err(format!("{0} can not be stored in a struct", value).into()),
+        }
+    }
+
+    // Enum declarations, variants and match expressions. Kept out of evaluate so its
+    // frame stays small for deep recursion.
+    #[inline(never)]
+    fn evaluate_enum_node(&mut self, ast: &Rc<Ast>, expr: NodeId) -> Result<EvalResult, Box<dyn error::Error>> {
+        match &ast[expr] {
+            AstNode::Enum {
+                identifier,
+                variants,
+            } => {
+                self.enums.insert(identifier.clone(), variants.clone());
+                Ok(EvalResult::EmptyResult)
+            }
+            AstNode::Variant {
+                enum_name,
+                variant,
+                arguments,
+            } => self.evaluate_variant_expression(ast, enum_name, variant, *arguments),
+            AstNode::Match { expr, arms } => self.evaluate_match_expression(ast, *expr, arms),
+            _ => Err("Couldn't evaluate".into()),
+        }
+    }
+
+    // Try blocks and throw. Kept out of evaluate so its frame stays small for deep recursion.
+    #[inline(never)]
+    fn evaluate_error_node(&mut self, ast: &Rc<Ast>, expr: NodeId) -> Result<EvalResult, Box<dyn error::Error>> {
+        match &ast[expr] {
+            AstNode::Try {
+                body,
+                catch,
+                finally,
+            } => self.evaluate_try_expression(ast, *body, *catch, *finally),
+            AstNode::Throw(value) => {
+                let value = self.evaluate(ast, *value)?;
+                let error = match value {
+                    // From a branch of the thrown value, e.g. `throw if x { return } else { 1 }`
+                    EvalResult::Break | EvalResult::Return(_) => return Ok(value),
+                    // Caught errors are thrown again as they are
+                    EvalResult::Error(error) => RuntimeError::from_value((*error).clone()),
+                    value => RuntimeError::new(ErrorKind::Error, Builtin::text(&value)),
+                };
+                Err(Box::new(error))
+            }
+            AstNode::Propagate(value) => {
+                let value = self.evaluate(ast, *value)?;
+                match value.as_result() {
+                    Some(Ok(inner)) => Ok(inner),
+                    Some(Err(failure)) => Err(Box::new(RuntimeError::propagate(value, failure))),
+                    None => Err(Box::new(RuntimeError::new(
+                        ErrorKind::TypeMismatch,
+                        format!("Operator ? expects a Result or an Option, got {0}", value),
+                    ))),
+                }
+            }
+            _ => Err("Couldn't evaluate".into()),
+        }
+    }
+
+    // Evaluate the body of a try, and the catch if the body failed.
+    /*
+        The error is assigned to the variable of the catch, which is evaluated instead of
+        the rest of the body. The finally block runs afterwards in any case, even if the
+        catch failed as well. A break or return in the finally block replaces the value or
+        error of the try, an error in it is passed up. Inside a function the catch lets a `?`
+        through, it returns from the function.
+    */
+    fn evaluate_try_expression(
+        &mut self,
+        ast: &Rc<Ast>,
+        body: NodeList,
+        catch: Option<(NodeId, NodeList)>,
+        finally: Option<NodeList>,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let mut result = self.evaluate_block(ast, body);
+        if let Some((binding, handler)) = catch {
+            if let Err(error) = result {
+                let error = RuntimeError::from_error(error);
+                // A `?` leaves the function, it is no error unless it reaches the top level
+                if error.propagated.is_some() && !self.call_stack.is_empty() {
+                    return self.finish_try(ast, Err(Box::new(error)), finally);
+                }
+                let value = VariableValue::Error(Rc::new(error.value));
+                result = self
+                    .store_variable(ast, binding, value)
+                    .and_then(|_| self.evaluate_block(ast, handler));
+            }
+        }
+        self.finish_try(ast, result, finally)
+    }
+
+    // Run the finally block of a try, if there is one.
+    fn finish_try(
+        &mut self,
+        ast: &Rc<Ast>,
+        result: Result<EvalResult, Box<dyn error::Error>>,
+        finally: Option<NodeList>,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        if let Some(finally) = finally {
+            if let signal @ (EvalResult::Break | EvalResult::Return(_)) = self.evaluate_block(ast, finally)? {
+                return Ok(signal);
+            }
+        }
+        result
+    }
+
+    // Imports and exports. Kept out of evaluate so its frame stays small for deep recursion.
+    #[inline(never)]
+    fn evaluate_module_node(&mut self, ast: &Rc<Ast>, expr: NodeId) -> Result<EvalResult, Box<dyn error::Error>> {
+        match &ast[expr] {
+            AstNode::Import { path, alias, names } => self.evaluate_import(ast, path, *alias, *names),
+            // The loader takes the exported values once the module has run
+            AstNode::Export(_) => Ok(EvalResult::EmptyResult),
+            _ => Err("Couldn't evaluate".into()),
+        }
+    }
+
+    // Bind the module of an import, or the names imported from it.
+    fn evaluate_import(
+        &mut self,
+        ast: &Rc<Ast>,
+        path: &str,
+        alias: Option<NodeId>,
+        names: NodeList,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let module = self.import(path)?;
+        if let Some(alias) = alias {
+            self.store_variable(ast, alias, VariableValue::Module(module.clone()))?;
+        }
+        for &name in ast.list(names) {
+            let value = match &ast[name] {
+                AstNode::Identifier(identifier) => module.get(identifier)?,
+                _ => return Err("Expected the name of a variable".into()),
+            };
+            match Evaluator::result_to_variable(value) {
+                Some(value) => self.store_variable(ast, name, value)?,
+                None => return Err("Invalid exported value".into()),
+            }
+        }
+        Ok(EvalResult::EmptyResult)
+    }
+
+    // Run a module in its own environment and take its exports.
+    /*
+        The module runs like a script of its own: outside of any call, with its own
+        globals and functions. Errors leaving it add the module to their stack trace.
+    */
+    fn load_module(
+        &mut self,
+        source: ModuleSource,
+        file: Option<&Path>,
+        name: &str,
+    ) -> Result<Rc<Module>, Box<dyn error::Error>> {
+        let id = self.modules.register(file);
+        self.environments.push(Environment {
+            functions: self.prelude.clone(),
+            ..Environment::default()
+        });
+        let caller = self.enter_module(id);
+        let call_stack = mem::take(&mut self.call_stack);
+        let exports = self.run_module(&source, name);
+        self.call_stack = call_stack;
+        self.enter_module(caller);
+        Ok(Rc::new(Module {
+            name: name.to_string(),
+            exports: exports?,
+        }))
+    }
+
+    fn run_module(
+        &mut self,
+        source: &ModuleSource,
+        name: &str,
+    ) -> Result<Vec<(String, EvalResult)>, Box<dyn error::Error>> {
+        self.declare_globals(&source.globals)?;
+        let ast = &source.ast;
+        for &statement in ast.list(ast.root()) {
+            let error: Box<dyn error::Error> = match self.evaluate(ast, statement) {
+                Ok(EvalResult::Break) => "Found break outside of a loop".into(),
+                Ok(EvalResult::Return(_)) => "Found return outside of a function".into(),
+                Ok(_) => continue,
+                Err(error) => error,
+            };
+            let mut error = RuntimeError::from_error(error).at_line(ast.line(statement));
+            // A `?` at the top level of the module does not return from the importer
+            error.propagated = None;
+            return Err(Box::new(error.leave(Some(format!("module {0}", name)))));
+        }
+        self.exported_values(name, &source.exports)
+    }
+
+    // Values of the exported names of the current module.
+    fn exported_values(
+        &self,
+        module: &str,
+        names: &[String],
+    ) -> Result<Vec<(String, EvalResult)>, Box<dyn error::Error>> {
+        let mut exports = Vec::new();
+        for name in names {
+            let value = match self.get_variable(name) {
+                Some(value) => value,
+                None => match self.functions.get(name) {
+                    Some(closure) => EvalResult::Function(closure.clone()),
+                    None => {
+                        return Err(format!("Module {0} exports {1}, which is not assigned", module, name).into())
+                    }
+                },
+            };
+            exports.push((name.clone(), value));
+        }
+        Ok(exports)
+    }
+
+    // Make the globals and functions of the given module the current ones, returns the
+    // module that was current before.
+    #[inline(never)]
+    fn enter_module(&mut self, module: usize) -> usize {
+        let previous = self.module;
+        if module == previous {
+            return previous;
+        }
+        self.environments[previous] = Environment {
+            globals: mem::take(&mut self.globals),
+            global_slots: mem::take(&mut self.global_slots),
+            functions: mem::take(&mut self.functions),
+        };
+        let environment = mem::take(&mut self.environments[module]);
+        self.globals = environment.globals;
+        self.global_slots = environment.global_slots;
+        self.functions = environment.functions;
+        self.module = module;
+        previous
+    }
+
+    // Amount of values of a declared variant.
+    fn variant_arity(&self, enum_name: &str, variant: &str) -> Result<usize, Box<dyn error::Error>> {
+        let variants = match self.enums.get(enum_name) {
+            Some(variants) => variants,
+            None => return Err(format!("Could not find an enum named {0}", enum_name).into()),
+        };
+        match variants.iter().find(|(name, _)| name == variant) {
+            Some((_, arity)) => Ok(*arity),
+            None => Err(format!("Enum {0} has no variant {1}", enum_name, variant).into()),
+        }
+    }
+
+    // Create a variant value, it has to be given a value for each of its fields.
+    fn evaluate_variant_expression(
+        &mut self,
+        ast: &Rc<Ast>,
+        enum_name: &str,
+        variant: &str,
+        arguments: NodeList,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let arity = self.variant_arity(enum_name, variant)?;
+        let arguments = ast.list(arguments);
+        if arguments.len() != arity {
+            return Err(format!(
+                "Variant {0}::{1} expects {2}, got {3}",
+                enum_name,
+                variant,
+                amount(arity, "value"),
+                arguments.len()
+            )
+            .into());
+        }
+        let mut values = Vec::new();
+        for &argument in arguments {
+            let value = self.evaluate(ast, argument)?;
+            match Evaluator::result_to_variable(value.clone()) {
+                Some(_) => values.push(value),
+                None => return Err(format!("{0} can not be stored in a variant", value).into()),
+            }
+        }
+        Ok(EvalResult::Enum(Rc::new(EnumValue {
+            enum_name: enum_name.to_string(),
+            variant: variant.to_string(),
+            values,
+        })))
+    }
+
+    // Evaluate the first arm whose pattern matches the value and whose guard holds.
+    /*
+        The bindings of a pattern are assigned before its guard is evaluated, so the guard
+        can use them. They belong to the arm: once it is done, the variables they shadow
+        get their previous values back. The value of the match is the value of the last
+        statement of the arm, a break or return inside the arm is passed up. A value no arm
+        matches is an error, the type checker warns about matches that are not exhaustive.
+    */
+    fn evaluate_match_expression(
+        &mut self,
+        ast: &Rc<Ast>,
+        expr: NodeId,
+        arms: &[AstArm],
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let value = self.evaluate(ast, expr)?;
+        for arm in arms {
+            let mut bindings = Vec::new();
+            if !self.match_pattern(ast, &arm.pattern, &value, &mut bindings)? {
+                continue;
+            }
+            let shadowed = self.bind_arm(ast, bindings)?;
+            let result = self.evaluate_arm(ast, arm);
+            self.unbind_arm(ast, shadowed)?;
+            if let Some(result) = result? {
+                return Ok(result);
+            }
+        }
+        Err(format!("No match arm matches {0}", value).into())
+    }
+
+    // Assign the bindings of a matching arm, returning the values they shadow.
+    #[inline(never)]
+    fn bind_arm(
+        &mut self,
+        ast: &Ast,
+        bindings: Vec<(NodeId, EvalResult)>,
+    ) -> Result<Shadowed, Box<dyn error::Error>> {
+        let mut shadowed = Vec::new();
+        for (binding, bound) in bindings {
+            shadowed.push((binding, self.lookup_variable(ast, binding)?));
+            match Evaluator::result_to_variable(bound.clone()) {
+                Some(variable) => self.store_variable(ast, binding, variable)?,
+                None => return Err(format!("{0} can not be assigned to a variable", bound).into()),
+            }
+        }
+        Ok(shadowed)
+    }
+
+    // Put back the values shadowed by the bindings of an arm, the last binding first.
+    #[inline(never)]
+    fn unbind_arm(
+        &mut self,
+        ast: &Ast,
+        shadowed: Shadowed,
+    ) -> Result<(), Box<dyn error::Error>> {
+        for (binding, value) in shadowed.into_iter().rev() {
+            self.set_variable(ast, binding, value)?;
+        }
+        Ok(())
+    }
+
+    // Guard and body of an arm whose pattern matched, None if the guard does not hold.
+    fn evaluate_arm(&mut self, ast: &Rc<Ast>, arm: &AstArm) -> Result<Option<EvalResult>, Box<dyn error::Error>> {
+        if let Some(guard) = arm.guard {
+            if !self.evaluate_condition(ast, guard)? {
+                return Ok(None);
+            }
+        }
+        self.evaluate_block(ast, arm.body).map(Some)
+    }
+
+    // Whether the value matches the pattern, collecting the values of its bindings.
+    fn match_pattern(
+        &mut self,
+        ast: &Rc<Ast>,
+        pattern: &AstPattern,
+        value: &EvalResult,
+        bindings: &mut Vec<(NodeId, EvalResult)>,
+    ) -> Result<bool, Box<dyn error::Error>> {
+        match pattern {
+            AstPattern::Wildcard => Ok(true),
+            AstPattern::Binding(binding) => {
+                bindings.push((*binding, value.clone()));
+                Ok(true)
+            }
+            AstPattern::Literal(literal) => Ok(*value == self.evaluate(ast, *literal)?),
+            AstPattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                let start = self.evaluate(ast, *start)?;
+                let end = self.evaluate(ast, *end)?;
+                let upper = if *inclusive {
+                    Token::LessThanOrEquals
+                } else {
+                    Token::LessThan
+                };
+                // Values that can not be compared to numbers are outside of every range
+                Ok(value.compare(&Token::GreaterThanOrEquals, &start).unwrap_or(false)
+                    && value.compare(&upper, &end).unwrap_or(false))
+            }
+            AstPattern::List { elements, rest } => {
+                let list = match value {
+                    EvalResult::List(list) => list.clone(),
+                    _ => return Ok(false),
+                };
+                let fits = match rest {
+                    Some(_) => list.len() >= elements.len(),
+                    None => list.len() == elements.len(),
+                };
+                if !fits {
+                    return Ok(false);
+                }
+                for (element, item) in elements.iter().zip(list.iter()) {
+                    if !self.match_pattern(ast, element, item, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                match rest {
+                    Some(rest) => {
+                        let remaining = EvalResult::List(Rc::new(list[elements.len()..].to_vec()));
+                        self.match_pattern(ast, rest, &remaining, bindings)
+                    }
+                    None => Ok(true),
+                }
+            }
+            AstPattern::Struct { identifier, fields } => {
+                let instance = match value {
+                    EvalResult::Struct(instance) if instance.name == *identifier => instance.clone(),
+                    _ => return Ok(false),
+                };
+                for (field, pattern) in fields {
+                    let field_value = match instance.get(field) {
+                        Some(field_value) => field_value,
+                        None => return Err(format!("{0} has no field {1}", identifier, field).into()),
+                    };
+                    if !self.match_pattern(ast, pattern, &field_value, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            AstPattern::Variant {
+                enum_name,
+                variant,
+                elements,
+            } => {
+                let arity = self.variant_arity(enum_name, variant)?;
+                if elements.len() != arity {
+                    return Err(format!(
+                        "Variant {0}::{1} expects {2}, got {3}",
+                        enum_name,
+                        variant,
+                        amount(arity, "value"),
+                        elements.len()
+                    )
+                    .into());
+                }
+                let enum_value = match value {
+                    EvalResult::Enum(enum_value)
+                        if enum_value.enum_name == *enum_name && enum_value.variant == *variant =>
+                    {
+                        enum_value.clone()
+                    }
+                    _ => return Ok(false),
+                };
+                for (element, item) in elements.iter().zip(enum_value.values.iter()) {
+                    if !self.match_pattern(ast, element, item, bindings)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    // Get an element of a list, indices start at zero.
+    fn evaluate_index_expression(
+        &mut self,
+        ast: &Rc<Ast>,
+        expr: NodeId,
+        index: NodeId,
+    ) -> Result<EvalResult, Box<dyn error::Error>> {
+        let list = match self.evaluate(ast, expr)? {
+            EvalResult::List(list) => list,
+            other => {
+                return Err(Box::new(RuntimeError::new(
+                    ErrorKind::TypeMismatch,
+                    format!("Can not index {0}, only lists can be indexed", other),
+                )))
+            }
+        };
+        let position = match self.evaluate(ast, index)? {
+            EvalResult::Number(Number::Integer(i)) => Some(i),
+            _ => None,
+        };
+        match position {
+            Some(position) if position >= 0 && (position as usize) < list.len() => {
+                Ok(list[position as usize].clone())
+            }
+            Some(position) => Err(format!(
+                "Index {0} is out of range for a list of {1} elements",
+                position,
+                list.len()
+            )
+            .into()),
+            None => Err(Box::new(RuntimeError::new(
+                ErrorKind::TypeMismatch,
+                "List index must be an integer".to_string(),
+            ))),
+        }
+    }
+
+    // Storage slot of a variable node, None for a global that does not exist yet.
+    /*
+        Resolved variables already know their slot. Otherwise the variables of the current
+        call and the calls it was created in shadow the globals.
+    */
+    fn variable_slot(&self, ast: &Ast, variable: NodeId) -> Result<Option<Slot>, Box<dyn error::Error>> {
+        let identifier = match &ast[variable] {
+            AstNode::Identifier(identifier) => identifier,
+            _ => return Err("Expected the name of a variable".into()),
+        };
+        if let Some(slot) = ast.slot(variable) {
+            return Ok(Some(slot));
+        }
+        Ok(self
+            .call_slot(identifier)
+            .or_else(|| self.global_slots.get(identifier).map(|slot| Slot::Global(*slot))))
+    }
+
+    // Slot of a variable owned by the current call or one it was created in.
+    /*
+        Parameters shadow the variables of enclosing calls. Other locals belong to the
+        outermost call assigning them, the same way the resolver binds them. Closures
+        created outside of named functions change existing globals instead.
+    */
+    fn call_slot(&self, identifier: &str) -> Option<Slot> {
+        let mut frame = self.call_stack.last();
+        let mut depth = 0;
+        let mut found = None;
+        let mut closures = true;
+        while let Some(current) = frame {
+            closures &= current.function.name.is_none();
+            if let Some(&slot) = current.function.locals.get(identifier) {
+                found = Some(match depth {
+                    0 => Slot::Local(slot),
+                    _ => Slot::Captured { depth, slot },
+                });
+                if current.function.parameters.iter().any(|name| name == identifier) {
+                    return found;
+                }
+            }
+            frame = current.parent.as_ref();
+            depth += 1;
+        }
+        let global = self.global_slots.get(identifier);
+        match closures && global.is_some_and(|slot| self.globals[*slot].is_some()) {
+            true => None,
+            false => found,
+        }
+    }
+
+    // Frame of the call `depth` levels out from the current one.
+    fn frame(&self, depth: usize) -> Result<&Rc<CallFrame>, Box<dyn error::Error>> {
+        let mut frame = match self.call_stack.last() {
+            Some(frame) => frame,
+            None => return Err("Found a local variable outside of a function".into()),
+        };
+        for _ in 0..depth {
+            frame = match &frame.parent {
+                Some(parent) => parent,
+                None => return Err("Found a captured variable outside of its function".into()),
+            };
+        }
+        Ok(frame)
+    }
+
+    fn global_slot(&mut self, identifier: &str) -> usize {
+        if let Some(slot) = self.global_slots.get(identifier) {
+            return *slot;
+        }
+        let slot = self.globals.len();
+        self.globals.push(None);
+        self.global_slots.insert(identifier.to_string(), slot);
+        slot
+    }
+
+    // Find the value of a variable, None if it has not been assigned.
+    fn lookup_variable(
+        &self,
+        ast: &Ast,
+        variable: NodeId,
+    ) -> Result<Option<VariableValue>, Box<dyn error::Error>> {
+        let value = match self.variable_slot(ast, variable)? {
+            None => return Ok(None),
+            Some(Slot::Global(slot)) => self.globals.get(slot).cloned(),
+            Some(Slot::Local(slot)) => self.frame(0)?.variables.borrow().get(slot).cloned(),
+            Some(Slot::Captured { depth, slot }) => {
+                self.frame(depth)?.variables.borrow().get(slot).cloned()
+            }
+        };
+        match value {
+            Some(value) => Ok(value),
+            None => Err("Invalid variable slot".into()),
+        }
+    }
+
+    // Assignments inside a function call create locals, unless the variable is captured.
+    fn store_variable(
+        &mut self,
+        ast: &Ast,
+        variable: NodeId,
+        value: VariableValue,
+    ) -> Result<(), Box<dyn error::Error>> {
+        self.set_variable(ast, variable, Some(value))
+    }
+
+    // Store the value of a variable, None makes it unassigned again.
+    fn set_variable(
+        &mut self,
+        ast: &Ast,
+        variable: NodeId,
+        value: Option<VariableValue>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let slot = match (ast.slot(variable), &ast[variable]) {
+            (Some(slot), _) => slot,
+            (None, AstNode::Identifier(identifier)) => match self.call_slot(identifier) {
+                Some(slot) => slot,
+                None => Slot::Global(self.global_slot(identifier)),
+            },
+            (None, _) => return Err("Expected the name of a variable".into()),
+        };
+        let stored = match slot {
+            Slot::Global(slot) => self.globals.get_mut(slot).map(|storage| *storage = value),
+            Slot::Local(slot) => self.frame(0)?.variables.borrow_mut().get_mut(slot).map(|storage| *storage = value),
+            Slot::Captured { depth, slot } => {
+                let frame = self.frame(depth)?;
+                let mut variables = frame.variables.borrow_mut();
+                variables.get_mut(slot).map(|storage| *storage = value)
+            }
+        };
+        match stored {
+            Some(()) => Ok(()),
+            None => Err("Invalid variable slot".into()),
+        }
+    }
+
+    fn result_to_variable(value: EvalResult) -> Option<VariableValue> {
+        match value {
+            // Numbers
+            EvalResult::Number(n) => Some(VariableValue::Number(n)),
+            // Numbers with units
+            EvalResult::Quantity(q) => Some(VariableValue::Quantity(q)),
+            // Strings
+            EvalResult::Literal(string) => Some(VariableValue::Literal(string)),
+            // Booleans
+            EvalResult::Boolean(boolean) => Some(VariableValue::Boolean(boolean)),
+            // Functions, lists, structs, variants and errors
+            EvalResult::Function(closure) => Some(VariableValue::Function(closure)),
+            EvalResult::List(list) => Some(VariableValue::List(list)),
+            EvalResult::Struct(instance) => Some(VariableValue::Struct(instance)),
+            EvalResult::Enum(value) => Some(VariableValue::Enum(value)),
+            EvalResult::Error(error) => Some(VariableValue::Error(error)),
+            EvalResult::Module(module) => Some(VariableValue::Module(module)),
+            _ => None,
+        }
+    }
+
+    fn variable_to_result(value: &VariableValue) -> EvalResult {
+        match value {
+            VariableValue::Boolean(b) => EvalResult::Boolean(*b),
+            VariableValue::Number(n) => EvalResult::Number(n.clone()),
+            VariableValue::Quantity(q) => EvalResult::Quantity(q.clone()),
+            VariableValue::Literal(s) => EvalResult::Literal(s.to_string()),
+            VariableValue::Function(closure) => EvalResult::Function(closure.clone()),
+            VariableValue::List(list) => EvalResult::List(list.clone()),
+            VariableValue::Struct(instance) => EvalResult::Struct(instance.clone()),
+            VariableValue::Enum(value) => EvalResult::Enum(value.clone()),
+            VariableValue::Error(error) => EvalResult::Error(error.clone()),
+            VariableValue::Module(module) => EvalResult::Module(module.clone()),
+        }
+    }
+}
+
+// Assigned variables of a function body or the top level. Nested functions have their
+// own variables.
+struct AssignmentCollector<'a> {
+    names: &'a mut HashSet<String>,
+}
+
+impl<'a> Visitor for AssignmentCollector<'a> {
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::AssignmentExpression { identifier, .. } => match identifier.as_ref() {
+                Node::IdentifierExpression(name)
+                | Node::VariableExpression {
+                    identifier: name, ..
+                } => {
+                    self.names.insert(name.clone());
+                    // The value can be a match assigning its own variables
+                    walk_node(self, node);
+                }
+                _ => {}
+            },
+            // Patterns of match arms assign their bindings
+            Node::MatchExpression { arms, .. } => {
+                for arm in arms {
+                    self.names.extend(arm.pattern.bindings());
+                }
+                walk_node(self, node);
+            }
+            // So does the catch of a try
+            Node::TryExpression { catch, .. } => {
+                if let Some(CatchClause {
+                    binding:
+                        Node::IdentifierExpression(name)
+                        | Node::VariableExpression {
+                            identifier: name, ..
+                        },
+                    ..
+                }) = catch.as_ref()
+                {
+                    self.names.insert(name.clone());
+                }
+                walk_node(self, node);
+            }
+            // And imports their names
+            Node::ImportExpression { alias, names, .. } => {
+                for name in alias.as_ref().iter().chain(names) {
+                    if let Node::IdentifierExpression(name)
+                    | Node::VariableExpression {
+                        identifier: name, ..
+                    } = name
+                    {
+                        self.names.insert(name.clone());
+                    }
+                }
+            }
+            Node::FunctionDeclaration { .. } | Node::FunctionExpression { .. } => {}
+            _ => walk_node(self, node),
+        }
+    }
 }
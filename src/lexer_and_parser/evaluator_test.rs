@@ -0,0 +1,527 @@
+// Tests for evaluating parsed expressions.
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::lexer_and_parser::arena::Ast;
+    use crate::lexer_and_parser::builtins::Builtin;
+    use crate::lexer_and_parser::evaluator::{EvalResult, Evaluator};
+    use crate::lexer_and_parser::numeric::Number;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::lexer_and_parser::units::{Dimension, Quantity};
+    use crate::passes::resolver::Resolver;
+
+    // Evaluate every expression in the given source and return the last result.
+    fn evaluate(source: &str) -> EvalResult {
+        let mut evaluator = Evaluator::new(None);
+        let mut result = EvalResult::EmptyResult;
+        for expression in Parser::new(source).unwrap().parse().unwrap() {
+            evaluator.ast = Some(expression);
+            result = evaluator.start_evaluating().unwrap();
+        }
+        result
+    }
+
+    fn number(source: &str) -> Number {
+        match evaluate(source) {
+            EvalResult::Number(n) => n,
+            other => panic!("Expected a number, got {0:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rational_arithmetic() {
+        let sum = number("1/3 + 1/6");
+        assert_eq!(sum.to_string(), "1/2");
+        assert!(matches!(sum, Number::Rational(_)));
+        // Exact division stays an integer
+        assert!(matches!(number("6/3"), Number::Integer(2)));
+        assert_eq!(number("1/3 * 3").to_string(), "1");
+        assert_eq!(number("-1/4 - 1/4").to_string(), "-1/2");
+    }
+
+    #[test]
+    fn test_big_integer_arithmetic() {
+        let big = number("2^200");
+        assert!(matches!(big, Number::BigInteger(_)));
+        assert_eq!(
+            big.to_string(),
+            "1606938044258990275541962092341162602522202993782792835301376"
+        );
+        // Overflowing i64 promotes instead of wrapping, and shrinks back when possible
+        assert_eq!(number("3037000500 * 3037000500").to_string(), "9223372037000250000");
+        assert!(matches!(number("2^64 - 2^64 + 5"), Number::Integer(5)));
+        // The only quotient of two i64 that does not fit into one
+        let quotient = number("x = 0 - 4611686018427387904 * 2\nx / (0 - 1)");
+        assert!(matches!(quotient, Number::BigInteger(_)));
+        assert_eq!(quotient.to_string(), "9223372036854775808");
+        assert_eq!(number("2^-2").to_string(), "1/4");
+        assert_eq!(number("(2/3)^3").to_string(), "8/27");
+        // Quotients and reductions of numbers with thousands of digits stay exact
+        assert_eq!(evaluate("3^20000 / 7^9000 * 7^9000 == 3^20000"), EvalResult::Boolean(true));
+        assert_eq!(number("(2^300 + 1) * (3^200 + 7) / (3^200 + 7)").to_string(), number("2^300 + 1").to_string());
+        assert_eq!(number("6^400 / 4^300").to_string(), number("3^400 / 2^200").to_string());
+    }
+
+    #[test]
+    fn test_float_conversion() {
+        // Any float operand makes the result a float
+        assert!(matches!(number("1/2 + 0.5"), Number::Float(f) if f == 1.0));
+        assert!(matches!(number("4^0.5"), Number::Float(f) if f == 2.0));
+        // A decimal point makes a float even if the value is integral
+        assert!(matches!(number("1.0"), Number::Float(f) if f == 1.0));
+        assert!(matches!(number("1.0 / 0.0"), Number::Float(f) if f == f64::INFINITY));
+        assert_eq!(number("18446744073709551616 - 1").to_string(), "18446744073709551615");
+        assert_eq!(number("1/4").to_f64(), 0.25);
+        // Numbers compare by value regardless of representation
+        assert_eq!(evaluate("0.5 == 1/2"), EvalResult::Boolean(true));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let mut evaluator = Evaluator::new(None);
+        evaluator.ast = Some(Parser::new("1/0").unwrap().parse().unwrap().remove(0));
+        assert!(evaluator.start_evaluating().is_err());
+    }
+
+    fn quantity(source: &str) -> Quantity {
+        match evaluate(source) {
+            EvalResult::Quantity(q) => q,
+            other => panic!("Expected a quantity, got {0:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_units_of_measure() {
+        assert_eq!(quantity("9.81 m/s^2").to_string(), "9.81 m/s^2");
+        // Addition keeps the unit of the left operand
+        assert_eq!(quantity("5 km + 300 m").to_string(), "53/10 km");
+        // Multiplication and division combine dimensions
+        let force = quantity("2 kg * 3 m/s^2");
+        assert_eq!(force.dimension, Dimension([1, 1, -2, 0, 0, 0, 0]));
+        assert_eq!(force, quantity("6 N"));
+        assert_eq!(quantity("(10 m) / (2 s)").to_string(), "5 m/s");
+        // Dimensionless results are plain numbers again
+        assert_eq!(evaluate("(6 m) / (3 m)"), EvalResult::Number(Number::Integer(2)));
+        assert_eq!(evaluate("1 km == 1000 m"), EvalResult::Boolean(true));
+    }
+
+    #[test]
+    fn test_unit_conversion() {
+        assert_eq!(quantity("x = 1500 m\nx in km").to_string(), "3/2 km");
+        assert_eq!(quantity("90 km/h in m/s").to_string(), "25 m/s");
+        assert_eq!(quantity("2 h in min").to_string(), "120 min");
+    }
+
+    #[test]
+    fn test_unit_errors() {
+        for source in ["5 m + 3 s", "5 m in s", "3 parsec", "2 ^ (1 m)"].iter() {
+            let mut evaluator = Evaluator::new(None);
+            evaluator.ast = Some(Parser::new(source).unwrap().parse().unwrap().remove(0));
+            assert!(evaluator.start_evaluating().is_err(), "{} should fail", source);
+        }
+        // Exponents of units have to fit into an i8
+        let error = run_resolved("x = (2 m)^100\nx * x").unwrap_err();
+        assert!(error.contains("Unit exponent out of range"), "{}", error);
+        assert!(Evaluator::new(None).units.parse("m/s^-128").is_err());
+    }
+
+    #[test]
+    fn test_custom_units() {
+        let mut evaluator = Evaluator::new(None);
+        evaluator.units.define("mph", Number::Integer(1), "mi/h").unwrap();
+        evaluator.ast = Some(Parser::new("60 mph in km/h").unwrap().parse().unwrap().remove(0));
+        match evaluator.start_evaluating().unwrap() {
+            EvalResult::Quantity(q) => assert_eq!(q.to_string(), "301752/3125 km/h"),
+            other => panic!("Expected a quantity, got {0:?}", other),
+        }
+    }
+
+    // Resolve and run the whole source, like the interpreter does.
+    fn run_resolved(source: &str) -> Result<EvalResult, String> {
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let lines = std::mem::take(&mut parser.statement_lines);
+        let mut resolver = Resolver::new().with_lines(lines.clone());
+        let nodes = resolver.resolve(nodes);
+        assert!(!resolver.has_errors(), "{0:?}", resolver.diagnostics);
+        let mut evaluator = Evaluator::new(None);
+        evaluator.declare_globals(&resolver.globals).unwrap();
+        evaluator
+            .run(&Rc::new(Ast::with_lines(nodes, lines)))
+            .map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_closures() {
+        let sources = [
+            // Closures keep the variables of the call they were created in
+            ("fn make_adder(n) {\n    return fn(x) { x + n }\n}\nadd_two = make_adder(2)\nadd_two(40)", 42),
+            ("fn make_adder(n) {\n    return fn(x) { x + n }\n}\nmake_adder(1)(2)", 3),
+            // Captured variables are read when the closure runs, not when it is created
+            ("fn counter() {\n    count = 0\n    get = fn() { count }\n    count = 5\n    return get\n}\ncounter()()", 5),
+            // Named functions are values as well
+            ("fn apply(f, x) {\n    return f(x)\n}\nfn double(n) {\n    return n * 2\n}\napply(double, 21)", 42),
+            ("twice = fn(f) { fn(v) { f(f(v)) } }\ntwice(fn(n) { n * 3 })(2)", 18),
+            // Closures change the variables they capture, globals included. Assignments in
+            // named functions create locals.
+            ("x = 1\nf = fn() {\n    x = 10\n    x\n}\nf() + x", 20),
+            ("count = 0\ninc = fn() {\n    count = count + 1\n}\ninc()\ninc()\ncount", 2),
+            ("x = 1\nf = fn() {\n    g = fn() {\n        x = x * 5\n    }\n    g()\n}\nf()\nx", 5),
+            ("x = 1\nfn f() {\n    x = 10\n    return x\n}\nf() + x", 11),
+            ("x = 1\nfn f() {\n    g = fn() {\n        x = 10\n    }\n    g()\n    return 0\n}\nf() + x", 1),
+            ("fn counter() {\n    n = 0\n    inc = fn() {\n        n = n + 1\n        n\n    }\n    return inc\n}\nc = counter()\nc()\nc() * 10 + c()", 23),
+            ("fn f(n) {\n    g = fn(n) {\n        n = n + 1\n        n\n    }\n    return g(n) + n\n}\nf(1)", 3),
+            ("fn f() {\n    n = 1\n    g = fn() {\n        h = fn() {\n            n = n * 10\n        }\n        h()\n    }\n    g()\n    return n\n}\nf()", 10),
+        ];
+        for (source, expected) in sources.iter() {
+            let expected = EvalResult::Number(Number::Integer(*expected));
+            assert_eq!(evaluate(source), expected, "{}", source);
+            assert_eq!(run_resolved(source), Ok(expected), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_if_values() {
+        let sources = [
+            ("x = 1\ny = if x == 1 { 10 } else { 20 }\ny", 10),
+            ("x = 3\nif x == 1 { 10 } elif x == 2 { 20 } else { 30 }", 30),
+            ("x = 1\n1 + if x > 0 { 2 } else { 3 }", 3),
+            // Blocks take the value of their last statement
+            ("y = if true {\n    a = 2\n    a * 5\n} else { 0 }\ny", 10),
+            ("sum([if 1 > 2 { 1 } else { 2 }, 3])", 5),
+            // Functions return the value of their last statement, e.g. an if with an else
+            ("f = fn(n) { if n > 0 { n } else { -n } }\nf(-4)", 4),
+            ("fn f(a) { a * 2 }\nf(21)", 42),
+            ("fn fact(n) {\n    if n < 2 { 1 } else { n * fact(n - 1) }\n}\nfact(5)", 120),
+            ("fn f(n) {\n    m = n + 1\n    m * m\n}\nf(2)", 9),
+            // Break and return inside a branch still leave the loop or function
+            ("fn f(n) {\n    y = if n > 0 { return 1 } else { 2 }\n    return y\n}\nf(5)", 1),
+            ("i = 0\nwhile true {\n    i = if i == 3 { break } else { i + 1 }\n}\ni", 3),
+        ];
+        for (source, expected) in sources.iter() {
+            let expected = EvalResult::Number(Number::Integer(*expected));
+            assert_eq!(run_resolved(source), Ok(expected), "{}", source);
+        }
+        // Nothing without a branch to take, and assignments have no value
+        assert_eq!(run_resolved("if 1 > 2 { 1 }"), Ok(EvalResult::EmptyResult));
+        assert_eq!(run_resolved("if true { x = 1 }"), Ok(EvalResult::EmptyResult));
+        assert_eq!(run_resolved("fn f() { x = 1 }\nf()"), Ok(EvalResult::EmptyResult));
+        assert_eq!(run_resolved("fn f(n) { if n > 0 { 1 } }\nf(0)"), Ok(EvalResult::EmptyResult));
+    }
+
+    #[test]
+    fn test_lists() {
+        let source = "handlers = [fn(x) { x + 1 }, fn(x) { x * 2 }]\nhandlers[1](5) + [1, 2, 3][2]";
+        assert_eq!(evaluate(source), EvalResult::Number(Number::Integer(13)));
+        match evaluate("[1, \"a\", [true]]") {
+            EvalResult::List(items) => assert_eq!(
+                items.as_ref(),
+                &vec![
+                    EvalResult::Number(Number::Integer(1)),
+                    EvalResult::Literal("a".to_string()),
+                    EvalResult::List(Rc::new(vec![EvalResult::Boolean(true)])),
+                ]
+            ),
+            other => panic!("Expected a list, got {0:?}", other),
+        }
+        for (source, message) in [
+            (
+                "[1, 2][2]",
+                "Index 2 is out of range for a list of 2 elements",
+            ),
+            ("[1][\"a\"]", "List index must be an integer"),
+            (
+                "x = 1\nx[0]",
+                "Can not index 1, only lists can be indexed",
+            ),
+            ("f = 1\nf(2)", "1 is not a function"),
+            (
+                "(fn(a) { a })()",
+                "Function <anonymous> expects 1 argument, got 0",
+            ),
+        ]
+        .iter()
+        {
+            assert_eq!(run_resolved(source), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    const POINT: &str = "struct Point { x, y }
+impl Point {
+    fn norm2(self) {
+        return self.x * self.x + self.y * self.y
+    }
+    fn shift(self, dx) {
+        self.x = self.x + dx
+    }
+}
+";
+
+    #[test]
+    fn test_structs() {
+        let sources = [
+            ("p = Point { y: 4, x: 3 }\np.norm2()", 25),
+            ("p = Point { x: 1, y: 2 }\np.y = 5\np.y", 5),
+            // Struct values are shared, methods change the fields of self
+            ("p = Point { x: 1, y: 2 }\nq = p\nq.shift(2)\np.x", 3),
+            ("fn make(n) {\n    return Point { x: n, y: n }\n}\nmake(7).x", 7),
+            // Fields holding functions are called like methods
+            ("struct Op { f }\nop = Op { f: fn(x) { x + 1 } }\nop.f(1)", 2),
+        ];
+        for (source, expected) in sources.iter() {
+            let source = format!("{0}{1}", POINT, source);
+            let expected = EvalResult::Number(Number::Integer(*expected));
+            assert_eq!(run_resolved(&source), Ok(expected), "{}", source);
+        }
+        assert_eq!(
+            run_resolved(&format!("{0}Point {{ x: 1, y: 2 }} == Point {{ y: 2, x: 1 }}", POINT)),
+            Ok(EvalResult::Boolean(true))
+        );
+        for (source, message) in [
+            ("Point { x: 1 }", "Missing field y for Point"),
+            ("Point { x: 1, y: 2, z: 3 }", "Struct Point has no field z"),
+            ("Line { a: 1 }", "Could not find a struct named Line"),
+            ("impl Line {\n}", "Could not find a struct named Line"),
+            ("p = Point { x: 1, y: 2 }\np.z", "Point has no field z"),
+            ("p = Point { x: 1, y: 2 }\np.z = 1", "Point has no field z"),
+            ("p = Point { x: 1, y: 2 }\np.scale(2)", "Point has no field or method scale"),
+            (
+                "p = Point { x: 1, y: 2 }\np.shift()",
+                "Method Point.shift expects 1 argument, got 0",
+            ),
+            ("x = 1\nx.y", "Can not get field y of 1"),
+            ("x = 1\nx.y()", "Can not call method y of 1"),
+        ]
+        .iter()
+        {
+            let source = format!("{0}{1}", POINT, source);
+            assert_eq!(run_resolved(&source), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    const SHAPE: &str = "enum Shape {
+    Circle(r),
+    Rect(w, h),
+    Empty
+}
+struct Point { x, y }
+fn area(shape) {
+    return match shape {
+        Shape::Circle(r) => 3 * r * r,
+        Shape::Rect(w, h) if w == h => w * w,
+        Shape::Rect(w, h) => w * h,
+        Shape::Empty => 0
+    }
+}
+";
+
+    #[test]
+    fn test_match() {
+        let sources = [
+            ("area(Shape::Circle(2))", 12),
+            ("area(Shape::Rect(3, 3))", 9),
+            ("area(Shape::Rect(2, 5))", 10),
+            ("area(Shape::Empty)", 0),
+            ("match 3 {\n    1 => 10,\n    2..=4 => 20,\n    _ => 30\n}", 20),
+            ("match 4 {\n    1..4 => 10\n    n => n * 2\n}", 8),
+            ("match \"b\" {\n    \"a\" => 1,\n    \"b\" => 2\n}", 2),
+            ("match [1, 2, 3] {\n    [] => 0,\n    [x, ..rest] => x + sum(rest)\n}", 6),
+            ("match [1, 2] {\n    [a] => a,\n    [a, b, ..] => a + b\n}", 3),
+            ("match (Point { x: 1, y: 5 }) {\n    Point { x: 0, y } => y,\n    Point { x, y: b } => x + b\n}", 6),
+            // Nested patterns
+            ("match Shape::Rect(1, [2, 3]) {\n    Shape::Rect(1, [_, c]) => c\n}", 3),
+            // Bindings are visible in the guard, after the arm shadowed variables are back
+            ("n = 7\nm = match 5 {\n    n if n > 9 => 0,\n    n => n\n}\nm * 10 + n", 57),
+            ("fn f(x) {\n    y = match [x] {\n        [x] => x * 2\n    }\n    return x + y\n}\nf(3)", 9),
+            // Block bodies take the value of their last statement
+            ("x = match 2 {\n    2 => {\n        y = 3\n        y * 2\n    }\n}\nx", 6),
+        ];
+        for (source, expected) in sources.iter() {
+            let source = format!("{0}{1}", SHAPE, source);
+            let expected = EvalResult::Number(Number::Integer(*expected));
+            assert_eq!(run_resolved(&source), Ok(expected), "{}", source);
+        }
+        assert_eq!(
+            run_resolved(&format!("{0}Shape::Rect(1, 2) == Shape::Rect(1, 2)", SHAPE)),
+            Ok(EvalResult::Boolean(true))
+        );
+        for (source, message) in [
+            ("match 5 {\n    1 => 1\n}", "No match arm matches 5"),
+            ("Shape::Rect(1)", "Variant Shape::Rect expects 2 values, got 1"),
+            ("Shape::Square", "Enum Shape has no variant Square"),
+            ("Color::Red", "Could not find an enum named Color"),
+            (
+                "match Shape::Empty {\n    Shape::Empty(x) => x\n}",
+                "Variant Shape::Empty expects 0 values, got 1",
+            ),
+            (
+                "match (Point { x: 1, y: 2 }) {\n    Point { z } => z\n}",
+                "Point has no field z",
+            ),
+        ]
+        .iter()
+        {
+            let source = format!("{0}{1}", SHAPE, source);
+            assert_eq!(run_resolved(&source), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    const AVERAGE: &str = "fn average(items, count) {
+    return sum(items) / count
+}
+";
+
+    #[test]
+    fn test_try_catch() {
+        let sources = [
+            ("try {\n    missing\n} catch e {\n    e.kind\n}", "UndefinedVariable"),
+            ("try {\n    1 + \"a\"\n} catch e {\n    e.kind\n}", "TypeMismatch"),
+            ("try {\n    average([], 0)\n} catch e {\n    e.message\n}", "Division by zero"),
+            ("try {\n    throw error(\"bad\", \"ValueError\")\n} catch e {\n    e.kind\n}", "ValueError"),
+            ("try {\n    throw \"plain\"\n} catch e {\n    e.message\n}", "plain"),
+            // Rethrowing keeps the original kind
+            (
+                "try {\n    try {\n        1 / 0\n    } catch e {\n        throw e\n    }\n} catch outer {\n    outer.kind\n}",
+                "DivisionByZero",
+            ),
+            // A try without an error takes the value of its body
+            ("try {\n    \"fine\"\n} catch e {\n    \"caught\"\n}", "fine"),
+        ];
+        for (source, expected) in sources.iter() {
+            let source = format!("{0}{1}", AVERAGE, source);
+            let expected = EvalResult::Literal(expected.to_string());
+            assert_eq!(run_resolved(&source), Ok(expected), "{}", source);
+        }
+        // The finally block always runs
+        let source = "x = 0\ntry {\n    throw \"a\"\n} catch e {\n    x = x + 1\n} finally {\n    x = x + 10\n}\nx";
+        assert_eq!(run_resolved(source), Ok(EvalResult::Number(Number::Integer(11))));
+        // Errors remember the line they were raised on
+        let source = "try {\n    1 / 0\n} catch e {\n    e.line\n}";
+        assert_eq!(run_resolved(source), Ok(EvalResult::Number(Number::Integer(2))));
+        assert_eq!(
+            run_resolved("try {\n    throw \"a\"\n} finally {\n    1\n}"),
+            Err("a".to_string())
+        );
+    }
+
+    #[test]
+    fn test_error_trace() {
+        let source = format!("{0}x = 1\naverage([], 0)", AVERAGE);
+        let mut parser = Parser::new(&source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let lines = std::mem::take(&mut parser.statement_lines);
+        let mut resolver = Resolver::new().with_lines(lines.clone());
+        let nodes = resolver.resolve(nodes);
+        let mut evaluator = Evaluator::new(None);
+        evaluator.declare_globals(&resolver.globals).unwrap();
+        let error = evaluator
+            .run(&Rc::new(Ast::with_lines(nodes, lines)))
+            .unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "DivisionByZero: Division by zero\n  in average at line 2\n  at line 5"
+        );
+    }
+
+    const PARSE: &str = "fn positive(n) {
+    if n < 0 {
+        return err(error(\"negative\", \"ValueError\"))
+    }
+    return ok(n)
+}
+fn add(a, b) {
+    return ok(positive(a)? + positive(b)?)
+}
+";
+
+    #[test]
+    fn test_propagation() {
+        let sources = [
+            ("add(1, 2)", "ok(3)"),
+            ("add(1, -2)", "err(ValueError: negative)"),
+            ("unwrap_or(add(-1, 2), 0)", "0"),
+            ("match add(2, 2) {\n    ok(n) => n\n    err(_) => 0\n}", "4"),
+            ("match add(2, -2) {\n    ok(n) => n\n    err(e) => e.kind\n}", "ValueError"),
+            // A ? inside a function is not caught by its try, it returns
+            (
+                "fn f(o) {\n    try {\n        v = o?\n    } catch e {\n        return \"caught\"\n    }\n    return some(v)\n}\nf(none)",
+                "none",
+            ),
+            ("fn f(o) {\n    return some(o? * 2)\n}\nf(some(4))", "some(8)"),
+            // At the top level it throws, like unwrap
+            ("try {\n    add(1, -1)?\n} catch e {\n    e.message\n}", "negative"),
+        ];
+        for (source, expected) in sources.iter() {
+            let source = format!("{0}{1}", PARSE, source);
+            let result = run_resolved(&source).map(|result| Builtin::text(&result));
+            assert_eq!(result, Ok(expected.to_string()), "{}", source);
+        }
+        assert_eq!(
+            run_resolved("x = 1\nx?"),
+            Err("Operator ? expects a Result or an Option, got 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_host_results() {
+        let parsed: Result<EvalResult, std::num::ParseIntError> =
+            "12".parse::<i64>().map(|n| EvalResult::Number(Number::Integer(n)));
+        assert_eq!(EvalResult::from(parsed), EvalResult::ok(EvalResult::Number(Number::Integer(12))));
+        let failed = "x".parse::<i64>().map(|n| EvalResult::Number(Number::Integer(n)));
+        assert_eq!(
+            EvalResult::from(failed),
+            EvalResult::err(EvalResult::Literal("invalid digit found in string".to_string()))
+        );
+        assert_eq!(EvalResult::from(None), EvalResult::none());
+        assert_eq!(
+            EvalResult::some(EvalResult::Boolean(true)).as_result(),
+            Some(Ok(EvalResult::Boolean(true)))
+        );
+        assert_eq!(EvalResult::none().as_result(), Some(Err(None)));
+        assert_eq!(EvalResult::Boolean(true).as_result(), None);
+    }
+
+    #[test]
+    fn test_host_functions() {
+        let run = |source: &str| {
+            let mut evaluator = Evaluator::new(None);
+            evaluator.register("parse_int", |arguments: &[EvalResult]| match arguments {
+                [EvalResult::Literal(text)] => text
+                    .parse::<i64>()
+                    .map(|n| EvalResult::Number(Number::Integer(n)))
+                    .map_err(|e| e.to_string()),
+                _ => Err(format!("Function parse_int expects a string, got {0} arguments", arguments.len())),
+            });
+            let mut parser = Parser::new(source).unwrap();
+            let nodes = parser.parse().unwrap();
+            let lines = std::mem::take(&mut parser.statement_lines);
+            let mut resolver = Resolver::new()
+                .with_lines(lines.clone())
+                .with_functions(evaluator.host_functions.keys().cloned().collect());
+            let nodes = resolver.resolve(nodes);
+            assert!(!resolver.has_errors(), "{0:?}", resolver.diagnostics);
+            evaluator.declare_globals(&resolver.globals).unwrap();
+            let result = evaluator.run(&Rc::new(Ast::with_lines(nodes, lines)));
+            result.map(|result| Builtin::text(&result)).map_err(|e| e.to_string())
+        };
+        let sources = [
+            ("parse_int(\"42\")", "ok(42)"),
+            ("parse_int(\"x\")", "err(invalid digit found in string)"),
+            ("parse_int()", "err(Function parse_int expects a string, got 0 arguments)"),
+            ("unwrap_or(parse_int(\"x\"), 0)", "0"),
+            ("match parse_int(\"x\") {\n    ok(n) => n\n    err(e) => e\n}", "invalid digit found in string"),
+            ("fn double(s) {\n    return ok(parse_int(s)? * 2)\n}\ndouble(\"21\")", "ok(42)"),
+            ("fn double(s) {\n    return ok(parse_int(s)? * 2)\n}\ndouble(\"a\")", "err(invalid digit found in string)"),
+            // Declared functions shadow the host's
+            ("fn parse_int(s) {\n    return 0\n}\nparse_int(\"1\")", "0"),
+        ];
+        for (source, expected) in sources.iter() {
+            assert_eq!(run(source), Ok(expected.to_string()), "{}", source);
+        }
+        assert_eq!(
+            run("unwrap(parse_int(\"x\"))"),
+            Err("invalid digit found in string".to_string())
+        );
+    }
+}
@@ -0,0 +1,230 @@
+// Standard libraries
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// Internal modules
+use super::arena::Ast;
+use super::ast::Node;
+use super::evaluator::EvalResult;
+use super::parser::Parser;
+use crate::passes::resolver::Resolver;
+use crate::passes::type_checker::TypeChecker;
+
+// Modules
+/*
+    Every file is a module with its own global variables and functions:
+
+        import "lib/geometry.wf" as geo     geo.area(2), geo.pi
+        from "lib/geometry.wf" import area  area(2)
+
+    The path of an import is relative to the file containing it, then to each of the
+    search paths in order. A module is parsed, resolved and type checked when it is
+    imported for the first time, errors abort the import. It runs once, later imports
+    of the same file get the same module. Importing a module that is still being loaded
+    is an error showing the cycle, e.g. `Import cycle: a.wf -> b.wf -> a.wf`.
+
+    Only the names listed by `export` statements at the top level of a module can be
+    imported, a module without exports has none. Structs and enums are not part of the
+    namespace, their declarations are shared by every module.
+*/
+pub struct ModuleLoader {
+    // Directories searched for imports after the directory of the importing file
+    pub search_paths: Vec<PathBuf>,
+    // File of every module by id, None if it was not read from a file. The script being
+    // run is module 0.
+    files: Vec<Option<PathBuf>>,
+    // Modules that have been run, by file
+    loaded: HashMap<PathBuf, Rc<Module>>,
+    // Files being loaded with the path they were imported as, outermost first
+    loading: Vec<(PathBuf, String)>,
+    // Functions of the prelude, modules are checked knowing they exist
+    pub prelude: Vec<String>,
+}
+
+// Value of an imported module, e.g. `geo` in `import "lib/geometry.wf" as geo`.
+/*
+    Exported values are taken once the module has run. Assignments in functions only
+    create locals, so the globals of a module can not change afterwards.
+*/
+pub struct Module {
+    // Path the module was first imported as
+    pub name: String,
+    pub exports: Vec<(String, EvalResult)>,
+}
+
+// Parsed and checked module, ready to run.
+pub struct ModuleSource {
+    pub ast: Rc<Ast>,
+    // Names of the global variable slots, see `Evaluator::declare_globals`
+    pub globals: Vec<String>,
+    // Names of the export statements
+    pub exports: Vec<String>,
+}
+
+// Public methods
+impl ModuleLoader {
+    pub fn new() -> Self {
+        ModuleLoader {
+            search_paths: Vec::new(),
+            files: vec![None],
+            loaded: HashMap::new(),
+            loading: Vec::new(),
+            prelude: Vec::new(),
+        }
+    }
+
+    // File of the script being run, imports of the script are relative to it. Set before
+    // the script runs.
+    pub fn set_script(&mut self, file: &Path) {
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        let name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.files[0] = Some(file.clone());
+        // Importing the script from one of its modules is a cycle as well
+        self.loading = vec![(file, name)];
+    }
+
+    // Find the file of an import made by the given module.
+    pub fn find(&self, path: &str, importer: usize) -> Result<PathBuf, Box<dyn error::Error>> {
+        let path = Path::new(path);
+        let directory = self
+            .files
+            .get(importer)
+            .and_then(|file| file.as_ref()?.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let candidates = std::iter::once(directory).chain(self.search_paths.iter().cloned());
+        for candidate in candidates.map(|directory| directory.join(path)) {
+            if candidate.is_file() {
+                return Ok(candidate.canonicalize()?);
+            }
+        }
+        Err(format!("Could not find module {0}", path.display()).into())
+    }
+
+    // Module of the file if it has been run already.
+    pub fn get(&self, file: &Path) -> Option<Rc<Module>> {
+        self.loaded.get(file).cloned()
+    }
+
+    // Start loading a file, fails if it is being loaded already.
+    pub fn enter(&mut self, file: &Path, name: &str) -> Result<(), Box<dyn error::Error>> {
+        if let Some(start) = self.loading.iter().position(|(loading, _)| loading == file) {
+            let mut names: Vec<&str> = self.loading[start..]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            names.push(name);
+            return Err(format!("Import cycle: {0}", names.join(" -> ")).into());
+        }
+        self.loading.push((file.to_path_buf(), name.to_string()));
+        Ok(())
+    }
+
+    // Id of a new module, read from the given file if any.
+    pub fn register(&mut self, file: Option<&Path>) -> usize {
+        self.files.push(file.map(Path::to_path_buf));
+        self.files.len() - 1
+    }
+
+    // Stop loading the innermost file, it is cached if it ran.
+    pub fn leave(
+        &mut self,
+        module: Result<Rc<Module>, Box<dyn error::Error>>,
+    ) -> Result<Rc<Module>, Box<dyn error::Error>> {
+        let (file, _) = match self.loading.pop() {
+            Some(loading) => loading,
+            None => return Err("No module is being loaded".into()),
+        };
+        let module = module?;
+        self.loaded.insert(file, module.clone());
+        Ok(module)
+    }
+
+    // Read, parse and check the file of a module.
+    pub fn read(&self, file: &Path, name: &str) -> Result<ModuleSource, Box<dyn error::Error>> {
+        let source = fs::read_to_string(file)
+            .map_err(|error| format!("Could not read module {0}: {1}", name, error))?;
+        self.compile(&source, name)
+    }
+
+    // Parse, resolve and type check the source of a module. Warnings are dropped, they
+    // are for the author of the module.
+    pub fn compile(&self, source: &str, name: &str) -> Result<ModuleSource, Box<dyn error::Error>> {
+        let parsed = Parser::new(source).and_then(|mut parser| {
+            let nodes = parser.parse()?;
+            Ok((nodes, std::mem::take(&mut parser.statement_lines)))
+        });
+        let (nodes, lines) = match parsed {
+            Ok(parsed) => parsed,
+            Err(error) => return Err(format!("{0}: {1}", name, error).into()),
+        };
+        let mut resolver = Resolver::new()
+            .with_lines(lines.clone())
+            .with_functions(self.prelude.clone());
+        let nodes = resolver.resolve(nodes);
+        let mut checker = TypeChecker::new()
+            .with_lines(lines.clone())
+            .with_functions(self.prelude.clone());
+        checker.check(&nodes);
+        let mut diagnostics = std::mem::take(&mut resolver.diagnostics);
+        diagnostics.extend(checker.diagnostics);
+        diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        let errors: Vec<String> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .map(|diagnostic| format!("{0}: {1}", name, diagnostic))
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors.join("\n").into());
+        }
+        let exports = nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::ExportStatement(names) => Some(names.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        Ok(ModuleSource {
+            ast: Rc::new(Ast::with_lines(nodes, lines)),
+            globals: resolver.globals,
+            exports,
+        })
+    }
+}
+
+impl Default for ModuleLoader {
+    fn default() -> Self {
+        ModuleLoader::new()
+    }
+}
+
+impl Module {
+    // Value of an exported name, e.g. `geo.area`.
+    pub fn get(&self, name: &str) -> Result<EvalResult, Box<dyn error::Error>> {
+        match self.exports.iter().find(|(export, _)| export == name) {
+            Some((_, value)) => Ok(value.clone()),
+            None => Err(format!("Module {0} does not export {1}", self.name, name).into()),
+        }
+    }
+}
+
+impl fmt::Debug for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "module {0}", self.name)
+    }
+}
+
+// Modules are only equal to themselves.
+impl PartialEq for Module {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
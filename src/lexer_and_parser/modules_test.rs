@@ -0,0 +1,212 @@
+// Tests for importing modules.
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+
+    use crate::lexer_and_parser::arena::Ast;
+    use crate::lexer_and_parser::builtins::Builtin;
+    use crate::lexer_and_parser::evaluator::{EvalResult, Evaluator};
+    use crate::lexer_and_parser::modules::ModuleLoader;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::passes::resolver::Resolver;
+
+    const GEOMETRY: &str = "pi = 3
+fn square(x) {
+    return x * x
+}
+fn area(r) {
+    return pi * square(r)
+}
+hidden = 1
+export area, pi
+";
+
+    // Write the files into a new directory, named after the test.
+    fn directory(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("wistful-{0}-{1}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        for (name, source) in files {
+            let file = directory.join(name);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, source).unwrap();
+        }
+        directory
+    }
+
+    // Run main.wf of the directory like the interpreter does.
+    fn run(directory: &Path, evaluator: &mut Evaluator) -> Result<EvalResult, String> {
+        let file = directory.join("main.wf");
+        let source = fs::read_to_string(&file).unwrap();
+        evaluator.modules.set_script(&file);
+        let mut parser = Parser::new(&source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let lines = std::mem::take(&mut parser.statement_lines);
+        let mut resolver = Resolver::new().with_lines(lines.clone());
+        let nodes = resolver.resolve(nodes);
+        assert!(!resolver.has_errors(), "{0:?}", resolver.diagnostics);
+        evaluator.declare_globals(&resolver.globals).unwrap();
+        evaluator
+            .run(&Rc::new(Ast::with_lines(nodes, lines)))
+            .map_err(|e| format!("{:#}", e))
+    }
+
+    fn run_main(test: &str, main: &str, files: &[(&str, &str)]) -> Result<String, String> {
+        let mut files = files.to_vec();
+        files.push(("main.wf", main));
+        let directory = directory(test, &files);
+        let result = run(&directory, &mut Evaluator::new(None));
+        let _ = fs::remove_dir_all(&directory);
+        result.map(|result| Builtin::text(&result))
+    }
+
+    #[test]
+    fn test_imports() {
+        let files = [("lib/geometry.wf", GEOMETRY)];
+        let sources = [
+            ("import \"lib/geometry.wf\" as geo\ngeo.area(2)", "12"),
+            ("import \"lib/geometry.wf\" as geo\ngeo.pi", "3"),
+            (
+                "from \"lib/geometry.wf\" import area, pi\narea(1) + pi",
+                "6",
+            ),
+            // Imported functions keep using the globals of their module
+            (
+                "pi = 100\nfrom \"lib/geometry.wf\" import area\narea(1)",
+                "3",
+            ),
+            (
+                "fn f() {\n    import \"lib/geometry.wf\" as geo\n    return geo.pi\n}\nf()",
+                "3",
+            ),
+            (
+                "import \"lib/geometry.wf\" as geo\ngeo",
+                "module lib/geometry.wf",
+            ),
+        ];
+        for (main, expected) in sources.iter() {
+            let result = run_main("imports", main, &files);
+            assert_eq!(result, Ok(expected.to_string()), "{}", main);
+        }
+    }
+
+    #[test]
+    fn test_visibility() {
+        let files = [
+            ("geometry.wf", GEOMETRY),
+            ("empty.wf", "x = 1\nfn f() {\n    return x\n}\n"),
+        ];
+        let sources = [
+            (
+                "import \"geometry.wf\" as geo\ngeo.hidden",
+                "Module geometry.wf does not export hidden",
+            ),
+            (
+                "import \"geometry.wf\" as geo\ngeo.square(2)",
+                "Module geometry.wf does not export square",
+            ),
+            (
+                "from \"geometry.wf\" import hidden",
+                "Module geometry.wf does not export hidden",
+            ),
+            (
+                "from \"empty.wf\" import f",
+                "Module empty.wf does not export f",
+            ),
+        ];
+        for (main, expected) in sources.iter() {
+            let result = run_main("visibility", main, &files)
+                .map_err(|e| e.lines().next().unwrap().to_string());
+            assert_eq!(
+                result,
+                Err(format!("RuntimeError: {0}", expected)),
+                "{}",
+                main
+            );
+        }
+    }
+
+    #[test]
+    fn test_caching() {
+        let main = "import \"geometry.wf\" as a\nfrom \"geometry.wf\" import area\nimport \"./geometry.wf\" as b";
+        let directory = directory("caching", &[("geometry.wf", GEOMETRY), ("main.wf", main)]);
+        let mut evaluator = Evaluator::new(None);
+        run(&directory, &mut evaluator).unwrap();
+        let _ = fs::remove_dir_all(&directory);
+        match (evaluator.get_variable("a"), evaluator.get_variable("b")) {
+            (Some(EvalResult::Module(a)), Some(EvalResult::Module(b))) => {
+                assert!(Rc::ptr_eq(&a, &b))
+            }
+            other => panic!("Expected modules, got {0:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cycles() {
+        let files = [
+            (
+                "a.wf",
+                "from \"b.wf\" import g\nfn f() {\n    return 1\n}\nexport f\n",
+            ),
+            (
+                "b.wf",
+                "import \"a.wf\" as a\nfn g() {\n    return 2\n}\nexport g\n",
+            ),
+            ("c.wf", "import \"main.wf\" as main\n"),
+        ];
+        let result = run_main("cycles", "import \"a.wf\" as a", &files);
+        assert!(result
+            .unwrap_err()
+            .starts_with("RuntimeError: Import cycle: a.wf -> b.wf -> a.wf"));
+        // The script is being loaded as well
+        let result = run_main("cycles", "import \"c.wf\" as c", &files);
+        assert!(result
+            .unwrap_err()
+            .starts_with("RuntimeError: Import cycle: main.wf -> c.wf -> main.wf"));
+    }
+
+    #[test]
+    fn test_search_paths() {
+        let directory = directory("search", &[("lib/geometry.wf", GEOMETRY), ("main.wf", "")]);
+        let mut loader = ModuleLoader::new();
+        loader.set_script(&directory.join("main.wf"));
+        assert_eq!(
+            loader.find("geometry.wf", 0).map_err(|e| e.to_string()),
+            Err("Could not find module geometry.wf".to_string())
+        );
+        loader.search_paths.push(directory.join("lib"));
+        let file = loader.find("geometry.wf", 0).unwrap();
+        assert_eq!(
+            file,
+            directory.join("lib/geometry.wf").canonicalize().unwrap()
+        );
+        // The directory of the importer comes first
+        assert_eq!(loader.find("lib/geometry.wf", 0).unwrap(), file);
+        let _ = fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn test_module_errors() {
+        let files = [
+            ("broken.wf", "x = y + 1\nexport x\n"),
+            ("missing.wf", "export nothing\n"),
+            ("failing.wf", "fn average(items, count) {\n    return items / count\n}\nx = average(1, 0)\nexport x\n"),
+        ];
+        let result = run_main("errors", "import \"broken.wf\" as broken", &files);
+        assert!(result
+            .unwrap_err()
+            .contains("broken.wf: line 1: error: Undefined variable y"));
+        let result = run_main("errors", "import \"missing.wf\" as missing", &files);
+        assert!(result
+            .unwrap_err()
+            .contains("Can not export nothing, it is never defined"));
+        // Runtime errors show the module in their trace
+        let result = run_main("errors", "x = 1\nimport \"failing.wf\" as failing", &files);
+        assert_eq!(
+            result,
+            Err("DivisionByZero: Division by zero\n  in average at line 2\n  in module failing.wf at line 4\n  at line 2".to_string())
+        );
+    }
+}
@@ -0,0 +1,938 @@
+// Standard libraries
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+// Upper bound for the size (in bits) of exact power results. Anything larger
+// than this is almost certainly a mistake and would take ages to compute.
+const MAX_EXACT_POW_BITS: u64 = 1 << 20;
+
+// Operands with fewer digits are multiplied digit by digit, which is faster for them.
+const KARATSUBA_THRESHOLD: usize = 32;
+
+// Error of dividing an exact number by zero, the evaluator reports it with its own kind.
+pub const DIVISION_BY_ZERO: &str = "Division by zero";
+
+// Arbitrary precision signed integer.
+/*
+    Magnitude is stored as little-endian base 2^32 digits without trailing zeros,
+    so zero is represented by an empty digit vector and is never negative.
+    Keeping the representation canonical lets us derive equality.
+*/
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+// Exact fraction of two big integers.
+/*
+    Always kept in lowest terms with a positive denominator, which again makes the
+    representation canonical.
+*/
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+// Numeric value produced by the evaluator.
+/*
+    Values are promoted automatically: Integer -> BigInteger when an operation overflows,
+    and Integer/BigInteger -> Rational when a division is not exact. Float is only produced
+    when one of the operands already is a float, or when the exact result can not be
+    represented (e.g. a fractional exponent).
+
+    Exact variants are kept normalized: a BigInteger never fits into an i64 and a Rational
+    never has a denominator of one.
+*/
+#[derive(Clone)]
+pub enum Number {
+    Integer(i64),
+    BigInteger(BigInt),
+    Rational(Rational),
+    Float(f64),
+}
+
+// Public methods
+impl BigInt {
+    pub fn zero() -> Self {
+        BigInt {
+            negative: false,
+            digits: Vec::new(),
+        }
+    }
+
+    pub fn one() -> Self {
+        BigInt::from_i64(1)
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        let mut magnitude = value.unsigned_abs();
+        let mut digits = Vec::new();
+        while magnitude > 0 {
+            digits.push(magnitude as u32);
+            magnitude >>= 32;
+        }
+        BigInt {
+            negative: value < 0,
+            digits,
+        }
+    }
+
+    // Convert an integral float into a big integer. Returns None for NaN, infinities and
+    // values with a fractional part.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() || value.fract() != 0.0 {
+            return None;
+        }
+        let negative = value < 0.0;
+        let mut magnitude = value.abs();
+        let mut digits = Vec::new();
+        // Peel off base 2^32 digits, every step is exact since value is integral.
+        while magnitude >= 1.0 {
+            let digit = magnitude % 4294967296.0;
+            digits.push(digit as u32);
+            magnitude = ((magnitude - digit) / 4294967296.0).floor();
+        }
+        Some(BigInt::from_parts(negative, digits))
+    }
+
+    // Construct a big integer from its sign and little-endian base 2^32 digits.
+    pub fn from_digits(negative: bool, digits: Vec<u32>) -> Self {
+        BigInt::from_parts(negative, digits)
+    }
+
+    // Little-endian base 2^32 digits of the magnitude.
+    pub fn digits(&self) -> &[u32] {
+        &self.digits
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt {
+            negative: false,
+            digits: self.digits.clone(),
+        }
+    }
+
+    // Returns the value as an i64 if it fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+        let mut magnitude: u64 = 0;
+        for (i, digit) in self.digits.iter().enumerate() {
+            magnitude |= (*digit as u64) << (32 * i);
+        }
+        if self.negative {
+            if magnitude <= i64::MAX as u64 + 1 {
+                Some((magnitude as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else if magnitude <= i64::MAX as u64 {
+            Some(magnitude as i64)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let mut value = 0.0;
+        for digit in self.digits.iter().rev() {
+            value = value * 4294967296.0 + *digit as f64;
+        }
+        if self.negative {
+            -value
+        } else {
+            value
+        }
+    }
+
+    // Number of significant bits in the magnitude.
+    pub fn bits(&self) -> u64 {
+        match self.digits.last() {
+            Some(top) => (self.digits.len() as u64 - 1) * 32 + (32 - top.leading_zeros() as u64),
+            None => 0,
+        }
+    }
+
+    // Truncating division, remainder has the sign of the dividend.
+    // Returns None when dividing by zero.
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitude(&self.digits, &divisor.digits);
+        Some((
+            BigInt::from_parts(self.negative != divisor.negative, quotient),
+            BigInt::from_parts(self.negative, remainder),
+        ))
+    }
+
+    // Binary gcd, only shifts and subtractions. The result is never negative.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        if self.is_zero() {
+            return other.abs();
+        }
+        if other.is_zero() {
+            return self.abs();
+        }
+        let (mut a, a_zeros) = without_trailing_zeros(&self.digits);
+        let (mut b, b_zeros) = without_trailing_zeros(&other.digits);
+        // Both odd from here on, their difference is even
+        loop {
+            if cmp_magnitude(&a, &b) == Ordering::Greater {
+                std::mem::swap(&mut a, &mut b);
+            }
+            sub_assign_magnitude(&mut b, &a);
+            if b.is_empty() {
+                break;
+            }
+            b = without_trailing_zeros(&b).0;
+        }
+        BigInt::from_parts(false, shift_left(&a, a_zeros.min(b_zeros)))
+    }
+
+    pub fn pow(&self, mut exponent: u32) -> BigInt {
+        let mut result = BigInt::one();
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            exponent >>= 1;
+            if exponent > 0 {
+                base = &base * &base;
+            }
+        }
+        result
+    }
+}
+
+// Private methods
+impl BigInt {
+    // Construct a big integer and make sure the representation is canonical.
+    fn from_parts(negative: bool, mut digits: Vec<u32>) -> BigInt {
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigInt {
+            negative: negative && !digits.is_empty(),
+            digits,
+        }
+    }
+}
+
+// Magnitude helpers operating on little-endian digit slices.
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+    Ordering::Equal
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+// Expects |a| >= |b|
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter().enumerate() {
+        let mut difference = *digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        if difference < 0 {
+            difference += 1 << 32;
+            borrow = 1;
+        } else {
+            borrow = 0;
+        }
+        result.push(difference as u32);
+    }
+    result
+}
+
+// Product of two magnitudes, the result may have zeros on top.
+/*
+    Large operands are multiplied with Karatsuba's method: with a = a1 * B + a0 and
+    b = b1 * B + b0, the product needs a1 * b1, a0 * b0 and (a1 + a0) * (b1 + b0), three
+    multiplications of half the size instead of four. Operands of very different length
+    are multiplied in pieces of the shorter length.
+*/
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (short, long) = match a.len() <= b.len() {
+        true => (a, b),
+        false => (b, a),
+    };
+    if short.len() < KARATSUBA_THRESHOLD {
+        return mul_schoolbook(a, b);
+    }
+    let mut result = vec![0u32; a.len() + b.len()];
+    if long.len() >= 2 * short.len() {
+        for (i, piece) in long.chunks(short.len()).enumerate() {
+            add_at(&mut result, &mul_magnitude(piece, short), i * short.len());
+        }
+        return result;
+    }
+    // Both operands are longer than the split
+    let split = long.len() / 2;
+    let (a0, a1) = a.split_at(split);
+    let (b0, b1) = b.split_at(split);
+    let low = mul_magnitude(a0, b0);
+    let high = mul_magnitude(a1, b1);
+    let middle = mul_magnitude(&add_magnitude(a0, a1), &add_magnitude(b0, b1));
+    let middle = sub_magnitude(&sub_magnitude(&middle, &low), &high);
+    add_at(&mut result, &low, 0);
+    add_at(&mut result, &middle, split);
+    add_at(&mut result, &high, 2 * split);
+    result
+}
+
+// result += value * 2^(32 * offset). The sum has to fit, only zeros may stick out.
+fn add_at(result: &mut [u32], value: &[u32], offset: usize) {
+    let mut carry = 0u64;
+    let mut i = offset;
+    for digit in value {
+        if i == result.len() {
+            break;
+        }
+        let sum = result[i] as u64 + *digit as u64 + carry;
+        result[i] = sum as u32;
+        carry = sum >> 32;
+        i += 1;
+    }
+    while carry > 0 && i < result.len() {
+        let sum = result[i] as u64 + carry;
+        result[i] = sum as u32;
+        carry = sum >> 32;
+        i += 1;
+    }
+}
+
+fn mul_schoolbook(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let product = *x as u64 * *y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    result
+}
+
+// Long division on whole digits, Knuth's algorithm D. Divisor must not be zero.
+/*
+    Both operands are shifted so the top digit of the divisor has its highest bit set.
+    Each quotient digit is then estimated from the top two digits of the remainder and
+    the top digit of the divisor, and is at most two too large: the estimate is checked
+    against the second digit of the divisor and, rarely, corrected by adding the divisor
+    back after the subtraction went negative.
+*/
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    // Fast path for single digit divisors, used heavily when printing numbers.
+    if b.len() == 1 {
+        let divisor = b[0] as u64;
+        let mut quotient = vec![0u32; a.len()];
+        let mut remainder = 0u64;
+        for i in (0..a.len()).rev() {
+            let current = (remainder << 32) | a[i] as u64;
+            quotient[i] = (current / divisor) as u32;
+            remainder = current % divisor;
+        }
+        return (quotient, vec![remainder as u32]);
+    }
+    if cmp_magnitude(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    let n = b.len();
+    let shift = b[n - 1].leading_zeros();
+    let divisor = shl_magnitude(b, shift);
+    let mut remainder = shl_magnitude(a, shift);
+    remainder.resize(a.len() + 1, 0);
+    let mut quotient = vec![0u32; a.len() - n + 1];
+    let (top, second) = (divisor[n - 1] as u64, divisor[n - 2] as u64);
+    for j in (0..quotient.len()).rev() {
+        let current = ((remainder[j + n] as u64) << 32) | remainder[j + n - 1] as u64;
+        let mut estimate = current / top;
+        let mut rest = current % top;
+        while estimate > u32::MAX as u64
+            || estimate * second > ((rest << 32) | remainder[j + n - 2] as u64)
+        {
+            estimate -= 1;
+            rest += top;
+            if rest > u32::MAX as u64 {
+                break;
+            }
+        }
+        // remainder[j..] -= estimate * divisor
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let product = estimate * divisor[i] as u64 + carry;
+            carry = product >> 32;
+            let difference = remainder[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+            remainder[i + j] = difference as u32;
+            borrow = (difference < 0) as i64;
+        }
+        let difference = remainder[j + n] as i64 - borrow - carry as i64;
+        remainder[j + n] = difference as u32;
+        // The estimate was one too large, add the divisor back
+        if difference < 0 {
+            estimate -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = remainder[i + j] as u64 + divisor[i] as u64 + carry;
+                remainder[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            remainder[j + n] = remainder[j + n].wrapping_add(carry as u32);
+        }
+        quotient[j] = estimate as u32;
+    }
+    remainder.truncate(n);
+    let mut remainder = shr_magnitude(&remainder, shift);
+    while remainder.last() == Some(&0) {
+        remainder.pop();
+    }
+    (quotient, remainder)
+}
+
+// Magnitude with its trailing zero bits shifted out, and how many there were.
+fn without_trailing_zeros(a: &[u32]) -> (Vec<u32>, u64) {
+    let words = a.iter().take_while(|digit| **digit == 0).count();
+    let bits = a[words].trailing_zeros();
+    let mut result = shr_magnitude(&a[words..], bits);
+    while result.last() == Some(&0) {
+        result.pop();
+    }
+    (result, words as u64 * 32 + bits as u64)
+}
+
+// Magnitude shifted left by any number of bits.
+fn shift_left(a: &[u32], bits: u64) -> Vec<u32> {
+    let mut result = vec![0u32; (bits / 32) as usize];
+    result.extend(shl_magnitude(a, (bits % 32) as u32));
+    result
+}
+
+// a -= b in place, without trailing zeros. Expects |a| >= |b|.
+fn sub_assign_magnitude(a: &mut Vec<u32>, b: &[u32]) {
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter_mut().enumerate() {
+        if i >= b.len() && borrow == 0 {
+            break;
+        }
+        let difference = *digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        *digit = difference as u32;
+        borrow = (difference < 0) as i64;
+    }
+    while a.last() == Some(&0) {
+        a.pop();
+    }
+}
+
+// Magnitude shifted left by less than a digit, with an extra digit if the top bits
+// carry over.
+fn shl_magnitude(a: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return a.to_vec();
+    }
+    let mut result = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u32;
+    for digit in a {
+        result.push((digit << shift) | carry);
+        carry = digit >> (32 - shift);
+    }
+    if carry > 0 {
+        result.push(carry);
+    }
+    result
+}
+
+// Magnitude shifted right by less than a digit.
+fn shr_magnitude(a: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return a.to_vec();
+    }
+    let mut result = vec![0u32; a.len()];
+    for i in 0..a.len() {
+        let high = a.get(i + 1).map_or(0, |digit| digit << (32 - shift));
+        result[i] = (a[i] >> shift) | high;
+    }
+    result
+}
+
+impl<'a> Add<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_magnitude(&self.digits, &other.digits));
+        }
+        // Different signs, subtract the smaller magnitude from the larger one.
+        match cmp_magnitude(&self.digits, &other.digits) {
+            Ordering::Less => {
+                BigInt::from_parts(other.negative, sub_magnitude(&other.digits, &self.digits))
+            }
+            _ => BigInt::from_parts(self.negative, sub_magnitude(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl<'a> Sub<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl<'a> Mul<&'a BigInt> for &'a BigInt {
+    type Output = BigInt;
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(
+            self.negative != other.negative,
+            mul_magnitude(&self.digits, &other.digits),
+        )
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.digits.clone())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.digits, &other.digits),
+            (true, true) => cmp_magnitude(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // Split into base 10^9 chunks, least significant first.
+        let mut chunks = Vec::new();
+        let mut magnitude = self.digits.clone();
+        while !magnitude.is_empty() {
+            let (quotient, remainder) = div_rem_magnitude(&magnitude, &[1_000_000_000]);
+            chunks.push(remainder[0]);
+            magnitude = quotient;
+            while magnitude.last() == Some(&0) {
+                magnitude.pop();
+            }
+        }
+        let mut output = String::new();
+        if self.negative {
+            output.push('-');
+        }
+        output += &chunks.pop().unwrap().to_string();
+        while let Some(chunk) = chunks.pop() {
+            output += &format!("{:09}", chunk);
+        }
+        write!(f, "{}", output)
+    }
+}
+
+impl fmt::Debug for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// Public methods
+impl Rational {
+    // Create a new fraction in lowest terms. Returns None if the denominator is zero.
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Option<Self> {
+        if denominator.is_zero() {
+            return None;
+        }
+        let divisor = numerator.gcd(&denominator);
+        let (mut numerator, _) = numerator.div_rem(&divisor)?;
+        let (mut denominator, _) = denominator.div_rem(&divisor)?;
+        if denominator.is_negative() {
+            numerator = -&numerator;
+            denominator = -&denominator;
+        }
+        Some(Rational {
+            numerator,
+            denominator,
+        })
+    }
+
+    pub fn from_integer(value: BigInt) -> Self {
+        Rational {
+            numerator: value,
+            denominator: BigInt::one(),
+        }
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator.to_f64() / self.denominator.to_f64()
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+impl fmt::Debug for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+// Public methods
+impl Number {
+    // Create a number from a literal, e.g. `42` or `-0.5`. Literals with a decimal point
+    // are floats, others are exact integers of any size. None if it is not a number.
+    pub fn from_decimal(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return None;
+        }
+        if digits.contains('.') {
+            return text.parse::<f64>().ok().map(Number::Float);
+        }
+        if let Ok(integer) = text.parse::<i64>() {
+            return Some(Number::Integer(integer));
+        }
+        let ten = BigInt::from_i64(10);
+        let mut value = BigInt::zero();
+        for digit in digits.chars().filter_map(|c| c.to_digit(10)) {
+            value = &(&value * &ten) + &BigInt::from_i64(digit as i64);
+        }
+        Some(Number::from_big(if negative { -&value } else { value }))
+    }
+
+    // Text of the number as a literal that reads back as the same number. Floats keep
+    // their decimal point, `1.0` is not the integer 1.
+    pub fn literal(&self) -> String {
+        match self {
+            Number::Float(f) if f.is_finite() && f.fract() == 0.0 => format!("{0:.1}", f),
+            number => number.to_string(),
+        }
+    }
+
+    // Normalized number from a big integer.
+    pub fn from_big(value: BigInt) -> Number {
+        match value.to_i64() {
+            Some(i) => Number::Integer(i),
+            None => Number::BigInteger(value),
+        }
+    }
+
+    // Normalized number from a fraction.
+    pub fn from_rational(value: Rational) -> Number {
+        if value.denominator == BigInt::one() {
+            Number::from_big(value.numerator)
+        } else {
+            Number::Rational(value)
+        }
+    }
+
+    // Conversion to a float on demand. Exact values may lose precision here.
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Integer(i) => *i as f64,
+            Number::BigInteger(b) => b.to_f64(),
+            Number::Rational(r) => r.to_f64(),
+            Number::Float(f) => *f,
+        }
+    }
+
+    pub fn to_float(&self) -> Number {
+        Number::Float(self.to_f64())
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Number::Float(_))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(i) => *i == 0,
+            Number::Float(f) => *f == 0.0,
+            // Normalized big integers and rationals are never zero
+            _ => false,
+        }
+    }
+
+    // Division. Dividing exact values by an exact zero is an error, floats follow IEEE 754.
+    pub fn divide(&self, divisor: &Number) -> Result<Number, String> {
+        // i64::MIN / -1 overflows, it is left to the exact path below
+        if let (Number::Integer(a), Number::Integer(b)) = (self, divisor) {
+            if a.checked_rem(*b) == Some(0) {
+                if let Some(quotient) = a.checked_div(*b) {
+                    return Ok(Number::Integer(quotient));
+                }
+            }
+        }
+        if !self.is_exact() || !divisor.is_exact() {
+            return Ok(Number::Float(self.to_f64() / divisor.to_f64()));
+        }
+        if divisor.is_zero() {
+            return Err(DIVISION_BY_ZERO.to_string());
+        }
+        let (a, b) = (self.to_rational(), divisor.to_rational());
+        let numerator = &a.numerator * &b.denominator;
+        let denominator = &a.denominator * &b.numerator;
+        Ok(Number::from_rational(
+            Rational::new(numerator, denominator).unwrap(),
+        ))
+    }
+
+    // Exponentiation. Integer exponents on exact bases give exact results.
+    pub fn pow(&self, exponent: &Number) -> Result<Number, String> {
+        let exponent_value = match exponent {
+            Number::Integer(e) if self.is_exact() => *e,
+            _ => return Ok(Number::Float(self.to_f64().powf(exponent.to_f64()))),
+        };
+        if exponent_value < 0 && self.is_zero() {
+            return Err(DIVISION_BY_ZERO.to_string());
+        }
+        let magnitude = exponent_value.unsigned_abs();
+        let base = self.to_rational();
+        let base_bits = base.numerator.bits().max(base.denominator.bits());
+        if magnitude > u32::MAX as u64
+            || (base_bits > 1 && (base_bits - 1).saturating_mul(magnitude) > MAX_EXACT_POW_BITS)
+        {
+            return Err(format!(
+                "Result of {0} ^ {1} is too large",
+                self, exponent_value
+            ));
+        }
+        let numerator = base.numerator.pow(magnitude as u32);
+        let denominator = base.denominator.pow(magnitude as u32);
+        let result = if exponent_value < 0 {
+            Rational::new(denominator, numerator)
+        } else {
+            Rational::new(numerator, denominator)
+        };
+        Ok(Number::from_rational(result.unwrap()))
+    }
+
+    // Text of the number with a fixed amount of decimals, rounding half to even like
+    // Rust does for floats. Exact numbers are written digit by digit, they may be far
+    // larger or more precise than any float.
+    pub fn decimals(&self, precision: usize) -> String {
+        let value = match self {
+            Number::Float(f) => return format!("{0:.1$}", f, precision),
+            exact => exact.to_rational(),
+        };
+        let scale = BigInt::from_i64(10).pow(precision as u32);
+        let scaled = &value.numerator.abs() * &scale;
+        let (mut digits, remainder) = scaled.div_rem(&value.denominator).unwrap();
+        let twice = &remainder * &BigInt::from_i64(2);
+        let odd = digits.digits().first().is_some_and(|digit| digit & 1 == 1);
+        match twice.cmp(&value.denominator) {
+            Ordering::Greater => digits = &digits + &BigInt::one(),
+            Ordering::Equal if odd => digits = &digits + &BigInt::one(),
+            _ => (),
+        }
+        let digits = format!("{0:0>1$}", digits.to_string(), precision + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - precision);
+        let sign = if value.numerator.is_negative() { "-" } else { "" };
+        match precision {
+            0 => format!("{0}{1}", sign, whole),
+            _ => format!("{0}{1}.{2}", sign, whole, fraction),
+        }
+    }
+}
+
+// Private methods
+impl Number {
+    // Every exact number as a fraction. Must not be called for floats.
+    fn to_rational(&self) -> Rational {
+        match self {
+            Number::Integer(i) => Rational::from_integer(BigInt::from_i64(*i)),
+            Number::BigInteger(b) => Rational::from_integer(b.clone()),
+            Number::Rational(r) => r.clone(),
+            Number::Float(f) => {
+                Rational::from_integer(BigInt::from_f64(f.trunc()).unwrap_or_else(BigInt::zero))
+            }
+        }
+    }
+
+    // Shared implementation for +, - and *. Integers use checked arithmetic and are
+    // promoted to rationals (and from there to big integers) on overflow.
+    fn combine(
+        &self,
+        other: &Number,
+        checked: fn(i64, i64) -> Option<i64>,
+        float: fn(f64, f64) -> f64,
+        exact: fn(&Rational, &Rational) -> Rational,
+    ) -> Number {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) if checked(*a, *b).is_some() => {
+                Number::Integer(checked(*a, *b).unwrap())
+            }
+            (Number::Float(_), _) | (_, Number::Float(_)) => {
+                Number::Float(float(self.to_f64(), other.to_f64()))
+            }
+            _ => Number::from_rational(exact(&self.to_rational(), &other.to_rational())),
+        }
+    }
+}
+
+impl<'a> Add<&'a Number> for &'a Number {
+    type Output = Number;
+    fn add(self, other: &Number) -> Number {
+        self.combine(
+            other,
+            i64::checked_add,
+            |a, b| a + b,
+            |a, b| {
+                let numerator = &(&a.numerator * &b.denominator) + &(&b.numerator * &a.denominator);
+                Rational::new(numerator, &a.denominator * &b.denominator).unwrap()
+            },
+        )
+    }
+}
+
+impl<'a> Sub<&'a Number> for &'a Number {
+    type Output = Number;
+    fn sub(self, other: &Number) -> Number {
+        self.combine(
+            other,
+            i64::checked_sub,
+            |a, b| a - b,
+            |a, b| {
+                let numerator = &(&a.numerator * &b.denominator) - &(&b.numerator * &a.denominator);
+                Rational::new(numerator, &a.denominator * &b.denominator).unwrap()
+            },
+        )
+    }
+}
+
+impl<'a> Mul<&'a Number> for &'a Number {
+    type Output = Number;
+    fn mul(self, other: &Number) -> Number {
+        self.combine(
+            other,
+            i64::checked_mul,
+            |a, b| a * b,
+            |a, b| {
+                Rational::new(&a.numerator * &b.numerator, &a.denominator * &b.denominator).unwrap()
+            },
+        )
+    }
+}
+
+impl Neg for &Number {
+    type Output = Number;
+    fn neg(self) -> Number {
+        match self {
+            Number::Integer(i) => match i.checked_neg() {
+                Some(negated) => Number::Integer(negated),
+                None => Number::BigInteger(-&BigInt::from_i64(*i)),
+            },
+            Number::BigInteger(b) => Number::from_big(-b),
+            Number::Rational(r) => Number::Rational(Rational {
+                numerator: -&r.numerator,
+                denominator: r.denominator.clone(),
+            }),
+            Number::Float(f) => Number::Float(-f),
+        }
+    }
+}
+
+// Numbers compare by value regardless of representation, so 1 == 1.0 holds.
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => Some(a.cmp(b)),
+            (Number::Float(_), _) | (_, Number::Float(_)) => {
+                self.to_f64().partial_cmp(&other.to_f64())
+            }
+            _ => {
+                let (a, b) = (self.to_rational(), other.to_rational());
+                Some((&a.numerator * &b.denominator).cmp(&(&b.numerator * &a.denominator)))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Integer(i) => write!(f, "{}", i),
+            Number::BigInteger(b) => write!(f, "{}", b),
+            Number::Rational(r) => write!(f, "{}", r),
+            Number::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl fmt::Debug for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Integer(i) => write!(f, "Integer({})", i),
+            Number::BigInteger(b) => write!(f, "BigInteger({})", b),
+            Number::Rational(r) => write!(f, "Rational({})", r),
+            Number::Float(x) => write!(f, "Float({:?})", x),
+        }
+    }
+}
@@ -1,15 +1,43 @@
+// Parse functions return explicitly from every branch.
+#![allow(clippy::needless_return)]
+
 use std::fmt;
-use crate::Node::ConditionExpression;
 
 // Internal uses
-use super::ast::Node;
+use super::ast::{BlockLines, CatchClause, Comment, MatchArm, Node, Pattern, Type};
+use super::builtins::builtin_variant;
+use super::numeric::Number;
 use super::token::{Keyword, Precedence, Token};
-use super::tokenizer::Tokenizer;
+use super::tokenizer::{Span, Tokenizer};
+
+// Parameters of a function, their annotations and the return type
+type Signature = (Vec<String>, Vec<Option<Type>>, Option<Type>);
 
 pub struct Parser<'a> {
     // input to be parsed
     pub tokenizer: Tokenizer<'a>,
     current_token: Token,
+    // Whether a line break was skipped before the current token
+    line_break: bool,
+    // Source line of the current token
+    line: usize,
+    // Position of the current token and of the one before it
+    span: Span,
+    previous: Span,
+    // Column after the last token of every line with tokens, by line
+    line_ends: Vec<(usize, usize)>,
+    // Starting line of every parsed statement, in the order they appear in the source.
+    // Used for the debug line table of compiled programs.
+    pub statement_lines: Vec<usize>,
+    // Part of every parsed statement on its starting line, in the same order
+    pub statement_spans: Vec<Span>,
+    // Comments in the order they appear in the source
+    pub comments: Vec<Comment>,
+    // Braces of every block that has statements, in the order the blocks end
+    pub blocks: Vec<BlockLines>,
+    // Whether `Name {` starts a struct value. Not in conditions, where the brace opens
+    // the block, e.g. `if p == origin { ... }`.
+    struct_literals: bool,
 }
 
 // Public methods
@@ -18,25 +46,43 @@ pub struct Parser<'a> {
 impl<'a> Parser<'a> {
     // Create a new instance of Parser
     pub fn new(expr: &'a str) -> Result<Self, ParseError> {
-        let mut lexer = Tokenizer::new(expr);
-        let cur_token = match lexer.next() {
-            Some(token) => token,
-            None => return Err(ParseError::InvalidOperator("Invalid character".into())),
+        let mut parser = Parser {
+            tokenizer: Tokenizer::new(expr),
+            current_token: Token::Newline,
+            line_break: false,
+            line: 0,
+            span: Span {
+                line: 0,
+                column: 0,
+                length: 0,
+            },
+            previous: Span {
+                line: 0,
+                column: 0,
+                length: 0,
+            },
+            line_ends: Vec::new(),
+            statement_lines: Vec::new(),
+            statement_spans: Vec::new(),
+            comments: Vec::new(),
+            blocks: Vec::new(),
+            struct_literals: true,
         };
-        Ok(Parser {
-            tokenizer: lexer,
-            current_token: cur_token,
-        })
+        // Skip leading whitespace and comments
+        parser.get_next_token()?;
+        Ok(parser)
     }
 
     // Method in the public interface for parsing the expression
     pub fn parse(&mut self) -> Result<Vec<Node>, ParseError> {
-        while self.current_token == Token::Whitespace {
+        while self.current_token == Token::Whitespace || self.current_token == Token::Newline {
             self.get_next_token()?;
         }
         let mut nodes = Vec::new();
         while self.current_token != Token::EOF {
+            let statement = self.start_statement();
             let ast = self.generate_ast(Precedence::Default)?;
+            self.end_statement(statement);
             nodes.push(ast);
         }
         Ok(nodes)
@@ -46,17 +92,59 @@ impl<'a> Parser<'a> {
 // Private methods
 impl<'a> Parser<'a> {
     // Move on to the next token to be parsed.
+    // Whitespace is skipped, line breaks are only remembered in `line_break`.
+    // Comments are collected to `comments`.
     fn get_next_token(&mut self) -> Result<(), ParseError> {
-        let next_token = match self.tokenizer.next() {
-            Some(token) => token,
-            None => return Err(ParseError::InvalidOperator("Invalid character".into())),
-        };
-        if next_token == Token::Whitespace {
-            self.get_next_token()?;
-        } else {
-            self.current_token = next_token;
+        self.line_break = false;
+        self.previous = self.span;
+        loop {
+            let (next_token, span) = match self.tokenizer.next_spanned() {
+                Some(spanned) => spanned,
+                None => return Err(ParseError::InvalidOperator("Invalid character".into())),
+            };
+            match next_token {
+                Token::Whitespace => {}
+                Token::Newline => self.line_break = true,
+                Token::Comment(text) => self.comments.push(Comment {
+                    line: self.tokenizer.line,
+                    text,
+                    // The previous token is on the same line
+                    trailing: self.line == self.tokenizer.line,
+                }),
+                _ => {
+                    if span.line != self.previous.line {
+                        let end = self.previous.column + self.previous.length;
+                        self.line_ends.push((self.previous.line, end));
+                    }
+                    self.current_token = next_token;
+                    self.line = self.tokenizer.line;
+                    self.span = span;
+                    return Ok(());
+                }
+            }
         }
-        Ok(())
+    }
+
+    // Record a statement starting at the current token. Returns its index.
+    fn start_statement(&mut self) -> usize {
+        self.statement_lines.push(self.line);
+        self.statement_spans.push(self.span);
+        self.statement_spans.len() - 1
+    }
+
+    // Extend the span of a statement that was just parsed to its last token on its
+    // starting line.
+    fn end_statement(&mut self, statement: usize) {
+        let span = self.statement_spans[statement];
+        let end = if self.previous.line == span.line {
+            self.previous.column + self.previous.length
+        } else {
+            match self.line_ends.binary_search_by_key(&span.line, |(line, _)| *line) {
+                Ok(index) => self.line_ends[index].1,
+                Err(_) => span.column + span.length,
+            }
+        };
+        self.statement_spans[statement].length = end - span.column;
     }
 
     fn generate_ast(&mut self, precedence: Precedence) -> Result<Node, ParseError> {
@@ -88,27 +176,58 @@ impl<'a> Parser<'a> {
             }
             Token::Num(i) => {
                 self.get_next_token()?;
-                // Check token to know if we're supposed to return an conditional expression
-                if self.check_token(Token::Equals)? {
-                    let r_expr = self.generate_ast(Precedence::Default)?;
-                    return Ok(ConditionExpression {
-                        l_expr: Box::new(Node::NumberExpression(i)),
-                        operator: Token::Equals,
-                        r_expr: Box::new(r_expr)
-                    })
+                let mut number = Node::NumberExpression(i);
+                // An identifier on the same line right after a number is its unit. e.g. `5 km`
+                if let Token::Identifier(_) = self.current_token {
+                    if !self.line_break {
+                        number = Node::QuantityExpression {
+                            value: Box::new(number),
+                            unit: self.parse_unit()?,
+                        };
+                    }
                 }
-                Ok(Node::NumberExpression(i))
+                Ok(number)
             }
             Token::LeftParenthese => {
                 self.get_next_token()?;
-                let l_expr = self.generate_ast(Precedence::Default)?;
+                let l_expr = self.with_struct_literals(|parser| parser.generate_ast(Precedence::Default))?;
                 // Make sure there is a pair for the opening parenthese. If not, return an error.
                 self.check_paren(Token::RightParenthese)?;
 
-                Ok(l_expr)
+                self.parse_postfix(l_expr)
+            }
+            Token::LeftBracket => {
+                self.get_next_token()?;
+                let elements = self.parse_list(Token::RightBracket)?;
+                self.parse_postfix(Node::ListExpression(elements))
             }
             Token::Identifier(string) => {
                 self.get_next_token()?;
+                // Variant of an enum, e.g. `Shape::Circle(1)`
+                if self.check_token(Token::DoubleColon)? {
+                    let variant = self.parse_variant_expression(string)?;
+                    return self.parse_postfix(variant);
+                }
+                // The empty Option, the other built-in variants are created by builtins
+                if let (Some((enum_name, variant)), "none") = (builtin_variant(&string), string.as_str()) {
+                    let none = Node::VariantExpression {
+                        enum_name: enum_name.to_string(),
+                        variant: variant.to_string(),
+                        arguments: Vec::new(),
+                    };
+                    return self.parse_postfix(none);
+                }
+                // Optional type annotation, only allowed when assigning. e.g. `x: num = 1`
+                let mut annotation = None;
+                if self.check_token(Token::Colon)? {
+                    annotation = Some(self.parse_type()?);
+                    if self.current_token != Token::Assignment {
+                        return Err(ParseError::UnableToParse(format!(
+                            "Expected an assignment after the type of {0}, got {1:?}",
+                            string, self.current_token
+                        )));
+                    }
+                }
                 // Expecting an assignment after identifier
                 if self.check_token(Token::Assignment)? {
                     let r_expr = self.generate_ast(Precedence::Default)?;
@@ -117,20 +236,27 @@ impl<'a> Parser<'a> {
                         identifier: Box::new(id_expr),
                         assignment_operator: Token::Assignment,
                         expr: Box::new(r_expr),
+                        annotation,
                     });
-                    // TODO: Instead of checking if token is an 'equals' token, check for any comparison tokens.
-                } else if self.check_token(Token::Equals)? {
-                    let r_expr = self.generate_ast(Precedence::Default)?;
-                    let id_expr = Node::IdentifierExpression(string);
-                    //
-                    return Ok(Node::ConditionExpression {
-                        l_expr: Box::new(id_expr),
-                        operator: Token::Equals,
-                        r_expr: Box::new(r_expr),
-                    });
-                } else {
-                    return Ok(Node::IdentifierExpression(string));
                 }
+                if self.current_token == Token::LeftBrace && self.struct_literals && !self.line_break {
+                    let value = self.parse_struct_expression(string)?;
+                    return self.parse_postfix(value);
+                }
+                let expr = self.parse_postfix(Node::IdentifierExpression(string))?;
+                // Assigning a field, e.g. `p.x = 1` or `orders[0].total = 5`
+                if let Node::FieldExpression { expr, field } = expr {
+                    if self.check_token(Token::Assignment)? {
+                        let value = self.generate_ast(Precedence::Default)?;
+                        return Ok(Node::FieldAssignmentExpression {
+                            expr,
+                            field,
+                            value: Box::new(value),
+                        });
+                    }
+                    return Ok(Node::FieldExpression { expr, field });
+                }
+                return Ok(expr);
             }
             Token::Literal { literal, keyword } => {
                 self.get_next_token()?;
@@ -138,13 +264,54 @@ impl<'a> Parser<'a> {
                 if keyword == Keyword::IF {
                     return self.parse_if_expression();
                 }
-                // Parse ELSE and ELIF branches
+                // ELSE and ELIF branches are parsed together with their IF
                 else if keyword == Keyword::ELSE || keyword == Keyword::ELIF {
-                    return self.parse_else_expression(keyword);
+                    return Err(ParseError::UnableToParse(format!(
+                        "Found {0} without a matching if",
+                        literal
+                    )));
                 }
                 // For loop
                 else if keyword == Keyword::WHILE {
                     return self.parse_while_expression();
+                } else if keyword == Keyword::BREAK {
+                    return Ok(Node::BreakExpression);
+                }
+                // Anonymous functions have no name, e.g. `fn(x) { x * 2 }`
+                else if keyword == Keyword::FN && self.current_token == Token::LeftParenthese {
+                    let function = self.parse_function_expression()?;
+                    return self.parse_postfix(function);
+                } else if keyword == Keyword::FN {
+                    return self.parse_function_declaration();
+                } else if keyword == Keyword::RETURN {
+                    return self.parse_return_expression();
+                } else if keyword == Keyword::STRUCT {
+                    return self.parse_struct_declaration();
+                } else if keyword == Keyword::IMPL {
+                    return self.parse_impl_declaration();
+                } else if keyword == Keyword::ENUM {
+                    return self.parse_enum_declaration();
+                } else if keyword == Keyword::MATCH {
+                    return self.parse_match_expression();
+                } else if keyword == Keyword::TRY {
+                    return self.parse_try_expression();
+                }
+                // Like else, catch and finally belong to a try
+                else if keyword == Keyword::CATCH || keyword == Keyword::FINALLY {
+                    return Err(ParseError::UnableToParse(format!(
+                        "Found {0} without a matching try",
+                        literal
+                    )));
+                } else if keyword == Keyword::THROW {
+                    return self.parse_throw_expression();
+                } else if keyword == Keyword::IMPORT || keyword == Keyword::FROM {
+                    return self.parse_import_expression(keyword);
+                } else if keyword == Keyword::EXPORT {
+                    return self.parse_export_statement();
+                } else if keyword == Keyword::AS {
+                    return Err(ParseError::UnableToParse(
+                        "Found as without a matching import".into(),
+                    ));
                 }
 
                 return Ok(Node::LiteralExpression(literal, keyword));
@@ -162,57 +329,28 @@ impl<'a> Parser<'a> {
     fn parse_if_expression(&mut self) -> Result<Node, ParseError> {
         // Handle if clause
         // Parse condition for THEN branch
-        let condition = Some(self.generate_ast(Precedence::Default)?);
-        // If we're missing an opening brace for the branch, return error.
-        if !self.check_token(Token::LeftBrace)? {
-            return Err(ParseError::UnableToParse(format!(
-                "Missing opening brace for {0:?}",
-                condition
-            )));
-        }
-        // Generate nodes for the branch until ENDIF or ELSE is reached
-        let mut else_branch = None;
-        let mut then_branch = Vec::new();
-        // If closing branch is found, set this flag variable to true, and check
-        // whether else clause is to be parsed.
-        let mut check_for_else = false;
-        loop {
-            // If eof has been reached without closing brace, return error.
-            if self.current_token == Token::EOF {
-                return Err(ParseError::UnableToParse(format!(
-                    "Missing closing brace for {0:?}",
-                    condition
-                )));
-            }
-            let branch_for_then = self.generate_ast(Precedence::Default)?;
-            match branch_for_then {
-                Node::ElseExpression {
-                    condition: _,
-                    then_branch: _,
-                    else_branch: _,
-                } => {
-                    else_branch = Some(branch_for_then);
-                    break;
-                }
-                _ => {
-                    if !check_for_else {
-                        then_branch.push(branch_for_then);
-                        // If closing brace is found, stop iterating.
-                        if self.check_token(Token::RightBrace)? {
-                            check_for_else = true;
-                        }
-                    } else {
-                        break;
-                    }
-                }
-            }
-        }
+        let condition = Some(self.parse_condition()?);
+        let then_branch = self.parse_block(format!("{0:?}", condition))?;
+        // Else and elif branches are optional
+        let else_branch = self.parse_else_branch()?;
         return Ok(Node::IfExpression {
             condition: Box::new(condition),
             then_branch: Box::new(then_branch),
             else_branch: Box::new(else_branch),
         });
     }
+
+    // Parse the else/elif branch following a block, if there is one.
+    fn parse_else_branch(&mut self) -> Result<Option<Node>, ParseError> {
+        match self.current_token.clone() {
+            Token::Literal { keyword, .. } if keyword == Keyword::ELSE || keyword == Keyword::ELIF => {
+                self.get_next_token()?;
+                Ok(Some(self.parse_else_expression(keyword)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
     // Parse else/elif expressions
     fn parse_else_expression(&mut self, keyword: Keyword) -> Result<Node, ParseError> {
         // Initialize condition as None, since condition is not mandatory for an else
@@ -220,108 +358,905 @@ impl<'a> Parser<'a> {
         let mut condition: Option<Node> = None;
         // Whether we have a condition to evaluate or not.
         if keyword == Keyword::ELIF {
-            condition = Some(self.generate_ast(Precedence::Default)?);
+            condition = Some(self.parse_condition()?);
         }
-        // If we're missing an opening brace for the branch, return error.
-        if !self.check_token(Token::LeftBrace)? {
+        let then_branch = match condition {
+            Some(_) => self.parse_block(format!("{0:?}", condition))?,
+            None => self.parse_block("Else".to_string())?,
+        };
+        // Only elif can be followed by another branch
+        let mut else_branch = None;
+        if keyword == Keyword::ELIF {
+            else_branch = self.parse_else_branch()?;
+        }
+        return Ok(Node::ElseExpression {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        });
+    }
+
+    // Parse try blocks, e.g. `try { ... } catch e { ... } finally { ... }`
+    fn parse_try_expression(&mut self) -> Result<Node, ParseError> {
+        let body = self.parse_block("try".to_string())?;
+        let mut catch = None;
+        if let Token::Literal {
+            keyword: Keyword::CATCH,
+            ..
+        } = self.current_token
+        {
+            self.get_next_token()?;
+            let name = self.parse_name("a name for the caught error")?;
+            let body = self.parse_block(format!("catch {0}", name))?;
+            catch = Some(CatchClause {
+                binding: Node::IdentifierExpression(name),
+                body,
+            });
+        }
+        let mut finally = None;
+        if let Token::Literal {
+            keyword: Keyword::FINALLY,
+            ..
+        } = self.current_token
+        {
+            self.get_next_token()?;
+            finally = Some(self.parse_block("finally".to_string())?);
+        }
+        if catch.is_none() && finally.is_none() {
+            return Err(ParseError::UnableToParse(
+                "Missing catch or finally for try".into(),
+            ));
+        }
+        return Ok(Node::TryExpression {
+            body: Box::new(body),
+            catch: Box::new(catch),
+            finally: Box::new(finally),
+        });
+    }
+
+    // Unlike return, throw always needs a value, e.g. `throw "empty list"`
+    fn parse_throw_expression(&mut self) -> Result<Node, ParseError> {
+        if self.line_break || self.current_token == Token::RightBrace || self.current_token == Token::EOF {
+            return Err(ParseError::UnableToParse(
+                "Missing value to throw".into(),
+            ));
+        }
+        let value = self.generate_ast(Precedence::Default)?;
+        return Ok(Node::ThrowExpression(Box::new(value)));
+    }
+
+    // Parse `import "path" as name` or `from "path" import a, b`, the first keyword is
+    // already consumed.
+    fn parse_import_expression(&mut self, keyword: Keyword) -> Result<Node, ParseError> {
+        let path = match self.current_token.clone() {
+            Token::Literal {
+                literal,
+                keyword: Keyword::None,
+            } if !self.line_break => {
+                self.get_next_token()?;
+                literal
+            }
+            _ => {
+                return Err(ParseError::UnableToParse(format!(
+                    "Expected the path of a module, got {0:?}",
+                    self.current_token
+                )))
+            }
+        };
+        let expected = match keyword {
+            Keyword::FROM => Keyword::IMPORT,
+            _ => Keyword::AS,
+        };
+        match &self.current_token {
+            Token::Literal { keyword, .. } if *keyword == expected && !self.line_break => {
+                self.get_next_token()?;
+            }
+            _ => {
+                return Err(ParseError::UnableToParse(format!(
+                    "Missing {0} after the path of {1}",
+                    expected.name().unwrap_or_default(),
+                    path
+                )))
+            }
+        }
+        if keyword != Keyword::FROM {
+            let alias = self.parse_name(&format!("a name for {0}", path))?;
+            return Ok(Node::ImportExpression {
+                path,
+                alias: Box::new(Some(Node::IdentifierExpression(alias))),
+                names: Vec::new(),
+            });
+        }
+        let names = self.parse_names(&format!("a name to import from {0}", path))?;
+        return Ok(Node::ImportExpression {
+            path,
+            alias: Box::new(None),
+            names: names.into_iter().map(Node::IdentifierExpression).collect(),
+        });
+    }
+
+    // Parse `export a, b`, the keyword is already consumed.
+    fn parse_export_statement(&mut self) -> Result<Node, ParseError> {
+        let names = self.parse_names("a name to export")?;
+        return Ok(Node::ExportStatement(names));
+    }
+
+    // Comma separated names on one line, at least one.
+    fn parse_names(&mut self, expected: &str) -> Result<Vec<String>, ParseError> {
+        let mut names: Vec<String> = Vec::new();
+        loop {
+            if self.line_break {
+                return Err(ParseError::UnableToParse(format!(
+                    "Expected {0}, got a line break",
+                    expected
+                )));
+            }
+            let name = self.parse_name(expected)?;
+            if names.contains(&name) {
+                return Err(ParseError::UnableToParse(format!("Duplicate name {0}", name)));
+            }
+            names.push(name);
+            if self.line_break || !self.check_token(Token::Comma)? {
+                return Ok(names);
+            }
+        }
+    }
+
+    fn parse_while_expression(&mut self) -> Result<Node, ParseError> {
+        // Get iteration condition
+        let mut condition: Option<Node> = None;
+        // If next token is opening brace, skip trying to parse an condition for the iteration
+        if self.current_token != Token::LeftBrace {
+            condition = Some(self.parse_condition()?);
+        }
+        let then_branch = match condition {
+            Some(_) => self.parse_block(format!("{0:?}", condition))?,
+            None => self.parse_block("While expression.".to_string())?,
+        };
+        return Ok(Node::WhileExpression {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch)
+        });
+    }
+
+    // Parse function declarations. e.g. fn add(a, b) { return a + b }
+    fn parse_function_declaration(&mut self) -> Result<Node, ParseError> {
+        let identifier = match self.current_token.clone() {
+            Token::Identifier(name) => name,
+            _ => {
+                return Err(ParseError::UnableToParse(format!(
+                    "Expected a function name, got {0:?}",
+                    self.current_token
+                )))
+            }
+        };
+        self.get_next_token()?;
+        let owner = format!("function {0}", identifier);
+        let (parameters, parameter_types, return_type) = self.parse_signature(&owner)?;
+        let body = self.parse_block(owner)?;
+        return Ok(Node::FunctionDeclaration {
+            identifier,
+            parameters,
+            parameter_types,
+            return_type,
+            body: Box::new(body),
+        });
+    }
+
+    // Parse anonymous functions, the `fn` keyword is already consumed.
+    fn parse_function_expression(&mut self) -> Result<Node, ParseError> {
+        let owner = "anonymous function".to_string();
+        let (parameters, parameter_types, return_type) = self.parse_signature(&owner)?;
+        let body = self.parse_block(owner)?;
+        return Ok(Node::FunctionExpression {
+            parameters,
+            parameter_types,
+            return_type,
+            body: Box::new(body),
+        });
+    }
+
+    // Parse the parameter list and the optional return type of a function.
+    fn parse_signature(&mut self, owner: &str) -> Result<Signature, ParseError> {
+        if !self.check_token(Token::LeftParenthese)? {
             return Err(ParseError::UnableToParse(format!(
-                "Missing opening brace for {0:?}",
-                condition
+                "Missing parameter list for {0}",
+                owner
             )));
         }
-        // Generate a statement
-        let mut else_branch = None;
-        let mut then_branch = Vec::new();
-        // If closing branch is found, set this flag variable to true, and check
-        // whether else clause is to be parsed
-        let mut check_for_else = false;
-        loop {
-            // If eof has been reached without closing brace, return error.
-            if self.current_token == Token::EOF {
-                if condition.is_some() {
+        let mut parameters = Vec::new();
+        let mut parameter_types = Vec::new();
+        while !self.check_token(Token::RightParenthese)? {
+            match self.current_token.clone() {
+                Token::Identifier(name) if parameters.contains(&name) => {
                     return Err(ParseError::UnableToParse(format!(
-                        "Missing closing brace for {0:?}",
-                        condition
+                        "Duplicate parameter {0} for {1}",
+                        name, owner
                     )))
                 }
-                else {
-                    return Err(ParseError::UnableToParse("Missing closing brace for Else".to_string()))
+                Token::Identifier(name) => parameters.push(name),
+                _ => {
+                    return Err(ParseError::UnableToParse(format!(
+                        "Expected a parameter name for {0}, got {1:?}",
+                        owner, self.current_token
+                    )))
                 }
             }
-            let branch_for_then = self.generate_ast(Precedence::Default)?;
-            match branch_for_then {
-                Node::ElseExpression {
-                    condition: _,
-                    then_branch: _,
-                    else_branch: _,
-                } => {
-                    else_branch = Some(branch_for_then);
-                    break;
+            self.get_next_token()?;
+            // Optional parameter type, e.g. `a: str`
+            let mut annotation = None;
+            if self.check_token(Token::Colon)? {
+                annotation = Some(self.parse_type()?);
+            }
+            parameter_types.push(annotation);
+            // Parameters are separated by commas
+            if !self.check_token(Token::Comma)? && self.current_token != Token::RightParenthese {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?}, got {1:?}",
+                    Token::RightParenthese,
+                    self.current_token
+                )));
+            }
+        }
+        // Optional return type, e.g. `-> bool`
+        let mut return_type = None;
+        if self.check_token(Token::Arrow)? {
+            return_type = Some(self.parse_type()?);
+        }
+        Ok((parameters, parameter_types, return_type))
+    }
+
+    // Parse the name of a type in an annotation.
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        let annotation = match &self.current_token {
+            Token::Identifier(name) => match Type::from_annotation(name) {
+                Some(annotation) => annotation,
+                None => return Err(ParseError::UnableToParse(format!("Unknown type {0}", name))),
+            },
+            _ => {
+                return Err(ParseError::UnableToParse(format!(
+                    "Expected a type, got {0:?}",
+                    self.current_token
+                )))
+            }
+        };
+        self.get_next_token()?;
+        Ok(annotation)
+    }
+
+    // Parse comma separated expressions up to the closing token, e.g. the arguments of
+    // a call. The opening token is already consumed.
+    fn parse_list(&mut self, closing: Token) -> Result<Vec<Node>, ParseError> {
+        self.with_struct_literals(|parser| parser.parse_list_elements(closing))
+    }
+
+    fn parse_list_elements(&mut self, closing: Token) -> Result<Vec<Node>, ParseError> {
+        let mut elements = Vec::new();
+        if self.check_token(closing.clone())? {
+            return Ok(elements);
+        }
+        loop {
+            elements.push(self.generate_ast(Precedence::Default)?);
+            if !self.check_token(Token::Comma)? {
+                self.check_paren(closing)?;
+                return Ok(elements);
+            }
+        }
+    }
+
+    // Calls, indexing, field access and ? following an expression. The opening parenthese,
+    // bracket, dot or ? has to be on the same line, e.g. `f(1)`, `xs[0]`, `p.x`, `parse(s)?`
+    // or `make_adder(1)(2)`.
+    fn parse_postfix(&mut self, mut expr: Node) -> Result<Node, ParseError> {
+        while !self.line_break {
+            if self.check_token(Token::LeftParenthese)? {
+                let arguments = self.parse_list(Token::RightParenthese)?;
+                expr = Node::CallExpression {
+                    callee: Box::new(expr),
+                    arguments,
+                };
+            } else if self.check_token(Token::LeftBracket)? {
+                let index = self.with_struct_literals(|parser| parser.generate_ast(Precedence::Default))?;
+                self.check_paren(Token::RightBracket)?;
+                expr = Node::IndexExpression {
+                    expr: Box::new(expr),
+                    index: Box::new(index),
+                };
+            } else if self.check_token(Token::Dot)? {
+                let field = self.parse_name("a field name after .")?;
+                expr = Node::FieldExpression {
+                    expr: Box::new(expr),
+                    field,
+                };
+            } else if self.check_token(Token::Question)? {
+                expr = Node::PropagateExpression(Box::new(expr));
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    // Parse struct declarations, e.g. `struct Point { x, y }`. Fields are separated by
+    // commas or line breaks.
+    fn parse_struct_declaration(&mut self) -> Result<Node, ParseError> {
+        let identifier = self.parse_name("a struct name")?;
+        let owner = format!("struct {0}", identifier);
+        if !self.check_token(Token::LeftBrace)? {
+            return Err(ParseError::UnableToParse(format!(
+                "Missing opening brace for {0}",
+                owner
+            )));
+        }
+        let mut fields = Vec::new();
+        while !self.check_token(Token::RightBrace)? {
+            let field = self.parse_name(&format!("a field name for {0}", owner))?;
+            if fields.contains(&field) {
+                return Err(ParseError::UnableToParse(format!(
+                    "Duplicate field {0} for {1}",
+                    field, owner
+                )));
+            }
+            fields.push(field);
+            if !self.check_token(Token::Comma)? && self.current_token != Token::RightBrace && !self.line_break {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?}, got {1:?}",
+                    Token::RightBrace,
+                    self.current_token
+                )));
+            }
+        }
+        return Ok(Node::StructDeclaration { identifier, fields });
+    }
+
+    // Parse the methods of a struct, e.g. `impl Point { fn norm(self) { ... } }`.
+    fn parse_impl_declaration(&mut self) -> Result<Node, ParseError> {
+        let identifier = self.parse_name("a struct name after impl")?;
+        let owner = format!("impl {0}", identifier);
+        if !self.check_token(Token::LeftBrace)? {
+            return Err(ParseError::UnableToParse(format!(
+                "Missing opening brace for {0}",
+                owner
+            )));
+        }
+        let mut methods = Vec::new();
+        while !self.check_token(Token::RightBrace)? {
+            match self.current_token {
+                Token::Literal {
+                    keyword: Keyword::FN,
+                    ..
+                } => {}
+                Token::EOF => {
+                    return Err(ParseError::UnableToParse(format!(
+                        "Missing closing brace for {0}",
+                        owner
+                    )))
                 }
                 _ => {
-                    if !check_for_else {
-                        then_branch.push(branch_for_then);
-                        // If closing brace is found, stop iterating.
-                        if self.check_token(Token::RightBrace)? {
-                            check_for_else = true;
-                            // If there is no condition, break out, we know that wont
-                            // be another else in the tree
-                            if keyword == Keyword::ELSE {
-                                break;
-                            }
-                        }
-                    } else {
-                        break;
+                    return Err(ParseError::UnableToParse(format!(
+                        "Expected a method for {0}, got {1:?}",
+                        owner, self.current_token
+                    )))
+                }
+            }
+            // Methods are statements of the impl block
+            let statement = self.start_statement();
+            self.get_next_token()?;
+            let method = self.parse_function_declaration()?;
+            self.end_statement(statement);
+            if let Node::FunctionDeclaration {
+                identifier: name,
+                parameters,
+                ..
+            } = &method
+            {
+                if parameters.first().map(String::as_str) != Some("self") {
+                    return Err(ParseError::UnableToParse(format!(
+                        "Method {0} of {1} must take self as its first parameter",
+                        name, identifier
+                    )));
+                }
+            }
+            methods.push(method);
+        }
+        return Ok(Node::ImplDeclaration {
+            identifier,
+            methods: Box::new(methods),
+        });
+    }
+
+    // Parse a struct value after its name, e.g. `Point { x: 1, y: 2 }`.
+    fn parse_struct_expression(&mut self, identifier: String) -> Result<Node, ParseError> {
+        self.check_paren(Token::LeftBrace)?;
+        let mut fields: Vec<(String, Node)> = Vec::new();
+        while !self.check_token(Token::RightBrace)? {
+            let field = self.parse_name(&format!("a field name for {0}", identifier))?;
+            if fields.iter().any(|(name, _)| *name == field) {
+                return Err(ParseError::UnableToParse(format!(
+                    "Duplicate field {0} for {1}",
+                    field, identifier
+                )));
+            }
+            self.check_paren(Token::Colon)?;
+            let value = self.generate_ast(Precedence::Default)?;
+            fields.push((field, value));
+            if !self.check_token(Token::Comma)? && self.current_token != Token::RightBrace {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?}, got {1:?}",
+                    Token::RightBrace,
+                    self.current_token
+                )));
+            }
+        }
+        return Ok(Node::StructExpression { identifier, fields });
+    }
+
+    // Parse enum declarations, e.g. `enum Shape { Circle(radius), Rect(w, h), Empty }`.
+    // Variants are separated by commas or line breaks.
+    fn parse_enum_declaration(&mut self) -> Result<Node, ParseError> {
+        let identifier = self.parse_name("an enum name")?;
+        let owner = format!("enum {0}", identifier);
+        if !self.check_token(Token::LeftBrace)? {
+            return Err(ParseError::UnableToParse(format!(
+                "Missing opening brace for {0}",
+                owner
+            )));
+        }
+        let mut variants: Vec<(String, Vec<String>)> = Vec::new();
+        while !self.check_token(Token::RightBrace)? {
+            let variant = self.parse_name(&format!("a variant name for {0}", owner))?;
+            if variants.iter().any(|(name, _)| *name == variant) {
+                return Err(ParseError::UnableToParse(format!(
+                    "Duplicate variant {0} for {1}",
+                    variant, owner
+                )));
+            }
+            // Fields of the variant are optional, e.g. `Circle(radius)`
+            let mut fields = Vec::new();
+            if self.check_token(Token::LeftParenthese)? {
+                while !self.check_token(Token::RightParenthese)? {
+                    fields.push(self.parse_name(&format!("a field name for {0}", variant))?);
+                    if !self.check_token(Token::Comma)? && self.current_token != Token::RightParenthese {
+                        return Err(ParseError::InvalidOperator(format!(
+                            "Expected {0:?}, got {1:?}",
+                            Token::RightParenthese,
+                            self.current_token
+                        )));
                     }
                 }
             }
+            variants.push((variant, fields));
+            if !self.check_token(Token::Comma)? && self.current_token != Token::RightBrace && !self.line_break {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?}, got {1:?}",
+                    Token::RightBrace,
+                    self.current_token
+                )));
+            }
         }
-        return Ok(Node::ElseExpression {
-            condition: Box::new(condition),
-            then_branch: Box::new(then_branch),
-            else_branch: Box::new(else_branch),
+        return Ok(Node::EnumDeclaration {
+            identifier,
+            variants,
         });
     }
 
-    fn parse_while_expression(&mut self) -> Result<Node, ParseError> {
-        // Get iteration condition
-        let mut condition: Option<Node> = None;
-        // If next token is opening brace, skip trying to parse an condition for the iteration
+    // Parse a variant after `Enum::`, e.g. `Circle(1)`. Variants without values have no
+    // parentheses.
+    fn parse_variant_expression(&mut self, enum_name: String) -> Result<Node, ParseError> {
+        let variant = self.parse_name("a variant name after ::")?;
+        let mut arguments = Vec::new();
+        if !self.line_break && self.check_token(Token::LeftParenthese)? {
+            arguments = self.parse_list(Token::RightParenthese)?;
+        }
+        return Ok(Node::VariantExpression {
+            enum_name,
+            variant,
+            arguments,
+        });
+    }
+
+    // Parse match expressions, the `match` keyword is already consumed.
+    /*
+        Arms are separated by commas or line breaks. The body of an arm is either a block
+        or a single expression, which counts as a statement of its own for the line table:
+
+            match shape {
+                Shape::Circle(r) => 3 * r * r
+                Shape::Rect(w, h) if w == h => {
+                    print("square")
+                    w * h
+                }
+                _ => 0
+            }
+    */
+    fn parse_match_expression(&mut self) -> Result<Node, ParseError> {
+        // Like a condition, the value is followed by a brace
+        let expr = self.parse_condition()?;
         if !self.check_token(Token::LeftBrace)? {
-            condition = Some(self.generate_ast(Precedence::Default)?);
+            return Err(ParseError::UnableToParse(
+                "Missing opening brace for match".into(),
+            ));
+        }
+        let arms = self.with_struct_literals(|parser| parser.parse_match_arms())?;
+        return Ok(Node::MatchExpression {
+            expr: Box::new(expr),
+            arms,
+        });
+    }
+
+    fn parse_match_arms(&mut self) -> Result<Vec<MatchArm>, ParseError> {
+        let mut arms = Vec::new();
+        while !self.check_token(Token::RightBrace)? {
+            if self.current_token == Token::EOF {
+                return Err(ParseError::UnableToParse(
+                    "Missing closing brace for match".into(),
+                ));
+            }
+            let pattern = self.parse_pattern()?;
+            // Optional guard, e.g. `n if n > 0`
+            let mut guard = None;
+            if let Token::Literal {
+                keyword: Keyword::IF,
+                ..
+            } = self.current_token
+            {
+                self.get_next_token()?;
+                guard = Some(self.generate_ast(Precedence::Default)?);
+            }
+            if !self.check_token(Token::FatArrow)? {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?} after the pattern of a match arm, got {1:?}",
+                    Token::FatArrow,
+                    self.current_token
+                )));
+            }
+            let body = if self.current_token == Token::LeftBrace {
+                self.parse_block("match arm".to_string())?
+            } else {
+                let statement = self.start_statement();
+                let body = self.generate_ast(Precedence::Default)?;
+                self.end_statement(statement);
+                vec![body]
+            };
+            arms.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+            if !self.check_token(Token::Comma)? && self.current_token != Token::RightBrace && !self.line_break {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?}, got {1:?}",
+                    Token::RightBrace,
+                    self.current_token
+                )));
+            }
         }
-        // If opening brace is not found, return an error
+        Ok(arms)
+    }
+
+    // Parse the pattern of a match arm.
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        match self.current_token.clone() {
+            Token::Identifier(name) if name == "_" => {
+                self.get_next_token()?;
+                Ok(Pattern::Wildcard)
+            }
+            Token::Identifier(name) => {
+                self.get_next_token()?;
+                // Built-in variants by their short names, e.g. `some(x)` or `none`
+                let short_variant = builtin_variant(&name)
+                    .filter(|_| name == "none" || self.current_token == Token::LeftParenthese);
+                if let Some((enum_name, variant)) = short_variant {
+                    let mut elements = Vec::new();
+                    if self.check_token(Token::LeftParenthese)? {
+                        elements = self.parse_patterns(Token::RightParenthese)?;
+                    }
+                    return Ok(Pattern::Variant {
+                        enum_name: enum_name.to_string(),
+                        variant: variant.to_string(),
+                        elements,
+                    });
+                }
+                if self.check_token(Token::DoubleColon)? {
+                    let variant = self.parse_name("a variant name after ::")?;
+                    let mut elements = Vec::new();
+                    if self.check_token(Token::LeftParenthese)? {
+                        elements = self.parse_patterns(Token::RightParenthese)?;
+                    }
+                    return Ok(Pattern::Variant {
+                        enum_name: name,
+                        variant,
+                        elements,
+                    });
+                }
+                if self.current_token == Token::LeftBrace {
+                    return self.parse_struct_pattern(name);
+                }
+                Ok(Pattern::Binding(Box::new(Node::IdentifierExpression(name))))
+            }
+            Token::Num(_) | Token::Subtract => {
+                let start = self.parse_pattern_number()?;
+                let inclusive = match self.current_token {
+                    Token::DotDot => false,
+                    Token::DotDotEquals => true,
+                    _ => return Ok(Pattern::Literal(Box::new(start))),
+                };
+                self.get_next_token()?;
+                let end = self.parse_pattern_number()?;
+                Ok(Pattern::Range {
+                    start: Box::new(start),
+                    end: Box::new(end),
+                    inclusive,
+                })
+            }
+            Token::Literal {
+                literal,
+                keyword: keyword @ (Keyword::None | Keyword::True | Keyword::False),
+            } => {
+                self.get_next_token()?;
+                Ok(Pattern::Literal(Box::new(Node::LiteralExpression(
+                    literal, keyword,
+                ))))
+            }
+            Token::LeftBracket => {
+                self.get_next_token()?;
+                self.parse_list_pattern()
+            }
+            _ => Err(ParseError::UnableToParse(format!(
+                "Expected a pattern, got {0:?}",
+                self.current_token
+            ))),
+        }
+    }
+
+    // Parse comma separated patterns up to the closing token.
+    fn parse_patterns(&mut self, closing: Token) -> Result<Vec<Pattern>, ParseError> {
+        let mut patterns = Vec::new();
+        while !self.check_token(closing.clone())? {
+            patterns.push(self.parse_pattern()?);
+            if !self.check_token(Token::Comma)? && self.current_token != closing {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?}, got {1:?}",
+                    closing, self.current_token
+                )));
+            }
+        }
+        Ok(patterns)
+    }
+
+    // Parse a list pattern after the opening bracket, e.g. `[x, y]` or `[first, ..rest]`.
+    // The rest has to come last.
+    fn parse_list_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let mut elements = Vec::new();
+        let mut rest = None;
+        while !self.check_token(Token::RightBracket)? {
+            if self.check_token(Token::DotDot)? {
+                let pattern = match self.current_token.clone() {
+                    Token::Identifier(name) if name != "_" => {
+                        self.get_next_token()?;
+                        Pattern::Binding(Box::new(Node::IdentifierExpression(name)))
+                    }
+                    Token::Identifier(_) => {
+                        self.get_next_token()?;
+                        Pattern::Wildcard
+                    }
+                    _ => Pattern::Wildcard,
+                };
+                rest = Some(Box::new(pattern));
+                self.check_paren(Token::RightBracket)?;
+                break;
+            }
+            elements.push(self.parse_pattern()?);
+            if !self.check_token(Token::Comma)? && self.current_token != Token::RightBracket {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?}, got {1:?}",
+                    Token::RightBracket,
+                    self.current_token
+                )));
+            }
+        }
+        Ok(Pattern::List { elements, rest })
+    }
+
+    // Parse a struct pattern after its name, e.g. `Point { x, y: 0 }`.
+    fn parse_struct_pattern(&mut self, identifier: String) -> Result<Pattern, ParseError> {
+        self.check_paren(Token::LeftBrace)?;
+        let mut fields: Vec<(String, Pattern)> = Vec::new();
+        while !self.check_token(Token::RightBrace)? {
+            let field = self.parse_name(&format!("a field name for {0}", identifier))?;
+            if fields.iter().any(|(name, _)| *name == field) {
+                return Err(ParseError::UnableToParse(format!(
+                    "Duplicate field {0} for {1}",
+                    field, identifier
+                )));
+            }
+            // A field alone binds its value to a variable of the same name
+            let pattern = if self.check_token(Token::Colon)? {
+                self.parse_pattern()?
+            } else {
+                Pattern::Binding(Box::new(Node::IdentifierExpression(field.clone())))
+            };
+            fields.push((field, pattern));
+            if !self.check_token(Token::Comma)? && self.current_token != Token::RightBrace {
+                return Err(ParseError::InvalidOperator(format!(
+                    "Expected {0:?}, got {1:?}",
+                    Token::RightBrace,
+                    self.current_token
+                )));
+            }
+        }
+        Ok(Pattern::Struct { identifier, fields })
+    }
+
+    // Number of a literal or range pattern, optionally negative, e.g. `-1`.
+    fn parse_pattern_number(&mut self) -> Result<Node, ParseError> {
+        let negative = self.check_token(Token::Subtract)?;
+        let number = match self.current_token {
+            Token::Num(ref number) => Node::NumberExpression(number.clone()),
+            _ => {
+                return Err(ParseError::UnableToParse(format!(
+                    "Expected a number in a pattern, got {0:?}",
+                    self.current_token
+                )))
+            }
+        };
+        self.get_next_token()?;
+        if negative {
+            return Ok(Node::NegativeNumberExpression(Box::new(number)));
+        }
+        Ok(number)
+    }
+
+    // Parse the name of a declaration or a field.
+    fn parse_name(&mut self, expected: &str) -> Result<String, ParseError> {
+        match self.current_token.clone() {
+            Token::Identifier(name) => {
+                self.get_next_token()?;
+                Ok(name)
+            }
+            _ => Err(ParseError::UnableToParse(format!(
+                "Expected {0}, got {1:?}",
+                expected, self.current_token
+            ))),
+        }
+    }
+
+    // Conditions of if, elif and while are followed by a block, so they can not contain
+    // struct values outside of parentheses.
+    fn parse_condition(&mut self) -> Result<Node, ParseError> {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, false);
+        let condition = self.generate_ast(Precedence::Default);
+        self.struct_literals = struct_literals;
+        condition
+    }
+
+    // Parse something delimited, where struct values are allowed again.
+    fn with_struct_literals<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, true);
+        let result = parse(self);
+        self.struct_literals = struct_literals;
+        result
+    }
+
+    // Return value has to start on the same line as the return keyword.
+    fn parse_return_expression(&mut self) -> Result<Node, ParseError> {
+        let mut value = None;
+        if !self.line_break
+            && self.current_token != Token::RightBrace
+            && self.current_token != Token::EOF
+        {
+            value = Some(self.generate_ast(Precedence::Default)?);
+        }
+        return Ok(Node::ReturnExpression(Box::new(value)));
+    }
+
+    // Parse a block of statements surrounded by braces.
+    fn parse_block(&mut self, owner: String) -> Result<Vec<Node>, ParseError> {
+        self.with_struct_literals(|parser| parser.parse_statements(owner))
+    }
+
+    fn parse_statements(&mut self, owner: String) -> Result<Vec<Node>, ParseError> {
+        // If we're missing an opening brace for the block, return error.
+        let open = self.line;
         if !self.check_token(Token::LeftBrace)? {
             return Err(ParseError::UnableToParse(format!(
-                "Missing opening brace for {0:?}",
-                condition
-            )))
+                "Missing opening brace for {0}",
+                owner
+            )));
         }
-        let mut then_branch = Vec::new();
+        let first_statement = self.statement_lines.len();
+        let mut statements = Vec::new();
         // Iterate until closing brace is found.
         loop {
+            let close = self.line;
             if self.check_token(Token::RightBrace)? {
+                if !statements.is_empty() {
+                    self.blocks.push(BlockLines {
+                        first_statement,
+                        open,
+                        close,
+                    });
+                }
                 break;
             }
-            let branch_for_then = self.generate_ast(Precedence::Default)?;
-            then_branch.push(branch_for_then);
             // If eof is reached and closing brace is not found, return error.
             if self.current_token == Token::EOF {
-                if condition.is_some() {
+                return Err(ParseError::UnableToParse(format!(
+                    "Missing closing brace for {0}",
+                    owner
+                )));
+            }
+            let statement = self.start_statement();
+            statements.push(self.generate_ast(Precedence::Default)?);
+            self.end_statement(statement);
+        }
+        Ok(statements)
+    }
+
+    // Parse a unit expression such as `m/s^2` into its textual form.
+    /*
+        Units are resolved by the evaluator's unit registry, so the parser only validates
+        the shape: names joined by `*` or `/`, each optionally raised to an integer power.
+        An operator only continues the unit when it is followed by a name, which keeps
+        `5 m / 2` a division.
+    */
+    fn parse_unit(&mut self) -> Result<String, ParseError> {
+        let mut unit = String::new();
+        loop {
+            match self.current_token.clone() {
+                Token::Identifier(name) => {
+                    unit += &name;
+                    self.get_next_token()?;
+                }
+                _ => {
                     return Err(ParseError::UnableToParse(format!(
-                        "Missing closing brace for {0:?}",
-                        condition
+                        "Expected a unit, got {0:?}",
+                        self.current_token
                     )))
                 }
-                else {
-                    return Err(ParseError::UnableToParse("Missing closing brace for While expression.".to_string()))
+            }
+            // Integer power for the unit
+            if self.current_token == Token::Pow {
+                self.get_next_token()?;
+                unit.push('^');
+                if self.check_token(Token::Subtract)? {
+                    unit.push('-');
+                }
+                match self.current_token {
+                    Token::Num(Number::Integer(power)) => {
+                        unit += &power.to_string();
+                        self.get_next_token()?;
+                    }
+                    _ => {
+                        return Err(ParseError::UnableToParse(format!(
+                            "Expected an integer power for unit {0}, got {1:?}",
+                            unit, self.current_token
+                        )))
+                    }
+                }
+            }
+            // Continue only if the operator is followed by another unit name
+            let operator = match self.current_token {
+                Token::Multiply => '*',
+                Token::Divide => '/',
+                _ => break,
+            };
+            let mut lookahead = self.tokenizer.expr.clone();
+            while lookahead.peek() == Some(&' ') || lookahead.peek() == Some(&'\t') {
+                lookahead.next();
+            }
+            match lookahead.peek() {
+                Some(c) if c.is_alphabetic() || *c == '_' => {
+                    unit.push(operator);
+                    self.get_next_token()?;
                 }
+                _ => break,
             }
         }
-        return Ok(Node::WhileExpression {
-            condition: Box::new(condition),
-            then_branch: Box::new(then_branch)
-        });
+        Ok(unit)
     }
 
     // Closing parenthese is always expected, if not found return error
@@ -351,7 +1286,7 @@ impl<'a> Parser<'a> {
         let token = self.current_token.clone();
         match token {
             Token::Add => {
-                let _capture = self.get_next_token()?;
+                self.get_next_token()?;
                 let r_expr = self.generate_ast(Precedence::AddAndSubtract)?;
                 Ok(Node::BinaryExpr {
                     l_expr: Box::new(l_expr),
@@ -360,7 +1295,7 @@ impl<'a> Parser<'a> {
                 })
             }
             Token::Subtract => {
-                let _capture = self.get_next_token()?;
+                self.get_next_token()?;
                 let r_expr = self.generate_ast(Precedence::AddAndSubtract)?;
                 Ok(Node::BinaryExpr {
                     l_expr: Box::new(l_expr),
@@ -369,7 +1304,7 @@ impl<'a> Parser<'a> {
                 })
             }
             Token::Multiply => {
-                let _capture = self.get_next_token()?;
+                self.get_next_token()?;
                 let r_expr = self.generate_ast(Precedence::MultiplyAndDivide)?;
                 Ok(Node::BinaryExpr {
                     l_expr: Box::new(l_expr),
@@ -378,7 +1313,7 @@ impl<'a> Parser<'a> {
                 })
             }
             Token::Divide => {
-                let _capture = self.get_next_token()?;
+                self.get_next_token()?;
                 let r_expr = self.generate_ast(Precedence::MultiplyAndDivide)?;
                 Ok(Node::BinaryExpr {
                     l_expr: Box::new(l_expr),
@@ -387,7 +1322,7 @@ impl<'a> Parser<'a> {
                 })
             }
             Token::Pow => {
-                let _capture = self.get_next_token()?;
+                self.get_next_token()?;
                 let r_expr = self.generate_ast(Precedence::Power)?;
                 Ok(Node::BinaryExpr {
                     l_expr: Box::new(l_expr),
@@ -395,6 +1330,32 @@ impl<'a> Parser<'a> {
                     r_expr: Box::new(r_expr),
                 })
             }
+            // Comparisons
+            Token::Equals
+            | Token::NotEquals
+            | Token::GreaterThan
+            | Token::LessThan
+            | Token::GreaterThanOrEquals
+            | Token::LessThanOrEquals => {
+                self.get_next_token()?;
+                let r_expr = self.generate_ast(Precedence::Comparison)?;
+                Ok(Node::ConditionExpression {
+                    l_expr: Box::new(l_expr),
+                    operator: token,
+                    r_expr: Box::new(r_expr),
+                })
+            }
+            // Unit conversion
+            Token::Literal {
+                keyword: Keyword::IN,
+                ..
+            } => {
+                self.get_next_token()?;
+                Ok(Node::ConversionExpression {
+                    expr: Box::new(l_expr),
+                    unit: self.parse_unit()?,
+                })
+            }
             _ => return Err(ParseError::InvalidOperator("Bad token".to_string())),
         }
     }
@@ -421,3 +1382,5 @@ impl fmt::Display for ParseError {
         }
     }
 }
+
+impl std::error::Error for ParseError {}
@@ -1,11 +1,16 @@
 // Different test for expressions which parser generates.
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod parser_test {
-    use crate::lexer_and_parser::ast::Node;
+    use crate::lexer_and_parser::ast::{CatchClause, MatchArm, Node, Pattern, Type};
+    use crate::lexer_and_parser::numeric::Number;
     use crate::lexer_and_parser::parser::Parser;
     use crate::lexer_and_parser::token::Token;
+    #[allow(unused_imports)]
     use crate::lexer_and_parser::token::Token::Num;
-    use crate::Node::{BinaryExpr, ConditionExpression, IdentifierExpression, NumberExpression};
+    use crate::lexer_and_parser::ast::Node::{
+        BinaryExpr, ConditionExpression, IdentifierExpression, NumberExpression,
+    };
 
     #[test]
     fn test_binary_expressions() {
@@ -22,39 +27,39 @@ mod parser_test {
             .unwrap();
         // 1 + 2
         let add = Node::BinaryExpr {
-            l_expr: Box::new(Node::NumberExpression(1.0)),
+            l_expr: Box::new(Node::NumberExpression(Number::Integer(1))),
             operator: Token::Add,
-            r_expr: Box::new(Node::NumberExpression(2.0)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
         };
         // 1 - 1
         let subtract = Node::BinaryExpr {
-            l_expr: Box::new(Node::NumberExpression(1.0)),
+            l_expr: Box::new(Node::NumberExpression(Number::Integer(1))),
             operator: Token::Subtract,
-            r_expr: Box::new(Node::NumberExpression(1.0)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(1))),
         };
         // 2 * 2
         let multiply = Node::BinaryExpr {
-            l_expr: Box::new(Node::NumberExpression(2.0)),
+            l_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
             operator: Token::Multiply,
-            r_expr: Box::new(Node::NumberExpression(2.0)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
         };
         // 10 / 2
         let divide = Node::BinaryExpr {
-            l_expr: Box::new(Node::NumberExpression(10.0)),
+            l_expr: Box::new(Node::NumberExpression(Number::Integer(10))),
             operator: Token::Divide,
-            r_expr: Box::new(Node::NumberExpression(2.0)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
         };
         // 5 ^ 2
         let pow = Node::BinaryExpr {
-            l_expr: Box::new(Node::NumberExpression(5.0)),
+            l_expr: Box::new(Node::NumberExpression(Number::Integer(5))),
             operator: Token::Pow,
-            r_expr: Box::new(Node::NumberExpression(2.0)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
         };
         // x + 2
         let add_to_variable = Node::BinaryExpr {
             l_expr: Box::new(Node::IdentifierExpression("x".to_string())),
             operator: Token::Add,
-            r_expr: Box::new(Node::NumberExpression(2.0)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
         };
         // Add expected expressions to vector.
         let expected_expressions = vec![add, subtract, multiply, divide, pow, add_to_variable];
@@ -72,23 +77,23 @@ mod parser_test {
             .unwrap();
         // 2 + 2 * 5
         let multi_precedence = Node::BinaryExpr {
-            l_expr: Box::new(Node::NumberExpression(2.0)),
+            l_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
             operator: Token::Add,
             r_expr: Box::new(Node::BinaryExpr {
-                l_expr: Box::new(Node::NumberExpression(2.0)),
+                l_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
                 operator: Token::Multiply,
-                r_expr: Box::new(Node::NumberExpression(5.0)),
+                r_expr: Box::new(Node::NumberExpression(Number::Integer(5))),
             }),
         };
         // (2 + 2) * 5
         let parentheses_precedence = Node::BinaryExpr {
             l_expr: Box::new(Node::BinaryExpr {
-                l_expr: Box::new(Node::NumberExpression(2.0)),
+                l_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
                 operator: Token::Add,
-                r_expr: Box::new(Node::NumberExpression(2.0)),
+                r_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
             }),
             operator: Token::Multiply,
-            r_expr: Box::new(Node::NumberExpression(5.0)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(5))),
         };
         let expected_expressions = vec![multi_precedence, parentheses_precedence];
         assert_eq!(parser.parse().unwrap(), expected_expressions)
@@ -109,7 +114,8 @@ mod parser_test {
         let expected = Node::AssignmentExpression {
             identifier: Box::new(Node::IdentifierExpression("x".to_string())),
             assignment_operator: Token::Assignment,
-            expr: Box::new(Node::NumberExpression(5.0)),
+            expr: Box::new(Node::NumberExpression(Number::Integer(5))),
+            annotation: None,
         };
         assert_eq!(parser.parse().unwrap()[0], expected);
     }
@@ -134,19 +140,20 @@ mod parser_test {
         let if_condition = Box::new(Some(Node::ConditionExpression {
             l_expr: Box::new(Node::IdentifierExpression("x".to_string())),
             operator: Token::Equals,
-            r_expr: Box::new(Node::NumberExpression(15.)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(15))),
         }));
         // then branch.. x = 25
         let then = Box::new(vec![Node::AssignmentExpression {
             identifier: Box::new(Node::IdentifierExpression("x".to_string())),
             assignment_operator: Token::Assignment,
-            expr: Box::new(NumberExpression(25.)),
+            expr: Box::new(NumberExpression(Number::Integer(25))),
+            annotation: None,
         }]);
         // elif condition - x == 10
         let elif_condition = Box::new(Some(Node::ConditionExpression {
             l_expr: Box::new(Node::IdentifierExpression("x".to_string())),
             operator: Token::Equals,
-            r_expr: Box::new(Node::NumberExpression(10.0)),
+            r_expr: Box::new(Node::NumberExpression(Number::Integer(10))),
         }));
         // elif then...
         // y = 10
@@ -155,7 +162,8 @@ mod parser_test {
             Node::AssignmentExpression {
                 identifier: Box::new(Node::IdentifierExpression("y".to_string())),
                 assignment_operator: Token::Assignment,
-                expr: Box::new(NumberExpression(10.)),
+                expr: Box::new(NumberExpression(Number::Integer(10))),
+                annotation: None,
             },
             Node::AssignmentExpression {
                 identifier: Box::new(Node::IdentifierExpression("x".to_string())),
@@ -163,15 +171,17 @@ mod parser_test {
                 expr: Box::new(Node::BinaryExpr {
                     l_expr: Box::new(IdentifierExpression("y".to_string())),
                     operator: Token::Add,
-                    r_expr: Box::new(Node::NumberExpression(2.)),
+                    r_expr: Box::new(Node::NumberExpression(Number::Integer(2))),
                 }),
+                annotation: None,
             }]);
         // else then
         // x = 17
         let else_then = Box::new(vec![Node::AssignmentExpression {
             identifier: Box::new(Node::IdentifierExpression("x".to_string())),
             assignment_operator: Token::Assignment,
-            expr: Box::new(NumberExpression(17.)),
+            expr: Box::new(NumberExpression(Number::Integer(17))),
+            annotation: None,
         }]);
 
         // else if expression
@@ -206,16 +216,510 @@ mod parser_test {
         ).unwrap();
         let expected = Node::WhileExpression {
             condition: Box::new(Some(ConditionExpression {
-                l_expr: Box::new(NumberExpression(1.)),
+                l_expr: Box::new(NumberExpression(Number::Integer(1))),
                 operator: Token::Equals,
-                r_expr: Box::new(NumberExpression(1.))
+                r_expr: Box::new(NumberExpression(Number::Integer(1)))
             })),
             then_branch: Box::new(vec![BinaryExpr {
-                l_expr: Box::new(NumberExpression(1.)),
+                l_expr: Box::new(NumberExpression(Number::Integer(1))),
                 operator: Token::Add,
-                r_expr: Box::new(NumberExpression(1.))
+                r_expr: Box::new(NumberExpression(Number::Integer(1)))
             }])
         };
         assert_eq!(parser.parse().unwrap()[0], expected);
     }
+
+    #[test]
+    fn test_unit_expressions() {
+        let mut parser = Parser::new(
+            "
+            g = 9.81 m/s^2
+            5 m / 2
+            x in km
+            5
+            m",
+        )
+        .unwrap();
+        // g = 9.81 m/s^2
+        let quantity = Node::AssignmentExpression {
+            identifier: Box::new(Node::IdentifierExpression("g".to_string())),
+            assignment_operator: Token::Assignment,
+            expr: Box::new(Node::QuantityExpression {
+                value: Box::new(NumberExpression(Number::Float(9.81))),
+                unit: "m/s^2".to_string(),
+            }),
+            annotation: None,
+        };
+        // Division by a number is not part of the unit
+        let division = Node::BinaryExpr {
+            l_expr: Box::new(Node::QuantityExpression {
+                value: Box::new(NumberExpression(Number::Integer(5))),
+                unit: "m".to_string(),
+            }),
+            operator: Token::Divide,
+            r_expr: Box::new(NumberExpression(Number::Integer(2))),
+        };
+        // x in km
+        let conversion = Node::ConversionExpression {
+            expr: Box::new(IdentifierExpression("x".to_string())),
+            unit: "km".to_string(),
+        };
+        // A line break ends the number, so `m` is a separate identifier
+        let expected = vec![
+            quantity,
+            division,
+            conversion,
+            NumberExpression(Number::Integer(5)),
+            IdentifierExpression("m".to_string()),
+        ];
+        assert_eq!(parser.parse().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_function_expressions() {
+        let mut parser = Parser::new(
+            "
+            fn add(a, b) {
+                return a + b
+            }
+            add(1, 2) > x + 1",
+        )
+        .unwrap();
+        let declaration = Node::FunctionDeclaration {
+            identifier: "add".to_string(),
+            parameters: vec!["a".to_string(), "b".to_string()],
+            parameter_types: vec![None, None],
+            return_type: None,
+            body: Box::new(vec![Node::ReturnExpression(Box::new(Some(BinaryExpr {
+                l_expr: Box::new(IdentifierExpression("a".to_string())),
+                operator: Token::Add,
+                r_expr: Box::new(IdentifierExpression("b".to_string())),
+            })))]),
+        };
+        // Comparisons bind looser than arithmetic
+        let comparison = ConditionExpression {
+            l_expr: Box::new(Node::CallExpression {
+                callee: Box::new(IdentifierExpression("add".to_string())),
+                arguments: vec![
+                    NumberExpression(Number::Integer(1)),
+                    NumberExpression(Number::Integer(2))
+                ],
+            }),
+            operator: Token::GreaterThan,
+            r_expr: Box::new(BinaryExpr {
+                l_expr: Box::new(IdentifierExpression("x".to_string())),
+                operator: Token::Add,
+                r_expr: Box::new(NumberExpression(Number::Integer(1))),
+            }),
+        };
+        assert_eq!(parser.parse().unwrap(), vec![declaration, comparison]);
+    }
+
+    #[test]
+    fn test_closures_and_lists() {
+        let mut parser = Parser::new("make(1)(2)\nf = fn(x) { x * 2 }\n[f, g][0](x)").unwrap();
+        let identifier = |name: &str| Box::new(IdentifierExpression(name.to_string()));
+        // Calls and indexing chain from left to right
+        let chained_call = Node::CallExpression {
+            callee: Box::new(Node::CallExpression {
+                callee: identifier("make"),
+                arguments: vec![NumberExpression(Number::Integer(1))],
+            }),
+            arguments: vec![NumberExpression(Number::Integer(2))],
+        };
+        // The last expression of an anonymous function is its value, without a return
+        let closure = Node::AssignmentExpression {
+            identifier: identifier("f"),
+            assignment_operator: Token::Assignment,
+            expr: Box::new(Node::FunctionExpression {
+                parameters: vec!["x".to_string()],
+                parameter_types: vec![None],
+                return_type: None,
+                body: Box::new(vec![BinaryExpr {
+                    l_expr: identifier("x"),
+                    operator: Token::Multiply,
+                    r_expr: Box::new(NumberExpression(Number::Integer(2))),
+                }]),
+            }),
+            annotation: None,
+        };
+        let indexed_call = Node::CallExpression {
+            callee: Box::new(Node::IndexExpression {
+                expr: Box::new(Node::ListExpression(vec![
+                    IdentifierExpression("f".to_string()),
+                    IdentifierExpression("g".to_string()),
+                ])),
+                index: Box::new(NumberExpression(Number::Integer(0))),
+            }),
+            arguments: vec![IdentifierExpression("x".to_string())],
+        };
+        assert_eq!(
+            parser.parse().unwrap(),
+            vec![chained_call, closure, indexed_call]
+        );
+        // A bracket on the next line starts a new statement
+        let nodes = Parser::new("x\n[1]").unwrap().parse().unwrap();
+        assert_eq!(nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_if_values() {
+        let mut parser = Parser::new("y = 1 + if a {\n    2\n} else { 3 }").unwrap();
+        let nodes = parser.parse().unwrap();
+        match &nodes[0] {
+            Node::AssignmentExpression { expr, .. } => match expr.as_ref() {
+                BinaryExpr { r_expr, .. } => assert!(r_expr.has_else()),
+                other => panic!("Expected a binary expression, got {0:?}", other),
+            },
+            other => panic!("Expected an assignment, got {0:?}", other),
+        }
+        // Statements of the branches keep their own lines
+        assert_eq!(parser.statement_lines, vec![1, 2, 3]);
+        // Only an if that always takes a branch gives a value
+        let body = |source: &str| match Parser::new(source).unwrap().parse().unwrap().remove(0) {
+            Node::FunctionExpression { body, .. } => *body,
+            other => panic!("Expected an anonymous function, got {0:?}", other),
+        };
+        assert!(body("fn(x) { if x { 1 } elif y { 2 } else { 3 } }")[0].has_value());
+        assert!(!body("fn(x) { if x { 1 } elif y { 2 } }")[0].has_value());
+    }
+
+    #[test]
+    fn test_type_annotations() {
+        let mut parser =
+            Parser::new("x: num = 5\nfn f(a: str, b) -> bool {\n return true\n}").unwrap();
+        let nodes = parser.parse().unwrap();
+        assert_eq!(
+            nodes[0],
+            Node::AssignmentExpression {
+                identifier: Box::new(IdentifierExpression("x".to_string())),
+                assignment_operator: Token::Assignment,
+                expr: Box::new(NumberExpression(Number::Integer(5))),
+                annotation: Some(Type::Number),
+            }
+        );
+        match &nodes[1] {
+            Node::FunctionDeclaration {
+                parameter_types,
+                return_type,
+                ..
+            } => {
+                assert_eq!(parameter_types, &vec![Some(Type::String), None]);
+                assert_eq!(return_type, &Some(Type::Boolean));
+            }
+            _ => panic!("Expected a function"),
+        }
+        // Other names are enums, the type checker knows which ones exist
+        let nodes = Parser::new("fn paint(c: Color) {\n    return c\n}").unwrap().parse().unwrap();
+        match &nodes[0] {
+            Node::FunctionDeclaration { parameter_types, .. } => {
+                assert_eq!(parameter_types, &vec![Some(Type::Enum("Color".to_string()))]);
+            }
+            _ => panic!("Expected a function"),
+        }
+        assert!(Parser::new("x: 5 = 5").unwrap().parse().is_err());
+        assert!(Parser::new("x: num").unwrap().parse().is_err());
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut parser = Parser::new("# header\nx = 1 # one\n\n# footer").unwrap();
+        let nodes = parser.parse().unwrap();
+        assert_eq!(nodes.len(), 1);
+        let comments: Vec<(usize, &str, bool)> = parser
+            .comments
+            .iter()
+            .map(|comment| (comment.line, comment.text.as_str(), comment.trailing))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![(1, " header", false), (2, " one", true), (4, " footer", false)]
+        );
+    }
+
+    #[test]
+    fn test_structs() {
+        let source = "struct Point {\n    x,\n    y\n}\nimpl Point {\n    fn get(self) {\n        return self.x\n    }\n}\np = Point { x: 1, y: 2 }\np.x = p.get()";
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let identifier = |name: &str| Box::new(IdentifierExpression(name.to_string()));
+        assert_eq!(
+            nodes[0],
+            Node::StructDeclaration {
+                identifier: "Point".to_string(),
+                fields: vec!["x".to_string(), "y".to_string()],
+            }
+        );
+        match &nodes[1] {
+            Node::ImplDeclaration {
+                identifier,
+                methods,
+            } => {
+                assert_eq!(identifier, "Point");
+                assert_eq!(methods.len(), 1);
+            }
+            other => panic!("Expected an impl, got {0:?}", other),
+        }
+        assert_eq!(
+            nodes[2],
+            Node::AssignmentExpression {
+                identifier: identifier("p"),
+                assignment_operator: Token::Assignment,
+                expr: Box::new(Node::StructExpression {
+                    identifier: "Point".to_string(),
+                    fields: vec![
+                        ("x".to_string(), NumberExpression(Number::Integer(1))),
+                        ("y".to_string(), NumberExpression(Number::Integer(2))),
+                    ],
+                }),
+                annotation: None,
+            }
+        );
+        // Method calls are calls of a field
+        assert_eq!(
+            nodes[3],
+            Node::FieldAssignmentExpression {
+                expr: identifier("p"),
+                field: "x".to_string(),
+                value: Box::new(Node::CallExpression {
+                    callee: Box::new(Node::FieldExpression {
+                        expr: identifier("p"),
+                        field: "get".to_string(),
+                    }),
+                    arguments: vec![],
+                }),
+            }
+        );
+        // Every method body has its own statement line
+        assert_eq!(parser.statement_lines, vec![1, 5, 6, 7, 10, 11]);
+        // A brace after a condition starts the block, struct values need parentheses there
+        let nodes = Parser::new("if p == (P { x: 1 }) {\n    p\n}")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(nodes.len(), 1);
+        for source in [
+            "struct P { x, x }",
+            "P { x: 1, x: 2 }",
+            "impl P {\n    x = 1\n}",
+            "impl P {\n    fn f(a) {\n    }\n}",
+            "impl P {\n",
+        ]
+        .iter()
+        {
+            assert!(Parser::new(source).unwrap().parse().is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_enums_and_match() {
+        let source = "enum Shape {\n    Circle(r),\n    Empty\n}\nmatch s {\n    Shape::Circle(r) if r > 1 => r,\n    [x, ..rest] => {\n        x\n    }\n    1..=5 => 0\n}";
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let identifier = |name: &str| Box::new(IdentifierExpression(name.to_string()));
+        assert_eq!(
+            nodes[0],
+            Node::EnumDeclaration {
+                identifier: "Shape".to_string(),
+                variants: vec![
+                    ("Circle".to_string(), vec!["r".to_string()]),
+                    ("Empty".to_string(), vec![]),
+                ],
+            }
+        );
+        assert_eq!(
+            nodes[1],
+            Node::MatchExpression {
+                expr: identifier("s"),
+                arms: vec![
+                    MatchArm {
+                        pattern: Pattern::Variant {
+                            enum_name: "Shape".to_string(),
+                            variant: "Circle".to_string(),
+                            elements: vec![Pattern::Binding(identifier("r"))],
+                        },
+                        guard: Some(ConditionExpression {
+                            l_expr: identifier("r"),
+                            operator: Token::GreaterThan,
+                            r_expr: Box::new(NumberExpression(Number::Integer(1))),
+                        }),
+                        body: vec![IdentifierExpression("r".to_string())],
+                    },
+                    MatchArm {
+                        pattern: Pattern::List {
+                            elements: vec![Pattern::Binding(identifier("x"))],
+                            rest: Some(Box::new(Pattern::Binding(identifier("rest")))),
+                        },
+                        guard: None,
+                        body: vec![IdentifierExpression("x".to_string())],
+                    },
+                    MatchArm {
+                        pattern: Pattern::Range {
+                            start: Box::new(NumberExpression(Number::Integer(1))),
+                            end: Box::new(NumberExpression(Number::Integer(5))),
+                            inclusive: true,
+                        },
+                        guard: None,
+                        body: vec![NumberExpression(Number::Integer(0))],
+                    },
+                ],
+            }
+        );
+        // Every arm body has its own statement lines
+        assert_eq!(parser.statement_lines, vec![1, 5, 6, 8, 10]);
+        let nodes = Parser::new("x = Shape::Circle(1)\nShape::Empty")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            nodes[1],
+            Node::VariantExpression {
+                enum_name: "Shape".to_string(),
+                variant: "Empty".to_string(),
+                arguments: vec![],
+            }
+        );
+        for source in [
+            "enum E { A, A }",
+            "match x {\n    1 2\n}",
+            "match x {\n    [..rest, a] => 1\n}",
+            "match x {\n    P { a, a } => 1\n}",
+            "match x {\n    1..a => 1\n}",
+            "match x 1",
+        ]
+        .iter()
+        {
+            assert!(Parser::new(source).unwrap().parse().is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_try() {
+        let identifier = |name: &str| Box::new(IdentifierExpression(name.to_string()));
+        let mut parser = Parser::new("try {\n    f()\n} catch e {\n    throw e\n} finally {\n    x = 1\n}").unwrap();
+        let nodes = parser.parse().unwrap();
+        assert_eq!(
+            nodes[0],
+            Node::TryExpression {
+                body: Box::new(vec![Node::CallExpression {
+                    callee: identifier("f"),
+                    arguments: vec![],
+                }]),
+                catch: Box::new(Some(CatchClause {
+                    binding: IdentifierExpression("e".to_string()),
+                    body: vec![Node::ThrowExpression(identifier("e"))],
+                })),
+                finally: Box::new(Some(vec![Node::AssignmentExpression {
+                    identifier: identifier("x"),
+                    assignment_operator: Token::Assignment,
+                    expr: Box::new(NumberExpression(Number::Integer(1))),
+                    annotation: None,
+                }])),
+            }
+        );
+        assert_eq!(parser.statement_lines, vec![1, 2, 4, 6]);
+        for (source, message) in [
+            ("try {\n    1\n}", "Missing catch or finally for try"),
+            ("catch e {\n    1\n}", "Found catch without a matching try"),
+            ("throw\n1", "Missing value to throw"),
+            ("try { 1 } catch { 2 }", "Expected a name for the caught error"),
+        ]
+        .iter()
+        {
+            let error = Parser::new(source).unwrap().parse().unwrap_err();
+            assert!(error.to_string().contains(message), "{0}: {1}", source, error);
+        }
+    }
+
+    #[test]
+    fn test_results_and_options() {
+        let identifier = |name: &str| Box::new(IdentifierExpression(name.to_string()));
+        let nodes = Parser::new("x = f(a)?.value?\nnone").unwrap().parse().unwrap();
+        let call = Node::CallExpression {
+            callee: identifier("f"),
+            arguments: vec![IdentifierExpression("a".to_string())],
+        };
+        assert_eq!(
+            nodes[0],
+            Node::AssignmentExpression {
+                identifier: identifier("x"),
+                assignment_operator: Token::Assignment,
+                expr: Box::new(Node::PropagateExpression(Box::new(Node::FieldExpression {
+                    expr: Box::new(Node::PropagateExpression(Box::new(call))),
+                    field: "value".to_string(),
+                }))),
+                annotation: None,
+            }
+        );
+        let none = Node::VariantExpression {
+            enum_name: "Option".to_string(),
+            variant: "None".to_string(),
+            arguments: vec![],
+        };
+        assert_eq!(nodes[1], none);
+        // Short names of built-in variants in patterns, other names stay bindings
+        let nodes = Parser::new("match r {\n    ok(v) => v\n    none => 0\n    some => 1\n}")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let patterns: Vec<Pattern> = match &nodes[0] {
+            Node::MatchExpression { arms, .. } => arms.iter().map(|arm| arm.pattern.clone()).collect(),
+            other => panic!("Expected a match, got {0:?}", other),
+        };
+        assert_eq!(
+            patterns,
+            vec![
+                Pattern::Variant {
+                    enum_name: "Result".to_string(),
+                    variant: "Ok".to_string(),
+                    elements: vec![Pattern::Binding(identifier("v"))],
+                },
+                Pattern::Variant {
+                    enum_name: "Option".to_string(),
+                    variant: "None".to_string(),
+                    elements: vec![],
+                },
+                Pattern::Binding(identifier("some")),
+            ]
+        );
+        // The ? has to be on the same line
+        assert!(Parser::new("x = f()\n?").unwrap().parse().is_err());
+    }
+
+    #[test]
+    fn test_modules() {
+        let source = "import \"lib/geometry.wf\" as geo\nfrom \"util.wf\" import f, g\nexport area, pi";
+        let nodes = Parser::new(source).unwrap().parse().unwrap();
+        assert_eq!(
+            nodes,
+            vec![
+                Node::ImportExpression {
+                    path: "lib/geometry.wf".to_string(),
+                    alias: Box::new(Some(IdentifierExpression("geo".to_string()))),
+                    names: vec![],
+                },
+                Node::ImportExpression {
+                    path: "util.wf".to_string(),
+                    alias: Box::new(None),
+                    names: vec![
+                        IdentifierExpression("f".to_string()),
+                        IdentifierExpression("g".to_string())
+                    ],
+                },
+                Node::ExportStatement(vec!["area".to_string(), "pi".to_string()]),
+            ]
+        );
+        let errors = [
+            ("import geometry as geo", "Expected the path of a module"),
+            ("import \"geometry.wf\"\ngeo", "Missing as after the path of geometry.wf"),
+            ("from \"geometry.wf\" as geo", "Missing import after the path of geometry.wf"),
+            ("from \"util.wf\" import f,\ng", "Expected a name to import from util.wf"),
+            ("export a, a", "Duplicate name a"),
+            ("x as y", "Found as without a matching import"),
+        ];
+        for (source, message) in errors.iter() {
+            let error = Parser::new(source).unwrap().parse().unwrap_err();
+            assert!(error.to_string().contains(message), "{0}: {1}", source, error);
+        }
+    }
 }
@@ -0,0 +1,32 @@
+// Internal modules
+use super::ast::Node;
+use super::parser::Parser;
+
+// Standard library written in wistful, bundled into the binary.
+/*
+    Every evaluator runs the prelude before the script, see `Evaluator::new`. It is a
+    module of its own: only its exported functions can be called, helpers like `fail`
+    stay private. Scripts and modules can replace an exported function by declaring one
+    with the same name. Hosts that want nothing but the builtins use
+    `Evaluator::without_prelude`, and can load their own prelude with
+    `Evaluator::load_prelude`.
+
+    The tests of the prelude are wistful scripts next to it, run by prelude_test.rs.
+*/
+pub const PRELUDE: &str = include_str!("prelude/prelude.wf");
+
+// Names of the functions exported by the bundled prelude, for checking code without an
+// evaluator.
+pub fn names() -> Vec<String> {
+    let nodes = Parser::new(PRELUDE)
+        .and_then(|mut parser| parser.parse())
+        .unwrap_or_default();
+    nodes
+        .into_iter()
+        .filter_map(|node| match node {
+            Node::ExportStatement(names) => Some(names),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
@@ -0,0 +1,137 @@
+// Tests for the prelude, and the wistful test scripts of its functions.
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::rc::Rc;
+
+    use crate::lexer_and_parser::arena::Ast;
+    use crate::lexer_and_parser::builtins::Builtin;
+    use crate::lexer_and_parser::evaluator::Evaluator;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::lexer_and_parser::prelude;
+    use crate::passes::resolver::Resolver;
+    use crate::passes::type_checker::TypeChecker;
+
+    const SCRIPTS: [(&str, &str); 3] = [
+        ("lists_test.wf", include_str!("prelude/lists_test.wf")),
+        ("strings_test.wf", include_str!("prelude/strings_test.wf")),
+        (
+            "assertions_test.wf",
+            include_str!("prelude/assertions_test.wf"),
+        ),
+    ];
+
+    // Check and run the source like the interpreter does, returns the text of the result.
+    fn run(source: &str, evaluator: &mut Evaluator) -> Result<String, String> {
+        let mut parser = Parser::new(source).map_err(|e| e.to_string())?;
+        let nodes = parser.parse().map_err(|e| e.to_string())?;
+        let lines = std::mem::take(&mut parser.statement_lines);
+        let functions: Vec<String> = evaluator.functions.keys().cloned().collect();
+        let mut resolver = Resolver::new()
+            .with_lines(lines.clone())
+            .with_functions(functions.clone());
+        let nodes = resolver.resolve(nodes);
+        let mut checker = TypeChecker::new()
+            .with_lines(lines.clone())
+            .with_functions(functions);
+        checker.check(&nodes);
+        let mut diagnostics = resolver.diagnostics;
+        diagnostics.extend(checker.diagnostics);
+        if let Some(error) = diagnostics.iter().find(|diagnostic| diagnostic.is_error()) {
+            return Err(error.to_string());
+        }
+        evaluator.declare_globals(&resolver.globals).unwrap();
+        evaluator
+            .run(&Rc::new(Ast::with_lines(nodes, lines)))
+            .map(|result| Builtin::text(&result))
+            .map_err(|e| format!("{:#}", e))
+    }
+
+    #[test]
+    fn test_scripts() {
+        for (name, source) in SCRIPTS.iter() {
+            if let Err(error) = run(source, &mut Evaluator::new(None)) {
+                panic!("{0} failed: {1}", name, error);
+            }
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        let names = prelude::names();
+        for name in ["first", "take", "is_empty", "assert_eq", "assert_throws"].iter() {
+            assert!(names.contains(&name.to_string()), "{}", name);
+        }
+        // Helpers that are not exported stay private
+        assert!(!names.contains(&"fail".to_string()));
+        let mut evaluator = Evaluator::new(None);
+        assert!(!evaluator.functions.contains_key("fail"));
+        assert_eq!(
+            run("fail(\"message\")", &mut evaluator),
+            Err("line 1: error: Could not find a function named fail".to_string())
+        );
+    }
+
+    #[test]
+    fn test_overriding() {
+        // Declared functions replace the ones of the prelude
+        let source = "fn first(items) {\n    return items[1]\n}\nfirst([1, 2])";
+        assert_eq!(run(source, &mut Evaluator::new(None)), Ok("2".to_string()));
+        // Other prelude functions keep calling their own helpers
+        let source = "fn fail(message) {\n    return 0\n}\nassert_eq(1, 2)";
+        let error = run(source, &mut Evaluator::new(None)).unwrap_err();
+        assert!(error.starts_with("AssertionError: Expected 2, got 1"), "{}", error);
+    }
+
+    #[test]
+    fn test_without_prelude() {
+        let mut evaluator = Evaluator::without_prelude(None);
+        assert!(evaluator.functions.is_empty());
+        assert_eq!(
+            run("first([1])", &mut evaluator),
+            Err("line 1: error: Could not find a function named first".to_string())
+        );
+        // Builtins are still there
+        assert_eq!(run("sum([1, 2])", &mut evaluator), Ok("3".to_string()));
+    }
+
+    #[test]
+    fn test_custom_prelude() {
+        let mut evaluator = Evaluator::without_prelude(None);
+        let source = "fn double(x) {\n    return x * 2\n}\nexport double\n";
+        evaluator.load_prelude(source).unwrap();
+        assert_eq!(run("double(21)", &mut evaluator), Ok("42".to_string()));
+        assert!(run("first([1])", &mut evaluator).is_err());
+        // Only functions can be exported
+        let error = evaluator.load_prelude("x = 1\nexport x\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The prelude can only export functions, x is not one"
+        );
+        // Errors in the source are reported like in any module
+        let error = evaluator.load_prelude("x = y\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "prelude: line 1: error: Undefined variable y"
+        );
+    }
+
+    #[test]
+    fn test_modules() {
+        // Imported modules can call the prelude as well
+        let directory =
+            std::env::temp_dir().join(format!("wistful-prelude-{0}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let library = "fn head(items) {\n    return unwrap(first(items))\n}\nexport head\n";
+        fs::write(directory.join("library.wf"), library).unwrap();
+        let main = directory.join("main.wf");
+        let mut evaluator = Evaluator::new(None);
+        evaluator.modules.set_script(&main);
+        let result = run(
+            "from \"library.wf\" import head\nhead([7, 8])",
+            &mut evaluator,
+        );
+        let _ = fs::remove_dir_all(&directory);
+        assert_eq!(result, Ok("7".to_string()));
+    }
+}
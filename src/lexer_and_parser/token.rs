@@ -1,23 +1,48 @@
+// Internal modules
+use super::numeric::Number;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Assignment,
     Add,
     Subtract,
     Equals,
+    NotEquals,
     Multiply,
     Divide,
     Pow,
     LeftParenthese,
     RightParenthese,
-    Num(f64),
+    Num(Number),
     Literal { literal: String, keyword: Keyword },
     Identifier(String),
     Whitespace,
+    Newline,
+    // Text after `#` until the end of the line
+    Comment(String),
     EOF,
     LeftBrace,
     RightBrace,
     GreaterThan,
-    LessThan
+    LessThan,
+    GreaterThanOrEquals,
+    LessThanOrEquals,
+    Comma,
+    Colon,
+    Arrow,
+    LeftBracket,
+    RightBracket,
+    // Field access, e.g. `p.x`
+    Dot,
+    // Variant of an enum, e.g. `Shape::Circle`
+    DoubleColon,
+    // Separates the pattern and the value of a match arm
+    FatArrow,
+    // Ranges and the rest of a list in patterns, e.g. `1..5`, `1..=5` and `[x, ..rest]`
+    DotDot,
+    DotDotEquals,
+    // Returns err and none from the function, e.g. `parse(s)?`
+    Question,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -31,6 +56,80 @@ pub enum Keyword {
     ELIF,
     WHILE,
     BREAK,
+    IN,
+    FN,
+    RETURN,
+    STRUCT,
+    IMPL,
+    MATCH,
+    ENUM,
+    TRY,
+    CATCH,
+    FINALLY,
+    THROW,
+    IMPORT,
+    FROM,
+    AS,
+    EXPORT,
+}
+
+impl Keyword {
+    pub const ALL: [Keyword; 24] = [
+        Keyword::None,
+        Keyword::True,
+        Keyword::False,
+        Keyword::IF,
+        Keyword::ENDIF,
+        Keyword::ELSE,
+        Keyword::ELIF,
+        Keyword::WHILE,
+        Keyword::BREAK,
+        Keyword::IN,
+        Keyword::FN,
+        Keyword::RETURN,
+        Keyword::STRUCT,
+        Keyword::IMPL,
+        Keyword::MATCH,
+        Keyword::ENUM,
+        Keyword::TRY,
+        Keyword::CATCH,
+        Keyword::FINALLY,
+        Keyword::THROW,
+        Keyword::IMPORT,
+        Keyword::FROM,
+        Keyword::AS,
+        Keyword::EXPORT,
+    ];
+
+    // Spelling in the source, plain literals have none
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Keyword::None => None,
+            Keyword::True => Some("true"),
+            Keyword::False => Some("false"),
+            Keyword::IF => Some("if"),
+            Keyword::ENDIF => Some("endif"),
+            Keyword::ELSE => Some("else"),
+            Keyword::ELIF => Some("elif"),
+            Keyword::WHILE => Some("while"),
+            Keyword::BREAK => Some("break"),
+            Keyword::IN => Some("in"),
+            Keyword::FN => Some("fn"),
+            Keyword::RETURN => Some("return"),
+            Keyword::STRUCT => Some("struct"),
+            Keyword::IMPL => Some("impl"),
+            Keyword::MATCH => Some("match"),
+            Keyword::ENUM => Some("enum"),
+            Keyword::TRY => Some("try"),
+            Keyword::CATCH => Some("catch"),
+            Keyword::FINALLY => Some("finally"),
+            Keyword::THROW => Some("throw"),
+            Keyword::IMPORT => Some("import"),
+            Keyword::FROM => Some("from"),
+            Keyword::AS => Some("as"),
+            Keyword::EXPORT => Some("export"),
+        }
+    }
 }
 
 // Arithmetic precedences
@@ -38,6 +137,10 @@ pub enum Keyword {
 pub enum Precedence {
     Default,
     // 0,
+    // Comparisons bind looser than any other operator
+    Comparison,
+    // Unit conversion `x in km` binds looser than any arithmetic
+    Conversion,
     AddAndSubtract,
     MultiplyAndDivide,
     Power,
@@ -54,7 +157,32 @@ impl Token {
             Add | Subtract => AddAndSubtract,
             Multiply | Divide => MultiplyAndDivide,
             Pow => Power,
+            Equals | NotEquals | GreaterThan | LessThan | GreaterThanOrEquals
+            | LessThanOrEquals => Comparison,
+            Literal {
+                keyword: Keyword::IN,
+                ..
+            } => Conversion,
             _ => Default,
         }
     }
+
+    // Spelling of an operator in the source
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Token::Assignment => "=",
+            Token::Add => "+",
+            Token::Subtract => "-",
+            Token::Multiply => "*",
+            Token::Divide => "/",
+            Token::Pow => "^",
+            Token::Equals => "==",
+            Token::NotEquals => "!=",
+            Token::GreaterThan => ">",
+            Token::LessThan => "<",
+            Token::GreaterThanOrEquals => ">=",
+            Token::LessThanOrEquals => "<=",
+            _ => "?",
+        }
+    }
 }
@@ -1,8 +1,12 @@
+// Each match arm returns its token explicitly.
+#![allow(clippy::needless_return)]
+
 // Standard libraries
 use std::iter::Peekable;
 use std::str::Chars;
 
 // Internal modules
+use super::numeric::Number;
 use super::token::{Keyword, Token};
 
 // Tokenizer
@@ -22,6 +26,21 @@ pub struct Tokenizer<'a> {
         .peek() return the next character in the give input without consuming it.
     */
     pub expr: Peekable<Chars<'a>>,
+    // Line the tokenizer is currently on, starting from 1
+    pub line: usize,
+    // Column of the next character on the line, starting from 0
+    pub column: usize,
+}
+
+// Position of a token in the source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    // Starting from 1
+    pub line: usize,
+    // Starting from 0, in characters
+    pub column: usize,
+    // Amount of characters
+    pub length: usize,
 }
 
 // Implementation of constructing a new instance of Tokenizer
@@ -31,8 +50,35 @@ impl<'a> Tokenizer<'a> {
     pub fn new(new_expr: &'a str) -> Self {
         Tokenizer {
             expr: new_expr.chars().peekable(),
+            line: 1,
+            column: 0,
         }
     }
+
+    // Next token together with its position, used by editor tooling.
+    pub fn next_spanned(&mut self) -> Option<(Token, Span)> {
+        let (line, column) = (self.line, self.column);
+        let token = self.next()?;
+        // Only a line break or a multi-line string changes the line
+        let length = if self.line == line {
+            self.column - column
+        } else {
+            1
+        };
+        Some((token, Span { line, column, length }))
+    }
+
+    // Consume the next character, keeping track of the position.
+    fn advance(&mut self) -> Option<char> {
+        let next_char = self.expr.next()?;
+        if next_char == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        Some(next_char)
+    }
 }
 
 // Implementation of iterator trait for the Tokenizer
@@ -44,7 +90,7 @@ impl<'a> Iterator for Tokenizer<'a> {
     //     * When the `Iterator` is finished, `None` is returned.
     //     * Otherwise, the next value is wrapped in `Some` and returned.
     fn next(&mut self) -> Option<Token> {
-        let next_char = self.expr.next();
+        let next_char = self.advance();
         // Match is basically the equilevant of switch.
         match next_char {
             // Check if char is a number
@@ -60,24 +106,27 @@ impl<'a> Iterator for Tokenizer<'a> {
                     If the next characters are numbers or a delimiter -> concat values into a
                     one number
                 */
-                while let Some(next_char) = self.expr.peek() {
-                    if next_char.is_numeric() || next_char == &'.' {
-                        number.push(self.expr.next()?);
-                    } else if next_char == &'(' {
+                while let Some(&next_char) = self.expr.peek() {
+                    // Two dots start a range, e.g. `1..5`
+                    let range = next_char == '.' && self.expr.clone().nth(1) == Some('.');
+                    if next_char.is_numeric() || (next_char == '.' && !range) {
+                        number.push(self.advance()?);
+                    } else if next_char == '(' {
                         return None;
                     } else {
                         break;
                     }
                 }
-                Some(Token::Num(number.parse::<f64>().unwrap()))
+                Some(Token::Num(Number::from_decimal(&number).unwrap()))
             }
-            Some('a'..='z') => {
+            Some('a'..='z') | Some('A'..='Z') | Some('_') => {
                 let mut characters = next_char?.to_string();
+                // Identifiers consist of letters, digits and underscores
                 while let Some(next_char) = self.expr.peek() {
-                    if next_char.is_whitespace() {
-                        break;
+                    if next_char.is_alphanumeric() || next_char == &'_' {
+                        characters.push(self.advance()?);
                     } else {
-                        characters.push(self.expr.next()?);
+                        break;
                     }
                 }
                 // Check whether any keywords is matched
@@ -130,6 +179,96 @@ impl<'a> Iterator for Tokenizer<'a> {
                             keyword: Keyword::BREAK,
                         })
                     }
+                    "in" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::IN,
+                        })
+                    }
+                    "fn" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::FN,
+                        })
+                    }
+                    "return" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::RETURN,
+                        })
+                    }
+                    "struct" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::STRUCT,
+                        })
+                    }
+                    "impl" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::IMPL,
+                        })
+                    }
+                    "match" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::MATCH,
+                        })
+                    }
+                    "enum" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::ENUM,
+                        })
+                    }
+                    "try" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::TRY,
+                        })
+                    }
+                    "catch" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::CATCH,
+                        })
+                    }
+                    "finally" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::FINALLY,
+                        })
+                    }
+                    "throw" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::THROW,
+                        })
+                    }
+                    "import" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::IMPORT,
+                        })
+                    }
+                    "from" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::FROM,
+                        })
+                    }
+                    "as" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::AS,
+                        })
+                    }
+                    "export" => {
+                        return Some(Token::Literal {
+                            literal: characters,
+                            keyword: Keyword::EXPORT,
+                        })
+                    }
                     // Rust retardness :D
                     _ => return Some(Token::Identifier(characters)),
                 };
@@ -138,9 +277,11 @@ impl<'a> Iterator for Tokenizer<'a> {
                 let mut characters = String::new();
                 while let Some(next_char) = self.expr.peek() {
                     if next_char == &'"' {
+                        // Skip the closing quote
+                        self.advance();
                         break;
                     } else {
-                        characters.push(self.expr.next()?);
+                        characters.push(self.advance()?);
                     }
                 }
                 Some(Token::Literal {
@@ -150,14 +291,84 @@ impl<'a> Iterator for Tokenizer<'a> {
             }
             // Operators
             Some('+') => Some(Token::Add),
-            Some('-') => Some(Token::Subtract),
+            Some('-') => {
+                if self.expr.peek() == Some(&'>') {
+                    self.advance()?;
+                    Some(Token::Arrow)
+                } else {
+                    Some(Token::Subtract)
+                }
+            }
             Some('*') => Some(Token::Multiply),
             Some('/') => Some(Token::Divide),
             Some('^') => Some(Token::Pow),
+            Some('>') => {
+                if self.expr.peek() == Some(&'=') {
+                    self.advance()?;
+                    Some(Token::GreaterThanOrEquals)
+                } else {
+                    Some(Token::GreaterThan)
+                }
+            }
+            Some('<') => {
+                if self.expr.peek() == Some(&'=') {
+                    self.advance()?;
+                    Some(Token::LessThanOrEquals)
+                } else {
+                    Some(Token::LessThan)
+                }
+            }
+            // '!' is only valid as a part of '!='
+            Some('!') => {
+                if self.expr.peek() == Some(&'=') {
+                    self.advance()?;
+                    Some(Token::NotEquals)
+                } else {
+                    None
+                }
+            }
+            Some('#') => {
+                let mut characters = String::new();
+                // The line break ending the comment is a token of its own
+                while let Some(next_char) = self.expr.peek() {
+                    if next_char == &'\n' {
+                        break;
+                    }
+                    characters.push(self.advance()?);
+                }
+                Some(Token::Comment(characters))
+            }
+            Some(',') => Some(Token::Comma),
+            Some('?') => Some(Token::Question),
+            Some(':') => {
+                if self.expr.peek() == Some(&':') {
+                    self.advance()?;
+                    Some(Token::DoubleColon)
+                } else {
+                    Some(Token::Colon)
+                }
+            }
+            // Decimal points are part of the number, so a dot here is a field access or
+            // a range
+            Some('.') => {
+                if self.expr.peek() != Some(&'.') {
+                    return Some(Token::Dot);
+                }
+                self.advance()?;
+                if self.expr.peek() == Some(&'=') {
+                    self.advance()?;
+                    Some(Token::DotDotEquals)
+                } else {
+                    Some(Token::DotDot)
+                }
+            }
             Some('=') => {
                 if self.expr.peek() == Some(&'=') {
-                    self.expr.next()?;
+                    self.advance()?;
                     Some(Token::Equals)
+                } else if self.expr.peek() == Some(&'>') {
+                    self.advance()?;
+                    Some(Token::FatArrow)
                 } else {
                     Some(Token::Assignment)
                 }
@@ -168,11 +379,14 @@ impl<'a> Iterator for Tokenizer<'a> {
             // Braces
             Some('{') => Some(Token::LeftBrace),
             Some('}') => Some(Token::RightBrace),
+            // Brackets
+            Some('[') => Some(Token::LeftBracket),
+            Some(']') => Some(Token::RightBracket),
             // Whitespace
             //c if c?.is_whitespace() => Some(Token::Whitespace),
             Some(' ') => Some(Token::Whitespace),
-            // EOF
-            Some('\n') => Some(Token::Whitespace),
+            // Line breaks are significant for unit annotations, so they get their own token
+            Some('\n') => Some(Token::Newline),
             Some('\r') => Some(Token::Whitespace),
             // Tab
             Some('\t') => Some(Token::Whitespace),
@@ -0,0 +1,416 @@
+// Standard libraries
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+// Internal modules
+use super::numeric::Number;
+
+// Symbols of the SI base units, in the same order as the exponents in Dimension.
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+// Physical dimension of a value as exponents of the SI base units.
+/*
+    For example acceleration (m/s^2) is [1, 0, -2, 0, 0, 0, 0].
+    A dimension with every exponent set to zero is a plain number.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Dimension(pub [i8; 7]);
+
+// Unit of measure: how many SI base units one of this unit is, and what it measures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unit {
+    pub factor: Number,
+    pub dimension: Dimension,
+}
+
+// Numeric value with a dimension attached.
+/*
+    Value is always stored in SI base units so arithmetic never has to convert.
+    Unit is only used for displaying the value, e.g. `5 km` is stored as 5000 with the
+    display unit ("km", 1000).
+*/
+#[derive(Debug, Clone)]
+pub struct Quantity {
+    pub value: Number,
+    pub dimension: Dimension,
+    pub unit: Option<(String, Number)>,
+}
+
+// Known units by name. Hosts can register their own units on top of the defaults.
+#[derive(Debug, Clone)]
+pub struct UnitRegistry {
+    units: HashMap<String, Unit>,
+}
+
+// Public methods
+impl Dimension {
+    pub const NONE: Dimension = Dimension([0; 7]);
+    pub const LENGTH: Dimension = Dimension([1, 0, 0, 0, 0, 0, 0]);
+    pub const MASS: Dimension = Dimension([0, 1, 0, 0, 0, 0, 0]);
+    pub const TIME: Dimension = Dimension([0, 0, 1, 0, 0, 0, 0]);
+    pub const CURRENT: Dimension = Dimension([0, 0, 0, 1, 0, 0, 0]);
+    pub const TEMPERATURE: Dimension = Dimension([0, 0, 0, 0, 1, 0, 0]);
+    pub const AMOUNT: Dimension = Dimension([0, 0, 0, 0, 0, 1, 0]);
+    pub const LUMINOSITY: Dimension = Dimension([0, 0, 0, 0, 0, 0, 1]);
+
+    pub fn is_none(&self) -> bool {
+        *self == Dimension::NONE
+    }
+
+    // Exponents are small, combining dimensions fails when one no longer fits into an i8.
+    pub fn multiply(&self, other: &Dimension) -> Result<Dimension, String> {
+        let mut exponents = self.0;
+        for (exponent, other) in exponents.iter_mut().zip(other.0.iter()) {
+            *exponent = exponent.checked_add(*other).ok_or_else(out_of_range)?;
+        }
+        Ok(Dimension(exponents))
+    }
+
+    pub fn divide(&self, other: &Dimension) -> Result<Dimension, String> {
+        self.multiply(&other.pow(-1)?)
+    }
+
+    pub fn pow(&self, power: i8) -> Result<Dimension, String> {
+        let mut exponents = self.0;
+        for exponent in exponents.iter_mut() {
+            *exponent = exponent.checked_mul(power).ok_or_else(out_of_range)?;
+        }
+        Ok(Dimension(exponents))
+    }
+}
+
+// Dimensions are displayed in SI base units, e.g. kg*m/s^2
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_unit = |symbol: &str, exponent: i8| {
+            if exponent == 1 {
+                symbol.to_string()
+            } else {
+                format!("{0}^{1}", symbol, exponent)
+            }
+        };
+        let mut positive = Vec::new();
+        let mut negative = Vec::new();
+        for (symbol, exponent) in BASE_UNITS.iter().zip(self.0.iter()) {
+            if *exponent > 0 {
+                positive.push(format_unit(symbol, *exponent));
+            } else if *exponent < 0 {
+                negative.push((symbol, *exponent));
+            }
+        }
+        if positive.is_empty() {
+            // Nothing to divide, so use negative exponents instead. e.g. s^-1
+            let parts: Vec<String> = negative
+                .iter()
+                .map(|(symbol, exponent)| format_unit(symbol, *exponent))
+                .collect();
+            return write!(f, "{}", parts.join("*"));
+        }
+        let mut output = positive.join("*");
+        for (symbol, exponent) in negative {
+            output += &format!("/{}", format_unit(symbol, -exponent));
+        }
+        write!(f, "{}", output)
+    }
+}
+
+impl Unit {
+    pub fn new(factor: Number, dimension: Dimension) -> Self {
+        Unit { factor, dimension }
+    }
+}
+
+// Public methods
+impl Quantity {
+    // Create a quantity from a value given in SI base units.
+    pub fn new(value: Number, dimension: Dimension) -> Self {
+        Quantity {
+            value,
+            dimension,
+            unit: None,
+        }
+    }
+
+    // Dimensionless quantity for plain numbers.
+    pub fn number(value: Number) -> Self {
+        Quantity::new(value, Dimension::NONE)
+    }
+
+    // Create a quantity from a value given in the named unit. e.g. (5, "km")
+    pub fn with_unit(value: Number, name: &str, unit: &Unit) -> Self {
+        Quantity {
+            value: &value * &unit.factor,
+            dimension: unit.dimension,
+            unit: Some((name.to_string(), unit.factor.clone())),
+        }
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.dimension.is_none()
+    }
+
+    // Addition and subtraction require matching dimensions. The left operand's display
+    // unit is kept, so `5 km + 300 m` is shown in km.
+    pub fn add(&self, other: &Quantity) -> Result<Quantity, String> {
+        self.check_dimensions("add", other)?;
+        Ok(self.keep_unit(&self.value + &other.value))
+    }
+
+    pub fn subtract(&self, other: &Quantity) -> Result<Quantity, String> {
+        self.check_dimensions("subtract", other)?;
+        Ok(self.keep_unit(&self.value - &other.value))
+    }
+
+    // Multiplication and division combine dimensions.
+    pub fn multiply(&self, other: &Quantity) -> Result<Quantity, String> {
+        Ok(Quantity::new(
+            &self.value * &other.value,
+            self.dimension.multiply(&other.dimension)?,
+        ))
+    }
+
+    pub fn divide(&self, other: &Quantity) -> Result<Quantity, String> {
+        Ok(Quantity::new(
+            self.value.divide(&other.value)?,
+            self.dimension.divide(&other.dimension)?,
+        ))
+    }
+
+    // Raising a dimensioned value to a power needs a dimensionless integer exponent.
+    pub fn pow(&self, exponent: &Quantity) -> Result<Quantity, String> {
+        if !exponent.is_dimensionless() {
+            return Err(format!(
+                "Exponent must be dimensionless, got {}",
+                exponent.dimension
+            ));
+        }
+        let value = self.value.pow(&exponent.value)?;
+        if self.is_dimensionless() {
+            return Ok(Quantity::number(value));
+        }
+        match exponent.value {
+            Number::Integer(power) if i8::try_from(power).is_ok() => {
+                Ok(Quantity::new(value, self.dimension.pow(power as i8)?))
+            }
+            _ => Err(format!(
+                "Can not raise a value of dimension {0} to the power of {1}",
+                self.dimension, exponent.value
+            )),
+        }
+    }
+
+    pub fn negate(&self) -> Quantity {
+        self.keep_unit(-&self.value)
+    }
+
+    // Express this quantity in the given unit. e.g. `x in km`
+    pub fn convert(&self, name: &str, unit: &Unit) -> Result<Quantity, String> {
+        if self.dimension != unit.dimension {
+            return Err(format!(
+                "Can not convert a value of dimension {0} to {1}",
+                self.display_unit(),
+                name
+            ));
+        }
+        Ok(Quantity {
+            value: self.value.clone(),
+            dimension: self.dimension,
+            unit: Some((name.to_string(), unit.factor.clone())),
+        })
+    }
+
+    // Value expressed in the display unit.
+    pub fn display_value(&self) -> Result<Number, String> {
+        match &self.unit {
+            Some((_, factor)) => self.value.divide(factor),
+            None => Ok(self.value.clone()),
+        }
+    }
+
+    pub fn display_unit(&self) -> String {
+        match &self.unit {
+            Some((name, _)) => name.clone(),
+            None => self.dimension.to_string(),
+        }
+    }
+}
+
+// Private methods
+impl Quantity {
+    fn check_dimensions(&self, operation: &str, other: &Quantity) -> Result<(), String> {
+        if self.dimension != other.dimension {
+            return Err(format!(
+                "Can not {0} values of dimension {1} and {2}",
+                operation,
+                self.dimension_name(),
+                other.dimension_name()
+            ));
+        }
+        Ok(())
+    }
+
+    fn dimension_name(&self) -> String {
+        if self.is_dimensionless() {
+            "none".to_string()
+        } else {
+            self.dimension.to_string()
+        }
+    }
+
+    fn keep_unit(&self, value: Number) -> Quantity {
+        Quantity {
+            value,
+            dimension: self.dimension,
+            unit: self.unit.clone(),
+        }
+    }
+}
+
+// Quantities are equal when they measure the same amount of the same thing.
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Self) -> bool {
+        self.dimension == other.dimension && self.value == other.value
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_dimensionless() && self.unit.is_none() {
+            return write!(f, "{}", self.value);
+        }
+        match self.display_value() {
+            Ok(value) => write!(f, "{0} {1}", value, self.display_unit()),
+            Err(_) => write!(f, "{0} {1}", self.value, self.dimension),
+        }
+    }
+}
+
+// Public methods
+impl UnitRegistry {
+    // Empty registry without any units.
+    pub fn empty() -> Self {
+        UnitRegistry {
+            units: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Unit> {
+        self.units.get(name)
+    }
+
+    // Add (or replace) a unit.
+    pub fn register(&mut self, name: &str, unit: Unit) {
+        self.units.insert(name.to_string(), unit);
+    }
+
+    // Add a unit defined in terms of already known units. e.g. ("mph", 1, "mi/h")
+    pub fn define(&mut self, name: &str, factor: Number, definition: &str) -> Result<(), String> {
+        let unit = self.parse(definition)?;
+        self.register(name, Unit::new(&factor * &unit.factor, unit.dimension));
+        Ok(())
+    }
+
+    // Resolve a unit expression such as `m/s^2` or `kg*m^2`.
+    /*
+        Operators are applied from left to right, so `m/s/s` is the same as `m/s^2`.
+    */
+    pub fn parse(&self, expr: &str) -> Result<Unit, String> {
+        let mut result = Unit::new(Number::Integer(1), Dimension::NONE);
+        let mut divide = false;
+        let mut rest = expr.trim();
+        while !rest.is_empty() {
+            // Split off one `name` or `name^power` term
+            let end = rest.find(['*', '/']).unwrap_or(rest.len());
+            let term = rest[..end].trim();
+            let (name, power) = match term.split_once('^') {
+                Some((name, power)) => (
+                    name.trim(),
+                    power
+                        .trim()
+                        .parse::<i8>()
+                        .map_err(|_| format!("Invalid unit exponent in {}", expr))?,
+                ),
+                None => (term, 1),
+            };
+            let unit = self.get(name).ok_or(format!("Unknown unit {}", name))?;
+            let power = match divide {
+                true => power.checked_neg().ok_or_else(out_of_range)?,
+                false => power,
+            };
+            let factor = unit.factor.pow(&Number::Integer(power as i64))?;
+            result = Unit::new(
+                &result.factor * &factor,
+                result.dimension.multiply(&unit.dimension.pow(power)?)?,
+            );
+            if end == rest.len() {
+                break;
+            }
+            divide = rest[end..].starts_with('/');
+            rest = rest[end + 1..].trim();
+            if rest.is_empty() {
+                return Err(format!("Incomplete unit expression {}", expr));
+            }
+        }
+        Ok(result)
+    }
+}
+
+// Default registry with SI units and some common non-SI units.
+impl Default for UnitRegistry {
+    fn default() -> Self {
+        let mut registry = UnitRegistry::empty();
+        let ratio = |numerator: i64, denominator: i64| {
+            Number::Integer(numerator)
+                .divide(&Number::Integer(denominator))
+                .unwrap()
+        };
+        // SI base units
+        for (symbol, dimension) in [
+            ("m", Dimension::LENGTH),
+            ("kg", Dimension::MASS),
+            ("s", Dimension::TIME),
+            ("A", Dimension::CURRENT),
+            ("K", Dimension::TEMPERATURE),
+            ("mol", Dimension::AMOUNT),
+            ("cd", Dimension::LUMINOSITY),
+        ] {
+            registry.register(symbol, Unit::new(Number::Integer(1), dimension));
+        }
+        // Scaled units
+        let scaled = [
+            ("km", ratio(1000, 1), "m"),
+            ("cm", ratio(1, 100), "m"),
+            ("mm", ratio(1, 1000), "m"),
+            ("mi", ratio(1609344, 1000), "m"),
+            ("yd", ratio(9144, 10000), "m"),
+            ("ft", ratio(3048, 10000), "m"),
+            ("g", ratio(1, 1000), "kg"),
+            ("mg", ratio(1, 1000000), "kg"),
+            ("t", ratio(1000, 1), "kg"),
+            ("lb", ratio(45359237, 100000000), "kg"),
+            ("ms", ratio(1, 1000), "s"),
+            ("min", ratio(60, 1), "s"),
+            ("h", ratio(3600, 1), "s"),
+            ("d", ratio(86400, 1), "s"),
+            // Derived SI units
+            ("N", ratio(1, 1), "kg*m/s^2"),
+            ("J", ratio(1, 1), "N*m"),
+            ("W", ratio(1, 1), "J/s"),
+            ("Pa", ratio(1, 1), "N/m^2"),
+            ("Hz", ratio(1, 1), "s^-1"),
+            ("C", ratio(1, 1), "A*s"),
+            ("V", ratio(1, 1), "W/A"),
+            ("L", ratio(1, 1000), "m^3"),
+            ("kWh", ratio(3600000, 1), "J"),
+        ];
+        for (name, factor, definition) in scaled.iter() {
+            registry.define(name, factor.clone(), definition).unwrap();
+        }
+        registry
+    }
+}
+
+fn out_of_range() -> String {
+    "Unit exponent out of range".to_string()
+}
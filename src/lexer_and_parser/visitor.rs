@@ -0,0 +1,572 @@
+// Internal modules
+use super::ast::{CatchClause, MatchArm, Node};
+
+// Traversal of syntax trees
+/*
+    Passes implement one of the traits below and override only the nodes they care
+    about, handing everything else to the matching walk function:
+
+        impl Visitor for Counter {
+            fn visit_node(&mut self, node: &Node) {
+                if let Node::BreakExpression = node {
+                    self.breaks += 1;
+                }
+                walk_node(self, node);
+            }
+        }
+
+    - Visitor reads the tree.
+    - MutVisitor changes the tree in place, blocks can gain or lose statements.
+    - Fold takes the tree by value and builds a new one, e.g. to replace nodes by nodes
+      of another variant.
+
+    The walk functions are the only place that lists the children of every variant, in
+    source order: conditions before their blocks, `then_branch` before `else_branch`.
+    Patterns of match arms list their own nodes, see `Pattern::nodes`.
+    Statements go through the block methods, which is where passes that follow the
+    parser's statement lines advance them.
+*/
+pub trait Visitor {
+    fn visit_node(&mut self, node: &Node) {
+        walk_node(self, node);
+    }
+
+    fn visit_block(&mut self, nodes: &[Node]) {
+        walk_block(self, nodes);
+    }
+}
+
+pub trait MutVisitor {
+    fn visit_node_mut(&mut self, node: &mut Node) {
+        walk_node_mut(self, node);
+    }
+
+    fn visit_block_mut(&mut self, nodes: &mut Vec<Node>) {
+        walk_block_mut(self, nodes);
+    }
+}
+
+pub trait Fold {
+    fn fold_node(&mut self, node: Node) -> Node {
+        fold_children(self, node)
+    }
+
+    fn fold_block(&mut self, nodes: Vec<Node>) -> Vec<Node> {
+        fold_statements(self, nodes)
+    }
+}
+
+// Visit the children of a node.
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    match node {
+        Node::NumberExpression(_)
+        | Node::LiteralExpression(..)
+        | Node::IdentifierExpression(_)
+        | Node::VariableExpression { .. }
+        | Node::BreakExpression
+        | Node::StructDeclaration { .. }
+        | Node::EnumDeclaration { .. }
+        | Node::ExportStatement(_)
+        | Node::EOF(_) => {}
+        Node::NegativeNumberExpression(expr)
+        | Node::QuantityExpression { value: expr, .. }
+        | Node::ConversionExpression { expr, .. } => visitor.visit_node(expr),
+        Node::BinaryExpr { l_expr, r_expr, .. }
+        | Node::ConditionExpression { l_expr, r_expr, .. } => {
+            visitor.visit_node(l_expr);
+            visitor.visit_node(r_expr);
+        }
+        Node::AssignmentExpression {
+            identifier, expr, ..
+        } => {
+            visitor.visit_node(identifier);
+            visitor.visit_node(expr);
+        }
+        Node::IfExpression {
+            condition,
+            then_branch,
+            else_branch,
+        }
+        | Node::ElseExpression {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            if let Some(condition) = condition.as_ref() {
+                visitor.visit_node(condition);
+            }
+            visitor.visit_block(then_branch);
+            if let Some(else_branch) = else_branch.as_ref() {
+                visitor.visit_node(else_branch);
+            }
+        }
+        Node::WhileExpression {
+            condition,
+            then_branch,
+        } => {
+            if let Some(condition) = condition.as_ref() {
+                visitor.visit_node(condition);
+            }
+            visitor.visit_block(then_branch);
+        }
+        Node::FunctionDeclaration { body, .. } | Node::FunctionExpression { body, .. } => {
+            visitor.visit_block(body)
+        }
+        Node::CallExpression { callee, arguments } => {
+            visitor.visit_node(callee);
+            for argument in arguments {
+                visitor.visit_node(argument);
+            }
+        }
+        Node::ListExpression(elements) => {
+            for element in elements {
+                visitor.visit_node(element);
+            }
+        }
+        Node::IndexExpression { expr, index } => {
+            visitor.visit_node(expr);
+            visitor.visit_node(index);
+        }
+        Node::ImplDeclaration { methods, .. } => visitor.visit_block(methods),
+        Node::StructExpression { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_node(value);
+            }
+        }
+        Node::FieldExpression { expr, .. } => visitor.visit_node(expr),
+        Node::FieldAssignmentExpression { expr, value, .. } => {
+            visitor.visit_node(expr);
+            visitor.visit_node(value);
+        }
+        Node::VariantExpression { arguments, .. } => {
+            for argument in arguments {
+                visitor.visit_node(argument);
+            }
+        }
+        Node::MatchExpression { expr, arms } => {
+            visitor.visit_node(expr);
+            for arm in arms {
+                for node in arm.pattern.nodes() {
+                    visitor.visit_node(node);
+                }
+                if let Some(guard) = &arm.guard {
+                    visitor.visit_node(guard);
+                }
+                visitor.visit_block(&arm.body);
+            }
+        }
+        Node::TryExpression {
+            body,
+            catch,
+            finally,
+        } => {
+            visitor.visit_block(body);
+            if let Some(catch) = catch.as_ref() {
+                visitor.visit_node(&catch.binding);
+                visitor.visit_block(&catch.body);
+            }
+            if let Some(finally) = finally.as_ref() {
+                visitor.visit_block(finally);
+            }
+        }
+        Node::ThrowExpression(value) | Node::PropagateExpression(value) => visitor.visit_node(value),
+        Node::ImportExpression { alias, names, .. } => {
+            if let Some(alias) = alias.as_ref() {
+                visitor.visit_node(alias);
+            }
+            for name in names {
+                visitor.visit_node(name);
+            }
+        }
+        Node::ReturnExpression(value) => {
+            if let Some(value) = value.as_ref() {
+                visitor.visit_node(value);
+            }
+        }
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, nodes: &[Node]) {
+    for node in nodes {
+        visitor.visit_node(node);
+    }
+}
+
+// Visit the children of a node, allowing them to be changed.
+pub fn walk_node_mut<V: MutVisitor + ?Sized>(visitor: &mut V, node: &mut Node) {
+    match node {
+        Node::NumberExpression(_)
+        | Node::LiteralExpression(..)
+        | Node::IdentifierExpression(_)
+        | Node::VariableExpression { .. }
+        | Node::BreakExpression
+        | Node::StructDeclaration { .. }
+        | Node::EnumDeclaration { .. }
+        | Node::ExportStatement(_)
+        | Node::EOF(_) => {}
+        Node::NegativeNumberExpression(expr)
+        | Node::QuantityExpression { value: expr, .. }
+        | Node::ConversionExpression { expr, .. } => visitor.visit_node_mut(expr),
+        Node::BinaryExpr { l_expr, r_expr, .. }
+        | Node::ConditionExpression { l_expr, r_expr, .. } => {
+            visitor.visit_node_mut(l_expr);
+            visitor.visit_node_mut(r_expr);
+        }
+        Node::AssignmentExpression {
+            identifier, expr, ..
+        } => {
+            visitor.visit_node_mut(identifier);
+            visitor.visit_node_mut(expr);
+        }
+        Node::IfExpression {
+            condition,
+            then_branch,
+            else_branch,
+        }
+        | Node::ElseExpression {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            if let Some(condition) = condition.as_mut() {
+                visitor.visit_node_mut(condition);
+            }
+            visitor.visit_block_mut(then_branch);
+            if let Some(else_branch) = else_branch.as_mut() {
+                visitor.visit_node_mut(else_branch);
+            }
+        }
+        Node::WhileExpression {
+            condition,
+            then_branch,
+        } => {
+            if let Some(condition) = condition.as_mut() {
+                visitor.visit_node_mut(condition);
+            }
+            visitor.visit_block_mut(then_branch);
+        }
+        Node::FunctionDeclaration { body, .. } | Node::FunctionExpression { body, .. } => {
+            visitor.visit_block_mut(body)
+        }
+        Node::CallExpression { callee, arguments } => {
+            visitor.visit_node_mut(callee);
+            for argument in arguments {
+                visitor.visit_node_mut(argument);
+            }
+        }
+        Node::ListExpression(elements) => {
+            for element in elements {
+                visitor.visit_node_mut(element);
+            }
+        }
+        Node::IndexExpression { expr, index } => {
+            visitor.visit_node_mut(expr);
+            visitor.visit_node_mut(index);
+        }
+        Node::ImplDeclaration { methods, .. } => visitor.visit_block_mut(methods),
+        Node::StructExpression { fields, .. } => {
+            for (_, value) in fields {
+                visitor.visit_node_mut(value);
+            }
+        }
+        Node::FieldExpression { expr, .. } => visitor.visit_node_mut(expr),
+        Node::FieldAssignmentExpression { expr, value, .. } => {
+            visitor.visit_node_mut(expr);
+            visitor.visit_node_mut(value);
+        }
+        Node::VariantExpression { arguments, .. } => {
+            for argument in arguments {
+                visitor.visit_node_mut(argument);
+            }
+        }
+        Node::MatchExpression { expr, arms } => {
+            visitor.visit_node_mut(expr);
+            for arm in arms {
+                for node in arm.pattern.nodes_mut() {
+                    visitor.visit_node_mut(node);
+                }
+                if let Some(guard) = &mut arm.guard {
+                    visitor.visit_node_mut(guard);
+                }
+                visitor.visit_block_mut(&mut arm.body);
+            }
+        }
+        Node::TryExpression {
+            body,
+            catch,
+            finally,
+        } => {
+            visitor.visit_block_mut(body);
+            if let Some(catch) = catch.as_mut() {
+                visitor.visit_node_mut(&mut catch.binding);
+                visitor.visit_block_mut(&mut catch.body);
+            }
+            if let Some(finally) = finally.as_mut() {
+                visitor.visit_block_mut(finally);
+            }
+        }
+        Node::ThrowExpression(value) | Node::PropagateExpression(value) => {
+            visitor.visit_node_mut(value)
+        }
+        Node::ImportExpression { alias, names, .. } => {
+            if let Some(alias) = alias.as_mut() {
+                visitor.visit_node_mut(alias);
+            }
+            for name in names {
+                visitor.visit_node_mut(name);
+            }
+        }
+        Node::ReturnExpression(value) => {
+            if let Some(value) = value.as_mut() {
+                visitor.visit_node_mut(value);
+            }
+        }
+    }
+}
+
+pub fn walk_block_mut<V: MutVisitor + ?Sized>(visitor: &mut V, nodes: &mut Vec<Node>) {
+    for node in nodes {
+        visitor.visit_node_mut(node);
+    }
+}
+
+// Rebuild a node from its folded children.
+pub fn fold_children<F: Fold + ?Sized>(folder: &mut F, node: Node) -> Node {
+    let mut fold = |node: Box<Node>| Box::new(folder.fold_node(*node));
+    match node {
+        Node::NumberExpression(_)
+        | Node::LiteralExpression(..)
+        | Node::IdentifierExpression(_)
+        | Node::VariableExpression { .. }
+        | Node::BreakExpression
+        | Node::StructDeclaration { .. }
+        | Node::EnumDeclaration { .. }
+        | Node::ExportStatement(_)
+        | Node::EOF(_) => node,
+        Node::NegativeNumberExpression(expr) => Node::NegativeNumberExpression(fold(expr)),
+        Node::QuantityExpression { value, unit } => Node::QuantityExpression {
+            value: fold(value),
+            unit,
+        },
+        Node::ConversionExpression { expr, unit } => Node::ConversionExpression {
+            expr: fold(expr),
+            unit,
+        },
+        Node::BinaryExpr {
+            l_expr,
+            operator,
+            r_expr,
+        } => Node::BinaryExpr {
+            l_expr: fold(l_expr),
+            operator,
+            r_expr: fold(r_expr),
+        },
+        Node::ConditionExpression {
+            l_expr,
+            operator,
+            r_expr,
+        } => Node::ConditionExpression {
+            l_expr: fold(l_expr),
+            operator,
+            r_expr: fold(r_expr),
+        },
+        Node::AssignmentExpression {
+            identifier,
+            assignment_operator,
+            expr,
+            annotation,
+        } => Node::AssignmentExpression {
+            identifier: fold(identifier),
+            assignment_operator,
+            expr: fold(expr),
+            annotation,
+        },
+        Node::IfExpression {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let (condition, then_branch, else_branch) =
+                fold_branches(folder, *condition, *then_branch, *else_branch);
+            Node::IfExpression {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            }
+        }
+        Node::ElseExpression {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            let (condition, then_branch, else_branch) =
+                fold_branches(folder, *condition, *then_branch, *else_branch);
+            Node::ElseExpression {
+                condition: Box::new(condition),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            }
+        }
+        Node::WhileExpression {
+            condition,
+            then_branch,
+        } => {
+            let condition = condition.map(|condition| folder.fold_node(condition));
+            Node::WhileExpression {
+                condition: Box::new(condition),
+                then_branch: Box::new(folder.fold_block(*then_branch)),
+            }
+        }
+        Node::FunctionDeclaration {
+            identifier,
+            parameters,
+            parameter_types,
+            return_type,
+            body,
+        } => Node::FunctionDeclaration {
+            identifier,
+            parameters,
+            parameter_types,
+            return_type,
+            body: Box::new(folder.fold_block(*body)),
+        },
+        Node::FunctionExpression {
+            parameters,
+            parameter_types,
+            return_type,
+            body,
+        } => Node::FunctionExpression {
+            parameters,
+            parameter_types,
+            return_type,
+            body: Box::new(folder.fold_block(*body)),
+        },
+        Node::CallExpression { callee, arguments } => {
+            let callee = fold(callee);
+            Node::CallExpression {
+                callee,
+                arguments: arguments
+                    .into_iter()
+                    .map(|argument| folder.fold_node(argument))
+                    .collect(),
+            }
+        }
+        Node::ListExpression(elements) => Node::ListExpression(
+            elements
+                .into_iter()
+                .map(|element| folder.fold_node(element))
+                .collect(),
+        ),
+        Node::IndexExpression { expr, index } => {
+            let expr = fold(expr);
+            Node::IndexExpression {
+                expr,
+                index: fold(index),
+            }
+        }
+        Node::ImplDeclaration {
+            identifier,
+            methods,
+        } => Node::ImplDeclaration {
+            identifier,
+            methods: Box::new(folder.fold_block(*methods)),
+        },
+        Node::StructExpression { identifier, fields } => Node::StructExpression {
+            identifier,
+            fields: fields
+                .into_iter()
+                .map(|(field, value)| (field, folder.fold_node(value)))
+                .collect(),
+        },
+        Node::FieldExpression { expr, field } => Node::FieldExpression {
+            expr: fold(expr),
+            field,
+        },
+        Node::FieldAssignmentExpression { expr, field, value } => {
+            let expr = fold(expr);
+            Node::FieldAssignmentExpression {
+                expr,
+                field,
+                value: fold(value),
+            }
+        }
+        Node::VariantExpression {
+            enum_name,
+            variant,
+            arguments,
+        } => Node::VariantExpression {
+            enum_name,
+            variant,
+            arguments: arguments
+                .into_iter()
+                .map(|argument| folder.fold_node(argument))
+                .collect(),
+        },
+        Node::MatchExpression { expr, arms } => {
+            let expr = fold(expr);
+            let arms = arms
+                .into_iter()
+                .map(|arm| {
+                    let pattern = arm.pattern.map(&mut |node| folder.fold_node(node));
+                    let guard = arm.guard.map(|guard| folder.fold_node(guard));
+                    MatchArm {
+                        pattern,
+                        guard,
+                        body: folder.fold_block(arm.body),
+                    }
+                })
+                .collect();
+            Node::MatchExpression { expr, arms }
+        }
+        Node::TryExpression {
+            body,
+            catch,
+            finally,
+        } => {
+            let body = folder.fold_block(*body);
+            let catch = catch.map(|catch| {
+                let binding = folder.fold_node(catch.binding);
+                CatchClause {
+                    binding,
+                    body: folder.fold_block(catch.body),
+                }
+            });
+            let finally = finally.map(|finally| folder.fold_block(finally));
+            Node::TryExpression {
+                body: Box::new(body),
+                catch: Box::new(catch),
+                finally: Box::new(finally),
+            }
+        }
+        Node::ThrowExpression(value) => Node::ThrowExpression(fold(value)),
+        Node::PropagateExpression(value) => Node::PropagateExpression(fold(value)),
+        Node::ImportExpression { path, alias, names } => Node::ImportExpression {
+            path,
+            alias: Box::new(alias.map(|alias| folder.fold_node(alias))),
+            names: names.into_iter().map(|name| folder.fold_node(name)).collect(),
+        },
+        Node::ReturnExpression(value) => {
+            Node::ReturnExpression(Box::new(value.map(|value| folder.fold_node(value))))
+        }
+    }
+}
+
+pub fn fold_statements<F: Fold + ?Sized>(folder: &mut F, nodes: Vec<Node>) -> Vec<Node> {
+    nodes
+        .into_iter()
+        .map(|node| folder.fold_node(node))
+        .collect()
+}
+
+// Condition, block and else branch of an if or elif
+fn fold_branches<F: Fold + ?Sized>(
+    folder: &mut F,
+    condition: Option<Node>,
+    then_branch: Vec<Node>,
+    else_branch: Option<Node>,
+) -> (Option<Node>, Vec<Node>, Option<Node>) {
+    let condition = condition.map(|condition| folder.fold_node(condition));
+    let then_branch = folder.fold_block(then_branch);
+    let else_branch = else_branch.map(|else_branch| folder.fold_node(else_branch));
+    (condition, then_branch, else_branch)
+}
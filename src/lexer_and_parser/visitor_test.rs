@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::ast::Node;
+    use crate::lexer_and_parser::numeric::Number;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::lexer_and_parser::visitor::{
+        fold_children, walk_block, walk_block_mut, walk_node, walk_node_mut, Fold, MutVisitor,
+        Visitor,
+    };
+
+    const SOURCE: &str = "x = -(1 + 2) * 3
+if x >= 2 km in m {
+    x = x ^ 2
+} elif x != 1 {
+    break
+} else {
+    y = true
+}
+while x < 10 {
+    x = x / 2
+}
+fn add(a, b) {
+    return a + b
+}
+add(x, y)";
+
+    fn parse(source: &str) -> Vec<Node> {
+        Parser::new(source).unwrap().parse().unwrap()
+    }
+
+    // Identifiers in visiting order, and the statements of every block
+    struct Collector {
+        identifiers: Vec<String>,
+        statements: usize,
+    }
+
+    impl Visitor for Collector {
+        fn visit_node(&mut self, node: &Node) {
+            if let Node::IdentifierExpression(identifier) = node {
+                self.identifiers.push(identifier.clone());
+            }
+            walk_node(self, node);
+        }
+
+        fn visit_block(&mut self, nodes: &[Node]) {
+            self.statements += nodes.len();
+            walk_block(self, nodes);
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let mut collector = Collector {
+            identifiers: Vec::new(),
+            statements: 0,
+        };
+        collector.visit_block(&parse(SOURCE));
+        assert_eq!(
+            collector.identifiers,
+            ["x", "x", "x", "x", "x", "y", "x", "x", "x", "a", "b", "add", "x", "y"]
+        );
+        // The parser records one line for every statement in every block
+        let mut parser = Parser::new(SOURCE).unwrap();
+        parser.parse().unwrap();
+        assert_eq!(collector.statements, parser.statement_lines.len());
+    }
+
+    // Renames variables and drops break statements
+    struct Renamer;
+
+    impl MutVisitor for Renamer {
+        fn visit_node_mut(&mut self, node: &mut Node) {
+            if let Node::IdentifierExpression(identifier) = node {
+                identifier.insert_str(0, "renamed_");
+            }
+            walk_node_mut(self, node);
+        }
+
+        fn visit_block_mut(&mut self, nodes: &mut Vec<Node>) {
+            nodes.retain(|node| *node != Node::BreakExpression);
+            walk_block_mut(self, nodes);
+        }
+    }
+
+    #[test]
+    fn test_mut_visitor() {
+        let mut nodes = parse("if x > 1 {\n    break\n    x = y\n}");
+        Renamer.visit_block_mut(&mut nodes);
+        assert_eq!(
+            nodes,
+            parse("if renamed_x > 1 {\n    renamed_x = renamed_y\n}")
+        );
+    }
+
+    // Replaces identifiers by numbers, a different variant
+    struct Substitute;
+
+    impl Fold for Substitute {
+        fn fold_node(&mut self, node: Node) -> Node {
+            match node {
+                Node::IdentifierExpression(identifier) if identifier == "x" => {
+                    Node::NumberExpression(Number::Integer(42))
+                }
+                node => fold_children(self, node),
+            }
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        let nodes = Substitute.fold_block(parse("while x < 10 {\n    y = x + 1\n}\nadd(x)"));
+        assert_eq!(nodes, parse("while 42 < 10 {\n    y = 42 + 1\n}\nadd(42)"));
+    }
+
+    #[test]
+    fn test_default_fold_is_identity() {
+        struct Identity;
+        impl Fold for Identity {}
+        let nodes = parse(SOURCE);
+        assert_eq!(Identity.fold_block(nodes.clone()), nodes);
+    }
+}
@@ -0,0 +1,6 @@
+// Add public modules
+pub mod bytecode;
+pub mod lexer_and_parser;
+pub mod lsp;
+pub mod passes;
+pub mod serialization;
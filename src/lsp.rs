@@ -0,0 +1,6 @@
+// Add public modules
+pub mod analysis;
+pub mod server;
+// Test modules
+pub mod analysis_test;
+pub mod server_test;
@@ -0,0 +1,576 @@
+// Standard libraries
+use std::collections::HashMap;
+
+// Internal modules
+use crate::lexer_and_parser::ast::{Node, Type};
+use crate::lexer_and_parser::parser::Parser;
+use crate::lexer_and_parser::prelude;
+use crate::lexer_and_parser::token::{Keyword, Token};
+use crate::lexer_and_parser::tokenizer::{Span, Tokenizer};
+use crate::passes::diagnostic::Diagnostic;
+use crate::passes::resolver::Resolver;
+use crate::passes::type_checker::TypeChecker;
+
+// Analysis of one document for the language server
+/*
+    The syntax tree has no positions, so everything that needs them works on the tokens:
+    function bodies are found by matching braces, definitions are names followed by `=`
+    or by a type annotation and the parameters of function declarations. Names after a
+    dot are fields or methods of a struct value and never refer to a definition. The resolution rules match the resolver,
+    a name assigned anywhere in a function is local to it, everything else is global.
+
+    Lines start from 1 and columns from 0, like in `Span`.
+*/
+pub struct Analysis {
+    // Tokens without whitespace and line breaks
+    tokens: Vec<(Token, Span)>,
+    pub diagnostics: Vec<Diagnostic>,
+    // Inferred variable types by statement line and name, see TypeChecker
+    variable_types: HashMap<(usize, String), Type>,
+    statement_lines: Vec<usize>,
+    // Signatures of declared functions, e.g. `fn add(a: num, b) -> num`
+    signatures: HashMap<String, String>,
+    functions: Vec<FunctionScope>,
+    definitions: Vec<Definition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Parameter,
+    Function,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // Position of the name
+    pub span: Span,
+    // Whole declaration, from the start of the name to the closing brace of a function
+    pub end: (usize, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionKind {
+    Keyword,
+    Variable,
+    Function,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+}
+
+// Token types of semantic highlighting, in the order of `SemanticKind::LEGEND`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SemanticKind {
+    Keyword,
+    Variable,
+    Function,
+    Parameter,
+    Number,
+    String,
+    Operator,
+    Comment,
+    Type,
+}
+
+struct FunctionScope {
+    // Body from the opening to the closing brace
+    start: (usize, usize),
+    end: (usize, usize),
+}
+
+struct Definition {
+    name: String,
+    kind: SymbolKind,
+    span: Span,
+    // Index of the function the definition belongs to, None for globals.
+    // Functions are global, their own index is kept for the symbol range.
+    scope: Option<usize>,
+}
+
+// Public methods
+impl SemanticKind {
+    pub const LEGEND: [&'static str; 9] = [
+        "keyword",
+        "variable",
+        "function",
+        "parameter",
+        "number",
+        "string",
+        "operator",
+        "comment",
+        "type",
+    ];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        let mut analysis = Analysis {
+            tokens: Vec::new(),
+            diagnostics: Vec::new(),
+            variable_types: HashMap::new(),
+            statement_lines: Vec::new(),
+            signatures: HashMap::new(),
+            functions: Vec::new(),
+            definitions: Vec::new(),
+        };
+        // Tokens up to the first invalid character
+        let mut tokenizer = Tokenizer::new(source);
+        while let Some((token, span)) = tokenizer.next_spanned() {
+            match token {
+                Token::EOF => break,
+                Token::Whitespace | Token::Newline => {}
+                _ => analysis.tokens.push((token, span)),
+            }
+        }
+        analysis.check(source);
+        analysis.index();
+        analysis
+    }
+
+    // Type of the variable or signature of the function at the position.
+    pub fn hover(&self, line: usize, column: usize) -> Option<String> {
+        let index = self.token_at(line, column)?;
+        let (token, span) = &self.tokens[index];
+        match token {
+            Token::Num(_) => Some("num".to_string()),
+            Token::Literal {
+                keyword: Keyword::None,
+                ..
+            } => Some("str".to_string()),
+            Token::Literal {
+                keyword: Keyword::True,
+                ..
+            }
+            | Token::Literal {
+                keyword: Keyword::False,
+                ..
+            } => Some("bool".to_string()),
+            Token::Identifier(name) => {
+                let definition = self.resolve(index);
+                if let Some(Definition {
+                    kind: SymbolKind::Function,
+                    ..
+                }) = definition
+                {
+                    return self.signatures.get(name).cloned();
+                }
+                // Type at the statement the name is used in
+                let statement = self
+                    .statement_lines
+                    .iter()
+                    .rev()
+                    .find(|statement| **statement <= span.line)?;
+                let name_type = match self.variable_types.get(&(*statement, name.clone())) {
+                    Some(name_type) => name_type.to_string(),
+                    None if definition.is_some() => "unknown".to_string(),
+                    None => return None,
+                };
+                Some(format!("{0}: {1}", name, name_type))
+            }
+            _ => None,
+        }
+    }
+
+    // Definition of the variable or function at the position.
+    pub fn definition(&self, line: usize, column: usize) -> Option<Span> {
+        let index = self.token_at(line, column)?;
+        self.resolve(index).map(|definition| definition.span)
+    }
+
+    // Functions and global variables of the document.
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = Vec::new();
+        for definition in &self.definitions {
+            let end = match definition.kind {
+                SymbolKind::Function => match definition.scope {
+                    Some(scope) => self.functions[scope].end,
+                    None => continue,
+                },
+                _ if definition.scope.is_some() => continue,
+                // Only the first assignment declares the variable
+                _ if symbols.iter().any(|symbol| symbol.name == definition.name) => continue,
+                _ => (
+                    definition.span.line,
+                    definition.span.column + definition.span.length,
+                ),
+            };
+            symbols.push(Symbol {
+                name: definition.name.clone(),
+                kind: definition.kind,
+                span: definition.span,
+                end,
+            });
+        }
+        symbols
+    }
+
+    // Keywords and the names visible at the position.
+    pub fn completions(&self, line: usize, column: usize) -> Vec<Completion> {
+        let mut completions: Vec<Completion> = Keyword::ALL
+            .iter()
+            .filter_map(Keyword::name)
+            .map(|name| Completion {
+                label: name.to_string(),
+                kind: CompletionKind::Keyword,
+            })
+            .collect();
+        let scopes = self.scopes_at(line, column);
+        for definition in &self.definitions {
+            let visible = match (definition.kind, definition.scope) {
+                (SymbolKind::Function, _) | (_, None) => true,
+                (_, Some(scope)) => scopes.contains(&scope),
+            };
+            if !visible || completions.iter().any(|c| c.label == definition.name) {
+                continue;
+            }
+            completions.push(Completion {
+                label: definition.name.clone(),
+                kind: match definition.kind {
+                    SymbolKind::Function => CompletionKind::Function,
+                    _ => CompletionKind::Variable,
+                },
+            });
+        }
+        completions
+    }
+
+    // Every token that has a highlighting kind, in source order.
+    pub fn semantic_tokens(&self) -> Vec<(Span, SemanticKind)> {
+        let mut tokens = Vec::new();
+        for (i, (token, span)) in self.tokens.iter().enumerate() {
+            let kind = match token {
+                Token::Comment(_) => SemanticKind::Comment,
+                Token::Num(_) => SemanticKind::Number,
+                Token::Literal {
+                    keyword: Keyword::None,
+                    ..
+                } => SemanticKind::String,
+                Token::Literal { .. } => SemanticKind::Keyword,
+                Token::Identifier(_) if self.follows_type_marker(i) => SemanticKind::Type,
+                Token::Identifier(_) => match self.resolve(i).map(|definition| definition.kind) {
+                    Some(SymbolKind::Function) => SemanticKind::Function,
+                    Some(SymbolKind::Parameter) => SemanticKind::Parameter,
+                    _ => SemanticKind::Variable,
+                },
+                Token::Add
+                | Token::Subtract
+                | Token::Multiply
+                | Token::Divide
+                | Token::Pow
+                | Token::Assignment
+                | Token::Equals
+                | Token::NotEquals
+                | Token::GreaterThan
+                | Token::LessThan
+                | Token::GreaterThanOrEquals
+                | Token::LessThanOrEquals
+                | Token::Arrow
+                | Token::FatArrow
+                | Token::Question => SemanticKind::Operator,
+                _ => continue,
+            };
+            tokens.push((*span, kind));
+        }
+        tokens
+    }
+}
+
+// Private methods
+impl Analysis {
+    // Parse, resolve and type check the source.
+    fn check(&mut self, source: &str) {
+        let mut parser = match Parser::new(source) {
+            Ok(parser) => parser,
+            Err(e) => {
+                self.diagnostics
+                    .push(Diagnostic::error(e.to_string(), Some(1)));
+                return;
+            }
+        };
+        let nodes = match parser.parse() {
+            Ok(nodes) => nodes,
+            Err(e) => {
+                let line = parser.tokenizer.line;
+                self.diagnostics
+                    .push(Diagnostic::error(e.to_string(), Some(line)));
+                return;
+            }
+        };
+        let lines = std::mem::take(&mut parser.statement_lines);
+        // Documents are run with the prelude loaded
+        let prelude = prelude::names();
+        let mut resolver = Resolver::new()
+            .with_lines(lines.clone())
+            .with_functions(prelude.clone());
+        resolver.resolve(nodes.clone());
+        let mut checker = TypeChecker::new()
+            .with_lines(lines.clone())
+            .with_functions(prelude);
+        checker.check(&nodes);
+        self.diagnostics = resolver.diagnostics;
+        self.diagnostics.extend(checker.diagnostics);
+        self.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        self.variable_types = checker.variable_types;
+        self.statement_lines = lines;
+        self.statement_lines.sort_unstable();
+        self.collect_signatures(&nodes);
+    }
+
+    fn collect_signatures(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::FunctionDeclaration {
+                    identifier,
+                    parameters,
+                    parameter_types,
+                    return_type,
+                    body,
+                } => {
+                    let parameters: Vec<String> = parameters
+                        .iter()
+                        .zip(parameter_types)
+                        .map(|(parameter, annotation)| match annotation {
+                            Some(annotation) => format!("{0}: {1}", parameter, annotation),
+                            None => parameter.clone(),
+                        })
+                        .collect();
+                    let mut signature = format!("fn {0}({1})", identifier, parameters.join(", "));
+                    if let Some(return_type) = return_type {
+                        signature += &format!(" -> {0}", return_type);
+                    }
+                    self.signatures.insert(identifier.clone(), signature);
+                    self.collect_signatures(body);
+                }
+                Node::IfExpression {
+                    then_branch,
+                    else_branch,
+                    ..
+                }
+                | Node::ElseExpression {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    self.collect_signatures(then_branch);
+                    if let Some(else_node) = else_branch.as_ref() {
+                        self.collect_signatures(std::slice::from_ref(else_node));
+                    }
+                }
+                Node::WhileExpression { then_branch, .. } => self.collect_signatures(then_branch),
+                _ => {}
+            }
+        }
+    }
+
+    // Find function bodies and definitions in the tokens.
+    fn index(&mut self) {
+        // Open braces, with the function scope they start
+        let mut braces: Vec<Option<usize>> = Vec::new();
+        // Function whose parameter list or body start is still ahead
+        let mut declaring: Option<usize> = None;
+        let mut in_parameters = false;
+        for i in 0..self.tokens.len() {
+            let (token, span) = self.tokens[i].clone();
+            let scope = braces.iter().rev().find_map(|brace| *brace);
+            match token {
+                Token::Identifier(_) if self.follows_type_marker(i) || self.is_field_or_struct(i) => {}
+                Token::Identifier(name) => {
+                    let previous = i.checked_sub(1).map(|p| &self.tokens[p].0);
+                    let next = self.tokens.get(i + 1).map(|(next, _)| next);
+                    // `x: num = 1`, not a field of a struct value such as `x: 1`
+                    let annotated = next == Some(&Token::Colon)
+                        && matches!(
+                            self.tokens.get(i + 3).map(|(token, _)| token),
+                            Some(Token::Assignment)
+                        );
+                    let (kind, scope) = match previous {
+                        Some(Token::Literal {
+                            keyword: Keyword::FN,
+                            ..
+                        }) => {
+                            self.functions.push(FunctionScope {
+                                start: (span.line, span.column),
+                                end: (span.line, span.column),
+                            });
+                            declaring = Some(self.functions.len() - 1);
+                            (SymbolKind::Function, declaring)
+                        }
+                        Some(Token::LeftParenthese) | Some(Token::Comma) if in_parameters => {
+                            (SymbolKind::Parameter, declaring)
+                        }
+                        // The error of a catch, e.g. `catch e { ... }`
+                        Some(Token::Literal {
+                            keyword: Keyword::CATCH,
+                            ..
+                        }) => (SymbolKind::Variable, scope),
+                        _ if next == Some(&Token::Assignment) || (annotated && !in_parameters) =>
+                        {
+                            (SymbolKind::Variable, scope)
+                        }
+                        _ => continue,
+                    };
+                    self.definitions.push(Definition {
+                        name,
+                        kind,
+                        span,
+                        scope,
+                    });
+                }
+                // Anonymous functions have a scope, but no definition
+                Token::Literal {
+                    keyword: Keyword::FN,
+                    ..
+                } if self.tokens.get(i + 1).map(|(next, _)| next) == Some(&Token::LeftParenthese) => {
+                    self.functions.push(FunctionScope {
+                        start: (span.line, span.column),
+                        end: (span.line, span.column),
+                    });
+                    declaring = Some(self.functions.len() - 1);
+                }
+                Token::LeftParenthese if declaring.is_some() && !in_parameters => {
+                    in_parameters = true
+                }
+                Token::RightParenthese if in_parameters => in_parameters = false,
+                Token::LeftBrace => {
+                    let function = declaring.take();
+                    if let Some(function) = function {
+                        self.functions[function].start = (span.line, span.column);
+                    }
+                    braces.push(function);
+                }
+                Token::RightBrace => {
+                    if let Some(Some(function)) = braces.pop() {
+                        self.functions[function].end = (span.line, span.column + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Index of the identifier or literal token at the position. A position right after a
+    // name, e.g. on the `(` of `add(x)`, refers to the name unless another one starts there.
+    fn token_at(&self, line: usize, column: usize) -> Option<usize> {
+        let named = |token: &Token| {
+            matches!(token, Token::Identifier(_) | Token::Literal { .. } | Token::Num(_))
+        };
+        let inside = self.tokens.iter().position(|(token, span)| {
+            span.line == line
+                && span.column <= column
+                && column < span.column + span.length
+                && named(token)
+        });
+        inside.or_else(|| {
+            self.tokens.iter().position(|(token, span)| {
+                span.line == line && span.column + span.length == column && named(token)
+            })
+        })
+    }
+
+    // Function bodies containing the position, innermost first. Closures see the
+    // variables of the functions around them.
+    fn scopes_at(&self, line: usize, column: usize) -> Vec<usize> {
+        let position = (line, column);
+        let mut scopes: Vec<usize> = (0..self.functions.len())
+            .filter(|i| self.functions[*i].start <= position && position < self.functions[*i].end)
+            .collect();
+        scopes.sort_by_key(|i| std::cmp::Reverse(self.functions[*i].start));
+        scopes
+    }
+
+    // Definition the identifier token refers to.
+    fn resolve(&self, index: usize) -> Option<&Definition> {
+        let (token, span) = &self.tokens[index];
+        let name = match token {
+            Token::Identifier(name) if !self.is_field_or_struct(index) => name,
+            _ => return None,
+        };
+        let function = self
+            .definitions
+            .iter()
+            .find(|definition| &definition.name == name && definition.kind == SymbolKind::Function);
+        // The name of a declaration or a call on the same line
+        let is_call = match self.tokens.get(index + 1) {
+            Some((Token::LeftParenthese, next)) => next.line == span.line,
+            _ => false,
+        };
+        if is_call || function.is_some_and(|function| function.span == *span) {
+            return function;
+        }
+        // Parameters belong to the function they are declared for
+        if let Some(definition) = self
+            .definitions
+            .iter()
+            .find(|definition| definition.span == *span && definition.kind == SymbolKind::Parameter)
+        {
+            return Some(definition);
+        }
+        self.scopes_at(span.line, span.column)
+            .into_iter()
+            .find_map(|scope| {
+                self.definitions.iter().find(|definition| {
+                    &definition.name == name
+                        && definition.kind != SymbolKind::Function
+                        && definition.scope == Some(scope)
+                })
+            })
+            .or_else(|| {
+                self.definitions.iter().find(|definition| {
+                    &definition.name == name
+                        && definition.kind == SymbolKind::Variable
+                        && definition.scope.is_none()
+                })
+            })
+            .or(function)
+    }
+
+    // Whether the token at the index is a type annotation, e.g. `num` in `x: num`. A
+    // colon is also followed by the values of struct fields, e.g. `Point { x: a }`.
+    fn follows_type_marker(&self, index: usize) -> bool {
+        let previous = index
+            .checked_sub(1)
+            .map(|previous| &self.tokens[previous].0);
+        match (previous, &self.tokens[index].0) {
+            (Some(Token::Arrow), _) => true,
+            (Some(Token::Colon), Token::Identifier(name)) => Type::from_name(name).is_some(),
+            _ => false,
+        }
+    }
+
+    // Whether the token at the index names a field or a method, e.g. `x` in `p.x`, the
+    // struct or enum of a declaration, or either part of a variant, e.g. `Shape::Circle`.
+    fn is_field_or_struct(&self, index: usize) -> bool {
+        if let Some((Token::DoubleColon, _)) = self.tokens.get(index + 1) {
+            return true;
+        }
+        matches!(
+            index
+                .checked_sub(1)
+                .map(|previous| &self.tokens[previous].0),
+            Some(Token::Dot)
+                | Some(Token::DoubleColon)
+                | Some(Token::Literal {
+                    keyword: Keyword::STRUCT,
+                    ..
+                })
+                | Some(Token::Literal {
+                    keyword: Keyword::IMPL,
+                    ..
+                })
+                | Some(Token::Literal {
+                    keyword: Keyword::ENUM,
+                    ..
+                })
+        )
+    }
+}
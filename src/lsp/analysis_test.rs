@@ -0,0 +1,154 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::tokenizer::Span;
+    use crate::lsp::analysis::{Analysis, CompletionKind, SemanticKind, SymbolKind};
+
+    const SOURCE: &str = "total: num = 0
+name = \"sum\"
+fn add(a: num, b) -> num {
+    result = a + b
+    return result
+}
+total = add(total, 2) # add
+name
+";
+
+    fn span(line: usize, column: usize, length: usize) -> Span {
+        Span {
+            line,
+            column,
+            length,
+        }
+    }
+
+    #[test]
+    fn test_hover() {
+        let analysis = Analysis::new(SOURCE);
+        assert!(analysis.diagnostics.is_empty());
+        assert_eq!(analysis.hover(1, 2), Some("total: num".to_string()));
+        assert_eq!(analysis.hover(2, 0), Some("name: str".to_string()));
+        assert_eq!(
+            analysis.hover(7, 10),
+            Some("fn add(a: num, b) -> num".to_string())
+        );
+        assert_eq!(analysis.hover(4, 13), Some("a: num".to_string()));
+        // Unannotated parameters are dynamic
+        assert_eq!(analysis.hover(4, 17), Some("b: unknown".to_string()));
+        assert_eq!(analysis.hover(2, 9), Some("str".to_string()));
+        assert_eq!(analysis.hover(6, 0), None);
+    }
+
+    #[test]
+    fn test_definition() {
+        let analysis = Analysis::new(SOURCE);
+        assert_eq!(analysis.definition(7, 9), Some(span(3, 3, 3)));
+        assert_eq!(analysis.definition(7, 14), Some(span(1, 0, 5)));
+        assert_eq!(analysis.definition(5, 12), Some(span(4, 4, 6)));
+        assert_eq!(analysis.definition(4, 17), Some(span(3, 15, 1)));
+        assert_eq!(analysis.definition(3, 10), None);
+    }
+
+    #[test]
+    fn test_boundaries() {
+        // Names right after `(` and `,` start where the punctuation ends
+        let analysis = Analysis::new("fn add(a,b) {\n    return a + b\n}\nx = 1\ny = add(x,2)");
+        assert_eq!(analysis.hover(5, 8), Some("x: num".to_string()));
+        assert_eq!(analysis.definition(5, 8), Some(span(4, 0, 1)));
+        assert_eq!(analysis.hover(1, 7), Some("a: unknown".to_string()));
+        assert_eq!(analysis.definition(1, 9), Some(span(1, 9, 1)));
+        // Right after a name, with nothing else starting there
+        assert_eq!(analysis.hover(5, 7), Some("fn add(a, b)".to_string()));
+        assert_eq!(analysis.definition(5, 7), Some(span(1, 3, 3)));
+    }
+
+    #[test]
+    fn test_symbols_and_completion() {
+        let analysis = Analysis::new(SOURCE);
+        let symbols: Vec<(String, SymbolKind, (usize, usize))> = analysis
+            .symbols()
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.kind, symbol.end))
+            .collect();
+        assert_eq!(
+            symbols,
+            vec![
+                ("total".to_string(), SymbolKind::Variable, (1, 5)),
+                ("name".to_string(), SymbolKind::Variable, (2, 4)),
+                ("add".to_string(), SymbolKind::Function, (6, 1)),
+            ]
+        );
+        let labels = |line, column| -> Vec<(String, CompletionKind)> {
+            analysis
+                .completions(line, column)
+                .into_iter()
+                .filter(|completion| completion.kind != CompletionKind::Keyword)
+                .map(|completion| (completion.label, completion.kind))
+                .collect()
+        };
+        // Locals are only visible inside their function
+        assert_eq!(
+            labels(4, 4),
+            vec![
+                ("total".to_string(), CompletionKind::Variable),
+                ("name".to_string(), CompletionKind::Variable),
+                ("add".to_string(), CompletionKind::Function),
+                ("a".to_string(), CompletionKind::Variable),
+                ("b".to_string(), CompletionKind::Variable),
+                ("result".to_string(), CompletionKind::Variable),
+            ]
+        );
+        assert_eq!(labels(7, 0).len(), 3);
+        assert!(analysis
+            .completions(7, 0)
+            .iter()
+            .any(|completion| completion.label == "while"));
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let analysis = Analysis::new("fn f(a: num) {\n return a # a\n}");
+        let kinds: Vec<SemanticKind> = analysis
+            .semantic_tokens()
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SemanticKind::Keyword,
+                SemanticKind::Function,
+                SemanticKind::Parameter,
+                SemanticKind::Type,
+                SemanticKind::Keyword,
+                SemanticKind::Parameter,
+                SemanticKind::Comment,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let analysis = Analysis::new("x = 1\nif x {\n y = \"a\" * 2\n}\nz = (");
+        let messages: Vec<String> = analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("line 5: error:"));
+        let analysis = Analysis::new("x = 1\nif x {\n y = \"a\" * 2\n}");
+        let messages: Vec<String> = analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "line 2: error: Condition of if must be a boolean, got num",
+                "line 3: warning: Variable y is assigned but never used",
+                "line 3: error: Operator * expects a num, got str",
+            ]
+        );
+    }
+}
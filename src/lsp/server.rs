@@ -0,0 +1,455 @@
+// Standard libraries
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+// Internal modules
+use super::analysis::{Analysis, CompletionKind, SemanticKind, SymbolKind};
+use crate::lexer_and_parser::tokenizer::Span;
+use crate::passes::diagnostic::Diagnostic;
+use crate::serialization::json::Json;
+
+// Language server
+/*
+    Speaks the Language Server Protocol over stdin/stdout. Every message is a JSON-RPC
+    object preceded by a `Content-Length` header. Documents are synchronised as full text
+    and analysed again on every change.
+
+    Positions in the protocol start from 0 for both lines and characters, and characters
+    are counted in UTF-16 code units. Analysis counts them in chars, so columns are
+    converted using the text of the line.
+*/
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exit: bool,
+}
+
+// Open document, its analysis and the lines of its text
+struct Document {
+    analysis: Analysis,
+    lines: Vec<String>,
+}
+
+// JSON-RPC error codes
+const METHOD_NOT_FOUND: f64 = -32601.0;
+const INVALID_REQUEST: f64 = -32600.0;
+const PARSE_ERROR: f64 = -32700.0;
+
+// Public methods
+impl Server {
+    pub fn new() -> Self {
+        Server {
+            documents: HashMap::new(),
+            shutdown: false,
+            exit: false,
+        }
+    }
+
+    // Serve until the client sends `exit` or closes the input.
+    pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> io::Result<()> {
+        while !self.exit {
+            let content = match Server::read_message(&mut input)? {
+                Some(content) => content,
+                None => break,
+            };
+            let replies = match Json::parse(&content) {
+                Ok(message) => self.handle(&message),
+                Err(e) => vec![Server::error(Json::Null, PARSE_ERROR, e.to_string())],
+            };
+            for reply in replies {
+                let body = reply.to_string();
+                write!(output, "Content-Length: {0}\r\n\r\n{1}", body.len(), body)?;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    // Handle one message, returning the response and notifications to send.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str);
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            // Notifications have no id and get no response
+            None => return self.notification(method.unwrap_or_default(), params),
+        };
+        let result = match method {
+            Some("initialize") => Ok(Server::capabilities()),
+            Some("shutdown") => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            Some("textDocument/hover") => Ok(self.hover(params)),
+            Some("textDocument/definition") => Ok(self.definition(params)),
+            Some("textDocument/documentSymbol") => Ok(self.symbols(params)),
+            Some("textDocument/completion") => Ok(self.completion(params)),
+            Some("textDocument/semanticTokens/full") => Ok(self.semantic_tokens(params)),
+            Some(method) => Err((METHOD_NOT_FOUND, format!("Unknown method {0}", method))),
+            None => Err((INVALID_REQUEST, "Missing method".to_string())),
+        };
+        match result {
+            Ok(result) => vec![Json::object(vec![
+                ("jsonrpc", "2.0".into()),
+                ("id", id),
+                ("result", result),
+            ])],
+            Err((code, message)) => vec![Server::error(id, code, message)],
+        }
+    }
+
+    // Whether the client asked the server to shut down before exiting.
+    pub fn was_shut_down(&self) -> bool {
+        self.shutdown
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server::new()
+    }
+}
+
+// Private methods
+impl Server {
+    // Content of the next message, None at the end of the input.
+    fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if input.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                // Blank lines between messages are skipped
+                if length.is_some() {
+                    break;
+                }
+                continue;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+        let mut content = vec![0; length.unwrap_or_default()];
+        input.read_exact(&mut content)?;
+        Ok(Some(String::from_utf8_lossy(&content).into_owned()))
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        match method {
+            "textDocument/didOpen" => {
+                let document = params.get("textDocument");
+                let uri = document.and_then(|d| d.get("uri")).and_then(Json::as_str);
+                let text = document.and_then(|d| d.get("text")).and_then(Json::as_str);
+                match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.update(uri, text)],
+                    _ => Vec::new(),
+                }
+            }
+            "textDocument/didChange" => {
+                let uri = Server::uri(params);
+                // Full synchronisation, the last change has the whole text
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.update(uri, text)],
+                    _ => Vec::new(),
+                }
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = Server::uri(params) {
+                    self.documents.remove(uri);
+                }
+                Vec::new()
+            }
+            "exit" => {
+                self.exit = true;
+                Vec::new()
+            }
+            // initialized and anything unknown
+            _ => Vec::new(),
+        }
+    }
+
+    // Analyse the new text of a document and publish its diagnostics.
+    fn update(&mut self, uri: &str, text: &str) -> Json {
+        let document = Document {
+            analysis: Analysis::new(text),
+            lines: text.lines().map(|line| line.to_string()).collect(),
+        };
+        let diagnostics: Vec<Json> = document
+            .analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| document.diagnostic(diagnostic))
+            .collect();
+        self.documents.insert(uri.to_string(), document);
+        Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            (
+                "params",
+                Json::object(vec![
+                    ("uri", uri.into()),
+                    ("diagnostics", diagnostics.into()),
+                ]),
+            ),
+        ])
+    }
+
+    fn capabilities() -> Json {
+        let legend: Vec<Json> = SemanticKind::LEGEND
+            .iter()
+            .map(|kind| (*kind).into())
+            .collect();
+        Json::object(vec![
+            (
+                "capabilities",
+                Json::object(vec![
+                    // Full text synchronisation
+                    ("textDocumentSync", 1usize.into()),
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                    ("documentSymbolProvider", true.into()),
+                    ("completionProvider", Json::object(vec![])),
+                    (
+                        "semanticTokensProvider",
+                        Json::object(vec![
+                            (
+                                "legend",
+                                Json::object(vec![
+                                    ("tokenTypes", legend.into()),
+                                    ("tokenModifiers", Json::Array(Vec::new())),
+                                ]),
+                            ),
+                            ("full", true.into()),
+                        ]),
+                    ),
+                ]),
+            ),
+            (
+                "serverInfo",
+                Json::object(vec![("name", "wistful-lsp".into())]),
+            ),
+        ])
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        let contents = self
+            .position(params)
+            .and_then(|(document, line, column)| document.analysis.hover(line, column));
+        match contents {
+            Some(contents) => Json::object(vec![(
+                "contents",
+                Json::object(vec![
+                    ("kind", "markdown".into()),
+                    ("value", format!("```wistful\n{0}\n```", contents).into()),
+                ]),
+            )]),
+            None => Json::Null,
+        }
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let position = self.position(params);
+        let span = position.and_then(|(document, line, column)| {
+            document.analysis.definition(line, column)
+        });
+        match (span, position, Server::uri(params)) {
+            (Some(span), Some((document, _, _)), Some(uri)) => Json::object(vec![
+                ("uri", uri.into()),
+                ("range", document.span_range(span)),
+            ]),
+            _ => Json::Null,
+        }
+    }
+
+    fn symbols(&self, params: &Json) -> Json {
+        let document = match Server::uri(params).and_then(|uri| self.documents.get(uri)) {
+            Some(document) => document,
+            None => return Json::Null,
+        };
+        let symbols = document
+            .analysis
+            .symbols()
+            .into_iter()
+            .map(|symbol| {
+                Json::object(vec![
+                    ("name", symbol.name.into()),
+                    (
+                        "kind",
+                        match symbol.kind {
+                            SymbolKind::Function => 12usize,
+                            _ => 13usize,
+                        }
+                        .into(),
+                    ),
+                    (
+                        "range",
+                        document.range((symbol.span.line, symbol.span.column), symbol.end),
+                    ),
+                    ("selectionRange", document.span_range(symbol.span)),
+                ])
+            })
+            .collect::<Vec<Json>>();
+        symbols.into()
+    }
+
+    fn completion(&self, params: &Json) -> Json {
+        let items = match self.position(params) {
+            Some((document, line, column)) => document.analysis.completions(line, column),
+            None => return Json::Null,
+        };
+        items
+            .into_iter()
+            .map(|item| {
+                Json::object(vec![
+                    ("label", item.label.into()),
+                    (
+                        "kind",
+                        match item.kind {
+                            CompletionKind::Keyword => 14usize,
+                            CompletionKind::Variable => 6usize,
+                            CompletionKind::Function => 3usize,
+                        }
+                        .into(),
+                    ),
+                ])
+            })
+            .collect::<Vec<Json>>()
+            .into()
+    }
+
+    // Tokens encoded relative to the previous one, five numbers each
+    fn semantic_tokens(&self, params: &Json) -> Json {
+        let document = match Server::uri(params).and_then(|uri| self.documents.get(uri)) {
+            Some(document) => document,
+            None => return Json::Null,
+        };
+        let mut data = Vec::new();
+        let (mut previous_line, mut previous_column) = (1, 0);
+        for (span, kind) in document.analysis.semantic_tokens() {
+            let column = document.utf16_column(span.line, span.column);
+            let length = document.utf16_column(span.line, span.column + span.length) - column;
+            let delta_line = span.line - previous_line;
+            let delta_column = if delta_line == 0 {
+                column - previous_column
+            } else {
+                column
+            };
+            for number in [delta_line, delta_column, length, kind.index(), 0] {
+                data.push(number.into());
+            }
+            previous_line = span.line;
+            previous_column = column;
+        }
+        Json::object(vec![("data", Json::Array(data))])
+    }
+
+    // Document and position of a request, converted to 1-based lines and char columns.
+    fn position(&self, params: &Json) -> Option<(&Document, usize, usize)> {
+        let document = self.documents.get(Server::uri(params)?)?;
+        let position = params.get("position")?;
+        let line = position.get("line").and_then(Json::as_usize)? + 1;
+        let character = position.get("character").and_then(Json::as_usize)?;
+        Some((document, line, document.char_column(line, character)))
+    }
+
+    fn uri(params: &Json) -> Option<&str> {
+        params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+    }
+
+    fn error(id: Json, code: f64, message: String) -> Json {
+        Json::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            (
+                "error",
+                Json::object(vec![("code", code.into()), ("message", message.into())]),
+            ),
+        ])
+    }
+}
+
+// Private methods
+impl Document {
+    fn diagnostic(&self, diagnostic: &Diagnostic) -> Json {
+        // Diagnostics cover the whole line of the statement
+        let line = diagnostic.line.unwrap_or(1);
+        let length = self
+            .lines
+            .get(line - 1)
+            .map(|line| line.chars().count())
+            .unwrap_or_default();
+        Json::object(vec![
+            ("range", self.range((line, 0), (line, length))),
+            // 1 is error, 2 is warning
+            (
+                "severity",
+                if diagnostic.is_error() {
+                    1usize
+                } else {
+                    2usize
+                }
+                .into(),
+            ),
+            ("source", "wistful".into()),
+            ("message", diagnostic.message.clone().into()),
+        ])
+    }
+
+    fn span_range(&self, span: Span) -> Json {
+        self.range(
+            (span.line, span.column),
+            (span.line, span.column + span.length),
+        )
+    }
+
+    fn range(&self, start: (usize, usize), end: (usize, usize)) -> Json {
+        let position = |(line, column): (usize, usize)| {
+            Json::object(vec![
+                ("line", (line - 1).into()),
+                ("character", self.utf16_column(line, column).into()),
+            ])
+        };
+        Json::object(vec![("start", position(start)), ("end", position(end))])
+    }
+
+    // UTF-16 code units before the given char of a line.
+    fn utf16_column(&self, line: usize, column: usize) -> usize {
+        let text = match self.lines.get(line - 1) {
+            Some(text) => text,
+            None => return column,
+        };
+        let units: usize = text.chars().take(column).map(char::len_utf16).sum();
+        // Past the end of the line every char is one unit
+        units + column.saturating_sub(text.chars().count())
+    }
+
+    // Char of a line at the given UTF-16 code unit, the inverse of `utf16_column`.
+    fn char_column(&self, line: usize, character: usize) -> usize {
+        let text = match self.lines.get(line - 1) {
+            Some(text) => text,
+            None => return character,
+        };
+        let mut units = 0;
+        for (column, c) in text.chars().enumerate() {
+            if units >= character {
+                return column;
+            }
+            units += c.len_utf16();
+        }
+        text.chars().count() + character.saturating_sub(units)
+    }
+}
@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests {
+    use crate::lsp::server::Server;
+    use crate::serialization::json::Json;
+
+    fn frame(messages: &[&str]) -> Vec<u8> {
+        let mut input = String::new();
+        for message in messages {
+            input.push_str(&format!(
+                "Content-Length: {0}\r\n\r\n{1}",
+                message.len(),
+                message
+            ));
+        }
+        input.into_bytes()
+    }
+
+    // Run a session and split the output into messages
+    fn session(messages: &[&str]) -> (Server, Vec<Json>) {
+        let mut server = Server::new();
+        let mut output = Vec::new();
+        server.run(&frame(messages)[..], &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let replies = output
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|message| {
+                let (_, body) = message.split_once("\r\n\r\n").unwrap();
+                Json::parse(body).unwrap()
+            })
+            .collect();
+        (server, replies)
+    }
+
+    fn result(replies: &[Json], id: usize) -> &Json {
+        replies
+            .iter()
+            .find(|reply| reply.get("id").and_then(Json::as_usize) == Some(id))
+            .and_then(|reply| reply.get("result"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_session() {
+        let (server, replies) = session(&[
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.wf","text":"x = 1\nif x {\n    x = 2\n}\nx"}}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.wf"},"position":{"line":4,"character":0}}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.wf"},"position":{"line":4,"character":0}}}"#,
+            r#"{"jsonrpc":"2.0","id":4,"method":"textDocument/semanticTokens/full","params":{"textDocument":{"uri":"file:///a.wf"}}}"#,
+            r#"{"jsonrpc":"2.0","id":5,"method":"unknown/method"}"#,
+            r#"{"jsonrpc":"2.0","id":6,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+            // Never read after exit
+            r#"{"jsonrpc":"2.0","id":7,"method":"shutdown"}"#,
+        ]);
+        assert!(server.was_shut_down());
+        assert_eq!(replies.len(), 7);
+        let capabilities = result(&replies, 1).get("capabilities").unwrap();
+        assert_eq!(
+            capabilities
+                .get("textDocumentSync")
+                .and_then(Json::as_usize),
+            Some(1)
+        );
+
+        let params = replies[1].get("params").unwrap();
+        assert_eq!(
+            params.get("uri").and_then(Json::as_str),
+            Some("file:///a.wf")
+        );
+        let diagnostics = params.get("diagnostics").and_then(Json::as_array).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            r#"{"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":6}},"severity":1,"source":"wistful","message":"Condition of if must be a boolean, got num"}"#
+        );
+
+        assert_eq!(
+            result(&replies, 2).to_string(),
+            r#"{"contents":{"kind":"markdown","value":"```wistful\nx: num\n```"}}"#
+        );
+        assert_eq!(
+            result(&replies, 3).to_string(),
+            r#"{"uri":"file:///a.wf","range":{"start":{"line":0,"character":0},"end":{"line":0,"character":1}}}"#
+        );
+        // `x`, `=` and `1` on the first line
+        let data = result(&replies, 4)
+            .get("data")
+            .and_then(Json::as_array)
+            .unwrap();
+        let data: Vec<usize> = data.iter().filter_map(Json::as_usize).collect();
+        assert_eq!(&data[..15], &[0, 0, 1, 1, 0, 0, 2, 1, 6, 0, 0, 2, 1, 4, 0]);
+        let error = replies[5].get("error").unwrap();
+        assert_eq!(error.get("code").and_then(Json::as_f64), Some(-32601.0));
+        assert_eq!(result(&replies, 6), &Json::Null);
+    }
+
+    #[test]
+    fn test_utf16_positions() {
+        // 😀 is one char but two UTF-16 code units
+        let (_, replies) = session(&[
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.wf","text":"v = 1\nu = [\"😀\", v]"}}}"#,
+            r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.wf"},"position":{"line":1,"character":11}}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/semanticTokens/full","params":{"textDocument":{"uri":"file:///a.wf"}}}"#,
+        ]);
+        assert_eq!(
+            result(&replies, 1).to_string(),
+            r#"{"contents":{"kind":"markdown","value":"```wistful\nv: num\n```"}}"#
+        );
+        let data = result(&replies, 2)
+            .get("data")
+            .and_then(Json::as_array)
+            .unwrap();
+        let data: Vec<usize> = data.iter().filter_map(Json::as_usize).collect();
+        // The string is four code units long and `v` starts at code unit 11
+        assert_eq!(&data[data.len() - 10..], &[0, 3, 4, 5, 0, 0, 6, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_names_after_punctuation() {
+        let (_, replies) = session(&[
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.wf","text":"fn add(a, b) {\n    return a + b\n}\nx = 1\ny = add(x, 2)"}}}"#,
+            r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.wf"},"position":{"line":4,"character":8}}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.wf"},"position":{"line":4,"character":8}}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.wf"},"position":{"line":0,"character":7}}}"#,
+            r#"{"jsonrpc":"2.0","id":4,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.wf"},"position":{"line":0,"character":7}}}"#,
+        ]);
+        assert_eq!(
+            result(&replies, 1).to_string(),
+            r#"{"contents":{"kind":"markdown","value":"```wistful\nx: num\n```"}}"#
+        );
+        assert_eq!(
+            result(&replies, 2).to_string(),
+            r#"{"uri":"file:///a.wf","range":{"start":{"line":3,"character":0},"end":{"line":3,"character":1}}}"#
+        );
+        assert_eq!(
+            result(&replies, 3).to_string(),
+            r#"{"contents":{"kind":"markdown","value":"```wistful\na: unknown\n```"}}"#
+        );
+        assert_eq!(
+            result(&replies, 4).to_string(),
+            r#"{"uri":"file:///a.wf","range":{"start":{"line":0,"character":7},"end":{"line":0,"character":8}}}"#
+        );
+    }
+
+    #[test]
+    fn test_invalid_message() {
+        let (server, replies) = session(&["{\"id\": 1,", r#"{"id":2,"params":{}}"#]);
+        assert!(!server.was_shut_down());
+        let codes: Vec<f64> = replies
+            .iter()
+            .filter_map(|reply| reply.get("error")?.get("code")?.as_f64())
+            .collect();
+        assert_eq!(codes, vec![-32700.0, -32600.0]);
+    }
+}
@@ -1,47 +1,372 @@
+use std::env;
+use std::error;
 use std::fs;
+use std::path::Path;
+use std::rc::Rc;
 
-use lexer_and_parser::evaluator::Evaluator;
-use lexer_and_parser::parser::{ParseError, Parser};
+use wistful::bytecode::binary::{read_program, write_program, MAGIC};
+use wistful::bytecode::compiler::{CompileError, Compiler};
+use wistful::bytecode::instruction::Program;
+use wistful::bytecode::vm::Vm;
+use wistful::lexer_and_parser::arena::Ast;
+use wistful::lexer_and_parser::ast::Node;
+use wistful::lexer_and_parser::ast_printer::{AstPrinter, Style};
+use wistful::lexer_and_parser::evaluator::{EvalResult, Evaluator};
+use wistful::lexer_and_parser::parser::Parser;
+use wistful::passes::diagnostic::Diagnostic;
+use wistful::passes::formatter::Formatter;
+use wistful::passes::linter::{LintConfig, Linter};
+use wistful::passes::optimizer::Optimizer;
+use wistful::passes::resolver::Resolver;
+use wistful::passes::type_checker::TypeChecker;
+use wistful::serialization::dot::{ast_to_dot, control_flow_to_dot};
+use wistful::serialization::json::Json;
+use wistful::serialization::syntax::{read_document, write_document};
 
-use crate::lexer_and_parser::ast::Node;
+const USAGE: &str = "Usage:
+    wistful                                 Evaluate ./test-source
+    wistful --vm                            Run ./test-source on the bytecode VM
+    wistful compile <script.wf> [-o <out>]  Compile a script to bytecode
+    wistful run <script.wf|script.wfc|script.json>
+                                            Run a script, a compiled program or a
+                                            syntax tree exported by parse
+    wistful lint <script.wf> [--config <file>]
+                                            Warn about likely mistakes, rules are read
+                                            from .wistful-lint next to the script
+    wistful fmt [--check] <script.wf>...    Format scripts in place, --check only lists
+                                            the scripts that are not formatted
+    wistful parse [--format <style>] <script.wf>
+                                            Print the syntax tree, styles are plain,
+                                            color, sexpr, json and dot, cfg prints the
+                                            control-flow graph as dot
 
-// Add internal modules & std
-mod lexer_and_parser;
+Options:
+    -O, --optimize                          Fold constants and remove dead branches";
 
 fn main() {
-    // Initialize evaluator.
-    let mut evaluator: Evaluator = Evaluator::new(None);
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // Optimisation can be enabled for every command
+    let optimize = args.iter().any(|arg| arg == "-O" || arg == "--optimize");
+    args.retain(|arg| arg != "-O" && arg != "--optimize");
+    // Subcommands
+    let result = match args.first().map(|arg| arg.as_str()) {
+        Some("compile") => compile_command(&args[1..], optimize),
+        Some("run") => run_command(&args[1..], optimize),
+        Some("lint") => lint_command(&args[1..]),
+        Some("fmt") => fmt_command(&args[1..]),
+        Some("parse") => parse_command(&args[1..], optimize),
+        Some("--vm") | None => {
+            run_test_source(!args.is_empty(), optimize);
+            Ok(())
+        }
+        Some(_) => Err(USAGE.into()),
+    };
+    if let Err(e) = result {
+        eprintln!("\x1b[0;31mError: {0}\x1b[0m", e);
+        std::process::exit(1);
+    }
+}
+
+// Old behaviour, run the test source with the tree walker or the VM.
+fn run_test_source(use_vm: bool, optimize: bool) {
     let input =
         fs::read_to_string("test-source").expect("\x1b[0;31mTest source was not found.\x1b[0m");
     //println!("{0:?}", input);
+    if use_vm {
+        match compile_source(&input, optimize).and_then(run_program) {
+            Ok(result) => println!("VM: {:?}", result),
+            Err(e) => println!("\x1b[0;31mError: {0}\x1b[0m", e),
+        }
+        return;
+    }
+    // Initialize evaluator, imports are relative to the test source
+    let mut evaluator: Evaluator = Evaluator::new(None);
+    evaluator.modules.set_script(Path::new("test-source"));
     match get_values(
         &input,
         &mut evaluator,
+        optimize,
     ) {
         Ok(_) => println!("Evaluating succeeded."),
-        Err(e) => println!("\x1b[0;31mParse error: {0}\x1b[0m", e),
+        Err(e) => println!("\x1b[0;31mError: {0:#}\x1b[0m", e),
+    }
+}
+
+// wistful compile script.wf -o script.wfc
+fn compile_command(args: &[String], optimize: bool) -> Result<(), Box<dyn error::Error>> {
+    let (source_path, output_path) = match args {
+        [source] => (source, Path::new(source).with_extension("wfc")),
+        [source, flag, output] if flag == "-o" => (source, Path::new(output).to_path_buf()),
+        _ => return Err(USAGE.into()),
+    };
+    let source = fs::read_to_string(source_path)
+        .map_err(|e| format!("Could not read {0}: {1}", source_path, e))?;
+    let program = compile_source(&source, optimize)?;
+    fs::write(&output_path, write_program(&program))
+        .map_err(|e| format!("Could not write {0}: {1}", output_path.display(), e))?;
+    Ok(())
+}
+
+// wistful run script.wfc, plain sources are compiled on the fly
+fn run_command(args: &[String], optimize: bool) -> Result<(), Box<dyn error::Error>> {
+    let path = match args {
+        [path] => path,
+        _ => return Err(USAGE.into()),
+    };
+    let bytes = fs::read(path).map_err(|e| format!("Could not read {0}: {1}", path, e))?;
+    let result = if bytes.starts_with(MAGIC) {
+        run_program(read_program(&bytes)?)?
+    } else {
+        let source = String::from_utf8(bytes).map_err(|_| format!("{0} is not UTF-8", path))?;
+        let (expressions, lines) =
+            if Path::new(path).extension().is_some_and(|extension| extension == "json") {
+                let document = Json::parse(&source).map_err(|e| format!("{0}: {1}", path, e))?;
+                read_document(&document)?
+            } else {
+                let mut parser = Parser::new(&source)?;
+                let expressions = parser.parse()?;
+                (expressions, std::mem::take(&mut parser.statement_lines))
+            };
+        run_nodes(expressions, lines, Path::new(path), optimize)?
+    };
+    match result {
+        EvalResult::Number(n) => println!("{}", n),
+        EvalResult::Quantity(q) => println!("{}", q),
+        EvalResult::Literal(s) => println!("{}", s),
+        EvalResult::Boolean(b) => println!("{}", b),
+        _ => {}
+    }
+    Ok(())
+}
+
+// wistful lint script.wf --config rules
+fn lint_command(args: &[String]) -> Result<(), Box<dyn error::Error>> {
+    let (source_path, config_path) = match args {
+        [source] => (source, Path::new(source).with_file_name(".wistful-lint")),
+        [source, flag, config] if flag == "--config" => (source, Path::new(config).to_path_buf()),
+        _ => return Err(USAGE.into()),
+    };
+    let source = fs::read_to_string(source_path)
+        .map_err(|e| format!("Could not read {0}: {1}", source_path, e))?;
+    // The default config file is optional, an explicitly given one is not
+    let config = match fs::read_to_string(&config_path) {
+        Ok(config) => LintConfig::parse(&config)
+            .map_err(|e| format!("{0}: {1}", config_path.display(), e))?,
+        Err(_) if args.len() == 1 => LintConfig::new(),
+        Err(e) => return Err(format!("Could not read {0}: {1}", config_path.display(), e).into()),
+    };
+    let mut parser = Parser::new(&source)?;
+    let expressions = parser.parse()?;
+    let mut linter = Linter::new(config)
+        .with_lines(std::mem::take(&mut parser.statement_lines))
+        .with_comments(std::mem::take(&mut parser.comments));
+    linter.lint(&expressions);
+    report(&linter.diagnostics)?;
+    match linter.diagnostics.len() {
+        0 => Ok(()),
+        1 => Err("Found 1 warning".into()),
+        warnings => Err(format!("Found {0} warnings", warnings).into()),
+    }
+}
+
+// wistful fmt --check a.wf b.wf
+fn fmt_command(args: &[String]) -> Result<(), Box<dyn error::Error>> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+    if paths.is_empty() {
+        return Err(USAGE.into());
+    }
+    let mut unformatted = 0;
+    for path in paths {
+        let source =
+            fs::read_to_string(path).map_err(|e| format!("Could not read {0}: {1}", path, e))?;
+        let formatted =
+            Formatter::format_source(&source).map_err(|e| format!("{0}: {1}", path, e))?;
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{0}", path);
+            unformatted += 1;
+        } else {
+            fs::write(path, formatted).map_err(|e| format!("Could not write {0}: {1}", path, e))?;
+        }
+    }
+    match unformatted {
+        0 => Ok(()),
+        1 => Err("1 script is not formatted".into()),
+        scripts => Err(format!("{0} scripts are not formatted", scripts).into()),
     }
 }
 
+// wistful parse --format sexpr script.wf
+fn parse_command(args: &[String], optimize: bool) -> Result<(), Box<dyn error::Error>> {
+    let (path, format) = match args {
+        [path] => (path, "plain"),
+        [flag, format, path] if flag == "--format" => (path, format.as_str()),
+        _ => return Err(USAGE.into()),
+    };
+    let source =
+        fs::read_to_string(path).map_err(|e| format!("Could not read {0}: {1}", path, e))?;
+    // Tokens and tree for external tools, exported as written
+    if format == "json" {
+        println!("{0}", write_document(&source)?);
+        return Ok(());
+    }
+    let mut expressions = Parser::new(&source)?.parse()?;
+    if optimize {
+        expressions = Optimizer::new().optimize(expressions);
+    }
+    // Graphs for Graphviz, e.g. `wistful parse --format cfg a.wf | dot -Tsvg`
+    match format {
+        "dot" => print!("{0}", ast_to_dot(&expressions)),
+        "cfg" => print!("{0}", control_flow_to_dot(&expressions)),
+        _ => {
+            let style =
+                Style::from_name(format).ok_or_else(|| format!("Unknown format {0}", format))?;
+            print!("{0}", AstPrinter::new(style).print(&expressions));
+        }
+    }
+    Ok(())
+}
+
 // Function to invoke Parser and evaluate expression
-fn get_values(expr: &str, evaluator: &mut Evaluator) -> Result<(), ParseError> {
+fn get_values(
+    expr: &str,
+    evaluator: &mut Evaluator,
+    optimize: bool,
+) -> Result<(), Box<dyn error::Error>> {
     // Vector of expressions to be evaluated
-    let mut expressions = Parser::new(&expr)?.parse()?;
-    // Print parsed expressions
-    print_ast(expressions.clone());
-    // Capture each removed node to a variable and feed it to evaluator to avoid
-    // borrowing/cloning of values.
-    while expressions.len() > 0 {
-        let expression = expressions.remove(0);
-        evaluator.ast = Some(expression);
-        evaluator.start_evaluating()?;
+    let mut parser = Parser::new(expr)?;
+    let mut expressions = parser.parse()?;
+    let mut lines = std::mem::take(&mut parser.statement_lines);
+    if optimize {
+        (expressions, lines) = optimize_nodes(expressions, lines);
     }
+    let expressions = resolve(expressions, &lines, evaluator)?;
+    // Print parsed expressions
+    print!("{0}", AstPrinter::new(Style::Coloured).print(&expressions));
+    // Move the statements into an arena once, the evaluator borrows from it
+    evaluator.run(&Rc::new(Ast::with_lines(expressions, lines)))?;
     Ok(())
 }
 
-fn print_ast(mut expressions: Vec<Node>) -> () {
-    while expressions.len() > 0 {
-        let expression = expressions.remove(0);
-        expression.print_stuff(expression.clone(), 1);
+// Optimise the statements, numeric variables are known from the type checker
+fn optimize_nodes(expressions: Vec<Node>, lines: Vec<usize>) -> (Vec<Node>, Vec<usize>) {
+    let mut checker = TypeChecker::new().with_lines(lines.clone());
+    checker.check(&expressions);
+    let mut optimizer = Optimizer::new()
+        .with_lines(lines)
+        .with_types(checker.variable_types);
+    let expressions = optimizer.optimize(expressions);
+    (expressions, optimizer.statement_lines)
+}
+
+// Bind variables to the evaluator's slots, and stop before running a broken program
+fn resolve(
+    expressions: Vec<Node>,
+    lines: &[usize],
+    evaluator: &mut Evaluator,
+) -> Result<Vec<Node>, Box<dyn error::Error>> {
+    let functions: Vec<String> = evaluator
+        .functions
+        .keys()
+        .chain(evaluator.host_functions.keys())
+        .cloned()
+        .collect();
+    let mut resolver = Resolver::new()
+        .with_lines(lines.to_vec())
+        .with_globals(evaluator.global_names())
+        .with_functions(functions.clone());
+    let expressions = resolver.resolve(expressions);
+    check(resolver.diagnostics, &expressions, lines.to_vec(), functions)?;
+    evaluator.declare_globals(&resolver.globals)?;
+    Ok(expressions)
+}
+
+// Run the statements on the VM. Programs using features only the tree walker has, e.g.
+// closures or builtins, are evaluated instead.
+fn run_nodes(
+    mut expressions: Vec<Node>,
+    mut lines: Vec<usize>,
+    path: &Path,
+    optimize: bool,
+) -> Result<EvalResult, Box<dyn error::Error>> {
+    if optimize {
+        (expressions, lines) = optimize_nodes(expressions, lines);
     }
+    // Imports are relative to the script
+    let mut evaluator = Evaluator::new(None);
+    evaluator.modules.set_script(path);
+    let resolved = resolve(expressions.clone(), &lines, &mut evaluator)?;
+    match Compiler::new().with_lines(lines.clone()).compile(&expressions) {
+        Ok(program) => run_program(program),
+        Err(CompileError::Unsupported(_)) => {
+            let ast = Rc::new(Ast::with_lines(resolved, lines));
+            let mut result = EvalResult::EmptyResult;
+            for &statement in ast.list(ast.root()) {
+                // Errors of the tree walker carry their trace
+                result = evaluator
+                    .evaluate_statement(&ast, statement)
+                    .map_err(|e| format!("{0:#}", e))?;
+            }
+            Ok(result)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Compile the source to bytecode, including the debug line table
+fn compile_source(expr: &str, optimize: bool) -> Result<Program, Box<dyn error::Error>> {
+    let mut parser = Parser::new(expr)?;
+    let expressions = parser.parse()?;
+    compile_nodes(expressions, std::mem::take(&mut parser.statement_lines), optimize)
+}
+
+fn compile_nodes(
+    mut expressions: Vec<Node>,
+    mut lines: Vec<usize>,
+    optimize: bool,
+) -> Result<Program, Box<dyn error::Error>> {
+    if optimize {
+        (expressions, lines) = optimize_nodes(expressions, lines);
+    }
+    let mut resolver = Resolver::new().with_lines(lines.clone());
+    resolver.resolve(expressions.clone());
+    check(resolver.diagnostics, &expressions, lines.clone(), Vec::new())?;
+    Ok(Compiler::new().with_lines(lines).compile(&expressions)?)
+}
+
+// Type check the program and report its diagnostics together with the resolver's.
+// Functions are the ones existing before the program, e.g. the prelude's.
+fn check(
+    mut diagnostics: Vec<Diagnostic>,
+    expressions: &[Node],
+    lines: Vec<usize>,
+    functions: Vec<String>,
+) -> Result<(), Box<dyn error::Error>> {
+    let mut checker = TypeChecker::new()
+        .with_lines(lines)
+        .with_functions(functions);
+    checker.check(expressions);
+    diagnostics.extend(checker.diagnostics);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    report(&diagnostics)
+}
+
+// Print diagnostics, errors abort the run
+fn report(diagnostics: &[Diagnostic]) -> Result<(), Box<dyn error::Error>> {
+    for diagnostic in diagnostics {
+        let colour = if diagnostic.is_error() { "31" } else { "33" };
+        eprintln!("\x1b[0;{0}m{1}\x1b[0m", colour, diagnostic);
+    }
+    match diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).count() {
+        0 => Ok(()),
+        1 => Err("Found 1 error".into()),
+        errors => Err(format!("Found {0} errors", errors).into()),
+    }
+}
+
+fn run_program(program: Program) -> Result<EvalResult, Box<dyn error::Error>> {
+    Vm::new(program).run()
 }
@@ -0,0 +1,13 @@
+// Add public modules
+pub mod diagnostic;
+pub mod formatter;
+pub mod linter;
+pub mod optimizer;
+pub mod resolver;
+pub mod type_checker;
+// Test modules
+pub mod formatter_test;
+pub mod linter_test;
+pub mod optimizer_test;
+pub mod resolver_test;
+pub mod type_checker_test;
@@ -0,0 +1,59 @@
+// Standard libraries
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// Problem found by a pass before the program is run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    // Line of the statement the problem was found in, if known
+    pub line: Option<usize>,
+}
+
+// Public methods
+impl Diagnostic {
+    pub fn error(message: String, line: Option<usize>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+            line,
+        }
+    }
+
+    pub fn warning(message: String, line: Option<usize>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message,
+            line,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+// e.g. "line 3: error: Undefined variable x"
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {0}: {1}: {2}", line, self.severity, self.message),
+            None => write!(f, "{0}: {1}", self.severity, self.message),
+        }
+    }
+}
@@ -0,0 +1,766 @@
+// Standard libraries
+use std::collections::HashMap;
+use std::vec;
+
+// Internal modules
+use crate::lexer_and_parser::ast::{BlockLines, Comment, MatchArm, Node, Pattern, Type};
+use crate::lexer_and_parser::builtins::short_variant_name;
+use crate::lexer_and_parser::parser::{ParseError, Parser};
+use crate::lexer_and_parser::token::{Keyword, Token};
+
+// Formatter
+/*
+    Prints the syntax tree back as canonical source:
+    - one statement per line, blocks indented by four spaces
+    - `} elif`/`} else` on the same line as the closing brace, empty blocks as `{}`
+    - single spaces around operators, parentheses only where precedence needs them
+    - at most one blank line between statements, where the source had one
+
+    Comments are not part of the tree. Comments on their own line are placed before the
+    statement following them, trailing comments stay at the end of the statement's first line.
+    Comments after the last statement of a block stay in the block, including the one after
+    its closing brace and the ones before the next `elif`, `else`, `catch` or `finally`.
+*/
+pub struct Formatter<'a> {
+    // Source lines, used to keep blank lines between statements
+    source: Vec<&'a str>,
+    lines: Option<vec::IntoIter<usize>>,
+    // Index of the next statement line, and the braces of blocks by their first statement
+    statement: usize,
+    blocks: HashMap<usize, BlockLines>,
+    // Comments that are not printed yet, in source order
+    comments: vec::IntoIter<Comment>,
+    next_comment: Option<Comment>,
+    // Line of the statement being formatted, until a closure in it takes its trailing comment
+    line: Option<usize>,
+    // Whether a condition is being formatted, struct values need parentheses there
+    in_condition: bool,
+    output: String,
+}
+
+const INDENT: &str = "    ";
+
+// Public methods
+impl<'a> Formatter<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Formatter {
+            source: source.lines().collect(),
+            lines: None,
+            statement: 0,
+            blocks: HashMap::new(),
+            comments: Vec::new().into_iter(),
+            next_comment: None,
+            line: None,
+            in_condition: false,
+            output: String::new(),
+        }
+    }
+
+    // Statement lines collected by the parser, needed for placing comments.
+    pub fn with_lines(mut self, statement_lines: Vec<usize>) -> Self {
+        self.lines = Some(statement_lines.into_iter());
+        self
+    }
+
+    // Braces of the blocks collected by the parser, needed for comments at the end of blocks.
+    pub fn with_blocks(mut self, blocks: Vec<BlockLines>) -> Self {
+        self.blocks = blocks
+            .into_iter()
+            .map(|block| (block.first_statement, block))
+            .collect();
+        self
+    }
+
+    // Comments collected by the parser.
+    pub fn with_comments(mut self, comments: Vec<Comment>) -> Self {
+        self.comments = comments.into_iter();
+        self.next_comment = self.comments.next();
+        self
+    }
+
+    // Parse and format a whole source file.
+    pub fn format_source(source: &str) -> Result<String, ParseError> {
+        let mut parser = Parser::new(source)?;
+        let nodes = parser.parse()?;
+        let formatter = Formatter::new(source)
+            .with_lines(std::mem::take(&mut parser.statement_lines))
+            .with_blocks(std::mem::take(&mut parser.blocks))
+            .with_comments(std::mem::take(&mut parser.comments));
+        Ok(formatter.format(&nodes))
+    }
+
+    // Format parsed statements.
+    pub fn format(mut self, nodes: &[Node]) -> String {
+        self.format_block(nodes, 0);
+        // Comments after the last statement
+        let mut first = self.output.is_empty();
+        while let Some(comment) = self.next_comment.take() {
+            self.write_comment(&comment, 0, first);
+            first = false;
+            self.next_comment = self.comments.next();
+        }
+        self.output
+    }
+
+    // Source text of a single expression or simple statement, e.g. `x = a + 1`.
+    pub fn format_expression(node: &Node) -> String {
+        Formatter::new("").expression(node, 0)
+    }
+
+    // Source text of a pattern of a match arm, e.g. `[first, ..rest]`.
+    pub fn format_pattern(pattern: &Pattern) -> String {
+        Formatter::new("").pattern(pattern, 0)
+    }
+}
+
+// Private methods
+impl<'a> Formatter<'a> {
+    fn format_block(&mut self, nodes: &[Node], indent: usize) {
+        for (i, node) in nodes.iter().enumerate() {
+            let line = self.next_line(i == 0, indent);
+            self.output += &INDENT.repeat(indent);
+            self.line = line;
+            self.format_statement(node, indent, line);
+        }
+    }
+
+    // Line of the next statement. The comments before it are written first, and a blank
+    // line where the source had one.
+    fn next_line(&mut self, mut first: bool, indent: usize) -> Option<usize> {
+        let line = self.take_line();
+        if let Some(line) = line {
+            while self
+                .next_comment
+                .as_ref()
+                .is_some_and(|comment| comment.line < line)
+            {
+                let comment = self.next_comment.take().unwrap();
+                self.write_comment(&comment, indent, first);
+                first = false;
+                self.next_comment = self.comments.next();
+            }
+            if !first && self.blank_before(line) {
+                self.output.push('\n');
+            }
+        }
+        line
+    }
+
+    // Line of the next statement, without writing anything.
+    fn take_line(&mut self) -> Option<usize> {
+        self.statement += 1;
+        self.lines.as_mut().and_then(|lines| lines.next())
+    }
+
+    // Whether a comment is left before the closing brace of the block starting with the
+    // next statement, the block can't be written on one line then.
+    fn comment_in_block(&self) -> bool {
+        match (self.blocks.get(&self.statement), &self.next_comment) {
+            (Some(block), Some(comment)) => comment.line < block.close,
+            _ => false,
+        }
+    }
+
+    fn write_comment(&mut self, comment: &Comment, indent: usize, first: bool) {
+        if !first && self.blank_before(comment.line) {
+            self.output.push('\n');
+        }
+        self.output += &INDENT.repeat(indent);
+        self.output += &format!("#{0}\n", comment.text.trim_end());
+    }
+
+    // Whether the source line before the given line is empty.
+    fn blank_before(&self, line: usize) -> bool {
+        line >= 2
+            && self
+                .source
+                .get(line - 2)
+                .is_some_and(|text| text.trim().is_empty())
+    }
+
+    // End the first line of a statement, keeping its trailing comment.
+    fn end_line(&mut self, line: Option<usize>) {
+        let trailing = match (&self.next_comment, line) {
+            (Some(comment), Some(line)) => comment.trailing && comment.line == line,
+            _ => false,
+        };
+        if trailing {
+            let comment = self.next_comment.take().unwrap();
+            self.output += &format!(" #{0}", comment.text.trim_end());
+            self.next_comment = self.comments.next();
+        }
+        self.output.push('\n');
+    }
+
+    fn format_statement(&mut self, node: &Node, indent: usize, line: Option<usize>) {
+        match node {
+            Node::IfExpression { .. } => {
+                self.format_if(node, indent, line);
+                self.end_line(line);
+            }
+            Node::TryExpression { .. } => {
+                self.format_try(node, indent, line);
+                self.end_line(line);
+            }
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => {
+                self.output += "while";
+                if let Some(condition) = condition.as_ref() {
+                    let condition = self.condition(condition, indent);
+                    self.output += &format!(" {0}", condition);
+                }
+                self.format_body(then_branch, indent, line);
+                self.end_line(line);
+            }
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                parameter_types,
+                return_type,
+                body,
+            } => {
+                let parameters = Formatter::parameters(parameters, parameter_types);
+                self.output += &format!("fn {0}({1})", identifier, parameters);
+                if let Some(return_type) = return_type {
+                    self.output += &format!(" -> {0}", return_type);
+                }
+                self.format_body(body, indent, line);
+                self.end_line(line);
+            }
+            // Methods are formatted like the statements of a block
+            Node::ImplDeclaration {
+                identifier,
+                methods,
+            } => {
+                self.output += &format!("impl {0}", identifier);
+                self.format_body(methods, indent, line);
+                self.end_line(line);
+            }
+            _ => {
+                let expression = self.expression(node, indent);
+                self.output += &expression;
+                self.end_line(line);
+            }
+        }
+    }
+
+    // The if, its elif/else chain and their blocks, without a line break after the last.
+    fn format_if(&mut self, node: &Node, indent: usize, line: Option<usize>) {
+        if let Node::IfExpression {
+            condition,
+            then_branch,
+            else_branch,
+        } = node
+        {
+            self.output += "if ";
+            if let Some(condition) = condition.as_ref() {
+                let condition = self.condition(condition, indent);
+                self.output += &condition;
+            }
+            // elif/else chain
+            let (mut block, mut line) = (then_branch.as_slice(), line);
+            let mut next = else_branch.as_ref().as_ref();
+            loop {
+                let branch = match next {
+                    Some(Node::ElseExpression { then_branch, .. }) => Some(then_branch.as_slice()),
+                    _ => None,
+                };
+                self.format_branch(block, indent, line, branch);
+                let (condition, then_branch, else_branch) = match next {
+                    Some(Node::ElseExpression {
+                        condition,
+                        then_branch,
+                        else_branch,
+                    }) => (condition, then_branch, else_branch),
+                    _ => break,
+                };
+                match condition.as_ref() {
+                    Some(condition) => {
+                        let condition = self.condition(condition, indent);
+                        self.output += &format!(" elif {0}", condition)
+                    }
+                    None => self.output += " else",
+                }
+                block = then_branch;
+                line = None;
+                next = else_branch.as_ref().as_ref();
+            }
+        }
+    }
+
+    // The try, its catch and finally blocks, without a line break after the last.
+    fn format_try(&mut self, node: &Node, indent: usize, line: Option<usize>) {
+        if let Node::TryExpression {
+            body,
+            catch,
+            finally,
+        } = node
+        {
+            self.output += "try";
+            let finally_block = finally.as_ref().as_ref().map(|finally| finally.as_slice());
+            let next = catch.as_ref().as_ref().map(|catch| catch.body.as_slice());
+            self.format_branch(body, indent, line, next.or(finally_block));
+            if let Some(catch) = catch.as_ref() {
+                let binding = self.expression(&catch.binding, indent);
+                self.output += &format!(" catch {0}", binding);
+                self.format_branch(&catch.body, indent, None, finally_block);
+            }
+            if let Some(finally) = finally.as_ref() {
+                self.output += " finally";
+                self.format_body(finally, indent, None);
+            }
+        }
+    }
+
+    // ` {`, the indented statements and the closing brace, without a line break after it.
+    // The trailing comment of the first line is kept, unless the block is empty.
+    fn format_body(&mut self, block: &[Node], indent: usize, line: Option<usize>) {
+        self.format_branch(block, indent, line, None);
+    }
+
+    // Like `format_body`, for a block followed by the block of another branch. Comments
+    // up to the next block belong to this one.
+    fn format_branch(
+        &mut self,
+        block: &[Node],
+        indent: usize,
+        line: Option<usize>,
+        next: Option<&[Node]>,
+    ) {
+        if block.is_empty() {
+            self.output += " {}";
+            return;
+        }
+        self.output += " {";
+        self.end_line(line);
+        let first = self.statement;
+        self.format_block(block, indent + 1);
+        let mut end = self.blocks.get(&first).map(|block| block.close);
+        // The next block starts with the next statement, unless it is empty
+        if next.is_some_and(|next| !next.is_empty()) {
+            if let Some(next) = self.blocks.get(&self.statement) {
+                end = Some(next.open - 1);
+            }
+        }
+        if let Some(end) = end {
+            while self
+                .next_comment
+                .as_ref()
+                .is_some_and(|comment| comment.line <= end)
+            {
+                let comment = self.next_comment.take().unwrap();
+                self.write_comment(&comment, indent + 1, false);
+                self.next_comment = self.comments.next();
+            }
+        }
+        self.output += &INDENT.repeat(indent);
+        self.output.push('}');
+    }
+
+    // ` {`, one arm per line and the closing brace, like `format_body`. Arms with a single
+    // statement are written as `pattern => statement`, unless the statement has a block.
+    fn format_arms(&mut self, arms: &[MatchArm], indent: usize, line: Option<usize>) {
+        if arms.is_empty() {
+            self.output += " {}";
+            return;
+        }
+        self.output += " {";
+        self.end_line(line);
+        for (i, arm) in arms.iter().enumerate() {
+            let mut head = self.pattern(&arm.pattern, indent + 1);
+            if let Some(guard) = &arm.guard {
+                head += &format!(" if {0}", self.expression(guard, indent + 1));
+            }
+            match arm.body.as_slice() {
+                [statement] if !Formatter::has_block(statement) && !self.comment_in_block() => {
+                    let line = self.next_line(i == 0, indent + 1);
+                    self.output += &INDENT.repeat(indent + 1);
+                    self.line = line;
+                    let statement = self.expression(statement, indent + 1);
+                    self.output += &format!("{0} => {1}", head, statement);
+                    self.end_line(line);
+                }
+                body => {
+                    self.output += &format!("{0}{1} =>", INDENT.repeat(indent + 1), head);
+                    self.format_body(body, indent + 1, None);
+                    self.output.push('\n');
+                }
+            }
+        }
+        self.output += &INDENT.repeat(indent);
+        self.output.push('}');
+    }
+
+    // Statements that `format_statement` writes with a block of their own.
+    fn has_block(node: &Node) -> bool {
+        matches!(
+            node,
+            Node::IfExpression { .. }
+                | Node::TryExpression { .. }
+                | Node::WhileExpression { .. }
+                | Node::FunctionDeclaration { .. }
+                | Node::ImplDeclaration { .. }
+        )
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, indent: usize) -> String {
+        match pattern {
+            Pattern::Wildcard => "_".to_string(),
+            Pattern::Binding(node) | Pattern::Literal(node) => self.expression(node, indent),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => format!(
+                "{0}{1}{2}",
+                self.expression(start, indent),
+                if *inclusive { "..=" } else { ".." },
+                self.expression(end, indent)
+            ),
+            Pattern::List { elements, rest } => {
+                let mut elements: Vec<String> = elements
+                    .iter()
+                    .map(|element| self.pattern(element, indent))
+                    .collect();
+                match rest.as_deref() {
+                    Some(Pattern::Wildcard) => elements.push("..".to_string()),
+                    Some(rest) => elements.push(format!("..{0}", self.pattern(rest, indent))),
+                    None => {}
+                }
+                format!("[{0}]", elements.join(", "))
+            }
+            Pattern::Struct { identifier, fields } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, pattern)| match pattern {
+                        // `x` is short for `x: x`
+                        Pattern::Binding(node) if Formatter::is_name(node, field) => field.clone(),
+                        pattern => format!("{0}: {1}", field, self.pattern(pattern, indent)),
+                    })
+                    .collect();
+                match fields.is_empty() {
+                    true => format!("{0} {{}}", identifier),
+                    false => format!("{0} {{ {1} }}", identifier, fields.join(", ")),
+                }
+            }
+            Pattern::Variant {
+                enum_name,
+                variant,
+                elements,
+            } => {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|element| self.pattern(element, indent))
+                    .collect();
+                // Built-in variants are written by their short names, e.g. `some(x)`
+                let name = match short_variant_name(enum_name, variant) {
+                    Some(name) => name.to_string(),
+                    None => format!("{0}::{1}", enum_name, variant),
+                };
+                match elements.is_empty() {
+                    true => name,
+                    false => format!("{0}({1})", name, elements.join(", ")),
+                }
+            }
+        }
+    }
+
+    fn is_name(node: &Node, name: &str) -> bool {
+        match node {
+            Node::IdentifierExpression(identifier) | Node::VariableExpression { identifier, .. } => {
+                identifier == name
+            }
+            _ => false,
+        }
+    }
+
+    // Condition of if, elif or while, and the value of a match.
+    fn condition(&mut self, node: &Node, indent: usize) -> String {
+        let in_condition = std::mem::replace(&mut self.in_condition, true);
+        let condition = self.expression(node, indent);
+        self.in_condition = in_condition;
+        condition
+    }
+
+    fn expression(&mut self, node: &Node, indent: usize) -> String {
+        match node {
+            Node::NumberExpression(number) => number.literal(),
+            Node::LiteralExpression(literal, Keyword::None) => format!("\"{0}\"", literal),
+            Node::LiteralExpression(literal, _) => literal.clone(),
+            Node::IdentifierExpression(identifier)
+            | Node::VariableExpression { identifier, .. } => identifier.clone(),
+            Node::NegativeNumberExpression(expr) => {
+                format!("-{0}", self.operand(expr, indent, Formatter::ATOM, false))
+            }
+            Node::BinaryExpr {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                let precedence = Formatter::precedence(node);
+                // A unit would swallow `* name`, `/ name` and `^ n` following a quantity
+                let unit_follows = Formatter::ends_with_quantity(l_expr)
+                    && matches!(operator, Token::Multiply | Token::Divide | Token::Pow);
+                format!(
+                    "{0} {1} {2}",
+                    self.operand(l_expr, indent, precedence, unit_follows),
+                    operator.symbol(),
+                    self.operand(r_expr, indent, precedence + 1, false)
+                )
+            }
+            Node::ConditionExpression {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                let precedence = Formatter::precedence(node);
+                format!(
+                    "{0} {1} {2}",
+                    self.operand(l_expr, indent, precedence, false),
+                    operator.symbol(),
+                    self.operand(r_expr, indent, precedence + 1, false)
+                )
+            }
+            Node::QuantityExpression { value, unit } => {
+                format!("{0} {1}", self.expression(value, indent), unit)
+            }
+            Node::ConversionExpression { expr, unit } => format!(
+                "{0} in {1}",
+                self.operand(expr, indent, Formatter::precedence(node), false),
+                unit
+            ),
+            Node::AssignmentExpression {
+                identifier,
+                expr,
+                annotation,
+                ..
+            } => {
+                let target = self.expression(identifier, indent);
+                match annotation {
+                    Some(annotation) => format!(
+                        "{0}: {1} = {2}",
+                        target,
+                        annotation,
+                        self.expression(expr, indent)
+                    ),
+                    None => format!("{0} = {1}", target, self.expression(expr, indent)),
+                }
+            }
+            Node::CallExpression { callee, arguments } => {
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument, indent))
+                    .collect();
+                format!(
+                    "{0}({1})",
+                    self.expression(callee, indent),
+                    arguments.join(", ")
+                )
+            }
+            Node::FunctionExpression {
+                parameters,
+                parameter_types,
+                return_type,
+                body,
+            } => {
+                let mut closure = format!("fn({0})", Formatter::parameters(parameters, parameter_types));
+                if let Some(return_type) = return_type {
+                    closure += &format!(" -> {0}", return_type);
+                }
+                match body.as_slice() {
+                    // A single value stays on one line, it is returned implicitly. Branches,
+                    // arms and other statements need lines of their own.
+                    [value]
+                        if value.has_value()
+                            && !matches!(
+                                value,
+                                Node::IfExpression { .. }
+                                    | Node::MatchExpression { .. }
+                                    | Node::TryExpression { .. }
+                            )
+                            && !self.comment_in_block() =>
+                    {
+                        self.take_line();
+                        let value = self.expression(value, indent);
+                        format!("{0} {{ {1} }}", closure, value)
+                    }
+                    _ => {
+                        let output = std::mem::take(&mut self.output);
+                        let line = self.line.take();
+                        self.format_body(body, indent, line);
+                        closure + &std::mem::replace(&mut self.output, output)
+                    }
+                }
+            }
+            Node::ListExpression(elements) => {
+                let elements: Vec<String> = elements
+                    .iter()
+                    .map(|element| self.expression(element, indent))
+                    .collect();
+                format!("[{0}]", elements.join(", "))
+            }
+            Node::IndexExpression { expr, index } => format!(
+                "{0}[{1}]",
+                self.operand(expr, indent, Formatter::ATOM, false),
+                self.expression(index, indent)
+            ),
+            Node::StructDeclaration { identifier, fields } if fields.is_empty() => {
+                format!("struct {0} {{}}", identifier)
+            }
+            Node::StructDeclaration { identifier, fields } => {
+                format!("struct {0} {{ {1} }}", identifier, fields.join(", "))
+            }
+            Node::StructExpression { identifier, fields } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| format!("{0}: {1}", field, self.expression(value, indent)))
+                    .collect();
+                let value = match fields.is_empty() {
+                    true => format!("{0} {{}}", identifier),
+                    false => format!("{0} {{ {1} }}", identifier, fields.join(", ")),
+                };
+                // The brace would open the block of the condition
+                match self.in_condition {
+                    true => format!("({0})", value),
+                    false => value,
+                }
+            }
+            Node::FieldExpression { expr, field } => format!(
+                "{0}.{1}",
+                self.operand(expr, indent, Formatter::ATOM, false),
+                field
+            ),
+            Node::FieldAssignmentExpression { expr, field, value } => format!(
+                "{0}.{1} = {2}",
+                self.operand(expr, indent, Formatter::ATOM, false),
+                field,
+                self.expression(value, indent)
+            ),
+            Node::EnumDeclaration {
+                identifier,
+                variants,
+            } => {
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|(variant, fields)| match fields.is_empty() {
+                        true => variant.clone(),
+                        false => format!("{0}({1})", variant, fields.join(", ")),
+                    })
+                    .collect();
+                match variants.is_empty() {
+                    true => format!("enum {0} {{}}", identifier),
+                    false => format!("enum {0} {{ {1} }}", identifier, variants.join(", ")),
+                }
+            }
+            Node::VariantExpression {
+                enum_name,
+                variant,
+                arguments,
+            } => {
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument, indent))
+                    .collect();
+                match arguments.is_empty() {
+                    true if short_variant_name(enum_name, variant) == Some("none") => "none".to_string(),
+                    true => format!("{0}::{1}", enum_name, variant),
+                    false => format!("{0}::{1}({2})", enum_name, variant, arguments.join(", ")),
+                }
+            }
+            // Branches, arms and try blocks are written on their own lines, like the
+            // statements of a closure
+            Node::IfExpression { .. } => {
+                let output = std::mem::take(&mut self.output);
+                let line = self.line.take();
+                self.format_if(node, indent, line);
+                std::mem::replace(&mut self.output, output)
+            }
+            Node::TryExpression { .. } => {
+                let output = std::mem::take(&mut self.output);
+                let line = self.line.take();
+                self.format_try(node, indent, line);
+                std::mem::replace(&mut self.output, output)
+            }
+            Node::MatchExpression { expr, arms } => {
+                let value = self.condition(expr, indent);
+                let output = std::mem::take(&mut self.output);
+                let line = self.line.take();
+                self.format_arms(arms, indent, line);
+                format!("match {0}", value) + &std::mem::replace(&mut self.output, output)
+            }
+            Node::BreakExpression => "break".to_string(),
+            Node::ThrowExpression(value) => format!("throw {0}", self.expression(value, indent)),
+            Node::PropagateExpression(value) => {
+                format!("{0}?", self.operand(value, indent, Formatter::ATOM, false))
+            }
+            Node::ImportExpression { path, alias, names } => match alias.as_ref() {
+                Some(alias) => format!("import \"{0}\" as {1}", path, self.expression(alias, indent)),
+                None => {
+                    let names: Vec<String> =
+                        names.iter().map(|name| self.expression(name, indent)).collect();
+                    format!("from \"{0}\" import {1}", path, names.join(", "))
+                }
+            },
+            Node::ExportStatement(names) => format!("export {0}", names.join(", ")),
+            Node::ReturnExpression(value) => match value.as_ref() {
+                Some(value) => format!("return {0}", self.expression(value, indent)),
+                None => "return".to_string(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    // Parameter list of a function, with the annotated types.
+    fn parameters(parameters: &[String], parameter_types: &[Option<Type>]) -> String {
+        let parameters: Vec<String> = parameters
+            .iter()
+            .zip(parameter_types)
+            .map(|(parameter, annotation)| match annotation {
+                Some(annotation) => format!("{0}: {1}", parameter, annotation),
+                None => parameter.clone(),
+            })
+            .collect();
+        parameters.join(", ")
+    }
+
+    // Whether the printed expression ends with a unit.
+    fn ends_with_quantity(node: &Node) -> bool {
+        match node {
+            Node::QuantityExpression { .. } => true,
+            Node::NegativeNumberExpression(expr) => Formatter::ends_with_quantity(expr),
+            Node::BinaryExpr { r_expr, .. } | Node::ConditionExpression { r_expr, .. } => {
+                Formatter::ends_with_quantity(r_expr)
+            }
+            _ => false,
+        }
+    }
+
+    // Operands binding looser than the operator get parentheses.
+    fn operand(&mut self, node: &Node, indent: usize, precedence: u8, force: bool) -> String {
+        if force || Formatter::precedence(node) < precedence {
+            format!("({0})", self.expression(node, indent))
+        } else {
+            self.expression(node, indent)
+        }
+    }
+
+    const ATOM: u8 = 7;
+
+    // Binding strength of an expression, same order as token::Precedence
+    fn precedence(node: &Node) -> u8 {
+        match node {
+            Node::AssignmentExpression { .. } | Node::FieldAssignmentExpression { .. } => 0,
+            Node::ConditionExpression { .. } => 1,
+            Node::ConversionExpression { .. } => 2,
+            Node::BinaryExpr { operator, .. } => match operator {
+                Token::Add | Token::Subtract => 3,
+                Token::Multiply | Token::Divide => 4,
+                _ => 5,
+            },
+            Node::NegativeNumberExpression(_) => 6,
+            _ => Formatter::ATOM,
+        }
+    }
+}
@@ -0,0 +1,292 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::passes::formatter::Formatter;
+
+    // Format the source, make sure the result parses to the same tree and formats to itself.
+    fn format(source: &str) -> String {
+        let formatted = Formatter::format_source(source).unwrap();
+        assert_eq!(
+            Parser::new(&formatted).unwrap().parse().unwrap(),
+            Parser::new(source).unwrap().parse().unwrap(),
+            "Formatting changed the meaning of:\n{0}",
+            formatted
+        );
+        assert_eq!(Formatter::format_source(&formatted).unwrap(), formatted);
+        formatted
+    }
+
+    #[test]
+    fn test_layout() {
+        let source = "x=1
+if x==1{ y=2 }
+elif x>2 {
+y = 3}
+else{}
+while x<10{x=x+1
+if x == 5 { break }}
+fn add(a:num,b)->num{return a+b}
+add(1,2)";
+        let expected = "x = 1
+if x == 1 {
+    y = 2
+} elif x > 2 {
+    y = 3
+} else {}
+while x < 10 {
+    x = x + 1
+    if x == 5 {
+        break
+    }
+}
+fn add(a: num, b) -> num {
+    return a + b
+}
+add(1, 2)
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(format("(1+2)*3-(4-5)"), "(1 + 2) * 3 - (4 - 5)\n");
+        assert_eq!(format("2^(3^2)\n(2^3)^2"), "2 ^ (3 ^ 2)\n2 ^ 3 ^ 2\n");
+        assert_eq!(
+            format("a = -(1+x)\nb = - -x\nc = -2^2"),
+            "a = -(1 + x)\nb = -(-x)\nc = -2 ^ 2\n"
+        );
+        assert_eq!(
+            format("s: str = \"hi\"\nb = true\nr = return_value()"),
+            "s: str = \"hi\"\nb = true\nr = return_value()\n"
+        );
+        // Units must not swallow the operators following them
+        assert_eq!(
+            format("g = 9.81 m/s^2\n(5 m) * x\n2 * (3 km) / t\nd in km"),
+            "g = 9.81 m/s^2\n(5 m) * x\n(2 * 3 km) / t\nd in km\n"
+        );
+        // A decimal point makes a float, it must not get lost
+        assert_eq!(format("x = 1.0 / 0.0"), "x = 1.0 / 0.0\n");
+    }
+
+    #[test]
+    fn test_closures_and_lists() {
+        assert_eq!(
+            format("double=fn(x){x*2}\nitems=[ 1,double ( 2 ) ]\n(a+b)[0]"),
+            "double = fn(x) { x * 2 }\nitems = [1, double(2)]\n(a + b)[0]\n"
+        );
+        // An explicit return is kept
+        assert_eq!(
+            format("double=fn(x){return x*2}"),
+            "double = fn(x) {\n    return x * 2\n}\n"
+        );
+        let source = "counter = fn(start: num) -> num { # first
+count = start # start
+  while count < 10 { count = count + 1 }
+return count }
+counter(1)";
+        let expected = "counter = fn(start: num) -> num { # first
+    count = start # start
+    while count < 10 {
+        count = count + 1
+    }
+    return count
+}
+counter(1)
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn test_if_values() {
+        let source = "y=1+if x{2}else{3}
+f=fn(n){if n>0{n}else{-n}}";
+        let expected = "y = 1 + if x {
+    2
+} else {
+    3
+}
+f = fn(n) {
+    if n > 0 {
+        n
+    } else {
+        -n
+    }
+}
+";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_structs() {
+        let source = "struct Point{x,
+y}
+struct Empty{}
+impl Point{fn norm2(self){return self.x*self.x+self.y*self.y}}
+p=Point{x:1,y:2}
+if p==(Point{x:1,y:2}){p.x=p.norm2()}";
+        let expected = "struct Point { x, y }
+struct Empty {}
+impl Point {
+    fn norm2(self) {
+        return self.x * self.x + self.y * self.y
+    }
+}
+p = Point { x: 1, y: 2 }
+if p == (Point { x: 1, y: 2 }) {
+    p.x = p.norm2()
+}
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn test_match() {
+        let source = "enum Shape{Circle(r),
+Empty}
+x=match s{Shape::Circle(r)if r>1=>r*2,Point{x,y:b}=>{y=b
+y}
+[1,..rest]=>rest
+1..=5=>Shape::Empty
+_=>0}";
+        let expected = "enum Shape { Circle(r), Empty }
+x = match s {
+    Shape::Circle(r) if r > 1 => r * 2
+    Point { x, y: b } => {
+        y = b
+        y
+    }
+    [1, ..rest] => rest
+    1..=5 => Shape::Empty
+    _ => 0
+}
+";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "# Header
+
+x = 1 # one
+
+
+# About y
+y = 2
+if x > 0 { # positive
+  # inside
+  x = 0
+}
+# The end";
+        let expected = "# Header
+
+x = 1 # one
+
+# About y
+y = 2
+if x > 0 { # positive
+    # inside
+    x = 0
+}
+# The end
+";
+        assert_eq!(format(source), expected);
+        assert_eq!(format("# Only a comment"), "# Only a comment\n");
+    }
+
+    #[test]
+    fn test_comments_at_the_end_of_blocks() {
+        let source = "if x > 0 {
+  y = 1
+  # inside
+} # after close
+# between
+elif x < 0 {
+  y = 2
+  # last
+}
+else { y = 3 }
+f = fn(a) {
+  a # tail
+}
+try { x = 1
+  # end of try
+} catch e { y = 2 }";
+        let expected = "if x > 0 {
+    y = 1
+    # inside
+    # after close
+    # between
+} elif x < 0 {
+    y = 2
+    # last
+} else {
+    y = 3
+}
+f = fn(a) {
+    a # tail
+}
+try {
+    x = 1
+    # end of try
+} catch e {
+    y = 2
+}
+";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+        // Comments inside a match arm keep the arm on its own lines
+        assert_eq!(
+            format("match x {\n1 => { a\n# one\n}\n_ => b\n}"),
+            "match x {\n    1 => {\n        a\n        # one\n    }\n    _ => b\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_try() {
+        let source = "try{x=1/0}catch e{throw error(e.message,\"Wrapped\")}finally{done=true}
+y=try{1}catch e{2}";
+        let expected = "try {
+    x = 1 / 0
+} catch e {
+    throw error(e.message, \"Wrapped\")
+} finally {
+    done = true
+}
+y = try {
+    1
+} catch e {
+    2
+}
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn test_results_and_options() {
+        let source = "fn f(a){return ok(g(a)?+(h(a)?))}
+x=match f(1){ok(v)=>v,err(_)=>none}";
+        let expected = "fn f(a) {
+    return ok(g(a)? + h(a)?)
+}
+x = match f(1) {
+    ok(v) => v
+    err(_) => none
+}
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn test_modules() {
+        let source = "import   \"lib/geometry.wf\"   as geo
+from \"util.wf\" import f,g
+export f , geo";
+        let expected = "import \"lib/geometry.wf\" as geo
+from \"util.wf\" import f, g
+export f, geo
+";
+        assert_eq!(format(source), expected);
+    }
+}
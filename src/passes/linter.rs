@@ -0,0 +1,441 @@
+// Standard libraries
+use std::collections::HashSet;
+use std::error;
+use std::vec;
+
+// Internal modules
+use super::diagnostic::Diagnostic;
+use crate::lexer_and_parser::ast::{Comment, Node};
+use crate::lexer_and_parser::evaluator::Evaluator;
+use crate::lexer_and_parser::token::Keyword;
+use crate::lexer_and_parser::visitor::{walk_node, Visitor};
+
+// Linter
+/*
+    Warns about code that runs, but most likely doesn't do what was meant.
+    Every rule can be turned off in a config file:
+
+        # .wistful-lint
+        shadowing = off
+
+    or in the source with pragma comments:
+
+        # lint: disable self-assignment, empty-block   (from this line on)
+        # lint: enable self-assignment                 (from this line on)
+        x = x # lint: ignore self-assignment           (only this line)
+
+    `all` stands for every rule.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    AssignmentInCondition,
+    UnreachableCode,
+    EmptyBlock,
+    SelfAssignment,
+    ConstantCondition,
+    Shadowing,
+    DuplicateCondition,
+}
+
+pub struct LintConfig {
+    disabled: HashSet<Rule>,
+}
+
+pub struct Linter {
+    config: LintConfig,
+    lines: Option<vec::IntoIter<usize>>,
+    // Line of the statement being linted
+    line: Option<usize>,
+    comments: Vec<Comment>,
+    // Variables assigned at the top level
+    globals: HashSet<String>,
+    // Findings before pragmas are applied
+    findings: Vec<(Rule, Diagnostic)>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// Public methods
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::AssignmentInCondition,
+        Rule::UnreachableCode,
+        Rule::EmptyBlock,
+        Rule::SelfAssignment,
+        Rule::ConstantCondition,
+        Rule::Shadowing,
+        Rule::DuplicateCondition,
+    ];
+
+    // Name used in config files and pragmas
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::AssignmentInCondition => "assignment-in-condition",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::EmptyBlock => "empty-block",
+            Rule::SelfAssignment => "self-assignment",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::Shadowing => "shadowing",
+            Rule::DuplicateCondition => "duplicate-condition",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+}
+
+impl LintConfig {
+    // Every rule enabled
+    pub fn new() -> Self {
+        LintConfig {
+            disabled: HashSet::new(),
+        }
+    }
+
+    // Read a config file. Each line is `rule = on` or `rule = off`, `#` starts a comment.
+    pub fn parse(source: &str) -> Result<Self, Box<dyn error::Error>> {
+        let mut config = LintConfig::new();
+        for (i, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => return Err(format!("Line {0}: Expected `rule = on|off`", i + 1).into()),
+            };
+            let enabled = match value {
+                "on" => true,
+                "off" => false,
+                _ => {
+                    return Err(
+                        format!("Line {0}: Expected on or off, got {1}", i + 1, value).into(),
+                    )
+                }
+            };
+            for rule in
+                LintConfig::rules(name).ok_or(format!("Line {0}: Unknown rule {1}", i + 1, name))?
+            {
+                config.set(rule, enabled);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn set(&mut self, rule: Rule, enabled: bool) {
+        if enabled {
+            self.disabled.remove(&rule);
+        } else {
+            self.disabled.insert(rule);
+        }
+    }
+
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig::new()
+    }
+}
+
+impl Linter {
+    pub fn new(config: LintConfig) -> Self {
+        Linter {
+            config,
+            lines: None,
+            line: None,
+            comments: Vec::new(),
+            globals: HashSet::new(),
+            findings: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // Attach the statement lines collected by the parser to the diagnostics.
+    pub fn with_lines(mut self, statement_lines: Vec<usize>) -> Self {
+        self.lines = Some(statement_lines.into_iter());
+        self
+    }
+
+    // Comments collected by the parser, used for pragmas.
+    pub fn with_comments(mut self, comments: Vec<Comment>) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    // Lint parsed statements. Warnings are collected into `diagnostics`.
+    pub fn lint(&mut self, nodes: &[Node]) {
+        Evaluator::collect_assignments(nodes, &mut self.globals);
+        self.visit_block(nodes);
+        self.apply_pragmas();
+        self.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    }
+}
+
+// Private methods
+impl LintConfig {
+    // Rules matching a name in a config file or pragma
+    fn rules(name: &str) -> Option<Vec<Rule>> {
+        match name {
+            "all" => Some(Rule::ALL.to_vec()),
+            _ => Rule::from_name(name).map(|rule| vec![rule]),
+        }
+    }
+}
+
+impl Linter {
+    fn warn(&mut self, rule: Rule, message: String) {
+        let message = format!("{0} ({1})", message, rule.name());
+        self.findings
+            .push((rule, Diagnostic::warning(message, self.line)));
+    }
+
+    fn lint_branch(&mut self, block: &[Node], owner: &str) {
+        if block.is_empty() {
+            self.warn(Rule::EmptyBlock, format!("Empty block in {0}", owner));
+        }
+    }
+
+    fn lint_condition(&mut self, condition: &Node, owner: &str) {
+        if let Node::AssignmentExpression { .. } = condition {
+            self.warn(
+                Rule::AssignmentInCondition,
+                format!(
+                    "Assignment in the condition of {0}, did you mean ==?",
+                    owner
+                ),
+            );
+        } else if Linter::is_constant(condition) {
+            self.warn(
+                Rule::ConstantCondition,
+                format!("Condition of {0} is always the same", owner),
+            );
+        }
+    }
+
+    // Locals of a function named like a global variable. The owner names the function.
+    fn lint_shadowing(&mut self, owner: &str, parameters: &[String], body: &[Node]) {
+        for name in Evaluator::local_names(parameters, body) {
+            if !self.globals.contains(&name) {
+                continue;
+            }
+            let message = if parameters.contains(&name) {
+                format!("Parameter {0} of {1} shadows a global variable", name, owner)
+            } else {
+                format!("Variable {0} in {1} shadows a global variable", name, owner)
+            };
+            self.warn(Rule::Shadowing, message);
+        }
+    }
+
+    // Whether the expression has the same value every time it is evaluated.
+    fn is_constant(node: &Node) -> bool {
+        match node {
+            Node::NumberExpression(_) | Node::LiteralExpression(..) => true,
+            Node::NegativeNumberExpression(expr)
+            | Node::QuantityExpression { value: expr, .. }
+            | Node::ConversionExpression { expr, .. } => Linter::is_constant(expr),
+            Node::BinaryExpr { l_expr, r_expr, .. }
+            | Node::ConditionExpression { l_expr, r_expr, .. } => {
+                Linter::is_constant(l_expr) && Linter::is_constant(r_expr)
+            }
+            _ => false,
+        }
+    }
+
+    fn variable_name(node: &Node) -> Option<&String> {
+        match node {
+            Node::IdentifierExpression(name)
+            | Node::VariableExpression {
+                identifier: name, ..
+            } => Some(name),
+            _ => None,
+        }
+    }
+
+    // Drop findings turned off by the config or pragma comments.
+    fn apply_pragmas(&mut self) {
+        let mut pragmas = Vec::new();
+        for comment in &self.comments {
+            match Linter::parse_pragma(&comment.text) {
+                Some(Ok((action, rules))) => pragmas.push((comment.line, action, rules)),
+                Some(Err(name)) => self.diagnostics.push(Diagnostic::warning(
+                    format!("Unknown lint rule {0} in pragma", name),
+                    Some(comment.line),
+                )),
+                None => {}
+            }
+        }
+        for (rule, diagnostic) in std::mem::take(&mut self.findings) {
+            let mut enabled = self.config.is_enabled(rule);
+            if let Some(line) = diagnostic.line {
+                for (pragma_line, action, rules) in &pragmas {
+                    if *pragma_line > line || !rules.contains(&rule) {
+                        continue;
+                    }
+                    match *action {
+                        "disable" => enabled = false,
+                        "enable" => enabled = true,
+                        _ if *pragma_line == line => enabled = false,
+                        _ => {}
+                    }
+                }
+            }
+            if enabled {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    // `lint: <disable|enable|ignore> <rules>`, None if the comment is not a pragma.
+    // Unknown rule names are returned as the error.
+    fn parse_pragma(text: &str) -> Option<Result<(&str, Vec<Rule>), String>> {
+        let mut words = text
+            .trim()
+            .strip_prefix("lint:")?
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty());
+        let action = match words.next()? {
+            action @ ("disable" | "enable" | "ignore") => action,
+            _ => return None,
+        };
+        let mut rules = Vec::new();
+        for name in words {
+            match LintConfig::rules(name) {
+                Some(named) => rules.extend(named),
+                None => return Some(Err(name.to_string())),
+            }
+        }
+        Some(Ok((action, rules)))
+    }
+}
+
+// Rules are checked on the way down, blocks keep track of the statement lines
+impl Visitor for Linter {
+    fn visit_block(&mut self, nodes: &[Node]) {
+        let outer_line = self.line;
+        // Statement after which the rest of the block never runs
+        let mut terminator = None;
+        let mut reported = false;
+        for node in nodes {
+            if let Some(lines) = self.lines.as_mut() {
+                self.line = lines.next();
+            }
+            if let Some(keyword) = terminator {
+                if !reported {
+                    self.warn(
+                        Rule::UnreachableCode,
+                        format!("Unreachable code after {0}", keyword),
+                    );
+                    reported = true;
+                }
+            }
+            self.visit_node(node);
+            if terminator.is_none() {
+                terminator = match node {
+                    Node::BreakExpression => Some("break"),
+                    Node::ReturnExpression(_) => Some("return"),
+                    Node::ThrowExpression(_) => Some("throw"),
+                    _ => None,
+                };
+            }
+        }
+        self.line = outer_line;
+    }
+
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::AssignmentExpression {
+                identifier, expr, ..
+            } => {
+                if let (Some(target), Some(value)) = (
+                    Linter::variable_name(identifier),
+                    Linter::variable_name(expr),
+                ) {
+                    if target == value {
+                        self.warn(
+                            Rule::SelfAssignment,
+                            format!("Variable {0} is assigned to itself", target),
+                        );
+                    }
+                }
+            }
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let mut conditions = Vec::new();
+                if let Some(condition) = condition.as_ref() {
+                    self.lint_condition(condition, "if");
+                    conditions.push(condition);
+                }
+                self.lint_branch(then_branch, "if");
+                // Walk the elif/else chain
+                let mut next = else_branch.as_ref().as_ref();
+                while let Some(Node::ElseExpression {
+                    condition,
+                    then_branch,
+                    else_branch,
+                }) = next
+                {
+                    match condition.as_ref() {
+                        Some(condition) => {
+                            self.lint_condition(condition, "elif");
+                            if conditions.contains(&condition) {
+                                self.warn(
+                                    Rule::DuplicateCondition,
+                                    "Condition of elif repeats an earlier condition, the branch is never taken".to_string(),
+                                );
+                            }
+                            conditions.push(condition);
+                            self.lint_branch(then_branch, "elif");
+                        }
+                        None => self.lint_branch(then_branch, "else"),
+                    }
+                    next = else_branch.as_ref().as_ref();
+                }
+            }
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => {
+                match condition.as_ref() {
+                    // The usual loop that is left with break or return
+                    Some(Node::LiteralExpression(_, Keyword::True)) => {}
+                    Some(condition) => self.lint_condition(condition, "while"),
+                    None => {}
+                }
+                self.lint_branch(then_branch, "while");
+            }
+            // An empty catch silently drops the error
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => {
+                self.lint_branch(body, "try");
+                if let Some(catch) = catch.as_ref() {
+                    self.lint_branch(&catch.body, "catch");
+                }
+                if let Some(finally) = finally.as_ref() {
+                    self.lint_branch(finally, "finally");
+                }
+            }
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                body,
+                ..
+            } => self.lint_shadowing(&format!("function {0}", identifier), parameters, body),
+            Node::FunctionExpression {
+                parameters, body, ..
+            } => self.lint_shadowing("anonymous function", parameters, body),
+            _ => {}
+        }
+        walk_node(self, node);
+    }
+}
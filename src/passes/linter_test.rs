@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::passes::linter::{LintConfig, Linter, Rule};
+
+    // Lint the source and return the warnings as text.
+    fn lint(source: &str, config: LintConfig) -> Vec<String> {
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let mut linter = Linter::new(config)
+            .with_lines(parser.statement_lines.clone())
+            .with_comments(parser.comments.clone());
+        linter.lint(&nodes);
+        linter
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let source = "
+            x = 1
+            if x = 2 {
+                x = x
+            } elif x == 1 {
+            } elif x == 1 {
+                x = 3
+            }
+            while 1 < 2 {
+                break
+                x = 4
+                x = 5
+            }
+            fn f(x) {
+                return x
+            }";
+        assert_eq!(
+            lint(source, LintConfig::new()),
+            vec![
+                "line 3: warning: Assignment in the condition of if, did you mean ==? (assignment-in-condition)",
+                "line 3: warning: Empty block in elif (empty-block)",
+                "line 3: warning: Condition of elif repeats an earlier condition, the branch is never taken (duplicate-condition)",
+                "line 4: warning: Variable x is assigned to itself (self-assignment)",
+                "line 9: warning: Condition of while is always the same (constant-condition)",
+                "line 11: warning: Unreachable code after break (unreachable-code)",
+                "line 14: warning: Parameter x of function f shadows a global variable (shadowing)",
+            ]
+        );
+        assert_eq!(
+            lint("try {\n throw 1\n x = 2\n} catch e {\n}", LintConfig::new()),
+            vec![
+                "line 1: warning: Empty block in catch (empty-block)",
+                "line 3: warning: Unreachable code after throw (unreachable-code)",
+            ]
+        );
+        // Nothing to complain about
+        assert!(lint(
+            "i = 0\nwhile i < 10 {\n if i == 5 { break }\n i = i + 1\n}",
+            LintConfig::new()
+        )
+        .is_empty());
+        assert!(lint(
+            "i = 0\nwhile true {\n i = i + 1\n if i == 5 { break }\n}",
+            LintConfig::new()
+        )
+        .is_empty());
+        // Only a literal true is the usual endless loop
+        assert_eq!(
+            lint("while false {\n break\n}\nwhile 1 == 1 {\n break\n}\nif true {\n x = 1\n}", LintConfig::new()),
+            vec![
+                "line 1: warning: Condition of while is always the same (constant-condition)",
+                "line 4: warning: Condition of while is always the same (constant-condition)",
+                "line 7: warning: Condition of if is always the same (constant-condition)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let config = LintConfig::parse(
+            "# Defaults are on\nself-assignment = off\n\nall=on\nshadowing = off",
+        )
+        .unwrap();
+        assert!(config.is_enabled(Rule::SelfAssignment));
+        assert!(!config.is_enabled(Rule::Shadowing));
+        assert_eq!(
+            lint("x = 1\nfn f(x) {\n return x\n}", config),
+            Vec::<String>::new()
+        );
+        assert!(LintConfig::parse("shadowing").is_err());
+        assert!(LintConfig::parse("shadowing = maybe").is_err());
+        assert!(LintConfig::parse("spelling = off").is_err());
+    }
+
+    #[test]
+    fn test_pragmas() {
+        let source = "
+            x = 1
+            x = x # lint: ignore self-assignment
+            x = x
+            # lint: disable self-assignment, empty-block
+            x = x
+            if x > 0 {}
+            # lint: enable all
+            x = x
+            # lint: disable spelling";
+        assert_eq!(
+            lint(source, LintConfig::new()),
+            vec![
+                "line 4: warning: Variable x is assigned to itself (self-assignment)",
+                "line 9: warning: Variable x is assigned to itself (self-assignment)",
+                "line 10: warning: Unknown lint rule spelling in pragma",
+            ]
+        );
+    }
+}
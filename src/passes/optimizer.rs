@@ -0,0 +1,401 @@
+// Standard libraries
+use std::collections::HashMap;
+use std::vec;
+
+// Internal modules
+use crate::lexer_and_parser::ast::{Node, Type};
+use crate::lexer_and_parser::evaluator::EvalResult;
+use crate::lexer_and_parser::numeric::Number;
+use crate::lexer_and_parser::token::{Keyword, Token};
+use crate::lexer_and_parser::visitor::{fold_children, walk_block, Fold, Visitor};
+
+// Optimisation pass over the syntax tree.
+/*
+    - Constant BinaryExpr, NegativeNumberExpression and ConditionExpression subtrees are
+      folded into a single literal. Results that a NumberExpression can not represent
+      (rationals, infinities and NaN) and operations that fail (e.g. division by zero)
+      are left for run time.
+    - Identities `x * 1`, `1 * x`, `x / 1`, `x + 0`, `0 + x`, `x - 0` and `--x` are
+      simplified to `x` when x is known to be a number, i.e. arithmetic on number literals
+      that could not be folded, such as `1/3`, and variables the type checker knows to be
+      numbers if it is given their types with `with_types`. Other variables are left
+      alone, adding 0 to a string has to keep failing at run time. The checker does not
+      track units, so adding 0 to a variable holding a quantity no longer fails.
+    - `if true` / `if false` branches are resolved at compile time and the statements of
+      the taken branch replace the if statement.
+
+    Removing statements would break the line numbers the parser recorded, so the optimizer
+    keeps them in sync if it is given the lines with `with_lines`.
+*/
+pub struct Optimizer {
+    lines: Option<vec::IntoIter<usize>>,
+    // Line of the statement being optimised
+    line: Option<usize>,
+    // Known type of each variable, by statement line and name
+    variable_types: HashMap<(usize, String), Type>,
+    // Lines of the statements that are left after optimising
+    pub statement_lines: Vec<usize>,
+}
+
+// Public methods
+impl Optimizer {
+    pub fn new() -> Self {
+        Optimizer {
+            lines: None,
+            line: None,
+            variable_types: HashMap::new(),
+            statement_lines: Vec::new(),
+        }
+    }
+
+    // Keep the statement lines collected by the parser in sync with the optimised tree.
+    pub fn with_lines(mut self, statement_lines: Vec<usize>) -> Self {
+        self.lines = Some(statement_lines.into_iter());
+        self
+    }
+
+    // Types of variables, as collected by `TypeChecker::variable_types`. Only used together
+    // with the statement lines.
+    pub fn with_types(mut self, variable_types: HashMap<(usize, String), Type>) -> Self {
+        self.variable_types = variable_types;
+        self
+    }
+
+    // Optimise parsed statements.
+    pub fn optimize(&mut self, nodes: Vec<Node>) -> Vec<Node> {
+        self.fold_block(nodes)
+    }
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Optimizer::new()
+    }
+}
+
+// Private methods
+impl Optimizer {
+    fn next_line(&mut self) -> Option<usize> {
+        self.lines.as_mut().and_then(|lines| lines.next())
+    }
+
+    fn keep_line(&mut self, line: Option<usize>) {
+        if let Some(line) = line {
+            self.statement_lines.push(line);
+        }
+    }
+
+    // Skip the lines of removed statements, including the statements in their blocks.
+    fn skip_block(&mut self, nodes: &[Node]) {
+        let mut counter = StatementCounter { statements: 0 };
+        counter.visit_block(nodes);
+        self.skip_lines(counter.statements);
+    }
+
+    fn skip_else(&mut self, else_branch: &Option<Node>) {
+        let mut counter = StatementCounter { statements: 0 };
+        if let Some(else_branch) = else_branch {
+            counter.visit_node(else_branch);
+        }
+        self.skip_lines(counter.statements);
+    }
+
+    fn skip_lines(&mut self, count: usize) {
+        for _ in 0..count {
+            self.next_line();
+        }
+    }
+
+    // If statement in a block. Returns the statements replacing it.
+    fn optimize_if(
+        &mut self,
+        line: Option<usize>,
+        condition: Node,
+        then_branch: Vec<Node>,
+        else_branch: Option<Node>,
+    ) -> Vec<Node> {
+        match Optimizer::constant_condition(&condition) {
+            Some(true) => {
+                let statements = self.fold_block(then_branch);
+                self.skip_else(&else_branch);
+                statements
+            }
+            Some(false) => {
+                self.skip_block(&then_branch);
+                match else_branch {
+                    Some(Node::ElseExpression {
+                        condition: else_condition,
+                        then_branch,
+                        else_branch,
+                    }) => match *else_condition {
+                        // The first elif takes the place of the if
+                        Some(condition) => {
+                            let condition = self.fold_node(condition);
+                            self.optimize_if(line, condition, *then_branch, *else_branch)
+                        }
+                        None => self.fold_block(*then_branch),
+                    },
+                    _ => Vec::new(),
+                }
+            }
+            None => {
+                self.keep_line(line);
+                let then_branch = self.fold_block(then_branch);
+                vec![Node::IfExpression {
+                    condition: Box::new(Some(condition)),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(self.optimize_else(else_branch)),
+                }]
+            }
+        }
+    }
+
+    // Elif chain after a condition that is not constant.
+    fn optimize_else(&mut self, else_branch: Option<Node>) -> Option<Node> {
+        let (condition, then_branch, else_branch) = match else_branch {
+            Some(Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            }) => (*condition, *then_branch, *else_branch),
+            other => return other,
+        };
+        let condition = match condition {
+            Some(condition) => self.fold_node(condition),
+            None => {
+                return Some(Node::ElseExpression {
+                    condition: Box::new(None),
+                    then_branch: Box::new(self.fold_block(then_branch)),
+                    else_branch: Box::new(None),
+                })
+            }
+        };
+        match Optimizer::constant_condition(&condition) {
+            // Always taken elif becomes the else branch
+            Some(true) => {
+                let then_branch = self.fold_block(then_branch);
+                self.skip_else(&else_branch);
+                Some(Node::ElseExpression {
+                    condition: Box::new(None),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(None),
+                })
+            }
+            Some(false) => {
+                self.skip_block(&then_branch);
+                self.optimize_else(else_branch)
+            }
+            None => {
+                let then_branch = self.fold_block(then_branch);
+                Some(Node::ElseExpression {
+                    condition: Box::new(Some(condition)),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(self.optimize_else(else_branch)),
+                })
+            }
+        }
+    }
+
+    // Fold a binary expression with optimised operands.
+    fn fold_binary(&self, l_expr: Node, operator: Token, r_expr: Node) -> Node {
+        if let (Node::NumberExpression(l), Node::NumberExpression(r)) = (&l_expr, &r_expr) {
+            let result = match operator {
+                Token::Add => Ok(l + r),
+                Token::Subtract => Ok(l - r),
+                Token::Multiply => Ok(l * r),
+                Token::Divide => l.divide(r),
+                Token::Pow => l.pow(r),
+                _ => Err("Not an arithmetic operator".to_string()),
+            };
+            if let Some(folded) = result.ok().and_then(Optimizer::to_literal) {
+                return folded;
+            }
+        }
+        let is = |node: &Node, value: i64| *node == Node::NumberExpression(Number::Integer(value));
+        let (l_number, r_number) = (self.is_number(&l_expr), self.is_number(&r_expr));
+        match operator {
+            Token::Multiply if l_number && is(&r_expr, 1) => l_expr,
+            Token::Multiply if r_number && is(&l_expr, 1) => r_expr,
+            Token::Divide if l_number && is(&r_expr, 1) => l_expr,
+            Token::Add if l_number && is(&r_expr, 0) => l_expr,
+            Token::Add if r_number && is(&l_expr, 0) => r_expr,
+            Token::Subtract if l_number && is(&r_expr, 0) => l_expr,
+            _ => Node::BinaryExpr {
+                l_expr: Box::new(l_expr),
+                operator,
+                r_expr: Box::new(r_expr),
+            },
+        }
+    }
+
+    // Whether the expression always evaluates to a number.
+    fn is_number(&self, node: &Node) -> bool {
+        match node {
+            Node::NumberExpression(_) => true,
+            Node::IdentifierExpression(name)
+            | Node::VariableExpression {
+                identifier: name, ..
+            } => match self.line {
+                Some(line) => {
+                    self.variable_types.get(&(line, name.clone())) == Some(&Type::Number)
+                }
+                None => false,
+            },
+            Node::NegativeNumberExpression(expr) => self.is_number(expr),
+            Node::BinaryExpr {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                matches!(
+                    operator,
+                    Token::Add | Token::Subtract | Token::Multiply | Token::Divide | Token::Pow
+                ) && self.is_number(l_expr)
+                    && self.is_number(r_expr)
+            }
+            _ => false,
+        }
+    }
+
+    // Number literal evaluating to exactly the given number, if there is one.
+    fn to_literal(number: Number) -> Option<Node> {
+        match number {
+            Number::Rational(_) => None,
+            Number::Float(f) if !f.is_finite() => None,
+            number => Some(Node::NumberExpression(number)),
+        }
+    }
+
+    // Value of a literal node.
+    fn constant(node: &Node) -> Option<EvalResult> {
+        match node {
+            Node::NumberExpression(number) => Some(EvalResult::Number(number.clone())),
+            Node::LiteralExpression(_, Keyword::True) => Some(EvalResult::Boolean(true)),
+            Node::LiteralExpression(_, Keyword::False) => Some(EvalResult::Boolean(false)),
+            Node::LiteralExpression(s, Keyword::None) => Some(EvalResult::Literal(s.clone())),
+            _ => None,
+        }
+    }
+
+    fn constant_condition(condition: &Node) -> Option<bool> {
+        match Optimizer::constant(condition) {
+            Some(EvalResult::Boolean(b)) => Some(b),
+            _ => None,
+        }
+    }
+
+    fn boolean(value: bool) -> Node {
+        match value {
+            true => Node::LiteralExpression("true".to_string(), Keyword::True),
+            false => Node::LiteralExpression("false".to_string(), Keyword::False),
+        }
+    }
+}
+
+// Blocks follow the statement lines and splice in the taken branch of constant ifs,
+// expressions are folded bottom-up
+impl Fold for Optimizer {
+    fn fold_block(&mut self, nodes: Vec<Node>) -> Vec<Node> {
+        let outer_line = self.line;
+        let mut statements = Vec::new();
+        for node in nodes {
+            let line = self.next_line();
+            self.line = line;
+            match node {
+                Node::IfExpression {
+                    condition: if_condition,
+                    then_branch,
+                    else_branch,
+                } => match *if_condition {
+                    Some(condition) => {
+                        let condition = self.fold_node(condition);
+                        statements.extend(self.optimize_if(
+                            line,
+                            condition,
+                            *then_branch,
+                            *else_branch,
+                        ));
+                    }
+                    None => {
+                        self.keep_line(line);
+                        statements.push(Node::IfExpression {
+                            condition: Box::new(None),
+                            then_branch: Box::new(self.fold_block(*then_branch)),
+                            else_branch: Box::new(self.optimize_else(*else_branch)),
+                        });
+                    }
+                },
+                node => {
+                    self.keep_line(line);
+                    statements.push(self.fold_node(node));
+                }
+            }
+        }
+        self.line = outer_line;
+        statements
+    }
+
+    fn fold_node(&mut self, node: Node) -> Node {
+        match node {
+            Node::NegativeNumberExpression(expr) => match self.fold_node(*expr) {
+                // Negating a literal is always exact
+                Node::NumberExpression(number) => Node::NumberExpression(-&number),
+                // Double negation
+                Node::NegativeNumberExpression(inner) if self.is_number(&inner) => *inner,
+                expr => Node::NegativeNumberExpression(Box::new(expr)),
+            },
+            Node::BinaryExpr {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                let l_expr = self.fold_node(*l_expr);
+                let r_expr = self.fold_node(*r_expr);
+                self.fold_binary(l_expr, operator, r_expr)
+            }
+            Node::ConditionExpression {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                let l_expr = self.fold_node(*l_expr);
+                let r_expr = self.fold_node(*r_expr);
+                if let (Some(l_value), Some(r_value)) =
+                    (Optimizer::constant(&l_expr), Optimizer::constant(&r_expr))
+                {
+                    if let Ok(result) = l_value.compare(&operator, &r_value) {
+                        return Optimizer::boolean(result);
+                    }
+                }
+                Node::ConditionExpression {
+                    l_expr: Box::new(l_expr),
+                    operator,
+                    r_expr: Box::new(r_expr),
+                }
+            }
+            // If used as a value keeps its shape, only the parts are optimised
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => Node::IfExpression {
+                condition: Box::new(condition.map(|condition| self.fold_node(condition))),
+                then_branch: Box::new(self.fold_block(*then_branch)),
+                else_branch: Box::new(self.optimize_else(*else_branch)),
+            },
+            node => fold_children(self, node),
+        }
+    }
+}
+
+// Number of statements in blocks, including nested blocks
+struct StatementCounter {
+    statements: usize,
+}
+
+impl Visitor for StatementCounter {
+    fn visit_block(&mut self, nodes: &[Node]) {
+        self.statements += nodes.len();
+        walk_block(self, nodes);
+    }
+}
@@ -0,0 +1,192 @@
+#[cfg(test)]
+mod tests {
+    use crate::bytecode::compiler::Compiler;
+    use crate::bytecode::vm::Vm;
+    use crate::lexer_and_parser::ast::Node;
+    use crate::lexer_and_parser::evaluator::EvalResult;
+    use crate::lexer_and_parser::numeric::Number;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::lexer_and_parser::token::Token;
+    use crate::passes::optimizer::Optimizer;
+    use crate::passes::type_checker::TypeChecker;
+
+    fn optimize(source: &str) -> Vec<Node> {
+        Optimizer::new().optimize(Parser::new(source).unwrap().parse().unwrap())
+    }
+
+    // Optimise with the variable types known to the type checker.
+    fn optimize_typed(source: &str) -> Vec<Node> {
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let mut checker = TypeChecker::new().with_lines(parser.statement_lines.clone());
+        checker.check(&nodes);
+        Optimizer::new()
+            .with_lines(parser.statement_lines)
+            .with_types(checker.variable_types)
+            .optimize(nodes)
+    }
+
+    fn run(nodes: &[Node]) -> Result<EvalResult, String> {
+        let program = Compiler::new().compile(nodes).unwrap();
+        Vm::new(program).run().map_err(|e| e.to_string())
+    }
+
+    // Optimised and unoptimised programs have to produce the same result.
+    fn assert_same(source: &str) {
+        let nodes = Parser::new(source).unwrap().parse().unwrap();
+        let optimized = Optimizer::new().optimize(nodes.clone());
+        assert_eq!(
+            run(&optimized),
+            run(&nodes),
+            "Results differ for:\n{}",
+            source
+        );
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        assert_eq!(optimize("2*((1+2)*2)"), vec![Node::NumberExpression(Number::Integer(12))]);
+        assert_eq!(optimize("-(2 - 4.5)"), vec![Node::NumberExpression(Number::Float(2.5))]);
+        assert_eq!(
+            optimize("x * ((1+2)*2)"),
+            vec![Node::BinaryExpr {
+                l_expr: Box::new(Node::IdentifierExpression("x".to_string())),
+                operator: Token::Multiply,
+                r_expr: Box::new(Node::NumberExpression(Number::Integer(6))),
+            }]
+        );
+        // Rationals can't be written as a number literal and are kept as they are
+        assert_eq!(
+            optimize("1/3"),
+            Parser::new("1/3").unwrap().parse().unwrap()
+        );
+        assert_eq!(
+            optimize("2^100"),
+            vec![Node::NumberExpression(
+                Number::from_decimal("1267650600228229401496703205376").unwrap()
+            )]
+        );
+        assert_eq!(optimize("0.5 * 4"), vec![Node::NumberExpression(Number::Float(2.0))]);
+        // Errors are left for run time
+        assert_eq!(
+            optimize("1/0"),
+            Parser::new("1/0").unwrap().parse().unwrap()
+        );
+        assert_same("x = 10\nx * ((1+2)*2)");
+        assert_same("1/3 + 1/6 * 2^100");
+        assert_same("2.5 * 4 - 0.1");
+        assert_same("(3 + 4) km in m");
+    }
+
+    #[test]
+    fn test_identities() {
+        let third = optimize("1/3");
+        for source in [
+            "1/3 * 1",
+            "1 * (1/3)",
+            "1/3 / 1",
+            "1/3 + 0",
+            "0 + 1/3",
+            "1/3 - 0",
+            "--(1/3)",
+            "(1/3 + (2 - 2)) * (3 - 2)",
+        ]
+        .iter()
+        {
+            assert_eq!(optimize(source), third, "{}", source);
+        }
+        // Without their types, variables may not be numbers and have to fail like before
+        for source in ["x * 1", "0 + x", "--x", "5 m + 0", "\"a\" + 0"].iter() {
+            assert_eq!(
+                optimize(source),
+                Parser::new(source).unwrap().parse().unwrap(),
+                "{}",
+                source
+            );
+        }
+        assert_same("x = 1/3\ny = x * 1 + 0\n-(-y)");
+        assert_same("x = 5 m\nx + 0");
+    }
+
+    #[test]
+    fn test_numeric_variables() {
+        for (source, expected) in [
+            ("x = 2\nx * 1", "x = 2\nx"),
+            ("x = 2\n1 * x", "x = 2\nx"),
+            ("x = 2\nx / 1", "x = 2\nx"),
+            ("x = 2\nx + 0", "x = 2\nx"),
+            ("x = 2\n0 + x", "x = 2\nx"),
+            ("x = 2\nx - 0", "x = 2\nx"),
+            ("x = 2\ny = --x", "x = 2\ny = x"),
+            ("x = 2\ny = 3\n(x + 0) * (y * 1)", "x = 2\ny = 3\nx * y"),
+            ("x: num = 2\nx * 1", "x: num = 2\nx"),
+            ("fn f(a: num) {\n    a * 1\n}", "fn f(a: num) {\n    a\n}"),
+            // Only the variable's type at the statement counts
+            ("x = 2\nx = \"a\"\nx + 0", "x = 2\nx = \"a\"\nx + 0"),
+            ("x = \"a\"\nx + 0", "x = \"a\"\nx + 0"),
+            ("fn f(a) {\n    a * 1\n}", "fn f(a) {\n    a * 1\n}"),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                optimize_typed(source),
+                Parser::new(expected).unwrap().parse().unwrap(),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_dead_branches() {
+        let source = "
+            if 1 > 2 {
+                x = 1
+            }
+            elif true {
+                x = 2
+                y = 3
+            }
+            else {
+                x = 4
+            }
+            if false { z = 1 }";
+        assert_eq!(optimize(source), optimize("x = 2\ny = 3"));
+        let source = "
+            if x > 1 {
+                y = 1
+            }
+            elif 1 == 2 {
+                y = 2
+            }
+            elif 2 == 2 {
+                y = 3
+            }
+            else {
+                y = 4
+            }";
+        assert_eq!(
+            optimize(source),
+            optimize("if x > 1 { y = 1 } else { y = 3 }")
+        );
+        assert_same("x = 0\nwhile x < 10 {\n if true { x = x + 1 } else { break }\n}\nx");
+        assert_same("fn f(a) {\n if 1 == 1 { return a * 2 }\n return 0\n}\nf(21)");
+    }
+
+    #[test]
+    fn test_statement_lines() {
+        let source =
+            "if false {\n    x = 1\n}\nelse {\n    y = 1\n}\nfn f() {\n    return 1 / 0\n}\nf()";
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let mut optimizer = Optimizer::new().with_lines(parser.statement_lines.clone());
+        let nodes = optimizer.optimize(nodes);
+        assert_eq!(optimizer.statement_lines, vec![5, 7, 8, 10]);
+        let program = Compiler::new()
+            .with_lines(optimizer.statement_lines)
+            .compile(&nodes)
+            .unwrap();
+        let error = Vm::new(program).run().unwrap_err();
+        assert_eq!(error.to_string(), "Line 8: Division by zero");
+    }
+}
@@ -0,0 +1,796 @@
+// Standard libraries
+use std::collections::{HashMap, HashSet};
+use std::vec;
+
+// Internal modules
+use super::diagnostic::Diagnostic;
+use crate::lexer_and_parser::ast::{CatchClause, MatchArm, Node, Slot};
+use crate::lexer_and_parser::evaluator::Evaluator;
+use crate::lexer_and_parser::visitor::{fold_children, walk_node, Fold, Visitor};
+
+// Name resolution pass.
+/*
+    Binds every variable to its storage slot, using the same scoping rules as the
+    Evaluator: top level variables are globals, inside a function the parameters and every
+    assigned variable are locals, locals of enclosing functions are captured and any other
+    variable refers to a global. Assigning to a captured variable changes the enclosing
+    local instead of creating a new one. Identifiers are replaced by VariableExpressions carrying
+    the slot, so the evaluator doesn't have to look variables up by name. Names of declared
+    functions are left alone unless a variable shadows them, the evaluator looks them up.
+
+    While resolving, the pass tracks which variables are assigned on every path and on
+    some path to each use, and reports:
+    - errors for variables that are never assigned or are used before any assignment
+    - warnings for variables that may be used before they are assigned
+    - warnings for variables and parameters that are never used. Names starting with
+      an underscore are exempt.
+
+    Inside the body of a try with a catch the errors are warnings, the catch handles them
+    at runtime. A throw ends its path like a return does.
+
+    Imports assign their names like assignments do. Exports have to be top level
+    statements naming variables or functions of the top level, which count as used.
+*/
+pub struct Resolver {
+    lines: Option<vec::IntoIter<usize>>,
+    // Line of the statement being resolved
+    line: Option<usize>,
+    // Names of the global variable slots
+    pub globals: Vec<String>,
+    global_slots: HashMap<String, usize>,
+    // Globals declared by the host, they count as assigned and used
+    predeclared: HashSet<String>,
+    // Globals assigned anywhere at top level
+    assigned_globals: HashSet<String>,
+    // Functions declared anywhere, or by earlier runs
+    functions: HashSet<String>,
+    // Top level scope, and the scopes of the functions being resolved, innermost last
+    global_scope: Scope,
+    function_scopes: Vec<Scope>,
+    // Bodies of try blocks with a catch around the statement being resolved
+    catching: usize,
+    // Blocks around the statement being resolved, the top level is the first
+    blocks: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// Variables of a scope and what is known about them at the current point.
+struct Scope {
+    // Local slots, empty for the top level
+    slots: HashMap<String, usize>,
+    // Anonymous function, which changes the variables it captures
+    closure: bool,
+    flow: Flow,
+    // Variables read somewhere in the scope
+    used: HashSet<String>,
+    // Line of the first assignment of every variable, in assignment order
+    declarations: Vec<(String, Option<usize>)>,
+}
+
+// Assignment state at a point of the program.
+#[derive(Clone)]
+struct Flow {
+    // Assigned on every path
+    assigned: HashSet<String>,
+    // Assigned on at least one path
+    maybe_assigned: HashSet<String>,
+    // After break, return or throw nothing is reachable
+    unreachable: bool,
+}
+
+// Public methods
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            lines: None,
+            line: None,
+            globals: Vec::new(),
+            global_slots: HashMap::new(),
+            predeclared: HashSet::new(),
+            assigned_globals: HashSet::new(),
+            functions: HashSet::new(),
+            global_scope: Scope::new(HashMap::new()),
+            function_scopes: Vec::new(),
+            catching: 0,
+            blocks: 0,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // Attach the statement lines collected by the parser to the diagnostics.
+    pub fn with_lines(mut self, statement_lines: Vec<usize>) -> Self {
+        self.lines = Some(statement_lines.into_iter());
+        self
+    }
+
+    // Globals that already exist, e.g. `Evaluator::global_names`. They keep their slots.
+    pub fn with_globals(mut self, names: Vec<String>) -> Self {
+        for name in names {
+            self.global_slot(&name);
+            self.global_scope.flow.assign(&name);
+            self.predeclared.insert(name);
+        }
+        self
+    }
+
+    // Functions that already exist, e.g. the keys of `Evaluator::functions`.
+    pub fn with_functions(mut self, names: Vec<String>) -> Self {
+        self.functions.extend(names);
+        self
+    }
+
+    // Find every function declared in the given block, including nested blocks.
+    pub(crate) fn collect_functions(block: &[Node], names: &mut HashSet<String>) {
+        FunctionNames { names }.visit_block(block);
+    }
+
+    // Resolve parsed statements. Diagnostics are collected into `diagnostics`.
+    pub fn resolve(&mut self, nodes: Vec<Node>) -> Vec<Node> {
+        Evaluator::collect_assignments(&nodes, &mut self.assigned_globals);
+        Resolver::collect_functions(&nodes, &mut self.functions);
+        let nodes = self.fold_block(nodes);
+        let scope = std::mem::replace(&mut self.global_scope, Scope::new(HashMap::new()));
+        self.report_unused(&scope, |name| {
+            format!("Variable {0} is assigned but never used", name)
+        });
+        self.global_scope = scope;
+        // Stable sort keeps the order of diagnostics on the same line
+        self.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        nodes
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.is_error())
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Resolver::new()
+    }
+}
+
+// Private methods
+impl Resolver {
+    fn scope(&mut self) -> &mut Scope {
+        match self.function_scopes.last_mut() {
+            Some(scope) => scope,
+            None => &mut self.global_scope,
+        }
+    }
+
+    // Whether the name is a variable somewhere in reach, variables shadow functions.
+    fn is_variable(&self, identifier: &str) -> bool {
+        self.function_scopes
+            .iter()
+            .any(|scope| scope.slots.contains_key(identifier))
+            || self.assigned_globals.contains(identifier)
+            || self.predeclared.contains(identifier)
+    }
+
+    // Whether the name is a local of a function enclosing the current one.
+    fn is_captured(&self, identifier: &str) -> bool {
+        self.function_scopes
+            .iter()
+            .any(|scope| scope.slots.contains_key(identifier))
+    }
+
+    // Whether the name is a global captured by closures, which it is as long as every
+    // function around the current point is a closure.
+    fn is_captured_global(&self, identifier: &str) -> bool {
+        self.function_scopes.iter().all(|scope| scope.closure)
+            && (self.assigned_globals.contains(identifier) || self.predeclared.contains(identifier))
+    }
+
+    fn global_slot(&mut self, identifier: &str) -> usize {
+        if let Some(slot) = self.global_slots.get(identifier) {
+            return *slot;
+        }
+        self.globals.push(identifier.to_string());
+        let slot = self.globals.len() - 1;
+        self.global_slots.insert(identifier.to_string(), slot);
+        slot
+    }
+
+    // Condition and branches of an if or elif. Assigned afterwards is what every
+    // branch assigns, a missing else branch assigns nothing.
+    fn resolve_branches(
+        &mut self,
+        condition: Option<Node>,
+        then_branch: Vec<Node>,
+        else_branch: Option<Node>,
+    ) -> (Option<Node>, Vec<Node>, Option<Node>) {
+        let condition = match condition {
+            Some(condition) => self.fold_node(condition),
+            // Plain else is the only path through its block
+            None => return (None, self.fold_block(then_branch), else_branch),
+        };
+        let before = self.scope().flow.clone();
+        let then_branch = self.fold_block(then_branch);
+        let after_then = std::mem::replace(&mut self.scope().flow, before);
+        let else_branch = else_branch.map(|else_node| self.fold_node(else_node));
+        let flow = &mut self.scope().flow;
+        flow.join(after_then);
+        (Some(condition), then_branch, else_branch)
+    }
+
+    // Arms of a match, each starts from the state after the value. Assigned afterwards
+    // is what every arm assigns, unless no arm matches every value: then the match may
+    // assign nothing. Bindings only count inside their arm.
+    fn resolve_arms(&mut self, arms: Vec<MatchArm>) -> Vec<MatchArm> {
+        let before = self.scope().flow.clone();
+        let mut after = before.clone();
+        after.unreachable |= arms
+            .iter()
+            .any(|arm| arm.guard.is_none() && arm.pattern.is_irrefutable());
+        let mut resolved = Vec::new();
+        for arm in arms {
+            self.scope().flow = before.clone();
+            // Patterns only contain literals and the names they assign
+            let mut bindings = Vec::new();
+            let pattern = arm.pattern.map(&mut |node| match node {
+                Node::IdentifierExpression(name)
+                | Node::VariableExpression {
+                    identifier: name, ..
+                } => {
+                    bindings.push(name.clone());
+                    self.resolve_assignment(name)
+                }
+                node => node,
+            });
+            let guard = arm.guard.map(|guard| self.fold_node(guard));
+            let body = self.fold_block(arm.body);
+            let mut flow = std::mem::replace(&mut self.scope().flow, before.clone());
+            for binding in bindings {
+                if !before.assigned.contains(&binding) {
+                    flow.assigned.remove(&binding);
+                }
+                if !before.maybe_assigned.contains(&binding) {
+                    flow.maybe_assigned.remove(&binding);
+                }
+            }
+            after.join(flow);
+            resolved.push(MatchArm {
+                pattern,
+                guard,
+                body,
+            });
+        }
+        self.scope().flow = after;
+        resolved
+    }
+
+    // Resolve the body of a function in a new scope. The owner names it in messages.
+    fn resolve_function(
+        &mut self,
+        owner: &str,
+        closure: bool,
+        parameters: &[String],
+        body: Vec<Node>,
+    ) -> Vec<Node> {
+        // Assigned variables of enclosing functions stay captured, their slots go unused.
+        // Closures outside of named functions capture globals as well.
+        let slots = Evaluator::local_names(parameters, &body)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, name)| {
+                parameters.contains(name)
+                    || !(self.is_captured(name) || closure && self.is_captured_global(name))
+            })
+            .map(|(slot, name)| (name, slot))
+            .collect();
+        let mut scope = Scope::new(slots);
+        scope.closure = closure;
+        for parameter in parameters {
+            scope.flow.assign(parameter);
+            scope.declarations.push((parameter.clone(), self.line));
+        }
+        self.function_scopes.push(scope);
+        let catching = std::mem::replace(&mut self.catching, 0);
+        let body = self.fold_block(body);
+        self.catching = catching;
+        let scope = self.function_scopes.pop().unwrap();
+        self.report_unused(&scope, |name| {
+            if parameters.iter().any(|parameter| parameter == name) {
+                format!("Parameter {0} of {1} is never used", name, owner)
+            } else {
+                format!("Variable {0} is assigned but never used", name)
+            }
+        });
+        body
+    }
+
+    fn resolve_use(&mut self, identifier: String) -> Node {
+        let line = self.line;
+        let in_function = !self.function_scopes.is_empty();
+        let local = self.scope().slots.get(&identifier).copied();
+        // Locals of enclosing functions can be read at any time, e.g. by a closure
+        // that is called after the enclosing function assigned them
+        if local.is_none() {
+            let enclosing = self.function_scopes.iter_mut().rev().enumerate().skip(1);
+            for (depth, scope) in enclosing {
+                if let Some(&slot) = scope.slots.get(&identifier) {
+                    scope.used.insert(identifier.clone());
+                    let slot = Slot::Captured { depth, slot };
+                    return Node::VariableExpression { identifier, slot };
+                }
+            }
+        }
+        // Declared functions used as values
+        if !self.is_variable(&identifier) && self.functions.contains(&identifier) {
+            return Node::IdentifierExpression(identifier);
+        }
+        // Check the assignment state of variables belonging to the current scope
+        if local.is_some() || !in_function {
+            let scope = self.scope();
+            scope.used.insert(identifier.clone());
+            let flow = &scope.flow;
+            let message = if flow.unreachable || flow.assigned.contains(&identifier) {
+                None
+            } else if flow.maybe_assigned.contains(&identifier) {
+                Some(Diagnostic::warning(
+                    format!("Variable {0} may be used before it is assigned", identifier),
+                    line,
+                ))
+            } else if local.is_some() || self.assigned_globals.contains(&identifier) {
+                Some(Diagnostic::error(
+                    format!("Variable {0} is used before it is assigned", identifier),
+                    line,
+                ))
+            } else {
+                Some(Diagnostic::error(
+                    format!("Undefined variable {0}", identifier),
+                    line,
+                ))
+            };
+            let message = message.map(|message| self.caught(message));
+            self.diagnostics.extend(message);
+        }
+        // Globals used in functions only have to be assigned somewhere at top level
+        else {
+            self.global_scope.used.insert(identifier.clone());
+            if !self.assigned_globals.contains(&identifier)
+                && !self.predeclared.contains(&identifier)
+            {
+                let message = Diagnostic::error(format!("Undefined variable {0}", identifier), line);
+                let message = self.caught(message);
+                self.diagnostics.push(message);
+            }
+        }
+        let slot = match local {
+            Some(slot) => Slot::Local(slot),
+            None => Slot::Global(self.global_slot(&identifier)),
+        };
+        Node::VariableExpression { identifier, slot }
+    }
+
+    // Errors inside a try with a catch are only warnings.
+    fn caught(&self, diagnostic: Diagnostic) -> Diagnostic {
+        match self.catching > 0 && diagnostic.is_error() {
+            true => Diagnostic::warning(diagnostic.message, diagnostic.line),
+            false => diagnostic,
+        }
+    }
+
+    // Body, catch and finally of a try. The body can fail at any point, so the catch
+    // starts from the state before it, with anything the body assigns maybe assigned.
+    /*
+        Assigned afterwards is what both the body and the catch assign, and whatever the
+        finally block assigns. Without a catch an error leaves the try, so only the body
+        counts. The finally block also runs after errors, it starts from the state of the
+        catch instead.
+    */
+    fn resolve_try(
+        &mut self,
+        body: Vec<Node>,
+        catch: Option<CatchClause>,
+        finally: Option<Vec<Node>>,
+    ) -> (Vec<Node>, Option<CatchClause>, Option<Vec<Node>>) {
+        let mut failed = self.scope().flow.clone();
+        let mut assigned = HashSet::new();
+        Evaluator::collect_assignments(&body, &mut assigned);
+        failed.maybe_assigned.extend(assigned);
+        self.catching += catch.is_some() as usize;
+        let body = self.fold_block(body);
+        self.catching -= catch.is_some() as usize;
+        let catch = catch.map(|catch| {
+            let after_body = std::mem::replace(&mut self.scope().flow, failed.clone());
+            let binding = match catch.binding {
+                Node::IdentifierExpression(name)
+                | Node::VariableExpression {
+                    identifier: name, ..
+                } => self.resolve_assignment(name),
+                other => other,
+            };
+            let body = self.fold_block(catch.body);
+            failed = self.scope().flow.clone();
+            self.scope().flow.join(after_body);
+            CatchClause { binding, body }
+        });
+        let finally = finally.map(|finally| {
+            let after = self.scope().flow.clone();
+            self.scope().flow.join(failed);
+            let finally = self.fold_block(finally);
+            let flow = &mut self.scope().flow;
+            flow.assigned.extend(after.assigned);
+            flow.unreachable |= after.unreachable;
+            finally
+        });
+        (body, catch, finally)
+    }
+
+    // Assignments target the current scope, unless they change a captured variable.
+    fn resolve_assignment(&mut self, identifier: String) -> Node {
+        let line = self.line;
+        if let Some(node) = self.resolve_captured_assignment(&identifier) {
+            return node;
+        }
+        let scope = self.scope();
+        scope.flow.assign(&identifier);
+        if !scope
+            .declarations
+            .iter()
+            .any(|(name, _)| *name == identifier)
+        {
+            scope.declarations.push((identifier.clone(), line));
+        }
+        let slot = match scope.slots.get(&identifier) {
+            Some(slot) => Slot::Local(*slot),
+            None => Slot::Global(self.global_slot(&identifier)),
+        };
+        Node::VariableExpression { identifier, slot }
+    }
+
+    // Assignment to a local of an enclosing function, or to a global captured by a closure.
+    // It doesn't count as assigned there, the closure may run at any time or not at all.
+    fn resolve_captured_assignment(&mut self, identifier: &str) -> Option<Node> {
+        if self.scope().slots.contains_key(identifier) {
+            return None;
+        }
+        let enclosing = self.function_scopes.iter().rev().enumerate().skip(1);
+        for (depth, scope) in enclosing {
+            if let Some(&slot) = scope.slots.get(identifier) {
+                return Some(Node::VariableExpression {
+                    identifier: identifier.to_string(),
+                    slot: Slot::Captured { depth, slot },
+                });
+            }
+        }
+        if !self.function_scopes.is_empty() && self.is_captured_global(identifier) {
+            return Some(Node::VariableExpression {
+                identifier: identifier.to_string(),
+                slot: Slot::Global(self.global_slot(identifier)),
+            });
+        }
+        None
+    }
+
+    // Exported names have to exist once the top level has run.
+    fn resolve_export(&mut self, names: &[String]) {
+        let line = self.line;
+        if self.blocks != 1 {
+            let message = "Exports are only allowed at the top level".to_string();
+            self.diagnostics.push(Diagnostic::error(message, line));
+            return;
+        }
+        for name in names {
+            self.global_scope.used.insert(name.clone());
+            if !self.assigned_globals.contains(name)
+                && !self.predeclared.contains(name)
+                && !self.functions.contains(name)
+            {
+                let message = format!("Can not export {0}, it is never defined", name);
+                self.diagnostics.push(Diagnostic::error(message, line));
+            }
+        }
+    }
+
+    fn report_unused(&mut self, scope: &Scope, message: impl Fn(&str) -> String) {
+        for (name, line) in &scope.declarations {
+            // Methods do not have to use `self`
+            if scope.used.contains(name)
+                || self.predeclared.contains(name)
+                || name.starts_with('_')
+                || name == "self"
+            {
+                continue;
+            }
+            self.diagnostics
+                .push(Diagnostic::warning(message(name), *line));
+        }
+    }
+}
+
+// Statements follow the lines collected by the parser, every variable use and
+// assignment is bound to its slot
+impl Fold for Resolver {
+    fn fold_block(&mut self, nodes: Vec<Node>) -> Vec<Node> {
+        let outer_line = self.line;
+        let mut statements = Vec::new();
+        self.blocks += 1;
+        for node in nodes {
+            if let Some(lines) = self.lines.as_mut() {
+                self.line = lines.next();
+            }
+            statements.push(self.fold_node(node));
+        }
+        self.blocks -= 1;
+        self.line = outer_line;
+        statements
+    }
+
+    fn fold_node(&mut self, node: Node) -> Node {
+        match node {
+            Node::IdentifierExpression(identifier)
+            | Node::VariableExpression { identifier, .. } => self.resolve_use(identifier),
+            Node::AssignmentExpression {
+                identifier,
+                assignment_operator,
+                expr,
+                annotation,
+            } => {
+                // Value is evaluated before the variable is assigned
+                let expr = self.fold_node(*expr);
+                let identifier = match *identifier {
+                    Node::IdentifierExpression(name)
+                    | Node::VariableExpression {
+                        identifier: name, ..
+                    } => self.resolve_assignment(name),
+                    other => other,
+                };
+                Node::AssignmentExpression {
+                    identifier: Box::new(identifier),
+                    assignment_operator,
+                    expr: Box::new(expr),
+                    annotation,
+                }
+            }
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let (condition, then_branch, else_branch) =
+                    self.resolve_branches(*condition, *then_branch, *else_branch);
+                Node::IfExpression {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(else_branch),
+                }
+            }
+            Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let (condition, then_branch, else_branch) =
+                    self.resolve_branches(*condition, *then_branch, *else_branch);
+                Node::ElseExpression {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(else_branch),
+                }
+            }
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => {
+                let before = self.scope().flow.clone();
+                // From the second iteration on, anything assigned in the body may be set
+                let mut assigned = HashSet::new();
+                Evaluator::collect_assignments(&then_branch, &mut assigned);
+                self.scope().flow.maybe_assigned.extend(assigned);
+                let condition = condition.map(|condition| self.fold_node(condition));
+                let then_branch = self.fold_block(*then_branch);
+                // The body might not run at all, or stop at any break
+                let flow = &mut self.scope().flow;
+                flow.assigned = before.assigned;
+                flow.unreachable = before.unreachable;
+                Node::WhileExpression {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(then_branch),
+                }
+            }
+            Node::MatchExpression { expr, arms } => {
+                let expr = self.fold_node(*expr);
+                Node::MatchExpression {
+                    expr: Box::new(expr),
+                    arms: self.resolve_arms(arms),
+                }
+            }
+            Node::BreakExpression => {
+                self.scope().flow.unreachable = true;
+                Node::BreakExpression
+            }
+            Node::ReturnExpression(value) => {
+                let value = value.map(|value| self.fold_node(value));
+                self.scope().flow.unreachable = true;
+                Node::ReturnExpression(Box::new(value))
+            }
+            Node::ThrowExpression(value) => {
+                let value = self.fold_node(*value);
+                self.scope().flow.unreachable = true;
+                Node::ThrowExpression(Box::new(value))
+            }
+            Node::ImportExpression { path, alias, names } => {
+                let mut bind = |name: Node| match name {
+                    Node::IdentifierExpression(name)
+                    | Node::VariableExpression {
+                        identifier: name, ..
+                    } => self.resolve_assignment(name),
+                    other => other,
+                };
+                let alias = alias.map(&mut bind);
+                let names = names.into_iter().map(&mut bind).collect();
+                Node::ImportExpression {
+                    path,
+                    alias: Box::new(alias),
+                    names,
+                }
+            }
+            Node::ExportStatement(names) => {
+                self.resolve_export(&names);
+                Node::ExportStatement(names)
+            }
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => {
+                let (body, catch, finally) = self.resolve_try(*body, *catch, *finally);
+                Node::TryExpression {
+                    body: Box::new(body),
+                    catch: Box::new(catch),
+                    finally: Box::new(finally),
+                }
+            }
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                parameter_types,
+                return_type,
+                body,
+            } => {
+                let owner = format!("function {0}", identifier);
+                let body = self.resolve_function(&owner, false, &parameters, *body);
+                Node::FunctionDeclaration {
+                    identifier,
+                    parameters,
+                    parameter_types,
+                    return_type,
+                    body: Box::new(body),
+                }
+            }
+            Node::ImplDeclaration {
+                identifier,
+                methods,
+            } => {
+                let outer_line = self.line;
+                let mut resolved = Vec::new();
+                for method in *methods {
+                    if let Some(lines) = self.lines.as_mut() {
+                        self.line = lines.next();
+                    }
+                    resolved.push(match method {
+                        Node::FunctionDeclaration {
+                            identifier: name,
+                            parameters,
+                            parameter_types,
+                            return_type,
+                            body,
+                        } => {
+                            let owner = format!("method {0}.{1}", identifier, name);
+                            let body = self.resolve_function(&owner, false, &parameters, *body);
+                            Node::FunctionDeclaration {
+                                identifier: name,
+                                parameters,
+                                parameter_types,
+                                return_type,
+                                body: Box::new(body),
+                            }
+                        }
+                        method => self.fold_node(method),
+                    });
+                }
+                self.line = outer_line;
+                Node::ImplDeclaration {
+                    identifier,
+                    methods: Box::new(resolved),
+                }
+            }
+            Node::FunctionExpression {
+                parameters,
+                parameter_types,
+                return_type,
+                body,
+            } => {
+                let body = self.resolve_function("anonymous function", true, &parameters, *body);
+                Node::FunctionExpression {
+                    parameters,
+                    parameter_types,
+                    return_type,
+                    body: Box::new(body),
+                }
+            }
+            // Called names are only resolved when they are variables, otherwise they name
+            // a declared function
+            Node::CallExpression { callee, arguments } => Node::CallExpression {
+                callee: match *callee {
+                    Node::IdentifierExpression(identifier) if !self.is_variable(&identifier) => {
+                        Box::new(Node::IdentifierExpression(identifier))
+                    }
+                    callee => Box::new(self.fold_node(callee)),
+                },
+                arguments: arguments
+                    .into_iter()
+                    .map(|argument| self.fold_node(argument))
+                    .collect(),
+            },
+            node => fold_children(self, node),
+        }
+    }
+}
+
+// Private methods
+impl Scope {
+    fn new(slots: HashMap<String, usize>) -> Self {
+        Scope {
+            slots,
+            closure: false,
+            flow: Flow {
+                assigned: HashSet::new(),
+                maybe_assigned: HashSet::new(),
+                unreachable: false,
+            },
+            used: HashSet::new(),
+            declarations: Vec::new(),
+        }
+    }
+}
+
+// Private methods
+impl Flow {
+    fn assign(&mut self, identifier: &str) {
+        self.assigned.insert(identifier.to_string());
+        self.maybe_assigned.insert(identifier.to_string());
+    }
+
+    // Merge the state of another path reaching the same point.
+    fn join(&mut self, other: Flow) {
+        if other.unreachable {
+            return;
+        }
+        if self.unreachable {
+            *self = other;
+            return;
+        }
+        self.assigned.retain(|name| other.assigned.contains(name));
+        self.maybe_assigned.extend(other.maybe_assigned);
+    }
+}
+
+// Names of every declared function, including nested ones. Methods are only called
+// through their struct values, so they are not functions.
+struct FunctionNames<'a> {
+    names: &'a mut HashSet<String>,
+}
+
+impl<'a> Visitor for FunctionNames<'a> {
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::FunctionDeclaration { identifier, .. } => {
+                self.names.insert(identifier.clone());
+            }
+            Node::ImplDeclaration { methods, .. } => {
+                for method in methods.iter() {
+                    walk_node(self, method);
+                }
+                return;
+            }
+            _ => {}
+        }
+        walk_node(self, node);
+    }
+}
@@ -0,0 +1,284 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::ast::{Node, Slot};
+    use crate::lexer_and_parser::evaluator::{EvalResult, Evaluator};
+    use crate::lexer_and_parser::numeric::Number;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::lexer_and_parser::token::Token;
+    use crate::passes::resolver::Resolver;
+
+    // Resolve the source and return the diagnostics as text.
+    fn diagnostics(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let mut resolver = Resolver::new().with_lines(parser.statement_lines.clone());
+        resolver.resolve(nodes);
+        resolver
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_slots() {
+        let nodes = Parser::new("x = 1\nfn f(a) {\n b = a + x\n return b\n}\nf(x)")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut resolver = Resolver::new();
+        let nodes = resolver.resolve(nodes);
+        assert!(resolver.diagnostics.is_empty());
+        assert_eq!(resolver.globals, vec!["x".to_string()]);
+        let variable = |name: &str, slot| Node::VariableExpression {
+            identifier: name.to_string(),
+            slot,
+        };
+        let body = match &nodes[1] {
+            Node::FunctionDeclaration { body, .. } => body.clone(),
+            _ => panic!("Expected a function"),
+        };
+        assert_eq!(
+            body[0],
+            Node::AssignmentExpression {
+                identifier: Box::new(variable("b", Slot::Local(1))),
+                assignment_operator: Token::Assignment,
+                expr: Box::new(Node::BinaryExpr {
+                    l_expr: Box::new(variable("a", Slot::Local(0))),
+                    operator: Token::Add,
+                    r_expr: Box::new(variable("x", Slot::Global(0))),
+                }),
+                annotation: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_undefined_variables() {
+        assert_eq!(
+            diagnostics("x = 1\ny = x + z"),
+            vec![
+                "line 2: error: Undefined variable z",
+                "line 2: warning: Variable y is assigned but never used",
+            ]
+        );
+        assert_eq!(
+            diagnostics("y = x\nx = 1\ny + x"),
+            vec!["line 1: error: Variable x is used before it is assigned"]
+        );
+        // Assigning a variable inside a function makes it local
+        assert_eq!(
+            diagnostics("y = 1\nfn g() {\n y = y + 1\n}\ng()"),
+            vec![
+                "line 1: warning: Variable y is assigned but never used",
+                "line 3: error: Variable y is used before it is assigned",
+            ]
+        );
+        // Globals only have to be assigned somewhere before the call
+        assert!(diagnostics("fn f() { return y }\ny = 1\nf()").is_empty());
+        assert_eq!(
+            diagnostics("fn f() {\n return y\n}\nf()"),
+            vec!["line 2: error: Undefined variable y"]
+        );
+    }
+
+    #[test]
+    fn test_conditional_assignments() {
+        assert_eq!(
+            diagnostics("c = true\nif c {\n x = 1\n}\nx"),
+            vec!["line 5: warning: Variable x may be used before it is assigned"]
+        );
+        assert!(
+            diagnostics("c = true\nif c { x = 1 } elif c { x = 2 } else { x = 3 }\nx").is_empty()
+        );
+        assert!(
+            diagnostics("fn f(c) {\n if c { return 1 } else { x = 2 }\n return x\n}\nf(true)")
+                .is_empty()
+        );
+        // Variables assigned later in a loop body are set from the second iteration on
+        assert_eq!(
+            diagnostics("i = 0\nwhile i < 3 {\n if i > 0 { y = last }\n last = i\n i = i + 1\n}\n"),
+            vec![
+                "line 3: warning: Variable last may be used before it is assigned",
+                "line 3: warning: Variable y is assigned but never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        assert_eq!(
+            diagnostics("fn f(a, _b) {\n c = 1\n _d = 2\n return 0\n}\nf(1, 2)"),
+            vec![
+                "line 1: warning: Parameter a of function f is never used",
+                "line 2: warning: Variable c is assigned but never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_methods() {
+        // Methods need not use self, and lines stay in sync after an impl
+        assert_eq!(
+            diagnostics("struct P { x }\nimpl P {\n fn f(self, a) {\n  return 1\n }\n}\ny = z"),
+            vec![
+                "line 3: warning: Parameter a of method P.f is never used",
+                "line 7: error: Undefined variable z",
+                "line 7: warning: Variable y is assigned but never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_captured_variables() {
+        let source = "fn make_adder(n) {\n return fn(x) { x + n }\n}\nadd = make_adder(1)\nadd(2)";
+        let nodes = Parser::new(source).unwrap().parse().unwrap();
+        let mut resolver = Resolver::new();
+        let nodes = resolver.resolve(nodes);
+        assert!(resolver.diagnostics.is_empty());
+        // Named functions are not variables, the closure is stored in one
+        assert_eq!(resolver.globals, vec!["add".to_string()]);
+        let closure = match &nodes[0] {
+            Node::FunctionDeclaration { body, .. } => match &body[0] {
+                Node::ReturnExpression(value) => value.as_ref().clone().unwrap(),
+                other => panic!("Expected a return, got {0:?}", other),
+            },
+            _ => panic!("Expected a function"),
+        };
+        let body = match closure {
+            Node::FunctionExpression { body, .. } => body,
+            other => panic!("Expected a closure, got {0:?}", other),
+        };
+        assert_eq!(
+            body[0],
+            Node::BinaryExpr {
+                l_expr: Box::new(Node::VariableExpression {
+                    identifier: "x".to_string(),
+                    slot: Slot::Local(0),
+                }),
+                operator: Token::Add,
+                r_expr: Box::new(Node::VariableExpression {
+                    identifier: "n".to_string(),
+                    slot: Slot::Captured { depth: 1, slot: 0 },
+                }),
+            }
+        );
+        // Functions used as values are not undefined variables
+        assert_eq!(
+            diagnostics("fn double(n) {\n return n * 2\n}\nfn apply(f, x) {\n return f(x)\n}\napply(double, 1)"),
+            Vec::<String>::new()
+        );
+        // Assignments in closures change captured variables, globals included
+        assert_eq!(
+            diagnostics("count = 0\ninc = fn() {\n count = count + 1\n}\ninc()"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            diagnostics("fn counter() {\n n = 0\n inc = fn() {\n n = n + 1\n n\n }\n return inc\n}\ncounter()()"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            diagnostics("f = fn(a) { 1 }\nf(1)"),
+            vec!["line 1: warning: Parameter a of anonymous function is never used"]
+        );
+    }
+
+    #[test]
+    fn test_resolved_evaluation() {
+        let source = "
+            total = 0
+            fn add(a, b) {
+                sum = a + b
+                return sum
+            }
+            i = 0
+            while i < 10 {
+                i = i + 1
+                total = add(total, i)
+            }
+            total";
+        let mut evaluator = Evaluator::new(None);
+        evaluator.ast = Some(
+            Parser::new("offset = 100")
+                .unwrap()
+                .parse()
+                .unwrap()
+                .remove(0),
+        );
+        evaluator.start_evaluating().unwrap();
+        // Existing globals keep their slots
+        let mut resolver = Resolver::new().with_globals(evaluator.global_names());
+        let nodes = resolver.resolve(Parser::new(source).unwrap().parse().unwrap());
+        assert!(!resolver.has_errors());
+        evaluator.declare_globals(&resolver.globals).unwrap();
+        let mut result = EvalResult::EmptyResult;
+        for node in nodes {
+            evaluator.ast = Some(node);
+            result = evaluator.start_evaluating().unwrap();
+        }
+        assert_eq!(result, EvalResult::Number(Number::Integer(55)));
+        assert_eq!(
+            evaluator.get_variable("offset"),
+            Some(EvalResult::Number(Number::Integer(100)))
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        // Bindings are variables, the code after a match runs if any arm falls through
+        assert_eq!(
+            diagnostics("match [1] {\n [x, ..rest] if x > 0 => rest\n n => 0\n}\ny = z"),
+            vec![
+                "line 1: warning: Variable n is assigned but never used",
+                "line 5: error: Undefined variable z",
+                "line 5: warning: Variable y is assigned but never used",
+            ]
+        );
+        // Bindings only exist inside their arm
+        assert_eq!(
+            diagnostics("match 5 {\n n => n\n}\nn"),
+            vec!["line 4: error: Variable n is used before it is assigned"]
+        );
+    }
+
+    #[test]
+    fn test_try() {
+        assert_eq!(
+            diagnostics("try {\n y = missing\n} catch e {\n e\n}\nz = other"),
+            vec![
+                "line 2: warning: Undefined variable missing",
+                "line 2: warning: Variable y is assigned but never used",
+                "line 6: error: Undefined variable other",
+                "line 6: warning: Variable z is assigned but never used",
+            ]
+        );
+        // The body might stop at any statement, the catch binding is only set on errors
+        assert_eq!(
+            diagnostics("try {\n x = 1\n} catch e {\n e\n}\nx + 1\ne"),
+            vec![
+                "line 6: warning: Variable x may be used before it is assigned",
+                "line 7: warning: Variable e may be used before it is assigned",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_modules() {
+        // Imported names are assigned, exported names are used
+        assert_eq!(
+            diagnostics("import \"a.wf\" as a\nfrom \"b.wf\" import f, g\nx = f(1)\nexport x"),
+            vec![
+                "line 1: warning: Variable a is assigned but never used",
+                "line 2: warning: Variable g is assigned but never used",
+            ]
+        );
+        assert_eq!(
+            diagnostics("export missing\nfn f() {\n export f\n}\nif true {\n export f\n}"),
+            vec![
+                "line 1: error: Can not export missing, it is never defined",
+                "line 3: error: Exports are only allowed at the top level",
+                "line 6: error: Exports are only allowed at the top level",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1058 @@
+// Standard libraries
+use std::collections::{HashMap, HashSet};
+use std::vec;
+
+// Internal modules
+use super::diagnostic::Diagnostic;
+use super::formatter::Formatter;
+use crate::lexer_and_parser::ast::{CatchClause, MatchArm, Node, Pattern, Type};
+use crate::lexer_and_parser::builtins::{amount, Builtin, BUILTIN_ENUMS};
+use crate::lexer_and_parser::evaluator::Evaluator;
+use crate::lexer_and_parser::token::{Keyword, Token};
+use crate::lexer_and_parser::visitor::{walk_node, Visitor};
+
+// Static type checker.
+/*
+    Types are optional. Annotated variables (`x: num = 1`) keep their type for the whole
+    scope, unannotated variables get the type of the value last assigned to them as long
+    as it is known on every path. Anything that can't be known statically, e.g. results of
+    functions without a return type or globals inside functions, is dynamic and never
+    reported. The checker reports:
+    - arithmetic on values that are not numbers, and comparisons between incompatible types
+    - conditions of if and while that are not booleans
+    - assignments that don't match the declared type of a variable
+    - calls with the wrong amount of arguments or mismatching argument types. Calls through
+      variables or other expressions are dynamic, the function is only known at runtime.
+    - return values that don't match the declared return type
+    - if used as a value without an else, it has no value when no branch is taken. Blocks,
+      branches and match arms have the type of their last statement.
+    - annotations naming an enum that is never declared
+    - patterns of match arms that can never match a value of the known type
+    - warnings for matches that are not exhaustive. Without a catch-all arm, matches on a
+      bool need both values and matches on an enum every variant. The enum is known from
+      the variants in the patterns or from the type of the value.
+
+    Inside the body of a try with a catch every error is reported as a warning instead,
+    the catch handles it at runtime. Functions declared there are checked as usual.
+*/
+pub struct TypeChecker {
+    lines: Option<vec::IntoIter<usize>>,
+    // Line of the statement being checked
+    line: Option<usize>,
+    // Every declared function. A function declared twice with different signatures
+    // has no signature, calls to it are not checked.
+    functions: HashMap<String, Option<Signature>>,
+    // Functions defined outside the checked code, e.g. by the prelude. Their signatures
+    // are unknown, declaring a function with the same name replaces them.
+    host_functions: HashSet<String>,
+    // Names assigned or used as a parameter anywhere, they shadow functions
+    variables: HashSet<String>,
+    // Variant names of every declared enum
+    enums: HashMap<String, Vec<String>>,
+    // Bodies of try blocks with a catch around the statement being checked
+    catching: usize,
+    // Top level scope, and the scope of the function being checked
+    global_scope: Scope,
+    function_scope: Option<Scope>,
+    pub diagnostics: Vec<Diagnostic>,
+    // Known type of each variable used or assigned, by statement line and name
+    pub variable_types: HashMap<(usize, String), Type>,
+}
+
+#[derive(Clone, PartialEq)]
+struct Signature {
+    parameters: Vec<Option<Type>>,
+    return_type: Option<Type>,
+}
+
+struct Scope {
+    // Name and return type of the function, None at top level
+    function: Option<(String, Option<Type>)>,
+    // Variables belonging to the scope, every variable at top level
+    locals: HashSet<String>,
+    // Annotated variables
+    declared: HashMap<String, Type>,
+    // Known types of unannotated variables at the current point
+    known: HashMap<String, Type>,
+}
+
+// Public methods
+impl TypeChecker {
+    pub fn new() -> Self {
+        TypeChecker {
+            lines: None,
+            line: None,
+            functions: HashMap::new(),
+            host_functions: HashSet::new(),
+            variables: HashSet::new(),
+            enums: BUILTIN_ENUMS
+                .iter()
+                .map(|(name, variants)| {
+                    let variants = variants.iter().map(|(variant, _)| variant.to_string());
+                    (name.to_string(), variants.collect())
+                })
+                .collect(),
+            catching: 0,
+            global_scope: Scope::new(None, HashSet::new()),
+            function_scope: None,
+            diagnostics: Vec::new(),
+            variable_types: HashMap::new(),
+        }
+    }
+
+    // Attach the statement lines collected by the parser to the diagnostics.
+    pub fn with_lines(mut self, statement_lines: Vec<usize>) -> Self {
+        self.lines = Some(statement_lines.into_iter());
+        self
+    }
+
+    // Functions the code can call without declaring them.
+    pub fn with_functions(mut self, names: Vec<String>) -> Self {
+        self.host_functions.extend(names);
+        self
+    }
+
+    // Check parsed (or resolved) statements. Diagnostics are collected into `diagnostics`.
+    pub fn check(&mut self, nodes: &[Node]) {
+        self.collect_functions(nodes);
+        VariableNames {
+            names: &mut self.variables,
+        }
+        .visit_block(nodes);
+        EnumVariants {
+            enums: &mut self.enums,
+        }
+        .visit_block(nodes);
+        let mut declared = HashMap::new();
+        self.collect_annotations(nodes, &mut declared);
+        self.global_scope.declared = declared;
+        self.check_block(nodes);
+        self.diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    }
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        TypeChecker::new()
+    }
+}
+
+// Private methods
+impl TypeChecker {
+    fn scope(&mut self) -> &mut Scope {
+        match &mut self.function_scope {
+            Some(scope) => scope,
+            None => &mut self.global_scope,
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        if self.catching > 0 {
+            return self.warning(message);
+        }
+        self.diagnostics.push(Diagnostic::error(message, self.line));
+    }
+
+    fn warning(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic::warning(message, self.line));
+    }
+
+    // Signatures of every function, including nested declarations.
+    fn collect_functions(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::FunctionDeclaration {
+                    identifier,
+                    parameter_types,
+                    return_type,
+                    body,
+                    ..
+                } => {
+                    let signature = Signature {
+                        parameters: parameter_types.clone(),
+                        return_type: return_type.clone(),
+                    };
+                    let entry = self
+                        .functions
+                        .entry(identifier.clone())
+                        .or_insert_with(|| Some(signature.clone()));
+                    if entry.as_ref() != Some(&signature) {
+                        *entry = None;
+                    }
+                    self.collect_functions(body);
+                }
+                // Methods are not functions, only what is declared inside them
+                Node::ImplDeclaration { methods, .. } => {
+                    for method in methods.iter() {
+                        for block in TypeChecker::blocks(method) {
+                            self.collect_functions(block);
+                        }
+                    }
+                }
+                _ => {
+                    for block in TypeChecker::blocks(node) {
+                        self.collect_functions(block);
+                    }
+                }
+            }
+        }
+    }
+
+    // Annotated assignments of a scope, functions have their own scope.
+    fn collect_annotations(&mut self, nodes: &[Node], declared: &mut HashMap<String, Type>) {
+        for node in nodes {
+            if let Node::AssignmentExpression {
+                identifier,
+                annotation: Some(annotation),
+                ..
+            } = node
+            {
+                let name = TypeChecker::variable_name(identifier).unwrap_or_default();
+                match declared.get(&name) {
+                    Some(previous) if previous != annotation => {
+                        self.diagnostics.push(Diagnostic::error(
+                            format!(
+                                "Variable {0} is declared as both {1} and {2}",
+                                name, previous, annotation
+                            ),
+                            None,
+                        ))
+                    }
+                    _ => {
+                        declared.insert(name, annotation.clone());
+                    }
+                }
+            }
+            if let Node::FunctionDeclaration { .. } = node {
+                continue;
+            }
+            for block in TypeChecker::blocks(node) {
+                self.collect_annotations(block, declared);
+            }
+        }
+    }
+
+    // Statement blocks directly inside a node.
+    fn blocks(node: &Node) -> Vec<&Vec<Node>> {
+        match node {
+            Node::IfExpression {
+                then_branch,
+                else_branch,
+                ..
+            }
+            | Node::ElseExpression {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let mut blocks = vec![then_branch.as_ref()];
+                if let Some(else_node) = else_branch.as_ref() {
+                    blocks.extend(TypeChecker::blocks(else_node));
+                }
+                blocks
+            }
+            Node::WhileExpression { then_branch, .. } => vec![then_branch.as_ref()],
+            Node::FunctionDeclaration { body, .. } => vec![body.as_ref()],
+            Node::MatchExpression { arms, .. } => arms.iter().map(|arm| &arm.body).collect(),
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => {
+                let mut blocks = vec![body.as_ref()];
+                blocks.extend(catch.as_ref().as_ref().map(|catch| &catch.body));
+                blocks.extend(finally.as_ref().as_ref());
+                blocks
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn variable_name(node: &Node) -> Option<String> {
+        match node {
+            Node::IdentifierExpression(name)
+            | Node::VariableExpression {
+                identifier: name, ..
+            } => Some(name.clone()),
+            _ => None,
+        }
+    }
+
+    // Type of the last statement of the block, None if it is not known.
+    fn check_block(&mut self, nodes: &[Node]) -> Option<Type> {
+        let outer_line = self.line;
+        let mut block_type = None;
+        for node in nodes {
+            if let Some(lines) = self.lines.as_mut() {
+                self.line = lines.next();
+            }
+            block_type = match node {
+                // Statements need no else
+                Node::IfExpression {
+                    condition,
+                    then_branch,
+                    else_branch,
+                } => self.check_branches(condition.as_ref().as_ref(), then_branch, else_branch),
+                _ => self.check_node(node),
+            };
+        }
+        self.line = outer_line;
+        block_type
+    }
+
+    // Type of the value the node evaluates to, None if it is not known.
+    fn check_node(&mut self, node: &Node) -> Option<Type> {
+        match node {
+            Node::NumberExpression(_) => Some(Type::Number),
+            Node::LiteralExpression(_, keyword) => match keyword {
+                Keyword::True | Keyword::False => Some(Type::Boolean),
+                Keyword::None => Some(Type::String),
+                _ => None,
+            },
+            Node::IdentifierExpression(name)
+            | Node::VariableExpression {
+                identifier: name, ..
+            } => self.variable_type(name),
+            Node::NegativeNumberExpression(expr) => {
+                let operand = self.check_node(expr);
+                self.expect_number(operand, "-");
+                Some(Type::Number)
+            }
+            Node::QuantityExpression { value: expr, .. } => {
+                let operand = self.check_node(expr);
+                self.expect_number(operand, "a unit");
+                Some(Type::Number)
+            }
+            Node::ConversionExpression { expr, .. } => {
+                let operand = self.check_node(expr);
+                self.expect_number(operand, "in");
+                Some(Type::Number)
+            }
+            Node::BinaryExpr {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                let l_type = self.check_node(l_expr);
+                let r_type = self.check_node(r_expr);
+                let symbol = operator.symbol();
+                self.expect_number(l_type, symbol);
+                self.expect_number(r_type, symbol);
+                Some(Type::Number)
+            }
+            Node::ConditionExpression {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                let l_type = self.check_node(l_expr);
+                let r_type = self.check_node(r_expr);
+                // Anything can be compared for equality, ordering needs numbers or strings
+                let ordering = !matches!(operator, Token::Equals | Token::NotEquals);
+                match (l_type, r_type) {
+                    (Some(Type::Boolean), _) | (_, Some(Type::Boolean)) if ordering => {
+                        self.error(format!(
+                            "Operator {0} can not be used with bool",
+                            operator.symbol()
+                        ))
+                    }
+                    (Some(l_type), Some(r_type)) if ordering && l_type != r_type => {
+                        self.error(format!("Can not compare {0} and {1}", l_type, r_type))
+                    }
+                    _ => {}
+                }
+                Some(Type::Boolean)
+            }
+            Node::AssignmentExpression {
+                identifier,
+                expr,
+                annotation,
+                ..
+            } => {
+                if let Some(annotation) = annotation {
+                    self.check_annotation(annotation);
+                }
+                let value_type = self.check_node(expr);
+                if let Some(name) = TypeChecker::variable_name(identifier) {
+                    self.assign(name, value_type);
+                }
+                None
+            }
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if !node.has_else() {
+                    self.error("If used as a value needs an else branch".to_string());
+                }
+                self.check_branches(condition.as_ref().as_ref(), then_branch, else_branch)
+            }
+            Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => self.check_branches(condition.as_ref().as_ref(), then_branch, else_branch),
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => {
+                // Types assigned in the body are only known for the rest of the iteration
+                let mut assigned = HashSet::new();
+                Evaluator::collect_assignments(then_branch, &mut assigned);
+                self.forget(&assigned);
+                if let Some(condition) = condition.as_ref() {
+                    self.check_condition(condition, "while");
+                }
+                self.check_block(then_branch);
+                self.forget(&assigned);
+                None
+            }
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                parameter_types,
+                return_type,
+                body,
+            } => {
+                self.check_function(identifier, parameters, parameter_types, return_type.clone(), body);
+                None
+            }
+            Node::FunctionExpression {
+                parameters,
+                parameter_types,
+                return_type,
+                body,
+            } => {
+                self.check_function("<anonymous>", parameters, parameter_types, return_type.clone(), body);
+                None
+            }
+            // Methods are statements of the impl block and named after their struct
+            Node::ImplDeclaration {
+                identifier,
+                methods,
+            } => {
+                let outer_line = self.line;
+                for method in methods.iter() {
+                    if let Some(lines) = self.lines.as_mut() {
+                        self.line = lines.next();
+                    }
+                    if let Node::FunctionDeclaration {
+                        identifier: name,
+                        parameters,
+                        parameter_types,
+                        return_type,
+                        body,
+                    } = method
+                    {
+                        let name = format!("{0}.{1}", identifier, name);
+                        self.check_function(&name, parameters, parameter_types, return_type.clone(), body);
+                    }
+                }
+                self.line = outer_line;
+                None
+            }
+            // Fields are dynamic
+            Node::StructExpression { fields, .. } => {
+                for (_, value) in fields {
+                    self.check_node(value);
+                }
+                None
+            }
+            Node::FieldExpression { expr, .. } => {
+                self.check_node(expr);
+                None
+            }
+            Node::FieldAssignmentExpression { expr, value, .. } => {
+                self.check_node(expr);
+                self.check_node(value);
+                None
+            }
+            Node::CallExpression { callee, arguments } => {
+                let name = match callee.as_ref() {
+                    Node::IdentifierExpression(name) if !self.variables.contains(name) => {
+                        Some(name)
+                    }
+                    callee => {
+                        self.check_node(callee);
+                        None
+                    }
+                };
+                let argument_types: Vec<Option<Type>> = arguments
+                    .iter()
+                    .map(|argument| self.check_node(argument))
+                    .collect();
+                match name {
+                    Some(name) => self.check_call(name, &argument_types),
+                    None => None,
+                }
+            }
+            Node::ListExpression(elements) => {
+                for element in elements {
+                    self.check_node(element);
+                }
+                None
+            }
+            Node::VariantExpression {
+                enum_name,
+                arguments,
+                ..
+            } => {
+                for argument in arguments {
+                    self.check_node(argument);
+                }
+                Some(Type::Enum(enum_name.clone()))
+            }
+            Node::MatchExpression { expr, arms } => self.check_match(expr, arms),
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => self.check_try(body, catch.as_ref().as_ref(), finally.as_ref().as_ref()),
+            Node::ThrowExpression(value) | Node::PropagateExpression(value) => {
+                self.check_node(value);
+                None
+            }
+            // Modules are checked when they are loaded, their values are dynamic here
+            Node::ImportExpression { alias, names, .. } => {
+                for name in alias.as_ref().iter().chain(names) {
+                    if let Some(name) = TypeChecker::variable_name(name) {
+                        self.assign(name, None);
+                    }
+                }
+                None
+            }
+            Node::IndexExpression { expr, index } => {
+                self.check_node(expr);
+                match self.check_node(index) {
+                    Some(Type::Number) | None => {}
+                    Some(other) => self.error(format!("List index must be a num, got {0}", other)),
+                }
+                None
+            }
+            Node::ReturnExpression(value) => {
+                let value_type = value
+                    .as_ref()
+                    .as_ref()
+                    .and_then(|value| self.check_node(value));
+                if let Some((name, Some(return_type))) = self.scope().function.clone() {
+                    match (value.as_ref(), value_type) {
+                        (None, _) => {
+                            self.error(format!("Function {0} must return a {1}", name, return_type))
+                        }
+                        (Some(_), Some(value_type)) if value_type != return_type => {
+                            self.error(format!(
+                                "Function {0} must return a {1}, got {2}",
+                                name, return_type, value_type
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    // Type of the value of the branches, known if every branch has the same type.
+    fn check_branches(
+        &mut self,
+        condition: Option<&Node>,
+        then_branch: &[Node],
+        else_branch: &Option<Node>,
+    ) -> Option<Type> {
+        let condition = match condition {
+            Some(condition) => condition,
+            // Plain else is the only path through its block
+            None => return self.check_block(then_branch),
+        };
+        self.check_condition(condition, "if");
+        let before = self.scope().known.clone();
+        let then_type = self.check_block(then_branch);
+        let after_then = std::mem::replace(&mut self.scope().known, before);
+        let else_type = match else_branch {
+            Some(else_node) => self.check_node(else_node),
+            None => None,
+        };
+        // Only types that are the same on both paths stay known
+        self.scope()
+            .known
+            .retain(|name, known| after_then.get(name) == Some(known));
+        then_type.filter(|then_type| else_type == Some(then_type.clone()))
+    }
+
+    // Arms are checked like branches, each starting from the state after the value.
+    // Only types that are the same before the match and after every arm stay known, the
+    // match has a type if every arm has the same type.
+    fn check_match(&mut self, expr: &Node, arms: &[MatchArm]) -> Option<Type> {
+        let value_type = self.check_node(expr);
+        let before = self.scope().known.clone();
+        let mut after = before.clone();
+        let mut arm_types = Vec::new();
+        for arm in arms {
+            self.check_pattern(&arm.pattern, value_type.clone());
+            if let Some(guard) = &arm.guard {
+                self.check_condition(guard, "match guard");
+            }
+            arm_types.push(self.check_block(&arm.body));
+            let known = std::mem::replace(&mut self.scope().known, before.clone());
+            after.retain(|name, known_type| known.get(name) == Some(known_type));
+        }
+        self.scope().known = after;
+        self.check_exhaustive(value_type, arms);
+        match arm_types.split_first() {
+            Some((first, rest)) if rest.iter().all(|arm_type| arm_type == first) => first.clone(),
+            _ => None,
+        }
+    }
+
+    // The catch starts from the state before the body, without anything the body may have
+    // changed, and so does the finally block. The try has a type if the body and the catch
+    // have the same type.
+    fn check_try(
+        &mut self,
+        body: &[Node],
+        catch: Option<&CatchClause>,
+        finally: Option<&Vec<Node>>,
+    ) -> Option<Type> {
+        let mut assigned = HashSet::new();
+        Evaluator::collect_assignments(body, &mut assigned);
+        let before = self.scope().known.clone();
+        self.catching += catch.is_some() as usize;
+        let mut try_type = self.check_block(body);
+        self.catching -= catch.is_some() as usize;
+        if let Some(catch) = catch {
+            let after_body = std::mem::replace(&mut self.scope().known, before);
+            self.forget(&assigned);
+            if let Some(name) = TypeChecker::variable_name(&catch.binding) {
+                self.assign(name, None);
+            }
+            let catch_type = self.check_block(&catch.body);
+            Evaluator::collect_assignments(&catch.body, &mut assigned);
+            self.scope()
+                .known
+                .retain(|name, known| after_body.get(name) == Some(known));
+            try_type = try_type.filter(|try_type| catch_type == Some(try_type.clone()));
+        }
+        if let Some(finally) = finally {
+            self.forget(&assigned);
+            self.check_block(finally);
+        }
+        try_type
+    }
+
+    // Bindings get the type of the value they match, the values inside lists, structs
+    // and variants are dynamic.
+    fn check_pattern(&mut self, pattern: &Pattern, value_type: Option<Type>) {
+        let pattern_type = match pattern {
+            Pattern::Wildcard => return,
+            Pattern::Binding(node) => {
+                if let Some(name) = TypeChecker::variable_name(node) {
+                    self.assign(name, value_type);
+                }
+                return;
+            }
+            Pattern::Literal(node) => self.check_node(node),
+            Pattern::Range { .. } => Some(Type::Number),
+            Pattern::List { .. } | Pattern::Struct { .. } | Pattern::Variant { .. } => {
+                for node in pattern.nodes() {
+                    match node {
+                        Node::IdentifierExpression(_) | Node::VariableExpression { .. } => {}
+                        literal => {
+                            self.check_node(literal);
+                        }
+                    }
+                }
+                for name in pattern.bindings() {
+                    self.assign(name, None);
+                }
+                match pattern {
+                    Pattern::Variant { enum_name, .. } => Some(Type::Enum(enum_name.clone())),
+                    _ => None,
+                }
+            }
+        };
+        if let Some(value_type) = value_type {
+            if pattern_type != Some(value_type.clone()) {
+                self.error(format!(
+                    "Pattern {0} can never match a {1}",
+                    Formatter::format_pattern(pattern),
+                    value_type
+                ));
+            }
+        }
+    }
+
+    // Warn about matches that can fail on a value of a known type.
+    fn check_exhaustive(&mut self, value_type: Option<Type>, arms: &[MatchArm]) {
+        // Guarded arms might not match
+        let patterns: Vec<&Pattern> = arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| &arm.pattern)
+            .collect();
+        if patterns.iter().any(|pattern| pattern.is_irrefutable()) {
+            return;
+        }
+        let enum_name = arms.iter().find_map(|arm| match &arm.pattern {
+            Pattern::Variant { enum_name, .. } => Some(enum_name.clone()),
+            _ => None,
+        });
+        let enum_name = enum_name.or(match &value_type {
+            Some(Type::Enum(enum_name)) => Some(enum_name.clone()),
+            _ => None,
+        });
+        let missing: Vec<String> = match (enum_name, value_type) {
+            (Some(enum_name), _) => match self.enums.get(&enum_name) {
+                Some(variants) => variants
+                    .iter()
+                    .filter(|variant| {
+                        !patterns
+                            .iter()
+                            .any(|pattern| TypeChecker::covers_variant(pattern, &enum_name, variant))
+                    })
+                    .map(|variant| format!("{0}::{1}", enum_name, variant))
+                    .collect(),
+                None => return,
+            },
+            (None, Some(Type::Boolean)) => [Keyword::True, Keyword::False]
+                .iter()
+                .filter(|value| {
+                    !patterns.iter().any(|pattern| match pattern {
+                        Pattern::Literal(node) => {
+                            matches!(node.as_ref(), Node::LiteralExpression(_, keyword) if keyword == *value)
+                        }
+                        _ => false,
+                    })
+                })
+                .filter_map(Keyword::name)
+                .map(str::to_string)
+                .collect(),
+            (None, Some(value_type)) => {
+                return self.warning(format!(
+                    "Match on a {0} is not exhaustive, add a _ arm",
+                    value_type
+                ))
+            }
+            (None, None) => return,
+        };
+        if !missing.is_empty() {
+            self.warning(format!(
+                "Match is not exhaustive, missing {0}",
+                missing.join(", ")
+            ));
+        }
+    }
+
+    // Whether the pattern matches every value of the variant.
+    fn covers_variant(pattern: &Pattern, enum_name: &str, variant: &str) -> bool {
+        match pattern {
+            Pattern::Variant {
+                enum_name: name,
+                variant: pattern_variant,
+                elements,
+            } => {
+                name == enum_name
+                    && pattern_variant == variant
+                    && elements.iter().all(Pattern::is_irrefutable)
+            }
+            _ => false,
+        }
+    }
+
+    fn check_condition(&mut self, condition: &Node, owner: &str) {
+        match self.check_node(condition) {
+            Some(Type::Boolean) | None => {}
+            Some(other) => self.error(format!(
+                "Condition of {0} must be a boolean, got {1}",
+                owner, other
+            )),
+        }
+    }
+
+    fn check_function(
+        &mut self,
+        identifier: &str,
+        parameters: &[String],
+        parameter_types: &[Option<Type>],
+        return_type: Option<Type>,
+        body: &[Node],
+    ) {
+        for annotation in parameter_types.iter().chain([&return_type]).flatten() {
+            self.check_annotation(annotation);
+        }
+        let locals = Evaluator::local_names(parameters, body)
+            .into_iter()
+            .collect();
+        let mut scope = Scope::new(Some((identifier.to_string(), return_type.clone())), locals);
+        let mut declared = HashMap::new();
+        for (parameter, annotation) in parameters.iter().zip(parameter_types) {
+            if let Some(annotation) = annotation {
+                declared.insert(parameter.clone(), annotation.clone());
+            }
+        }
+        self.collect_annotations(body, &mut declared);
+        scope.declared = declared;
+        let outer_scope = self.function_scope.replace(scope);
+        let catching = std::mem::replace(&mut self.catching, 0);
+        let value_type = self.check_block(body);
+        self.function_scope = outer_scope;
+        if let Some(return_type) = return_type {
+            match body.last() {
+                _ if TypeChecker::always_returns(body) => {}
+                // The value of the last statement is returned
+                Some(last) if last.has_value() => match value_type {
+                    Some(value_type) if value_type != return_type => self.error(format!(
+                        "Function {0} must return a {1}, got {2}",
+                        identifier, return_type, value_type
+                    )),
+                    _ => {}
+                },
+                _ => self.error(format!(
+                    "Function {0} must return a {1} on every path",
+                    identifier, return_type
+                )),
+            }
+        }
+        self.catching = catching;
+    }
+
+    // Enums in annotations have to be declared somewhere in the program.
+    fn check_annotation(&mut self, annotation: &Type) {
+        if let Type::Enum(name) = annotation {
+            if !self.enums.contains_key(name) {
+                self.error(format!("Unknown type {0}", name));
+            }
+        }
+    }
+
+    fn check_call(&mut self, name: &str, argument_types: &[Option<Type>]) -> Option<Type> {
+        let signature = match self.functions.get(name) {
+            Some(Some(signature)) => signature.clone(),
+            Some(None) => return None,
+            None if self.host_functions.contains(name) => return None,
+            None => {
+                if let Some(builtin) = Builtin::from_name(name) {
+                    return self.check_builtin_call(builtin, argument_types.len());
+                }
+                self.error(format!("Could not find a function named {0}", name));
+                return None;
+            }
+        };
+        if signature.parameters.len() != argument_types.len() {
+            self.error(format!(
+                "Function {0} expects {1}, got {2}",
+                name,
+                amount(signature.parameters.len(), "argument"),
+                argument_types.len()
+            ));
+            return signature.return_type;
+        }
+        for (i, (expected, actual)) in signature.parameters.iter().zip(argument_types).enumerate() {
+            if let (Some(expected), Some(actual)) = (expected, actual) {
+                if expected != actual {
+                    self.error(format!(
+                        "Argument {0} of {1} must be a {2}, got {3}",
+                        i + 1,
+                        name,
+                        expected,
+                        actual
+                    ));
+                }
+            }
+        }
+        signature.return_type
+    }
+
+    // Builtins only have their argument count checked, most of them take lists and
+    // functions which have no type here.
+    fn check_builtin_call(&mut self, builtin: Builtin, arguments: usize) -> Option<Type> {
+        let (min, max) = builtin.arity();
+        if arguments < min || arguments > max {
+            self.error(format!(
+                "Function {0} expects {1}, got {2}",
+                builtin.name(),
+                builtin.expected_arguments(),
+                arguments
+            ));
+        }
+        builtin.return_type()
+    }
+
+    // Whether every path through the block ends in a return. Matches only count with an
+    // arm that matches every value, a throw never gets to the end of the block.
+    fn always_returns(nodes: &[Node]) -> bool {
+        nodes.iter().any(|node| match node {
+            Node::ReturnExpression(_) | Node::ThrowExpression(_) => true,
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => {
+                let handled = TypeChecker::always_returns(body)
+                    && catch
+                        .as_ref()
+                        .as_ref()
+                        .is_none_or(|catch| TypeChecker::always_returns(&catch.body));
+                handled
+                    || finally
+                        .as_ref()
+                        .as_ref()
+                        .is_some_and(|finally| TypeChecker::always_returns(finally))
+            }
+            Node::MatchExpression { arms, .. } => {
+                arms.iter()
+                    .any(|arm| arm.guard.is_none() && arm.pattern.is_irrefutable())
+                    && arms.iter().all(|arm| TypeChecker::always_returns(&arm.body))
+            }
+            Node::ElseExpression {
+                condition,
+                then_branch,
+                ..
+            } if condition.is_none() => TypeChecker::always_returns(then_branch),
+            Node::IfExpression {
+                then_branch,
+                else_branch,
+                ..
+            }
+            | Node::ElseExpression {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                TypeChecker::always_returns(then_branch)
+                    && else_branch.as_ref().as_ref().is_some_and(|else_node| {
+                        TypeChecker::always_returns(std::slice::from_ref(else_node))
+                    })
+            }
+            _ => false,
+        })
+    }
+
+    fn variable_type(&mut self, name: &str) -> Option<Type> {
+        let in_function = self.function_scope.is_some();
+        let scope = self.scope();
+        let variable_type = if !in_function || scope.locals.contains(name) {
+            scope
+                .declared
+                .get(name)
+                .or_else(|| scope.known.get(name))
+                .cloned()
+        } else {
+            // Globals can change between calls, only their declared type is known
+            self.global_scope.declared.get(name).cloned()
+        };
+        self.record(name, variable_type.clone());
+        variable_type
+    }
+
+    fn record(&mut self, name: &str, variable_type: Option<Type>) {
+        if let (Some(line), Some(variable_type)) = (self.line, variable_type) {
+            self.variable_types
+                .insert((line, name.to_string()), variable_type);
+        }
+    }
+
+    fn assign(&mut self, name: String, value_type: Option<Type>) {
+        let scope = self.scope();
+        if let Some(declared) = scope.declared.get(&name).cloned() {
+            self.record(&name, Some(declared.clone()));
+            if let Some(value_type) = value_type {
+                if value_type != declared {
+                    self.error(format!(
+                        "Can not assign {0} to {1} of type {2}",
+                        value_type, name, declared
+                    ));
+                }
+            }
+            return;
+        }
+        match &value_type {
+            Some(value_type) => scope.known.insert(name.clone(), value_type.clone()),
+            None => scope.known.remove(&name),
+        };
+        self.record(&name, value_type);
+    }
+
+    fn forget(&mut self, names: &HashSet<String>) {
+        self.scope().known.retain(|name, _| !names.contains(name));
+    }
+
+    fn expect_number(&mut self, operand: Option<Type>, operator: &str) {
+        match operand {
+            Some(Type::Number) | None => {}
+            Some(other) => self.error(format!(
+                "Operator {0} expects a num, got {1}",
+                operator, other
+            )),
+        }
+    }
+}
+
+// Private methods
+impl Scope {
+    fn new(function: Option<(String, Option<Type>)>, locals: HashSet<String>) -> Self {
+        Scope {
+            function,
+            locals,
+            declared: HashMap::new(),
+            known: HashMap::new(),
+        }
+    }
+}
+
+// Assigned variables and parameters of the whole program.
+struct VariableNames<'a> {
+    names: &'a mut HashSet<String>,
+}
+
+impl<'a> Visitor for VariableNames<'a> {
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::AssignmentExpression { identifier, .. } => {
+                self.names.extend(TypeChecker::variable_name(identifier));
+            }
+            Node::FunctionDeclaration { parameters, .. }
+            | Node::FunctionExpression { parameters, .. } => {
+                self.names.extend(parameters.iter().cloned());
+            }
+            Node::MatchExpression { arms, .. } => {
+                for arm in arms {
+                    self.names.extend(arm.pattern.bindings());
+                }
+            }
+            Node::TryExpression { catch, .. } => {
+                if let Some(catch) = catch.as_ref() {
+                    self.names.extend(TypeChecker::variable_name(&catch.binding));
+                }
+            }
+            Node::ImportExpression { alias, names, .. } => {
+                for name in alias.as_ref().iter().chain(names) {
+                    self.names.extend(TypeChecker::variable_name(name));
+                }
+            }
+            _ => {}
+        }
+        walk_node(self, node);
+    }
+}
+
+// Declared enums of the whole program.
+struct EnumVariants<'a> {
+    enums: &'a mut HashMap<String, Vec<String>>,
+}
+
+impl<'a> Visitor for EnumVariants<'a> {
+    fn visit_node(&mut self, node: &Node) {
+        if let Node::EnumDeclaration {
+            identifier,
+            variants,
+        } = node
+        {
+            let names = variants.iter().map(|(variant, _)| variant.clone()).collect();
+            self.enums.insert(identifier.clone(), names);
+        }
+        walk_node(self, node);
+    }
+}
@@ -0,0 +1,236 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::passes::type_checker::TypeChecker;
+
+    // Type check the source and return the diagnostics as text.
+    fn diagnostics(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let mut checker = TypeChecker::new().with_lines(parser.statement_lines.clone());
+        checker.check(&nodes);
+        checker
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_operands() {
+        assert_eq!(
+            diagnostics("s = \"a\"\nx = s * 2\ny = -true"),
+            vec![
+                "line 2: error: Operator * expects a num, got str",
+                "line 3: error: Operator - expects a num, got bool",
+            ]
+        );
+        assert_eq!(
+            diagnostics("a = 1 < \"b\"\nb = true > false\nc = 1 == true"),
+            vec![
+                "line 1: error: Can not compare num and str",
+                "line 2: error: Operator > can not be used with bool",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(
+            diagnostics("x = 1\nif x {\n x = 2\n}\nwhile x + 1 {\n break\n}"),
+            vec![
+                "line 2: error: Condition of if must be a boolean, got num",
+                "line 5: error: Condition of while must be a boolean, got num",
+            ]
+        );
+        assert!(diagnostics("x = 1\nif x > 0 {\n x = 2\n}").is_empty());
+    }
+
+    #[test]
+    fn test_annotations() {
+        assert_eq!(
+            diagnostics("x: num = 1\nx = \"one\""),
+            vec!["line 2: error: Can not assign str to x of type num"]
+        );
+        // Types only stay known if every path agrees
+        assert!(diagnostics("c = true\nx = 1\nif c {\n x = \"a\"\n}\ny = x * 2").is_empty());
+        assert_eq!(
+            diagnostics("c = true\nx = 1\nif c {\n x = \"a\"\n} else {\n x = \"b\"\n}\ny = x * 2"),
+            vec!["line 8: error: Operator * expects a num, got str"]
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let source = "
+            fn greet(name: str, times: num) -> str {
+                return name
+            }
+            greet(\"a\")
+            greet(1, 2)
+            n: num = greet(\"a\", 2)
+            missing()";
+        assert_eq!(
+            diagnostics(source),
+            vec![
+                "line 5: error: Function greet expects 2 arguments, got 1",
+                "line 6: error: Argument 1 of greet must be a str, got num",
+                "line 7: error: Can not assign str to n of type num",
+                "line 8: error: Could not find a function named missing",
+            ]
+        );
+        assert_eq!(
+            diagnostics("fn f(a) -> bool {\n if a > 1 {\n return 1\n }\n}"),
+            vec![
+                "line 1: error: Function f must return a bool on every path",
+                "line 3: error: Function f must return a bool, got num",
+            ]
+        );
+        // The value of the last statement is returned
+        assert!(diagnostics("fn f(a: num) -> num {\n a * 2\n}").is_empty());
+        assert_eq!(
+            diagnostics("fn f(a: num) -> bool {\n a * 2\n}\nfn g(a) -> num {\n b = a\n}"),
+            vec![
+                "line 1: error: Function f must return a bool, got num",
+                "line 4: error: Function g must return a num on every path",
+            ]
+        );
+        // Unannotated code stays dynamic
+        assert!(diagnostics("fn f(a) {\n return a * 2\n}\nx = f(\"a\") + 1").is_empty());
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(
+            diagnostics("s: str = sum([1, 2])
+map([1])
+range(1, 2, 3, 4)
+n: num = upper(\"a\")
+format()
+b: bool = starts_with(\"ab\", \"a\")"),
+            vec![
+                "line 1: error: Can not assign num to s of type str",
+                "line 2: error: Function map expects 2 arguments, got 1",
+                "line 3: error: Function range expects 1 to 3 arguments, got 4",
+                "line 4: error: Can not assign str to n of type num",
+                "line 5: error: Function format expects at least 1 argument, got 0",
+            ]
+        );
+        // Declared functions shadow builtins
+        assert!(diagnostics("fn sum(a, b) {\n return a\n}\nsum(1, 2)").is_empty());
+    }
+
+    #[test]
+    fn test_match() {
+        let enums = "enum Shape {\n Circle(r),\n Rect(w, h),\n Empty\n}\ns = Shape::Empty\n";
+        assert_eq!(
+            diagnostics(&format!(
+                "{0}match s {{\n Shape::Circle(_) => 1\n Shape::Rect(1, h) => h\n}}",
+                enums
+            )),
+            vec!["line 7: warning: Match is not exhaustive, missing Shape::Rect, Shape::Empty"]
+        );
+        assert_eq!(
+            diagnostics(&format!(
+                "{0}match s {{\n Shape::Circle(_) => 1\n Shape::Rect(w, h) => 2\n Shape::Empty => 3\n}}",
+                enums
+            )),
+            Vec::<String>::new()
+        );
+        // Declared enums are types, the value tells which variants a match needs
+        assert_eq!(
+            diagnostics(&format!(
+                "{0}fn area(shape: Shape) -> num {{\n return match shape {{\n Shape::Circle(r) => r\n _ => 0\n }}\n}}\narea(s)\narea(1)\nmatch s {{\n 1 => 1\n}}\nfn paint(c: Color) {{\n return c\n}}",
+                enums
+            )),
+            vec![
+                "line 14: error: Argument 1 of area must be a Shape, got num",
+                "line 15: error: Pattern 1 can never match a Shape",
+                "line 15: warning: Match is not exhaustive, missing Shape::Circle, Shape::Rect, Shape::Empty",
+                "line 18: error: Unknown type Color",
+            ]
+        );
+        // Guarded arms might not match, catch-all arms match anything
+        assert_eq!(
+            diagnostics("b = true\nmatch b {\n true => 1\n false if b => 2\n}\nmatch 1 {\n 1 => 1\n}\nmatch 2 {\n n => n\n}"),
+            vec![
+                "line 2: warning: Match is not exhaustive, missing false",
+                "line 6: warning: Match on a num is not exhaustive, add a _ arm",
+            ]
+        );
+        // Bindings take the type of the value
+        assert_eq!(
+            diagnostics("match \"a\" {\n 1 => 1\n [x] => x\n s => s * 2\n}"),
+            vec![
+                "line 1: error: Pattern 1 can never match a str",
+                "line 1: error: Pattern [x] can never match a str",
+                "line 4: error: Operator * expects a num, got str",
+            ]
+        );
+        assert_eq!(
+            diagnostics("match 1 {\n n if n => n\n _ => 0\n}"),
+            vec!["line 1: error: Condition of match guard must be a boolean, got num"]
+        );
+    }
+
+    #[test]
+    fn test_if_values() {
+        // Values of every branch with the same type give the if a type
+        assert_eq!(
+            diagnostics("b = true\ny = if b { \"a\" } elif 1 > 2 { \"b\" } else { \"c\" }\nz = y * 2"),
+            vec!["line 3: error: Operator * expects a num, got str"]
+        );
+        assert_eq!(
+            diagnostics("b = true\ny = if b { 1 } else { \"a\" }\nz = y * 2\nif b { 1 }"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            diagnostics("b = true\ny = if b {\n 1\n} elif b {\n 2\n}"),
+            vec!["line 2: error: If used as a value needs an else branch"]
+        );
+    }
+
+    #[test]
+    fn test_try() {
+        // Errors that are caught at runtime are only warnings
+        assert_eq!(
+            diagnostics("try {\n x = 1 + \"a\"\n} catch e {\n e.message\n}\ny = 1 + \"b\""),
+            vec![
+                "line 2: warning: Operator + expects a num, got str",
+                "line 6: error: Operator + expects a num, got str",
+            ]
+        );
+        assert_eq!(
+            diagnostics("try {\n 1 + \"a\"\n} finally {\n 1\n}"),
+            vec!["line 2: error: Operator + expects a num, got str"]
+        );
+        assert_eq!(
+            diagnostics("y = try { \"a\" } catch e { \"b\" }\nz = y * 2"),
+            vec!["line 2: error: Operator * expects a num, got str"]
+        );
+        // A function that ends in a throw does not fall off its end
+        assert!(diagnostics("fn f(x) -> num {\n if x > 1 { return 1 }\n throw \"small\"\n}\nf(2)").is_empty());
+    }
+
+    #[test]
+    fn test_results_and_options() {
+        // Result and Option are known without being declared
+        assert_eq!(
+            diagnostics("r = ok(1)\nmatch r {\n ok(v) => v\n}\nmatch none {\n some(v) => v\n none => 0\n}"),
+            vec!["line 2: warning: Match is not exhaustive, missing Result::Err"]
+        );
+        assert_eq!(
+            diagnostics("fn f(r) {\n return ok(r? + \"a\")\n}"),
+            vec!["line 2: error: Operator + expects a num, got str"]
+        );
+    }
+
+    #[test]
+    fn test_modules() {
+        // Imported values are dynamic, imported functions are called through variables
+        assert_eq!(
+            diagnostics("from \"util.wf\" import f, pi\nimport \"geo.wf\" as geo\nx = f(pi) + geo.area(1)"),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -0,0 +1,8 @@
+// Add public modules
+pub mod dot;
+pub mod json;
+pub mod syntax;
+// Test modules
+pub mod dot_test;
+pub mod json_test;
+pub mod syntax_test;
@@ -0,0 +1,660 @@
+// Standard libraries
+use std::fmt::Write;
+
+// Internal modules
+use crate::lexer_and_parser::ast::{Node, Slot};
+use crate::lexer_and_parser::token::Keyword;
+use crate::passes::formatter::Formatter;
+
+// Graphviz DOT output
+/*
+    Two views of a program for `dot -Tsvg`:
+
+    The syntax tree has one graph node per AST node. Edges are labelled with the field
+    they come from, e.g. `l_expr` and `r_expr` of a binary expression, and statements
+    of a block with their index, e.g. `then_branch[1]`.
+
+    The control-flow graph has a cluster for the top level and for every function.
+    Straight-line statements are grouped into boxes, conditions of `if`, `elif` and
+    `while` are diamonds with `true` and `false` edges, and every path ends in the exit
+    of its cluster. Arms of a `match` are tried in order, each of them is a diamond
+    like an `elif`. Statements after `break`, `return` or `throw` have no incoming edges.
+    A `throw` continues at the catch of the enclosing try, or at the exit.
+*/
+pub fn ast_to_dot(nodes: &[Node]) -> String {
+    let mut graph = AstGraph {
+        output: String::new(),
+        count: 0,
+    };
+    graph.output += "digraph ast {\n    node [shape=box, fontname=\"monospace\"];\n";
+    let root = graph.add("Program".to_string());
+    for (i, node) in nodes.iter().enumerate() {
+        let child = graph.node(node);
+        graph.edge(root, child, &i.to_string());
+    }
+    graph.output += "}\n";
+    graph.output
+}
+
+pub fn control_flow_to_dot(nodes: &[Node]) -> String {
+    let mut output = String::from("digraph cfg {\n    node [fontname=\"monospace\"];\n");
+    let mut functions = Vec::new();
+    ControlFlow::build("main", "main", nodes, &mut output, &mut functions);
+    // Functions declared inside functions are found while building their parent
+    while !functions.is_empty() {
+        let (name, body) = functions.remove(0);
+        // Methods are named after their struct, e.g. `Point.norm`
+        let prefix = format!("fn_{0}", name.replace('.', "_"));
+        ControlFlow::build(
+            &prefix,
+            &format!("fn {0}", name),
+            &body,
+            &mut output,
+            &mut functions,
+        );
+    }
+    output += "}\n";
+    output
+}
+
+struct AstGraph {
+    output: String,
+    count: usize,
+}
+
+impl AstGraph {
+    fn add(&mut self, label: String) -> usize {
+        let id = self.count;
+        self.count += 1;
+        // Writing to a String can not fail
+        writeln!(self.output, "    n{0} [label=\"{1}\"];", id, escape(&label)).unwrap();
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        writeln!(
+            self.output,
+            "    n{0} -> n{1} [label=\"{2}\"];",
+            from, to, label
+        )
+        .unwrap();
+    }
+
+    fn child(&mut self, parent: usize, node: &Node, field: &str) {
+        let child = self.node(node);
+        self.edge(parent, child, field);
+    }
+
+    fn optional(&mut self, parent: usize, node: &Option<Node>, field: &str) {
+        if let Some(node) = node {
+            self.child(parent, node, field);
+        }
+    }
+
+    fn block(&mut self, parent: usize, nodes: &[Node], field: &str) {
+        for (i, node) in nodes.iter().enumerate() {
+            self.child(parent, node, &format!("{0}[{1}]", field, i));
+        }
+    }
+
+    // Add the node and its children, returns its id
+    fn node(&mut self, node: &Node) -> usize {
+        match node {
+            Node::NumberExpression(number) => self.add(format!("Number {0}", number.literal())),
+            Node::NegativeNumberExpression(expr) => {
+                let id = self.add("Negative".to_string());
+                self.child(id, expr, "expr");
+                id
+            }
+            Node::LiteralExpression(literal, Keyword::None) => {
+                self.add(format!("Literal \"{0}\"", literal))
+            }
+            Node::LiteralExpression(literal, _) => self.add(format!("Literal {0}", literal)),
+            Node::IdentifierExpression(identifier) => {
+                self.add(format!("Identifier {0}", identifier))
+            }
+            Node::VariableExpression { identifier, slot } => self.add(match slot {
+                Slot::Global(index) => format!("Variable {0} (global {1})", identifier, index),
+                Slot::Local(index) => format!("Variable {0} (local {1})", identifier, index),
+                Slot::Captured { depth, slot } => format!(
+                    "Variable {0} (captured {1}, depth {2})",
+                    identifier, slot, depth
+                ),
+            }),
+            Node::BinaryExpr {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                let id = self.add(format!("Binary {0}", operator.symbol()));
+                self.child(id, l_expr, "l_expr");
+                self.child(id, r_expr, "r_expr");
+                id
+            }
+            Node::ConditionExpression {
+                l_expr,
+                operator,
+                r_expr,
+            } => {
+                let id = self.add(format!("Condition {0}", operator.symbol()));
+                self.child(id, l_expr, "l_expr");
+                self.child(id, r_expr, "r_expr");
+                id
+            }
+            Node::AssignmentExpression {
+                identifier,
+                assignment_operator,
+                expr,
+                annotation,
+            } => {
+                let label = match annotation {
+                    Some(annotation) => {
+                        format!(
+                            "Assignment {0} ({1})",
+                            assignment_operator.symbol(),
+                            annotation
+                        )
+                    }
+                    None => format!("Assignment {0}", assignment_operator.symbol()),
+                };
+                let id = self.add(label);
+                self.child(id, identifier, "identifier");
+                self.child(id, expr, "expr");
+                id
+            }
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let id = self.add("If".to_string());
+                self.optional(id, condition, "condition");
+                self.block(id, then_branch, "then_branch");
+                self.optional(id, else_branch, "else_branch");
+                id
+            }
+            Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let label = if condition.is_some() { "Elif" } else { "Else" };
+                let id = self.add(label.to_string());
+                self.optional(id, condition, "condition");
+                self.block(id, then_branch, "then_branch");
+                self.optional(id, else_branch, "else_branch");
+                id
+            }
+            Node::QuantityExpression { value, unit } => {
+                let id = self.add(format!("Quantity {0}", unit));
+                self.child(id, value, "value");
+                id
+            }
+            Node::ConversionExpression { expr, unit } => {
+                let id = self.add(format!("Conversion {0}", unit));
+                self.child(id, expr, "expr");
+                id
+            }
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => {
+                let id = self.add("While".to_string());
+                self.optional(id, condition, "condition");
+                self.block(id, then_branch, "then_branch");
+                id
+            }
+            Node::BreakExpression => self.add("Break".to_string()),
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                body,
+                ..
+            } => {
+                let id = self.add(format!(
+                    "Function {0}({1})",
+                    identifier,
+                    parameters.join(", ")
+                ));
+                self.block(id, body, "body");
+                id
+            }
+            Node::FunctionExpression {
+                parameters, body, ..
+            } => {
+                let id = self.add(format!("Closure fn({0})", parameters.join(", ")));
+                self.block(id, body, "body");
+                id
+            }
+            Node::CallExpression { callee, arguments } => {
+                let id = self.add("Call".to_string());
+                self.child(id, callee, "callee");
+                self.block(id, arguments, "arguments");
+                id
+            }
+            Node::ListExpression(elements) => {
+                let id = self.add("List".to_string());
+                self.block(id, elements, "elements");
+                id
+            }
+            Node::IndexExpression { expr, index } => {
+                let id = self.add("Index".to_string());
+                self.child(id, expr, "expr");
+                self.child(id, index, "index");
+                id
+            }
+            Node::StructDeclaration { identifier, fields } => {
+                self.add(format!("Struct {0} {{ {1} }}", identifier, fields.join(", ")))
+            }
+            Node::ImplDeclaration {
+                identifier,
+                methods,
+            } => {
+                let id = self.add(format!("Impl {0}", identifier));
+                self.block(id, methods, "methods");
+                id
+            }
+            Node::StructExpression { identifier, fields } => {
+                let id = self.add(format!("StructValue {0}", identifier));
+                for (field, value) in fields {
+                    self.child(id, value, field);
+                }
+                id
+            }
+            Node::FieldExpression { expr, field } => {
+                let id = self.add(format!("Field {0}", field));
+                self.child(id, expr, "expr");
+                id
+            }
+            Node::FieldAssignmentExpression { expr, field, value } => {
+                let id = self.add(format!("FieldAssignment {0}", field));
+                self.child(id, expr, "expr");
+                self.child(id, value, "value");
+                id
+            }
+            Node::EnumDeclaration {
+                identifier,
+                variants,
+            } => {
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|(variant, fields)| match fields.is_empty() {
+                        true => variant.clone(),
+                        false => format!("{0}({1})", variant, fields.join(", ")),
+                    })
+                    .collect();
+                self.add(format!("Enum {0} {{ {1} }}", identifier, variants.join(", ")))
+            }
+            Node::VariantExpression {
+                enum_name,
+                variant,
+                arguments,
+            } => {
+                let id = self.add(format!("Variant {0}::{1}", enum_name, variant));
+                self.block(id, arguments, "arguments");
+                id
+            }
+            // Patterns are labels of their arm
+            Node::MatchExpression { expr, arms } => {
+                let id = self.add("Match".to_string());
+                self.child(id, expr, "expr");
+                for (i, arm) in arms.iter().enumerate() {
+                    let pattern = Formatter::format_pattern(&arm.pattern);
+                    let arm_id = self.add(format!("Arm {0}", pattern));
+                    self.edge(id, arm_id, &format!("arms[{0}]", i));
+                    self.optional(arm_id, &arm.guard, "guard");
+                    self.block(arm_id, &arm.body, "body");
+                }
+                id
+            }
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => {
+                let id = self.add("Try".to_string());
+                self.block(id, body, "body");
+                if let Some(catch) = catch.as_ref() {
+                    let catch_id = self.add("Catch".to_string());
+                    self.edge(id, catch_id, "catch");
+                    self.child(catch_id, &catch.binding, "binding");
+                    self.block(catch_id, &catch.body, "body");
+                }
+                if let Some(finally) = finally.as_ref() {
+                    self.block(id, finally, "finally");
+                }
+                id
+            }
+            Node::ThrowExpression(value) => {
+                let id = self.add("Throw".to_string());
+                self.child(id, value, "value");
+                id
+            }
+            Node::PropagateExpression(value) => {
+                let id = self.add("Propagate".to_string());
+                self.child(id, value, "value");
+                id
+            }
+            Node::ImportExpression { path, alias, names } => {
+                let id = self.add(format!("Import \"{0}\"", path));
+                self.optional(id, alias, "alias");
+                self.block(id, names, "names");
+                id
+            }
+            Node::ExportStatement(names) => self.add(format!("Export {0}", names.join(", "))),
+            Node::ReturnExpression(value) => {
+                let id = self.add("Return".to_string());
+                self.optional(id, value, "value");
+                id
+            }
+            Node::EOF(_) => self.add("EOF".to_string()),
+        }
+    }
+}
+
+// Builder of the control-flow graph of one function or the top level
+struct ControlFlow<'a> {
+    prefix: &'a str,
+    output: String,
+    count: usize,
+    // Box that the next simple statement is appended to
+    open: Option<(usize, Vec<String>)>,
+    // Edges waiting for the next graph node, with their labels
+    pending: Vec<(String, &'static str)>,
+    // Edges leaving through `break` in each enclosing loop
+    loops: Vec<Vec<(String, &'static str)>>,
+    // Edges leaving through `throw` in each enclosing try with a catch
+    handlers: Vec<Vec<(String, &'static str)>>,
+    functions: Vec<(String, Vec<Node>)>,
+}
+
+impl<'a> ControlFlow<'a> {
+    fn build(
+        prefix: &str,
+        name: &str,
+        nodes: &[Node],
+        output: &mut String,
+        functions: &mut Vec<(String, Vec<Node>)>,
+    ) {
+        let mut flow = ControlFlow {
+            prefix,
+            output: String::new(),
+            count: 0,
+            open: None,
+            pending: Vec::new(),
+            loops: Vec::new(),
+            handlers: Vec::new(),
+            functions: Vec::new(),
+        };
+        let entry = flow.id("entry");
+        writeln!(
+            flow.output,
+            "        {0} [label=\"entry\", shape=oval];",
+            entry
+        )
+        .unwrap();
+        flow.pending.push((entry, ""));
+        flow.block(nodes);
+        flow.close();
+        let exit = flow.id("exit");
+        writeln!(
+            flow.output,
+            "        {0} [label=\"exit\", shape=oval];",
+            exit
+        )
+        .unwrap();
+        flow.connect(&exit);
+        writeln!(
+            output,
+            "    subgraph cluster_{0} {{\n        label=\"{1}\";\n{2}    }}",
+            prefix,
+            escape(name),
+            flow.output
+        )
+        .unwrap();
+        functions.append(&mut flow.functions);
+    }
+
+    fn id(&self, suffix: &str) -> String {
+        format!("{0}_{1}", self.prefix, suffix)
+    }
+
+    // Graph node with a fresh id, connected to the pending edges
+    fn add(&mut self, label: &str, shape: &str) -> String {
+        self.close();
+        let id = self.id(&self.count.to_string());
+        self.count += 1;
+        writeln!(
+            self.output,
+            "        {0} [label=\"{1}\", shape={2}];",
+            id,
+            escape(label),
+            shape
+        )
+        .unwrap();
+        self.connect(&id);
+        id
+    }
+
+    fn connect(&mut self, to: &str) {
+        self.close();
+        for (from, label) in std::mem::take(&mut self.pending) {
+            match label {
+                "" => writeln!(self.output, "        {0} -> {1};", from, to).unwrap(),
+                label => writeln!(
+                    self.output,
+                    "        {0} -> {1} [label=\"{2}\"];",
+                    from, to, label
+                )
+                .unwrap(),
+            }
+        }
+    }
+
+    fn statement(&mut self, text: String) {
+        match self.open.as_mut() {
+            Some((_, statements)) => statements.push(text),
+            None => {
+                self.open = Some((self.count, vec![text]));
+                self.count += 1;
+            }
+        }
+    }
+
+    // Write the open box, which continues to whatever comes next
+    fn close(&mut self) {
+        if let Some((index, statements)) = self.open.take() {
+            let id = self.id(&index.to_string());
+            // Left-justified lines, also within statements such as an assigned match
+            let label: String = statements
+                .iter()
+                .map(|statement| format!("{0}\\l", escape(statement).replace('\n', "\\l")))
+                .collect();
+            writeln!(
+                self.output,
+                "        {0} [label=\"{1}\", shape=box];",
+                id, label
+            )
+            .unwrap();
+            self.connect(&id);
+            self.pending.push((id, ""));
+        }
+    }
+
+    fn block(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::IfExpression {
+                condition,
+                then_branch,
+                else_branch,
+            }
+            | Node::ElseExpression {
+                condition,
+                then_branch,
+                else_branch,
+            } => match condition.as_ref() {
+                Some(condition) => {
+                    let keyword = match node {
+                        Node::IfExpression { .. } => "if",
+                        _ => "elif",
+                    };
+                    let decision = self.add(
+                        &format!("{0} {1}", keyword, Formatter::format_expression(condition)),
+                        "diamond",
+                    );
+                    self.pending.push((decision.clone(), "true"));
+                    self.block(then_branch);
+                    self.close();
+                    let mut exits = std::mem::take(&mut self.pending);
+                    self.pending.push((decision, "false"));
+                    if let Some(else_branch) = else_branch.as_ref() {
+                        self.node(else_branch);
+                        self.close();
+                    }
+                    exits.append(&mut self.pending);
+                    self.pending = exits;
+                }
+                // Final `else`
+                None => self.block(then_branch),
+            },
+            Node::WhileExpression {
+                condition,
+                then_branch,
+            } => {
+                let label = match condition.as_ref() {
+                    Some(condition) => {
+                        format!("while {0}", Formatter::format_expression(condition))
+                    }
+                    None => "while".to_string(),
+                };
+                let decision = self.add(&label, "diamond");
+                self.pending.push((decision.clone(), "true"));
+                self.loops.push(Vec::new());
+                self.block(then_branch);
+                // Back to the condition
+                self.connect(&decision);
+                let breaks = self.loops.pop().unwrap_or_default();
+                if condition.is_some() {
+                    self.pending.push((decision, "false"));
+                }
+                self.pending.extend(breaks);
+            }
+            Node::BreakExpression => {
+                self.statement("break".to_string());
+                self.close();
+                let pending = std::mem::take(&mut self.pending);
+                match self.loops.last_mut() {
+                    Some(breaks) => breaks.extend(pending),
+                    None => self.pending = pending,
+                }
+            }
+            Node::ReturnExpression(_) => {
+                self.statement(Formatter::format_expression(node));
+                self.close();
+                let exit = self.id("exit");
+                self.connect(&exit);
+            }
+            Node::ThrowExpression(_) => {
+                self.statement(Formatter::format_expression(node));
+                self.close();
+                let pending = std::mem::take(&mut self.pending);
+                match self.handlers.last_mut() {
+                    Some(throws) => throws.extend(pending),
+                    None => {
+                        self.pending = pending;
+                        let exit = self.id("exit");
+                        self.connect(&exit);
+                    }
+                }
+            }
+            // Any statement of the body can fail, which is the `error` edge from the try to
+            // its catch. Without a catch errors leave the try, there is no edge for them.
+            Node::TryExpression {
+                body,
+                catch,
+                finally,
+            } => {
+                let start = self.add("try", "box");
+                self.pending.push((start.clone(), ""));
+                if catch.is_some() {
+                    self.handlers.push(vec![(start, "error")]);
+                }
+                self.block(body);
+                self.close();
+                if let Some(catch) = catch.as_ref() {
+                    let mut exits = std::mem::take(&mut self.pending);
+                    self.pending = self.handlers.pop().unwrap_or_default();
+                    let binding = Formatter::format_expression(&catch.binding);
+                    let handler = self.add(&format!("catch {0}", binding), "box");
+                    self.pending.push((handler, ""));
+                    self.block(&catch.body);
+                    self.close();
+                    exits.append(&mut self.pending);
+                    self.pending = exits;
+                }
+                if let Some(finally) = finally.as_ref() {
+                    let start = self.add("finally", "box");
+                    self.pending.push((start, ""));
+                    self.block(finally);
+                }
+            }
+            Node::FunctionDeclaration {
+                identifier,
+                parameters,
+                body,
+                ..
+            } => {
+                self.statement(format!("fn {0}({1})", identifier, parameters.join(", ")));
+                self.functions.push((identifier.clone(), *body.clone()));
+            }
+            Node::ImplDeclaration {
+                identifier,
+                methods,
+            } => {
+                self.statement(format!("impl {0}", identifier));
+                for method in methods.iter() {
+                    if let Node::FunctionDeclaration {
+                        identifier: name,
+                        body,
+                        ..
+                    } = method
+                    {
+                        let name = format!("{0}.{1}", identifier, name);
+                        self.functions.push((name, *body.clone()));
+                    }
+                }
+            }
+            Node::MatchExpression { expr, arms } => {
+                self.statement(format!("match {0}", Formatter::format_expression(expr)));
+                let mut exits = Vec::new();
+                for arm in arms {
+                    let mut label = Formatter::format_pattern(&arm.pattern);
+                    if let Some(guard) = &arm.guard {
+                        label += &format!(" if {0}", Formatter::format_expression(guard));
+                    }
+                    let decision = self.add(&label, "diamond");
+                    self.pending.push((decision.clone(), "true"));
+                    self.block(&arm.body);
+                    self.close();
+                    exits.append(&mut self.pending);
+                    self.pending.push((decision, "false"));
+                }
+                // Without a matching arm the match fails, there is no edge for it
+                self.pending = exits;
+            }
+            Node::EOF(_) => {}
+            _ => self.statement(Formatter::format_expression(node)),
+        }
+    }
+}
+
+// Quotes and backslashes in DOT strings
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::ast::Node;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::serialization::dot::{ast_to_dot, control_flow_to_dot};
+
+    fn parse(source: &str) -> Vec<Node> {
+        Parser::new(source).unwrap().parse().unwrap()
+    }
+
+    #[test]
+    fn test_ast() {
+        // Precedence is visible in the shape of the tree
+        let expected = r#"digraph ast {
+    node [shape=box, fontname="monospace"];
+    n0 [label="Program"];
+    n1 [label="Binary +"];
+    n2 [label="Number 1"];
+    n1 -> n2 [label="l_expr"];
+    n3 [label="Binary *"];
+    n4 [label="Number 2"];
+    n3 -> n4 [label="l_expr"];
+    n5 [label="Number 3"];
+    n3 -> n5 [label="r_expr"];
+    n1 -> n3 [label="r_expr"];
+    n0 -> n1 [label="0"];
+    n6 [label="If"];
+    n7 [label="Literal true"];
+    n6 -> n7 [label="condition"];
+    n8 [label="Literal \"a\\b\""];
+    n6 -> n8 [label="then_branch[0]"];
+    n9 [label="Else"];
+    n6 -> n9 [label="else_branch"];
+    n0 -> n6 [label="1"];
+}
+"#;
+        assert_eq!(
+            ast_to_dot(&parse("1 + 2 * 3\nif true { \"a\\b\" } else {}")),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let source = "x = 0
+while x < 10 {
+    x = x + 1
+    if x == 5 {
+        break
+    } else {
+        y = 1
+    }
+}
+x";
+        let expected = r#"digraph cfg {
+    node [fontname="monospace"];
+    subgraph cluster_main {
+        label="main";
+        main_entry [label="entry", shape=oval];
+        main_0 [label="x = 0\l", shape=box];
+        main_entry -> main_0;
+        main_1 [label="while x < 10", shape=diamond];
+        main_0 -> main_1;
+        main_2 [label="x = x + 1\l", shape=box];
+        main_1 -> main_2 [label="true"];
+        main_3 [label="if x == 5", shape=diamond];
+        main_2 -> main_3;
+        main_4 [label="break\l", shape=box];
+        main_3 -> main_4 [label="true"];
+        main_5 [label="y = 1\l", shape=box];
+        main_3 -> main_5 [label="false"];
+        main_5 -> main_1;
+        main_6 [label="x\l", shape=box];
+        main_1 -> main_6 [label="false"];
+        main_4 -> main_6;
+        main_exit [label="exit", shape=oval];
+        main_6 -> main_exit;
+    }
+}
+"#;
+        assert_eq!(control_flow_to_dot(&parse(source)), expected);
+    }
+
+    #[test]
+    fn test_functions() {
+        let source = "fn f(a) {
+    if a > 1 {
+        return a
+    }
+    return 0
+    a = 1
+}
+f(2)";
+        let graph = control_flow_to_dot(&parse(source));
+        assert!(graph.contains("main_0 [label=\"fn f(a)\\lf(2)\\l\", shape=box];"));
+        let function = &graph[graph.find("subgraph cluster_fn_f").unwrap()..];
+        assert!(function.contains("label=\"fn f\";"));
+        assert!(
+            function.contains("fn_f_0 -> fn_f_1 [label=\"true\"];\n        fn_f_1 -> fn_f_exit;")
+        );
+        assert!(
+            function.contains("fn_f_0 -> fn_f_2 [label=\"false\"];\n        fn_f_2 -> fn_f_exit;")
+        );
+        // Unreachable statements have no incoming edges
+        assert!(function.contains("fn_f_3 [label=\"a = 1\\l\", shape=box];"));
+        assert!(!function.contains("-> fn_f_3"));
+        assert!(function.contains("fn_f_3 -> fn_f_exit;"));
+    }
+}
@@ -0,0 +1,349 @@
+// Standard libraries
+use std::error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// JSON value
+/*
+    Minimal JSON support for the language server and the AST export.
+    Objects keep the order of their keys, numbers are f64 like in JavaScript.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JsonError {
+    UnexpectedEnd,
+    UnexpectedCharacter(char),
+    InvalidNumber(String),
+    InvalidEscape(String),
+}
+
+// Public methods
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, JsonError> {
+        let mut reader = Reader {
+            chars: text.chars().peekable(),
+        };
+        let value = reader.value()?;
+        reader.skip_whitespace();
+        match reader.chars.next() {
+            Some(c) => Err(JsonError::UnexpectedCharacter(c)),
+            None => Ok(value),
+        }
+    }
+
+    // Object from key/value pairs, e.g. `Json::object(vec![("id", 1.into())])`
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    // Member of an object, None for missing keys and other values
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    // Non-negative integral number
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        Json::Number(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(values: Vec<Json>) -> Self {
+        Json::Array(values)
+    }
+}
+
+// Compact serialisation without whitespace
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            // JSON has no infinities or NaN
+            Json::Number(number) if !number.is_finite() => write!(f, "null"),
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(string) => write_string(f, string),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::UnexpectedEnd => write!(f, "Unexpected end of JSON"),
+            JsonError::UnexpectedCharacter(c) => write!(f, "Unexpected character {0:?} in JSON", c),
+            JsonError::InvalidNumber(number) => write!(f, "Invalid number {0} in JSON", number),
+            JsonError::InvalidEscape(escape) => write!(f, "Invalid escape \\{0} in JSON", escape),
+        }
+    }
+}
+
+impl error::Error for JsonError {}
+
+// Private methods
+fn write_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{0:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Reader<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn next(&mut self) -> Result<char, JsonError> {
+        self.chars.next().ok_or(JsonError::UnexpectedEnd)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => Err(JsonError::UnexpectedCharacter(c)),
+        }
+    }
+
+    // Rest of a literal such as `true`
+    fn expect_word(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        for expected in word.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.chars.peek().copied().ok_or(JsonError::UnexpectedEnd)? {
+            'n' => self.expect_word("null", Json::Null),
+            't' => self.expect_word("true", Json::Bool(true)),
+            'f' => self.expect_word("false", Json::Bool(false)),
+            '"' => Ok(Json::String(self.string()?)),
+            '[' => {
+                self.next()?;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&']') {
+                    self.next()?;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => {}
+                        ']' => return Ok(Json::Array(values)),
+                        c => return Err(JsonError::UnexpectedCharacter(c)),
+                    }
+                }
+            }
+            '{' => {
+                self.next()?;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.chars.peek() == Some(&'}') {
+                    self.next()?;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => {}
+                        '}' => return Ok(Json::Object(members)),
+                        c => return Err(JsonError::UnexpectedCharacter(c)),
+                    }
+                }
+            }
+            '-' | '0'..='9' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.peek() {
+                    if !(c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                        break;
+                    }
+                    number.push(self.next()?);
+                }
+                number
+                    .parse::<f64>()
+                    .map(Json::Number)
+                    .map_err(|_| JsonError::InvalidNumber(number))
+            }
+            c => Err(JsonError::UnexpectedCharacter(c)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(string),
+                '\\' => match self.next()? {
+                    '"' => string.push('"'),
+                    '\\' => string.push('\\'),
+                    '/' => string.push('/'),
+                    'b' => string.push('\u{8}'),
+                    'f' => string.push('\u{c}'),
+                    'n' => string.push('\n'),
+                    'r' => string.push('\r'),
+                    't' => string.push('\t'),
+                    'u' => {
+                        let code = self.hex_code()?;
+                        // Characters outside the basic plane come as surrogate pairs
+                        let code = if (0xD800..0xDC00).contains(&code) {
+                            self.expect('\\')?;
+                            self.expect('u')?;
+                            let low = self.hex_code()?;
+                            0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+                        } else {
+                            code
+                        };
+                        string.push(
+                            char::from_u32(code).ok_or_else(|| {
+                                JsonError::InvalidEscape(format!("u{0:04x}", code))
+                            })?,
+                        );
+                    }
+                    c => return Err(JsonError::InvalidEscape(c.to_string())),
+                },
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn hex_code(&mut self) -> Result<u32, JsonError> {
+        let mut digits = String::new();
+        for _ in 0..4 {
+            digits.push(self.next()?);
+        }
+        u32::from_str_radix(&digits, 16)
+            .map_err(|_| JsonError::InvalidEscape(format!("u{0}", digits)))
+    }
+}
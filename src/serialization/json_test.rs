@@ -0,0 +1,67 @@
+#[cfg(test)]
+mod tests {
+    use crate::serialization::json::{Json, JsonError};
+
+    #[test]
+    fn test_parse() {
+        let value = Json::parse(
+            r#" {"id": 1, "params": {"text": "a\n\"b\"", "list": [true, false, null, -2.5e1]}} "#,
+        )
+        .unwrap();
+        assert_eq!(value.get("id").and_then(Json::as_usize), Some(1));
+        let params = value.get("params").unwrap();
+        assert_eq!(params.get("text").and_then(Json::as_str), Some("a\n\"b\""));
+        assert_eq!(
+            params.get("list"),
+            Some(&Json::Array(vec![
+                Json::Bool(true),
+                Json::Bool(false),
+                Json::Null,
+                Json::Number(-25.),
+            ]))
+        );
+        assert_eq!(
+            Json::parse(r#""\u00e4\ud83d\ude00""#),
+            Ok(Json::String("ä😀".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Json::parse("[1, 2"), Err(JsonError::UnexpectedEnd));
+        assert_eq!(
+            Json::parse("[1] x"),
+            Err(JsonError::UnexpectedCharacter('x'))
+        );
+        assert_eq!(
+            Json::parse("{\"a\" 1}"),
+            Err(JsonError::UnexpectedCharacter('1'))
+        );
+        assert_eq!(
+            Json::parse("\"\\q\""),
+            Err(JsonError::InvalidEscape("q".to_string()))
+        );
+        assert_eq!(
+            Json::parse("1.2.3"),
+            Err(JsonError::InvalidNumber("1.2.3".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_serialise() {
+        let value = Json::object(vec![
+            ("name", "line\t\"1\"".into()),
+            (
+                "values",
+                vec![Json::from(1.5), Json::from(2usize), Json::Null].into(),
+            ),
+            ("empty", Json::object(vec![])),
+        ]);
+        let text = value.to_string();
+        assert_eq!(
+            text,
+            r#"{"name":"line\t\"1\"","values":[1.5,2,null],"empty":{}}"#
+        );
+        assert_eq!(Json::parse(&text), Ok(value));
+    }
+}
@@ -0,0 +1,1064 @@
+// Standard libraries
+use std::fmt;
+
+// Internal modules
+use super::json::Json;
+use crate::lexer_and_parser::ast::{CatchClause, MatchArm, Node, Pattern, Slot, Type};
+use crate::lexer_and_parser::numeric::{BigInt, Number};
+use crate::lexer_and_parser::parser::{ParseError, Parser};
+use crate::lexer_and_parser::token::{Keyword, Token};
+use crate::lexer_and_parser::tokenizer::{Span, Tokenizer};
+
+// JSON format of tokens and syntax trees
+/*
+    Used by external tools, e.g. the web playground. A document is an object with
+
+        version          SCHEMA_VERSION
+        tokens           every token of the source including whitespace, optional
+        statements       the syntax tree, one node per top-level statement
+        statement_lines  line of every statement in pre-order, optional
+
+    A token is `{"kind": "add", "span": {"line": 1, "column": 2, "length": 1}}`. The
+    kind is the snake_case name of the Token variant, `num`, `identifier` and `comment`
+    carry a `value`, `num` also has a `float` flag for literals with a decimal point, and
+    `literal` carries the `literal` text and its `keyword`, which is `none` for strings
+    and plain literals. Lines start from 1, columns from 0. Numbers are JSON numbers,
+    except for exact ones a float can not hold, which are text like "9007199254740993"
+    or "1/3".
+
+    A node is an object with a `type` and the fields of its variant:
+
+        number       value, float
+        negative     expr
+        literal      value, keyword
+        identifier   name
+        variable     name, slot: {"kind": "global", "local" or "captured", "index"}
+                     captured slots also have the `depth` of their function
+        binary       operator, left, right
+        condition    operator, left, right
+        assignment   operator, target, value, annotation
+        if           condition, then, else
+        else         condition, then, else          (`elif` has a condition)
+        quantity     value, unit
+        conversion   expr, unit
+        while        condition, body
+        break
+        function     name, parameters: [{"name", "type"}], return_type, body
+        closure      parameters, return_type, body
+        call         callee, arguments
+        list         elements
+        index        expr, index
+        struct       name, fields: [name]
+        impl         name, methods: [function]
+        struct_value name, fields: [{"name", "value"}]
+        field        expr, name
+        field_assign expr, name, value
+        enum         name, variants: [{"name", "fields": [name]}]
+        variant      enum, name, arguments
+        match        expr, arms: [{"pattern", "guard", "body"}]
+        try          body, catch: {"binding", "body"}, finally
+        throw        value
+        propagate    value                          (`value?`)
+        import       path, alias, names             (`from` imports have names)
+        export       names: [name]
+        return       value
+        eof
+
+    A pattern is an object with a `kind` and the fields of its variant:
+
+        wildcard
+        binding      target                         (an identifier or variable node)
+        literal      value                          (a number, negative or literal node)
+        range        start, end, inclusive
+        list         elements, rest                 (rest is a pattern or null)
+        struct       name, fields: [{"name", "pattern"}]
+        variant      enum, name, elements
+
+    Operators are written as in the source, e.g. `+` or `>=`, and types by their
+    annotation names. Missing optional values are null, blocks are arrays of nodes.
+*/
+// Bump whenever the layout changes
+pub const SCHEMA_VERSION: usize = 1;
+
+const OPERATORS: [Token; 12] = [
+    Token::Assignment,
+    Token::Add,
+    Token::Subtract,
+    Token::Multiply,
+    Token::Divide,
+    Token::Pow,
+    Token::Equals,
+    Token::NotEquals,
+    Token::GreaterThan,
+    Token::LessThan,
+    Token::GreaterThanOrEquals,
+    Token::LessThanOrEquals,
+];
+
+// Tokens and syntax tree of a source as a document.
+pub fn write_document(source: &str) -> Result<Json, ParseError> {
+    let mut parser = Parser::new(source)?;
+    let statements = parser.parse()?;
+    let lines: Vec<Json> = parser
+        .statement_lines
+        .iter()
+        .map(|line| (*line).into())
+        .collect();
+    Ok(Json::object(vec![
+        ("version", SCHEMA_VERSION.into()),
+        ("tokens", tokens_to_json(source)),
+        ("statements", nodes_to_json(&statements)),
+        ("statement_lines", lines.into()),
+    ]))
+}
+
+// Syntax tree and statement lines of a document. Tokens are not needed to run it.
+pub fn read_document(document: &Json) -> Result<(Vec<Node>, Vec<usize>), SchemaError> {
+    match field(document, "version")?.as_usize() {
+        Some(SCHEMA_VERSION) => {}
+        Some(version) => return Err(SchemaError::UnsupportedVersion(version)),
+        None => return Err(invalid("version")),
+    }
+    let statements = nodes_from_json(field(document, "statements")?)?;
+    let lines = match document.get("statement_lines") {
+        None | Some(Json::Null) => Vec::new(),
+        Some(lines) => array(lines, "statement_lines")?
+            .iter()
+            .map(|line| line.as_usize().ok_or_else(|| invalid("statement_lines")))
+            .collect::<Result<_, _>>()?,
+    };
+    Ok((statements, lines))
+}
+
+// Token stream of the tokenizer, ending with the EOF token or at an invalid character.
+pub fn tokens_to_json(source: &str) -> Json {
+    let mut tokens = Vec::new();
+    let mut tokenizer = Tokenizer::new(source);
+    while let Some((token, span)) = tokenizer.next_spanned() {
+        let end = token == Token::EOF;
+        tokens.push(token_to_json(&token, Some(span)));
+        if end {
+            break;
+        }
+    }
+    tokens.into()
+}
+
+pub fn token_to_json(token: &Token, span: Option<Span>) -> Json {
+    let mut members = vec![("kind", token_kind(token).into())];
+    match token {
+        Token::Num(number) => {
+            members.push(("value", number_to_json(number)));
+            members.push(("float", is_float(number).into()));
+        }
+        Token::Identifier(value) | Token::Comment(value) => {
+            members.push(("value", value.as_str().into()))
+        }
+        Token::Literal { literal, keyword } => {
+            members.push(("literal", literal.as_str().into()));
+            members.push(("keyword", keyword_name(keyword).into()));
+        }
+        _ => {}
+    }
+    if let Some(span) = span {
+        members.push((
+            "span",
+            Json::object(vec![
+                ("line", span.line.into()),
+                ("column", span.column.into()),
+                ("length", span.length.into()),
+            ]),
+        ));
+    }
+    Json::object(members)
+}
+
+pub fn token_from_json(json: &Json) -> Result<(Token, Option<Span>), SchemaError> {
+    let token = match string(json, "kind")? {
+        "assignment" => Token::Assignment,
+        "add" => Token::Add,
+        "subtract" => Token::Subtract,
+        "equals" => Token::Equals,
+        "not_equals" => Token::NotEquals,
+        "multiply" => Token::Multiply,
+        "divide" => Token::Divide,
+        "pow" => Token::Pow,
+        "left_parenthese" => Token::LeftParenthese,
+        "right_parenthese" => Token::RightParenthese,
+        "num" => Token::Num(number_value(json)?),
+        "literal" => Token::Literal {
+            literal: string(json, "literal")?.to_string(),
+            keyword: keyword(json)?,
+        },
+        "identifier" => Token::Identifier(string(json, "value")?.to_string()),
+        "whitespace" => Token::Whitespace,
+        "newline" => Token::Newline,
+        "comment" => Token::Comment(string(json, "value")?.to_string()),
+        "eof" => Token::EOF,
+        "left_brace" => Token::LeftBrace,
+        "right_brace" => Token::RightBrace,
+        "greater_than" => Token::GreaterThan,
+        "less_than" => Token::LessThan,
+        "greater_than_or_equals" => Token::GreaterThanOrEquals,
+        "less_than_or_equals" => Token::LessThanOrEquals,
+        "comma" => Token::Comma,
+        "colon" => Token::Colon,
+        "arrow" => Token::Arrow,
+        "left_bracket" => Token::LeftBracket,
+        "right_bracket" => Token::RightBracket,
+        "dot" => Token::Dot,
+        "double_colon" => Token::DoubleColon,
+        "fat_arrow" => Token::FatArrow,
+        "dot_dot" => Token::DotDot,
+        "dot_dot_equals" => Token::DotDotEquals,
+        "question" => Token::Question,
+        kind => {
+            return Err(SchemaError::Invalid(format!(
+                "Unknown token kind {0}",
+                kind
+            )))
+        }
+    };
+    let span = match json.get("span") {
+        None | Some(Json::Null) => None,
+        Some(span) => Some(Span {
+            line: index(span, "line")?,
+            column: index(span, "column")?,
+            length: index(span, "length")?,
+        }),
+    };
+    Ok((token, span))
+}
+
+pub fn nodes_to_json(nodes: &[Node]) -> Json {
+    nodes.iter().map(node_to_json).collect::<Vec<Json>>().into()
+}
+
+pub fn node_to_json(node: &Node) -> Json {
+    let optional = |node: &Option<Node>| node.as_ref().map(node_to_json).unwrap_or(Json::Null);
+    let annotation = |annotation: &Option<Type>| match annotation {
+        Some(annotation) => annotation.to_string().into(),
+        None => Json::Null,
+    };
+    let (kind, mut members) = match node {
+        Node::NumberExpression(number) => (
+            "number",
+            vec![
+                ("value", number_to_json(number)),
+                ("float", is_float(number).into()),
+            ],
+        ),
+        Node::NegativeNumberExpression(expr) => ("negative", vec![("expr", node_to_json(expr))]),
+        Node::LiteralExpression(value, keyword) => (
+            "literal",
+            vec![
+                ("value", value.as_str().into()),
+                ("keyword", keyword_name(keyword).into()),
+            ],
+        ),
+        Node::IdentifierExpression(name) => ("identifier", vec![("name", name.as_str().into())]),
+        Node::VariableExpression { identifier, slot } => {
+            let slot = match slot {
+                Slot::Global(index) => vec![("kind", "global".into()), ("index", (*index).into())],
+                Slot::Local(index) => vec![("kind", "local".into()), ("index", (*index).into())],
+                Slot::Captured { depth, slot } => vec![
+                    ("kind", "captured".into()),
+                    ("index", (*slot).into()),
+                    ("depth", (*depth).into()),
+                ],
+            };
+            (
+                "variable",
+                vec![
+                    ("name", identifier.as_str().into()),
+                    ("slot", Json::object(slot)),
+                ],
+            )
+        }
+        Node::BinaryExpr {
+            l_expr,
+            operator,
+            r_expr,
+        } => (
+            "binary",
+            vec![
+                ("operator", operator.symbol().into()),
+                ("left", node_to_json(l_expr)),
+                ("right", node_to_json(r_expr)),
+            ],
+        ),
+        Node::ConditionExpression {
+            l_expr,
+            operator,
+            r_expr,
+        } => (
+            "condition",
+            vec![
+                ("operator", operator.symbol().into()),
+                ("left", node_to_json(l_expr)),
+                ("right", node_to_json(r_expr)),
+            ],
+        ),
+        Node::AssignmentExpression {
+            identifier,
+            assignment_operator,
+            expr,
+            annotation: declared,
+        } => (
+            "assignment",
+            vec![
+                ("operator", assignment_operator.symbol().into()),
+                ("target", node_to_json(identifier)),
+                ("value", node_to_json(expr)),
+                ("annotation", annotation(declared)),
+            ],
+        ),
+        Node::IfExpression {
+            condition,
+            then_branch,
+            else_branch,
+        } => (
+            "if",
+            vec![
+                ("condition", optional(condition)),
+                ("then", nodes_to_json(then_branch)),
+                ("else", optional(else_branch)),
+            ],
+        ),
+        Node::ElseExpression {
+            condition,
+            then_branch,
+            else_branch,
+        } => (
+            "else",
+            vec![
+                ("condition", optional(condition)),
+                ("then", nodes_to_json(then_branch)),
+                ("else", optional(else_branch)),
+            ],
+        ),
+        Node::QuantityExpression { value, unit } => (
+            "quantity",
+            vec![
+                ("value", node_to_json(value)),
+                ("unit", unit.as_str().into()),
+            ],
+        ),
+        Node::ConversionExpression { expr, unit } => (
+            "conversion",
+            vec![("expr", node_to_json(expr)), ("unit", unit.as_str().into())],
+        ),
+        Node::WhileExpression {
+            condition,
+            then_branch,
+        } => (
+            "while",
+            vec![
+                ("condition", optional(condition)),
+                ("body", nodes_to_json(then_branch)),
+            ],
+        ),
+        Node::BreakExpression => ("break", Vec::new()),
+        Node::FunctionDeclaration {
+            identifier,
+            parameters,
+            parameter_types,
+            return_type,
+            body,
+        } => (
+            "function",
+            vec![
+                ("name", identifier.as_str().into()),
+                ("parameters", parameters_to_json(parameters, parameter_types)),
+                ("return_type", annotation(return_type)),
+                ("body", nodes_to_json(body)),
+            ],
+        ),
+        Node::FunctionExpression {
+            parameters,
+            parameter_types,
+            return_type,
+            body,
+        } => (
+            "closure",
+            vec![
+                ("parameters", parameters_to_json(parameters, parameter_types)),
+                ("return_type", annotation(return_type)),
+                ("body", nodes_to_json(body)),
+            ],
+        ),
+        Node::CallExpression { callee, arguments } => (
+            "call",
+            vec![
+                ("callee", node_to_json(callee)),
+                ("arguments", nodes_to_json(arguments)),
+            ],
+        ),
+        Node::ListExpression(elements) => ("list", vec![("elements", nodes_to_json(elements))]),
+        Node::IndexExpression { expr, index } => (
+            "index",
+            vec![("expr", node_to_json(expr)), ("index", node_to_json(index))],
+        ),
+        Node::StructDeclaration { identifier, fields } => (
+            "struct",
+            vec![
+                ("name", identifier.as_str().into()),
+                ("fields", names_to_json(fields)),
+            ],
+        ),
+        Node::ImplDeclaration {
+            identifier,
+            methods,
+        } => (
+            "impl",
+            vec![
+                ("name", identifier.as_str().into()),
+                ("methods", nodes_to_json(methods)),
+            ],
+        ),
+        Node::StructExpression { identifier, fields } => (
+            "struct_value",
+            vec![
+                ("name", identifier.as_str().into()),
+                (
+                    "fields",
+                    fields
+                        .iter()
+                        .map(|(field, value)| {
+                            Json::object(vec![
+                                ("name", field.as_str().into()),
+                                ("value", node_to_json(value)),
+                            ])
+                        })
+                        .collect::<Vec<Json>>()
+                        .into(),
+                ),
+            ],
+        ),
+        Node::FieldExpression { expr, field } => (
+            "field",
+            vec![("expr", node_to_json(expr)), ("name", field.as_str().into())],
+        ),
+        Node::FieldAssignmentExpression { expr, field, value } => (
+            "field_assign",
+            vec![
+                ("expr", node_to_json(expr)),
+                ("name", field.as_str().into()),
+                ("value", node_to_json(value)),
+            ],
+        ),
+        Node::EnumDeclaration {
+            identifier,
+            variants,
+        } => (
+            "enum",
+            vec![
+                ("name", identifier.as_str().into()),
+                (
+                    "variants",
+                    variants
+                        .iter()
+                        .map(|(variant, fields)| {
+                            Json::object(vec![
+                                ("name", variant.as_str().into()),
+                                ("fields", names_to_json(fields)),
+                            ])
+                        })
+                        .collect::<Vec<Json>>()
+                        .into(),
+                ),
+            ],
+        ),
+        Node::VariantExpression {
+            enum_name,
+            variant,
+            arguments,
+        } => (
+            "variant",
+            vec![
+                ("enum", enum_name.as_str().into()),
+                ("name", variant.as_str().into()),
+                ("arguments", nodes_to_json(arguments)),
+            ],
+        ),
+        Node::MatchExpression { expr, arms } => (
+            "match",
+            vec![
+                ("expr", node_to_json(expr)),
+                (
+                    "arms",
+                    arms.iter()
+                        .map(|arm| {
+                            Json::object(vec![
+                                ("pattern", pattern_to_json(&arm.pattern)),
+                                ("guard", optional(&arm.guard)),
+                                ("body", nodes_to_json(&arm.body)),
+                            ])
+                        })
+                        .collect::<Vec<Json>>()
+                        .into(),
+                ),
+            ],
+        ),
+        Node::TryExpression {
+            body,
+            catch,
+            finally,
+        } => (
+            "try",
+            vec![
+                ("body", nodes_to_json(body)),
+                (
+                    "catch",
+                    match catch.as_ref() {
+                        Some(catch) => Json::object(vec![
+                            ("binding", node_to_json(&catch.binding)),
+                            ("body", nodes_to_json(&catch.body)),
+                        ]),
+                        None => Json::Null,
+                    },
+                ),
+                (
+                    "finally",
+                    match finally.as_ref() {
+                        Some(finally) => nodes_to_json(finally),
+                        None => Json::Null,
+                    },
+                ),
+            ],
+        ),
+        Node::ThrowExpression(value) => ("throw", vec![("value", node_to_json(value))]),
+        Node::PropagateExpression(value) => ("propagate", vec![("value", node_to_json(value))]),
+        Node::ImportExpression { path, alias, names } => (
+            "import",
+            vec![
+                ("path", path.as_str().into()),
+                ("alias", optional(alias)),
+                ("names", nodes_to_json(names)),
+            ],
+        ),
+        Node::ExportStatement(names) => ("export", vec![("names", names_to_json(names))]),
+        Node::ReturnExpression(value) => ("return", vec![("value", optional(value))]),
+        Node::EOF(_) => ("eof", Vec::new()),
+    };
+    members.insert(0, ("type", kind.into()));
+    Json::object(members)
+}
+
+pub fn nodes_from_json(json: &Json) -> Result<Vec<Node>, SchemaError> {
+    array(json, "block")?.iter().map(node_from_json).collect()
+}
+
+pub fn node_from_json(json: &Json) -> Result<Node, SchemaError> {
+    let child = |name: &str| Ok(Box::new(node_from_json(field(json, name)?)?));
+    let optional = |name: &str| match json.get(name) {
+        None | Some(Json::Null) => Ok(None),
+        Some(node) => node_from_json(node).map(Some),
+    };
+    let block = |name: &str| Ok(Box::new(nodes_from_json(field(json, name)?)?));
+    let node = match string(json, "type")? {
+        "number" => Node::NumberExpression(number_value(json)?),
+        "negative" => Node::NegativeNumberExpression(child("expr")?),
+        "literal" => Node::LiteralExpression(string(json, "value")?.to_string(), keyword(json)?),
+        "identifier" => Node::IdentifierExpression(string(json, "name")?.to_string()),
+        "variable" => {
+            let slot = field(json, "slot")?;
+            let index = index(slot, "index")?;
+            Node::VariableExpression {
+                identifier: string(json, "name")?.to_string(),
+                slot: match string(slot, "kind")? {
+                    "global" => Slot::Global(index),
+                    "local" => Slot::Local(index),
+                    "captured" => Slot::Captured {
+                        depth: self::index(slot, "depth")?,
+                        slot: index,
+                    },
+                    kind => {
+                        return Err(SchemaError::Invalid(format!("Unknown slot kind {0}", kind)))
+                    }
+                },
+            }
+        }
+        "binary" => Node::BinaryExpr {
+            l_expr: child("left")?,
+            operator: operator(json)?,
+            r_expr: child("right")?,
+        },
+        "condition" => Node::ConditionExpression {
+            l_expr: child("left")?,
+            operator: operator(json)?,
+            r_expr: child("right")?,
+        },
+        "assignment" => Node::AssignmentExpression {
+            identifier: child("target")?,
+            assignment_operator: operator(json)?,
+            expr: child("value")?,
+            annotation: annotation(json, "annotation")?,
+        },
+        "if" => Node::IfExpression {
+            condition: Box::new(optional("condition")?),
+            then_branch: block("then")?,
+            else_branch: Box::new(optional("else")?),
+        },
+        "else" => Node::ElseExpression {
+            condition: Box::new(optional("condition")?),
+            then_branch: block("then")?,
+            else_branch: Box::new(optional("else")?),
+        },
+        "quantity" => Node::QuantityExpression {
+            value: child("value")?,
+            unit: string(json, "unit")?.to_string(),
+        },
+        "conversion" => Node::ConversionExpression {
+            expr: child("expr")?,
+            unit: string(json, "unit")?.to_string(),
+        },
+        "while" => Node::WhileExpression {
+            condition: Box::new(optional("condition")?),
+            then_branch: block("body")?,
+        },
+        "break" => Node::BreakExpression,
+        "function" => {
+            let (parameters, parameter_types) = parameters_from_json(json)?;
+            Node::FunctionDeclaration {
+                identifier: string(json, "name")?.to_string(),
+                parameters,
+                parameter_types,
+                return_type: annotation(json, "return_type")?,
+                body: block("body")?,
+            }
+        }
+        "closure" => {
+            let (parameters, parameter_types) = parameters_from_json(json)?;
+            Node::FunctionExpression {
+                parameters,
+                parameter_types,
+                return_type: annotation(json, "return_type")?,
+                body: block("body")?,
+            }
+        }
+        "call" => Node::CallExpression {
+            callee: child("callee")?,
+            arguments: nodes_from_json(field(json, "arguments")?)?,
+        },
+        "list" => Node::ListExpression(nodes_from_json(field(json, "elements")?)?),
+        "index" => Node::IndexExpression {
+            expr: child("expr")?,
+            index: child("index")?,
+        },
+        "struct" => Node::StructDeclaration {
+            identifier: string(json, "name")?.to_string(),
+            fields: names_from_json(field(json, "fields")?, "fields")?,
+        },
+        "impl" => Node::ImplDeclaration {
+            identifier: string(json, "name")?.to_string(),
+            methods: block("methods")?,
+        },
+        "struct_value" => {
+            let mut fields = Vec::new();
+            for value in array(field(json, "fields")?, "fields")? {
+                fields.push((
+                    string(value, "name")?.to_string(),
+                    node_from_json(field(value, "value")?)?,
+                ));
+            }
+            Node::StructExpression {
+                identifier: string(json, "name")?.to_string(),
+                fields,
+            }
+        }
+        "field" => Node::FieldExpression {
+            expr: child("expr")?,
+            field: string(json, "name")?.to_string(),
+        },
+        "field_assign" => Node::FieldAssignmentExpression {
+            expr: child("expr")?,
+            field: string(json, "name")?.to_string(),
+            value: child("value")?,
+        },
+        "enum" => {
+            let mut variants = Vec::new();
+            for variant in array(field(json, "variants")?, "variants")? {
+                variants.push((
+                    string(variant, "name")?.to_string(),
+                    names_from_json(field(variant, "fields")?, "fields")?,
+                ));
+            }
+            Node::EnumDeclaration {
+                identifier: string(json, "name")?.to_string(),
+                variants,
+            }
+        }
+        "variant" => Node::VariantExpression {
+            enum_name: string(json, "enum")?.to_string(),
+            variant: string(json, "name")?.to_string(),
+            arguments: nodes_from_json(field(json, "arguments")?)?,
+        },
+        "match" => {
+            let mut arms = Vec::new();
+            for arm in array(field(json, "arms")?, "arms")? {
+                let guard = match arm.get("guard") {
+                    None | Some(Json::Null) => None,
+                    Some(guard) => Some(node_from_json(guard)?),
+                };
+                arms.push(MatchArm {
+                    pattern: pattern_from_json(field(arm, "pattern")?)?,
+                    guard,
+                    body: nodes_from_json(field(arm, "body")?)?,
+                });
+            }
+            Node::MatchExpression {
+                expr: child("expr")?,
+                arms,
+            }
+        }
+        "try" => {
+            let catch = match json.get("catch") {
+                None | Some(Json::Null) => None,
+                Some(catch) => Some(CatchClause {
+                    binding: node_from_json(field(catch, "binding")?)?,
+                    body: nodes_from_json(field(catch, "body")?)?,
+                }),
+            };
+            let finally = match json.get("finally") {
+                None | Some(Json::Null) => None,
+                Some(finally) => Some(nodes_from_json(finally)?),
+            };
+            Node::TryExpression {
+                body: block("body")?,
+                catch: Box::new(catch),
+                finally: Box::new(finally),
+            }
+        }
+        "throw" => Node::ThrowExpression(child("value")?),
+        "propagate" => Node::PropagateExpression(child("value")?),
+        "import" => Node::ImportExpression {
+            path: string(json, "path")?.to_string(),
+            alias: Box::new(optional("alias")?),
+            names: nodes_from_json(field(json, "names")?)?,
+        },
+        "export" => Node::ExportStatement(names_from_json(field(json, "names")?, "names")?),
+        "return" => Node::ReturnExpression(Box::new(optional("value")?)),
+        "eof" => Node::EOF("EOF".to_string()),
+        kind => return Err(SchemaError::Invalid(format!("Unknown node type {0}", kind))),
+    };
+    Ok(node)
+}
+
+pub fn pattern_to_json(pattern: &Pattern) -> Json {
+    let patterns = |patterns: &[Pattern]| {
+        patterns
+            .iter()
+            .map(pattern_to_json)
+            .collect::<Vec<Json>>()
+            .into()
+    };
+    let (kind, mut members) = match pattern {
+        Pattern::Wildcard => ("wildcard", Vec::new()),
+        Pattern::Binding(target) => ("binding", vec![("target", node_to_json(target))]),
+        Pattern::Literal(value) => ("literal", vec![("value", node_to_json(value))]),
+        Pattern::Range {
+            start,
+            end,
+            inclusive,
+        } => (
+            "range",
+            vec![
+                ("start", node_to_json(start)),
+                ("end", node_to_json(end)),
+                ("inclusive", (*inclusive).into()),
+            ],
+        ),
+        Pattern::List { elements, rest } => (
+            "list",
+            vec![
+                ("elements", patterns(elements)),
+                (
+                    "rest",
+                    rest.as_deref().map(pattern_to_json).unwrap_or(Json::Null),
+                ),
+            ],
+        ),
+        Pattern::Struct { identifier, fields } => (
+            "struct",
+            vec![
+                ("name", identifier.as_str().into()),
+                (
+                    "fields",
+                    fields
+                        .iter()
+                        .map(|(field, pattern)| {
+                            Json::object(vec![
+                                ("name", field.as_str().into()),
+                                ("pattern", pattern_to_json(pattern)),
+                            ])
+                        })
+                        .collect::<Vec<Json>>()
+                        .into(),
+                ),
+            ],
+        ),
+        Pattern::Variant {
+            enum_name,
+            variant,
+            elements,
+        } => (
+            "variant",
+            vec![
+                ("enum", enum_name.as_str().into()),
+                ("name", variant.as_str().into()),
+                ("elements", patterns(elements)),
+            ],
+        ),
+    };
+    members.insert(0, ("kind", kind.into()));
+    Json::object(members)
+}
+
+pub fn pattern_from_json(json: &Json) -> Result<Pattern, SchemaError> {
+    let child = |name: &str| Ok(Box::new(node_from_json(field(json, name)?)?));
+    let patterns = |name: &str| -> Result<Vec<Pattern>, SchemaError> {
+        array(field(json, name)?, name)?
+            .iter()
+            .map(pattern_from_json)
+            .collect()
+    };
+    let pattern = match string(json, "kind")? {
+        "wildcard" => Pattern::Wildcard,
+        "binding" => Pattern::Binding(child("target")?),
+        "literal" => Pattern::Literal(child("value")?),
+        "range" => Pattern::Range {
+            start: child("start")?,
+            end: child("end")?,
+            inclusive: field(json, "inclusive")?
+                .as_bool()
+                .ok_or_else(|| invalid("inclusive"))?,
+        },
+        "list" => Pattern::List {
+            elements: patterns("elements")?,
+            rest: match json.get("rest") {
+                None | Some(Json::Null) => None,
+                Some(rest) => Some(Box::new(pattern_from_json(rest)?)),
+            },
+        },
+        "struct" => {
+            let mut fields = Vec::new();
+            for value in array(field(json, "fields")?, "fields")? {
+                fields.push((
+                    string(value, "name")?.to_string(),
+                    pattern_from_json(field(value, "pattern")?)?,
+                ));
+            }
+            Pattern::Struct {
+                identifier: string(json, "name")?.to_string(),
+                fields,
+            }
+        }
+        "variant" => Pattern::Variant {
+            enum_name: string(json, "enum")?.to_string(),
+            variant: string(json, "name")?.to_string(),
+            elements: patterns("elements")?,
+        },
+        kind => return Err(SchemaError::Invalid(format!("Unknown pattern kind {0}", kind))),
+    };
+    Ok(pattern)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SchemaError {
+    UnsupportedVersion(usize),
+    Missing(String),
+    Invalid(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            self::SchemaError::UnsupportedVersion(version) => write!(
+                f,
+                "Syntax tree version {} is not supported, expected version {}",
+                version, SCHEMA_VERSION
+            ),
+            self::SchemaError::Missing(name) => write!(f, "Syntax tree is missing {}", name),
+            self::SchemaError::Invalid(e) => write!(f, "Invalid syntax tree: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+// Private methods
+fn token_kind(token: &Token) -> &'static str {
+    match token {
+        Token::Assignment => "assignment",
+        Token::Add => "add",
+        Token::Subtract => "subtract",
+        Token::Equals => "equals",
+        Token::NotEquals => "not_equals",
+        Token::Multiply => "multiply",
+        Token::Divide => "divide",
+        Token::Pow => "pow",
+        Token::LeftParenthese => "left_parenthese",
+        Token::RightParenthese => "right_parenthese",
+        Token::Num(_) => "num",
+        Token::Literal { .. } => "literal",
+        Token::Identifier(_) => "identifier",
+        Token::Whitespace => "whitespace",
+        Token::Newline => "newline",
+        Token::Comment(_) => "comment",
+        Token::EOF => "eof",
+        Token::LeftBrace => "left_brace",
+        Token::RightBrace => "right_brace",
+        Token::GreaterThan => "greater_than",
+        Token::LessThan => "less_than",
+        Token::GreaterThanOrEquals => "greater_than_or_equals",
+        Token::LessThanOrEquals => "less_than_or_equals",
+        Token::Comma => "comma",
+        Token::Colon => "colon",
+        Token::Arrow => "arrow",
+        Token::LeftBracket => "left_bracket",
+        Token::RightBracket => "right_bracket",
+        Token::Dot => "dot",
+        Token::DoubleColon => "double_colon",
+        Token::FatArrow => "fat_arrow",
+        Token::DotDot => "dot_dot",
+        Token::DotDotEquals => "dot_dot_equals",
+        Token::Question => "question",
+    }
+}
+
+fn parameters_to_json(parameters: &[String], parameter_types: &[Option<Type>]) -> Json {
+    let parameters: Vec<Json> = parameters
+        .iter()
+        .zip(parameter_types)
+        .map(|(parameter, declared)| {
+            let declared = match declared {
+                Some(declared) => declared.to_string().into(),
+                None => Json::Null,
+            };
+            Json::object(vec![("name", parameter.as_str().into()), ("type", declared)])
+        })
+        .collect();
+    parameters.into()
+}
+
+fn parameters_from_json(json: &Json) -> Result<(Vec<String>, Vec<Option<Type>>), SchemaError> {
+    let mut parameters = Vec::new();
+    let mut parameter_types = Vec::new();
+    for parameter in array(field(json, "parameters")?, "parameters")? {
+        parameters.push(string(parameter, "name")?.to_string());
+        parameter_types.push(annotation(parameter, "type")?);
+    }
+    Ok((parameters, parameter_types))
+}
+
+fn names_to_json(names: &[String]) -> Json {
+    names
+        .iter()
+        .map(|name| name.as_str().into())
+        .collect::<Vec<Json>>()
+        .into()
+}
+
+fn names_from_json(json: &Json, name: &str) -> Result<Vec<String>, SchemaError> {
+    array(json, name)?
+        .iter()
+        .map(|value| value.as_str().map(str::to_string).ok_or_else(|| invalid(name)))
+        .collect()
+}
+
+fn keyword_name(keyword: &Keyword) -> &'static str {
+    keyword.name().unwrap_or("none")
+}
+
+fn keyword(json: &Json) -> Result<Keyword, SchemaError> {
+    let name = string(json, "keyword")?;
+    Keyword::ALL
+        .iter()
+        .find(|keyword| keyword_name(keyword) == name)
+        .cloned()
+        .ok_or_else(|| SchemaError::Invalid(format!("Unknown keyword {0}", name)))
+}
+
+fn operator(json: &Json) -> Result<Token, SchemaError> {
+    let symbol = string(json, "operator")?;
+    OPERATORS
+        .iter()
+        .find(|operator| operator.symbol() == symbol)
+        .cloned()
+        .ok_or_else(|| SchemaError::Invalid(format!("Unknown operator {0}", symbol)))
+}
+
+fn annotation(json: &Json, name: &str) -> Result<Option<Type>, SchemaError> {
+    match json.get(name) {
+        None | Some(Json::Null) => Ok(None),
+        Some(Json::String(type_name)) => match Type::from_annotation(type_name) {
+            Some(annotation) => Ok(Some(annotation)),
+            None => Err(SchemaError::Invalid(format!("Unknown type {0}", type_name))),
+        },
+        Some(_) => Err(invalid(name)),
+    }
+}
+
+fn field<'a>(json: &'a Json, name: &str) -> Result<&'a Json, SchemaError> {
+    json.get(name)
+        .ok_or_else(|| SchemaError::Missing(name.to_string()))
+}
+
+fn string<'a>(json: &'a Json, name: &str) -> Result<&'a str, SchemaError> {
+    field(json, name)?.as_str().ok_or_else(|| invalid(name))
+}
+
+fn number(json: &Json, name: &str) -> Result<f64, SchemaError> {
+    field(json, name)?.as_f64().ok_or_else(|| invalid(name))
+}
+
+fn is_float(number: &Number) -> bool {
+    matches!(number, Number::Float(_))
+}
+
+// Exact numbers a JSON number can not hold are written as text, e.g. "9007199254740993"
+// or "1/3"
+fn number_to_json(number: &Number) -> Json {
+    match number {
+        Number::Integer(i) if i.unsigned_abs() <= 1 << f64::MANTISSA_DIGITS => (*i as f64).into(),
+        Number::Float(f) => (*f).into(),
+        exact => exact.to_string().into(),
+    }
+}
+
+// Documents without a `float` flag only make numbers with a fraction floats
+fn number_value(json: &Json) -> Result<Number, SchemaError> {
+    if let Some(text) = field(json, "value")?.as_str() {
+        let exact = |text: &str| Number::from_decimal(text).filter(Number::is_exact);
+        let value = match text.split_once('/') {
+            Some((numerator, denominator)) => match (exact(numerator), exact(denominator)) {
+                (Some(numerator), Some(denominator)) => numerator.divide(&denominator).ok(),
+                _ => None,
+            },
+            None => exact(text),
+        };
+        return value.ok_or_else(|| invalid("value"));
+    }
+    let value = number(json, "value")?;
+    let float = json.get("float").and_then(Json::as_bool).unwrap_or(false);
+    if float || value.fract() != 0.0 {
+        return Ok(Number::Float(value));
+    }
+    match BigInt::from_f64(value) {
+        Some(integer) => Ok(Number::from_big(integer)),
+        None => Err(invalid("value")),
+    }
+}
+
+fn index(json: &Json, name: &str) -> Result<usize, SchemaError> {
+    field(json, name)?.as_usize().ok_or_else(|| invalid(name))
+}
+
+fn array<'a>(json: &'a Json, name: &str) -> Result<&'a Vec<Json>, SchemaError> {
+    json.as_array().ok_or_else(|| invalid(name))
+}
+
+fn invalid(name: &str) -> SchemaError {
+    SchemaError::Invalid(format!("Unexpected value of {0}", name))
+}
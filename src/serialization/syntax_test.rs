@@ -0,0 +1,267 @@
+#[cfg(test)]
+mod tests {
+    use crate::lexer_and_parser::ast::{Node, Slot};
+    use crate::lexer_and_parser::numeric::Number;
+    use crate::lexer_and_parser::parser::Parser;
+    use crate::lexer_and_parser::token::{Keyword, Token};
+    use crate::lexer_and_parser::tokenizer::Span;
+    use crate::passes::resolver::Resolver;
+    use crate::serialization::json::Json;
+    use crate::serialization::syntax::{
+        node_from_json, node_to_json, nodes_from_json, nodes_to_json, read_document,
+        token_from_json, token_to_json, tokens_to_json, write_document, SchemaError,
+    };
+
+    const SOURCE: &str = "x: num = -(1 + 2) * 3
+if x >= 2 km in m {
+    x = x ^ 2
+} elif x != 1 {
+    break
+} else {
+    flag = true
+}
+while x < 10 {
+    x = x / 2 # halve
+}
+fn add(a: num, b) -> num {
+    return a + b
+}
+fn nothing(shape: Shape) {
+    return
+}
+twice = fn(f) { fn(v) { f(f(v)) } }
+items = [1, twice(fn(n: num) -> num { n + 1 })(x)]
+add(\"one\", items[0])
+struct P { x }
+impl P {
+    fn get(self) {
+        return self.x
+    }
+}
+p = P { x: 1 }
+p.x = p.get()
+enum Shape {
+    Circle(r),
+    Empty
+}
+match Shape::Circle(1) {
+    Shape::Circle(r) if r > 0 => r
+    [1, ..rest] => rest
+    P { x: -1..=5 } => {
+        x
+    }
+    _ => \"none\"
+}
+try {
+    throw error(\"bad\", \"ValueError\")
+} catch e {
+    e.kind
+} finally {
+    x = 0
+}
+y = match some(x) {
+    some(v) => ok(v?)
+    none => none
+}
+import \"lib/geometry.wf\" as geo
+from \"util.wf\" import area, pi
+export add, y";
+
+    #[test]
+    fn test_round_trip() {
+        let mut parser = Parser::new(SOURCE).unwrap();
+        let nodes = parser.parse().unwrap();
+        // Text and back, to cover the JSON printer and parser as well
+        let document = Json::parse(&write_document(SOURCE).unwrap().to_string()).unwrap();
+        assert_eq!(
+            read_document(&document),
+            Ok((nodes.clone(), parser.statement_lines.clone()))
+        );
+        // Resolved trees keep their slots
+        let resolved = Resolver::new().resolve(nodes);
+        assert_eq!(
+            nodes_from_json(&nodes_to_json(&resolved)),
+            Ok(resolved.clone())
+        );
+        assert_eq!(
+            nodes_to_json(&resolved[4..5]).to_string(),
+            r#"[{"type":"function","name":"nothing","parameters":[{"name":"shape","type":"Shape"}],"return_type":null,"body":[{"type":"return","value":null}]}]"#
+        );
+        // Variables of enclosing functions are captured
+        let closure = Node::FunctionExpression {
+            parameters: vec!["v".to_string()],
+            parameter_types: vec![None],
+            return_type: None,
+            body: Box::new(vec![Node::ReturnExpression(Box::new(Some(
+                Node::CallExpression {
+                    callee: Box::new(Node::VariableExpression {
+                        identifier: "f".to_string(),
+                        slot: Slot::Captured { depth: 1, slot: 0 },
+                    }),
+                    arguments: vec![Node::VariableExpression {
+                        identifier: "v".to_string(),
+                        slot: Slot::Local(0),
+                    }],
+                },
+            )))]),
+        };
+        assert_eq!(
+            nodes_to_json(&[closure]).to_string(),
+            [
+                r#"[{"type":"closure","parameters":[{"name":"v","type":null}],"return_type":null,"#,
+                r#""body":[{"type":"return","value":{"type":"call","callee":{"type":"variable","#,
+                r#""name":"f","slot":{"kind":"captured","index":0,"depth":1}},"arguments":["#,
+                r#"{"type":"variable","name":"v","slot":{"kind":"local","index":0}}]}}]}]"#,
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn test_exact_numbers() {
+        let source = "x = 9007199254740993\ny = -123456789012345678901234567890\n9007199254740992";
+        let mut parser = Parser::new(source).unwrap();
+        let nodes = parser.parse().unwrap();
+        let document = Json::parse(&write_document(source).unwrap().to_string()).unwrap();
+        assert_eq!(
+            read_document(&document),
+            Ok((nodes.clone(), parser.statement_lines.clone()))
+        );
+        // Only numbers a float can not hold are written as text
+        assert_eq!(
+            nodes_to_json(&nodes[1..]).to_string(),
+            [
+                r#"[{"type":"assignment","operator":"=","target":{"type":"identifier","name":"y"},"#,
+                r#""value":{"type":"negative","expr":{"type":"number","#,
+                r#""value":"123456789012345678901234567890","float":false}},"annotation":null},"#,
+                r#"{"type":"number","value":9007199254740992,"float":false}]"#,
+            ]
+            .concat()
+        );
+        let third = Number::Integer(1).divide(&Number::Integer(3)).unwrap();
+        let big = Number::from_decimal("-9007199254740993").unwrap();
+        for number in [third, big] {
+            let node = Node::NumberExpression(number.clone());
+            assert_eq!(node_from_json(&node_to_json(&node)), Ok(node));
+            let token = Token::Num(number);
+            assert_eq!(
+                token_from_json(&token_to_json(&token, None)),
+                Ok((token, None))
+            );
+        }
+        assert_eq!(
+            node_from_json(&Json::parse(r#"{"type":"number","value":"1.5"}"#).unwrap()),
+            Err(SchemaError::Invalid("Unexpected value of value".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens = tokens_to_json("x >= 2.5 # a\nif");
+        assert_eq!(
+            tokens.to_string(),
+            [
+                r#"[{"kind":"identifier","value":"x","span":{"line":1,"column":0,"length":1}},"#,
+                r#"{"kind":"whitespace","span":{"line":1,"column":1,"length":1}},"#,
+                r#"{"kind":"greater_than_or_equals","span":{"line":1,"column":2,"length":2}},"#,
+                r#"{"kind":"whitespace","span":{"line":1,"column":4,"length":1}},"#,
+                r#"{"kind":"num","value":2.5,"float":true,"span":{"line":1,"column":5,"length":3}},"#,
+                r#"{"kind":"whitespace","span":{"line":1,"column":8,"length":1}},"#,
+                r#"{"kind":"comment","value":" a","span":{"line":1,"column":9,"length":3}},"#,
+                r#"{"kind":"newline","span":{"line":1,"column":12,"length":1}},"#,
+                r#"{"kind":"literal","literal":"if","keyword":"if","span":{"line":2,"column":0,"length":2}},"#,
+                r#"{"kind":"eof","span":{"line":2,"column":2,"length":0}}]"#,
+            ]
+            .concat()
+        );
+        // Every token kind survives a round trip
+        let all = vec![
+            Token::Assignment,
+            Token::Add,
+            Token::Subtract,
+            Token::Equals,
+            Token::NotEquals,
+            Token::Multiply,
+            Token::Divide,
+            Token::Pow,
+            Token::LeftParenthese,
+            Token::RightParenthese,
+            Token::Num(Number::Float(-1.5)),
+            Token::Identifier("x".to_string()),
+            Token::Whitespace,
+            Token::Newline,
+            Token::Comment("note".to_string()),
+            Token::EOF,
+            Token::LeftBrace,
+            Token::RightBrace,
+            Token::GreaterThan,
+            Token::LessThan,
+            Token::GreaterThanOrEquals,
+            Token::LessThanOrEquals,
+            Token::Comma,
+            Token::Colon,
+            Token::Arrow,
+            Token::LeftBracket,
+            Token::RightBracket,
+        ];
+        let literals = Keyword::ALL.iter().map(|keyword| Token::Literal {
+            literal: "word".to_string(),
+            keyword: keyword.clone(),
+        });
+        for token in all.into_iter().chain(literals) {
+            assert_eq!(
+                token_from_json(&token_to_json(&token, None)),
+                Ok((token, None))
+            );
+        }
+        let span = Span {
+            line: 3,
+            column: 1,
+            length: 2,
+        };
+        assert_eq!(
+            token_from_json(&token_to_json(&Token::Arrow, Some(span))),
+            Ok((Token::Arrow, Some(span)))
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let read = |text: &str| read_document(&Json::parse(text).unwrap());
+        assert_eq!(
+            read(r#"{"version":2,"statements":[]}"#),
+            Err(SchemaError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            read(r#"{"version":1}"#),
+            Err(SchemaError::Missing("statements".to_string()))
+        );
+        assert_eq!(
+            read(r#"{"version":1,"statements":[]}"#),
+            Ok((Vec::new(), Vec::new()))
+        );
+        let node = |text: &str| node_from_json(&Json::parse(text).unwrap());
+        assert_eq!(
+            node(r#"{"type":"loop"}"#),
+            Err(SchemaError::Invalid("Unknown node type loop".to_string()))
+        );
+        assert_eq!(
+            node(
+                r#"{"type":"binary","operator":"%","left":{"type":"number","value":1},"right":{"type":"number","value":2}}"#
+            ),
+            Err(SchemaError::Invalid("Unknown operator %".to_string()))
+        );
+        assert_eq!(
+            node(
+                r#"{"type":"assignment","operator":"=","target":{"type":"identifier","name":"x"},"value":{"type":"number","value":1},"annotation":"big int"}"#
+            ),
+            Err(SchemaError::Invalid("Unknown type big int".to_string()))
+        );
+        assert_eq!(
+            node(r#"{"type":"literal","value":"a","keyword":7}"#),
+            Err(SchemaError::Invalid(
+                "Unexpected value of keyword".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,86 @@
+// Tests for the command line, run against the built binary.
+use std::fs;
+use std::process::{Command, Output};
+
+// Write the files into a fresh directory and `wistful run` the first one.
+fn run(name: &str, files: &[(&str, &str)]) -> Output {
+    let directory =
+        std::env::temp_dir().join(format!("wistful-cli-{0}-{1}", name, std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    for (file, source) in files {
+        fs::write(directory.join(file), source).unwrap();
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_wistful"))
+        .arg("run")
+        .arg(directory.join(files[0].0))
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    assert!(!output.status.success(), "Should fail");
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn test_run_on_the_vm() {
+    let output = run("vm", &[("main.wf", "fn f(a) { return a + 1 }\nf(41)\n")]);
+    assert_eq!(stdout(&output), "42\n");
+}
+
+#[test]
+fn test_run_with_the_evaluator() {
+    // Closures and builtins are not compiled
+    let output = run(
+        "closures",
+        &[("main.wf", "xs = map([1, 2, 3], fn(x) { x * 2 })\nlen(xs)\n")],
+    );
+    assert_eq!(stdout(&output), "3\n");
+    let output = run(
+        "values",
+        &[("main.wf", "fn f(a) { return a + 1 }\ng = f\ng(1)\n")],
+    );
+    assert_eq!(stdout(&output), "2\n");
+}
+
+#[test]
+fn test_run_with_the_prelude() {
+    let output = run("prelude", &[("main.wf", "assert_eq(1, 1)\nmaximum([1, 3, 2])\n")]);
+    assert_eq!(stdout(&output), "3\n");
+    let error = stderr(&run("prelude-error", &[("main.wf", "assert_eq(1, 2)\n")]));
+    assert!(error.contains("AssertionError: Expected 2, got 1"), "{}", error);
+}
+
+#[test]
+fn test_run_with_imports() {
+    // Imports are found next to the script being run
+    let output = run(
+        "imports",
+        &[
+            (
+                "main.wf",
+                "import \"geometry.wf\" as geo\nfrom \"geometry.wf\" import square\ngeo.square(3) + square(1)\n",
+            ),
+            ("geometry.wf", "fn square(x) { return x * x }\nexport square\n"),
+        ],
+    );
+    assert_eq!(stdout(&output), "10\n");
+}
+
+#[test]
+fn test_run_errors() {
+    assert!(stderr(&run("vm-error", &[("main.wf", "1 / 0\n")])).contains("Division by zero"));
+    let error = stderr(&run("error", &[("main.wf", "x = [1]\nx(1)\n")]));
+    assert!(error.contains("at line 2"), "{}", error);
+}
@@ -0,0 +1,80 @@
+// Tests for the language server, talking to the built binary over stdin/stdout.
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use wistful::serialization::json::Json;
+
+// Send the messages, close the input and return the replies and whether the server
+// exited successfully.
+fn session(messages: &[&str]) -> (Vec<Json>, bool) {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_wistful-lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = server.stdin.take().unwrap();
+    for message in messages {
+        write!(
+            input,
+            "Content-Length: {0}\r\n\r\n{1}",
+            message.len(),
+            message
+        )
+        .unwrap();
+    }
+    drop(input);
+    let output = server.wait_with_output().unwrap();
+    let replies = String::from_utf8(output.stdout)
+        .unwrap()
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|message| {
+            let (_, body) = message.split_once("\r\n\r\n").unwrap();
+            Json::parse(body).unwrap()
+        })
+        .collect();
+    (replies, output.status.success())
+}
+
+fn result(replies: &[Json], id: usize) -> &Json {
+    replies
+        .iter()
+        .find(|reply| reply.get("id").and_then(Json::as_usize) == Some(id))
+        .and_then(|reply| reply.get("result"))
+        .unwrap()
+}
+
+#[test]
+fn test_session() {
+    let (replies, success) = session(&[
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#,
+        r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.wf","text":"v = 1\nu = [\"😀\", v]"}}}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.wf"},"position":{"line":1,"character":11}}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"shutdown"}"#,
+        r#"{"jsonrpc":"2.0","method":"exit"}"#,
+    ]);
+    assert!(success);
+    let capabilities = result(&replies, 1).get("capabilities").unwrap();
+    assert_eq!(
+        capabilities.get("hoverProvider").and_then(Json::as_bool),
+        Some(true)
+    );
+    // Diagnostics of the opened document
+    assert_eq!(
+        replies[1].get("method").and_then(Json::as_str),
+        Some("textDocument/publishDiagnostics")
+    );
+    assert_eq!(
+        result(&replies, 2).to_string(),
+        r#"{"uri":"file:///a.wf","range":{"start":{"line":0,"character":0},"end":{"line":0,"character":1}}}"#
+    );
+    assert_eq!(result(&replies, 3), &Json::Null);
+}
+
+#[test]
+fn test_exit_without_shutdown() {
+    let (replies, success) = session(&[r#"{"jsonrpc":"2.0","method":"exit"}"#]);
+    assert!(replies.is_empty());
+    assert!(!success);
+}